@@ -0,0 +1,327 @@
+use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the content-addressed asset folder at the workspace root
+pub const ASSETS_DIR: &str = ".excalidraw-assets";
+
+/// Key that replaces `dataURL` in a `files` entry once its data lives in the asset folder
+const ASSET_REF_KEY: &str = "assetRef";
+
+/// Moves base64 image data out of the scene into `<workspace>/.excalidraw-assets`.
+///
+/// Assets are named by the SHA-256 of their bytes, so the same image used in
+/// several drawings is only stored once.
+pub fn externalize_assets(content: &str, workspace: &Path) -> Result<String, String> {
+    let mut scene: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return Ok(content.to_string());
+    };
+
+    let assets_dir = workspace.join(ASSETS_DIR);
+    let mut changed = false;
+
+    for entry in files.values_mut() {
+        let Some(data_url) = entry.get("dataURL").and_then(|d| d.as_str()) else {
+            continue;
+        };
+        let Some((mime_type, bytes)) = decode_data_url(data_url) else {
+            continue;
+        };
+
+        let asset_name = format!("{}.{}", hash_bytes(&bytes), extension_for_mime(&mime_type));
+        let asset_path = assets_dir.join(&asset_name);
+
+        if !asset_path.exists() {
+            fs::create_dir_all(&assets_dir)
+                .map_err(|e| format!("Failed to create asset folder: {}", e))?;
+            fs::write(&asset_path, &bytes)
+                .map_err(|e| format!("Failed to write asset {}: {}", asset_name, e))?;
+        }
+
+        if let Some(obj) = entry.as_object_mut() {
+            obj.remove("dataURL");
+            obj.entry("mimeType")
+                .or_insert_with(|| serde_json::Value::String(mime_type));
+            obj.insert(ASSET_REF_KEY.to_string(), serde_json::Value::String(asset_name));
+            changed = true;
+        }
+    }
+
+    if !changed {
+        return Ok(content.to_string());
+    }
+
+    serde_json::to_string_pretty(&scene)
+        .map_err(|e| format!("Failed to serialize content: {}", e))
+}
+
+/// Puts externalized image data back into the scene as data URLs.
+///
+/// Each asset is looked up in the asset folders of the drawing's directory and its
+/// ancestors, so drawings keep working when moved around inside the workspace.
+pub fn inline_assets(content: &str, file_path: &Path) -> Result<String, String> {
+    if !content.contains(ASSET_REF_KEY) {
+        return Ok(content.to_string());
+    }
+
+    let mut scene: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return Ok(content.to_string());
+    };
+
+    let mut changed = false;
+
+    for entry in files.values_mut() {
+        let Some(asset_name) = entry.get(ASSET_REF_KEY).and_then(|a| a.as_str()) else {
+            continue;
+        };
+        let asset_name = asset_name.to_string();
+
+        let asset_path = find_asset(file_path, &asset_name)?;
+        let bytes = fs::read(&asset_path)
+            .map_err(|e| format!("Failed to read asset {}: {}", asset_name, e))?;
+
+        let mime_type = entry
+            .get("mimeType")
+            .and_then(|m| m.as_str())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        if let Some(obj) = entry.as_object_mut() {
+            obj.remove(ASSET_REF_KEY);
+            obj.insert(
+                "dataURL".to_string(),
+                serde_json::Value::String(encode_data_url(&mime_type, &bytes)),
+            );
+            changed = true;
+        }
+    }
+
+    if !changed {
+        return Ok(content.to_string());
+    }
+
+    serde_json::to_string_pretty(&scene)
+        .map_err(|e| format!("Failed to serialize content: {}", e))
+}
+
+/// Finds the nearest asset folder in the file's directory or one of its ancestors
+pub fn find_assets_dir(file_path: &Path) -> Option<PathBuf> {
+    file_path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(ASSETS_DIR))
+        .find(|dir| dir.is_dir())
+}
+
+/// Finds an asset in the asset folders of the file's directory and its ancestors.
+///
+/// Drawings are saved against the workspace root, so a nearer asset folder (one an
+/// imported bundle created, say) does not necessarily hold their assets.
+pub fn find_asset(file_path: &Path, asset_name: &str) -> Result<PathBuf, String> {
+    for dir in file_path.ancestors().skip(1).map(|dir| dir.join(ASSETS_DIR)) {
+        let path = security::safe_path_join(&dir, asset_name)?;
+        if path.is_file() {
+            return Ok(path);
+        }
+    }
+    Err(format!("Asset not found: {}", asset_name))
+}
+
+/// Every asset folder in `dir` and its subfolders
+fn collect_assets_dirs(dir: &Path, dirs: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| e.to_string())?;
+    for path in entries.flatten().map(|entry| entry.path()).filter(|path| path.is_dir()) {
+        if path.file_name().is_some_and(|name| name == ASSETS_DIR) {
+            dirs.push(path);
+        } else {
+            collect_assets_dirs(&path, dirs)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes assets that no drawing in the workspace references any more.
+///
+/// Every asset folder in the workspace is collected. A drawing's reference only keeps the
+/// copy it resolves to, the one in the nearest asset folder that has it.
+#[tauri::command]
+pub async fn gc_assets(
+    directory: String,
+    dry_run: Option<bool>,
+) -> Result<models::AssetGcReport, String> {
+    let validated_dir = security::validate_path(Path::new(&directory), None)?;
+    gc_workspace_assets(&validated_dir, dry_run.unwrap_or(false))
+}
+
+fn gc_workspace_assets(validated_dir: &Path, dry_run: bool) -> Result<models::AssetGcReport, String> {
+    let mut report = models::AssetGcReport {
+        referenced: 0,
+        removed: Vec::new(),
+        freed_bytes: 0,
+    };
+
+    let mut assets_dirs = Vec::new();
+    collect_assets_dirs(validated_dir, &mut assets_dirs)?;
+    if assets_dirs.is_empty() {
+        return Ok(report);
+    }
+
+    let mut files = Vec::new();
+    file_ops::collect_excalidraw_files_recursive(validated_dir, &mut files)?;
+
+    let mut referenced = HashSet::new();
+    for file in &files {
+        let path = Path::new(&file.path);
+        let content = formats::read_scene_content(path)
+            .map_err(|e| format!("Failed to read {}: {}", file.path, e))?;
+        // A drawing that cannot be parsed may still use assets, so nothing is safe to remove
+        let scene = serde_json::from_str::<serde_json::Value>(&content)
+            .map_err(|e| format!("Failed to parse {}, no assets removed: {}", file.path, e))?;
+        if let Some(entries) = scene.get("files").and_then(|f| f.as_object()) {
+            for entry in entries.values() {
+                let resolved = entry
+                    .get(ASSET_REF_KEY)
+                    .and_then(|a| a.as_str())
+                    .and_then(|name| find_asset(path, name).ok());
+                if let Some(asset_path) = resolved {
+                    referenced.insert(asset_path);
+                }
+            }
+        }
+    }
+    report.referenced = referenced.len();
+
+    for assets_dir in &assets_dirs {
+        let entries = fs::read_dir(assets_dir).map_err(|e| e.to_string())?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_file() || referenced.contains(&path) {
+                continue;
+            }
+
+            let name = path
+                .strip_prefix(validated_dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            if !dry_run {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to remove asset {}: {}", name, e))?;
+            }
+            report.removed.push(name);
+            report.freed_bytes += size;
+        }
+    }
+
+    report.removed.sort();
+    Ok(report)
+}
+
+/// Splits a base64 data URL into its mime type and decoded bytes
+pub fn decode_data_url(data_url: &str) -> Option<(String, Vec<u8>)> {
+    let rest = data_url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime_type = meta.strip_suffix(";base64")?;
+    let bytes = BASE64.decode(data.trim()).ok()?;
+    Some((mime_type.to_string(), bytes))
+}
+
+pub fn encode_data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type, BASE64.encode(bytes))
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn mime_for_extension(extension: &str) -> Option<&'static str> {
+    match extension.to_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "avif" => Some("image/avif"),
+        "bmp" => Some("image/bmp"),
+        "svg" => Some("image/svg+xml"),
+        "ico" => Some("image/x-icon"),
+        _ => None,
+    }
+}
+
+pub fn extension_for_mime(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/bmp" => "bmp",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn drawing(asset: &str) -> String {
+        json!({
+            "type": "excalidraw",
+            "version": 2,
+            "elements": [],
+            "appState": {},
+            "files": { "img": { "id": "img", "mimeType": "image/png", ASSET_REF_KEY: asset } },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn gc_collects_every_asset_folder_and_keeps_the_copies_drawings_resolve_to() {
+        let dir = std::env::temp_dir().join(format!("assets-gc-test-{}", std::process::id()));
+        let root_assets = dir.join(ASSETS_DIR);
+        let sub_assets = dir.join("sub").join(ASSETS_DIR);
+        fs::create_dir_all(&root_assets).unwrap();
+        fs::create_dir_all(&sub_assets).unwrap();
+        fs::write(dir.join("root.excalidraw"), drawing("a.png")).unwrap();
+        fs::write(dir.join("sub").join("nested.excalidraw"), drawing("b.png")).unwrap();
+        for path in [
+            root_assets.join("a.png"),
+            root_assets.join("unused.png"),
+            sub_assets.join("a.png"),
+            sub_assets.join("b.png"),
+        ] {
+            fs::write(path, b"png").unwrap();
+        }
+
+        let report = gc_workspace_assets(&dir, false).unwrap();
+        let remaining = (root_assets.join("a.png").is_file(), sub_assets.join("b.png").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.referenced, 2);
+        let expected = [
+            Path::new(ASSETS_DIR).join("unused.png"),
+            Path::new("sub").join(ASSETS_DIR).join("a.png"),
+        ];
+        let removed: Vec<PathBuf> = report.removed.iter().map(PathBuf::from).collect();
+        assert_eq!(removed, expected);
+        assert_eq!(report.freed_bytes, 6);
+        assert_eq!(remaining, (true, true));
+    }
+}
@@ -0,0 +1,383 @@
+use super::*;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tauri::State;
+
+const MANIFEST_NAME: &str = "manifest.json";
+const BUNDLE_FORMAT: &str = "sag-excalidraw-bundle";
+const BUNDLE_VERSION: u64 = 1;
+
+/// Top-level archive folders for each kind of entry
+const DRAWINGS_PREFIX: &str = "drawings/";
+const ASSETS_PREFIX: &str = "assets/";
+const TEMPLATES_PREFIX: &str = "templates/";
+
+/// Archive entries by name
+type Entries = BTreeMap<String, Vec<u8>>;
+
+/// Folders that belong to the workspace itself and are never bundled as drawings
+pub fn is_workspace_folder(name: &str) -> bool {
+    name == templates::TEMPLATES_DIR || name == library::LIBRARIES_DIR || name == assets::ASSETS_DIR
+}
+
+fn archive_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Drawings to bundle, with their path relative to the folder holding all of them
+fn collect_drawings(paths: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, String> {
+    let mut drawings = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut files = Vec::new();
+            file_ops::collect_excalidraw_files_recursive(path, &mut files)?;
+            drawings.extend(files.into_iter().map(|f| PathBuf::from(f.path)));
+        } else if formats::is_drawing_file(path) {
+            drawings.push(path.clone());
+        } else {
+            return Err(format!("Not a drawing or folder: {}", path.display()));
+        }
+    }
+
+    // A chosen folder keeps its own name inside the bundle
+    let base = paths
+        .iter()
+        .filter_map(|p| p.parent())
+        .fold(None::<PathBuf>, |common, parent| match common {
+            None => Some(parent.to_path_buf()),
+            Some(common) => common.ancestors().find(|a| parent.starts_with(a)).map(Path::to_path_buf),
+        })
+        .unwrap_or_default();
+
+    let mut result: Vec<(PathBuf, String)> = drawings
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&base).ok()?.to_path_buf();
+            let hidden = relative
+                .components()
+                .any(|c| is_workspace_folder(&c.as_os_str().to_string_lossy()));
+            (!hidden).then(|| (path, archive_name(&relative)))
+        })
+        .collect();
+    result.sort_by(|a, b| a.1.cmp(&b.1));
+    result.dedup_by(|a, b| a.1 == b.1);
+    Ok(result)
+}
+
+/// Externalized assets a drawing refers to, read from the raw scene since loading inlines them
+fn referenced_assets(path: &Path) -> Result<Vec<String>, String> {
+    let content = formats::read_scene_content(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let scene: Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    Ok(scene
+        .get("files")
+        .and_then(|f| f.as_object())
+        .map(|files| {
+            files
+                .values()
+                .filter_map(|entry| render::str_field(entry, "assetRef").map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Packages drawings and folders into a zip together with their externalized assets,
+/// the workspace templates and a manifest of SHA-256 hashes
+#[tauri::command]
+pub async fn export_bundle(
+    paths: Vec<String>,
+    output_path: String,
+    include_templates: Option<bool>,
+    state: State<'_, models::AppState>,
+) -> Result<models::BundleExportReport, String> {
+    let paths = paths
+        .iter()
+        .map(|p| security::validate_path(Path::new(p), None))
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty() {
+        return Err("Nothing to export".to_string());
+    }
+
+    let drawings = collect_drawings(&paths)?;
+    if drawings.is_empty() {
+        return Err("No drawings found to export".to_string());
+    }
+
+    let mut entries = Entries::new();
+    for (path, name) in &drawings {
+        let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        entries.insert(format!("{}{}", DRAWINGS_PREFIX, name), bytes);
+
+        for asset in referenced_assets(path)? {
+            let key = format!("{}{}", ASSETS_PREFIX, asset);
+            if entries.contains_key(&key) {
+                continue;
+            }
+            let asset_path = assets::find_asset(path, &asset)?;
+            let bytes = fs::read(&asset_path).map_err(|e| format!("Failed to read asset {}: {}", asset, e))?;
+            entries.insert(key, bytes);
+        }
+    }
+
+    if include_templates.unwrap_or(true) {
+        // Templates above the open workspace belong to someone else
+        let workspace = file_ops::workspace_dir(&state, &paths[0]);
+        let templates_dir = file_ops::find_workspace_folder(&paths[0], &workspace, templates::TEMPLATES_DIR);
+        if let Some(dir) = templates_dir
+            && let Ok(read) = fs::read_dir(&dir)
+        {
+            for path in read.flatten().map(|e| e.path()) {
+                if path.is_file() && path.extension().is_some_and(|ext| ext == "excalidraw") {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let bytes = fs::read(&path).map_err(|e| format!("Failed to read template {}: {}", name, e))?;
+                    entries.insert(format!("{}{}", TEMPLATES_PREFIX, name), bytes);
+                }
+            }
+        }
+    }
+
+    let count = |prefix: &str| entries.keys().filter(|k| k.starts_with(prefix)).count();
+    let (drawing_count, asset_count, template_count) =
+        (count(DRAWINGS_PREFIX), count(ASSETS_PREFIX), count(TEMPLATES_PREFIX));
+
+    let manifest = json!({
+        "format": BUNDLE_FORMAT,
+        "version": BUNDLE_VERSION,
+        "source": "SAG-Excalidraw",
+        "created": chrono::Utc::now().to_rfc3339(),
+        "files": entries
+            .iter()
+            .map(|(name, bytes)| json!({ "path": name, "size": bytes.len(), "sha256": assets::hash_bytes(bytes) }))
+            .collect::<Vec<_>>(),
+    });
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+
+    let mut archive: Vec<(String, Vec<u8>)> = vec![(MANIFEST_NAME.to_string(), manifest)];
+    archive.extend(entries);
+    let bytes = zip::write_zip(&archive)?;
+
+    let output = PathBuf::from(&output_path);
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or("Output path has no parent directory")?;
+    let parent = security::validate_path(parent, None)?;
+    let file_name = output.file_name().ok_or("Output path has no file name")?.to_string_lossy();
+    let output = security::safe_path_join(&parent, &file_name)?;
+    fs::write(&output, &bytes).map_err(|e| format!("Failed to write bundle: {}", e))?;
+
+    Ok(models::BundleExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        drawings: drawing_count,
+        assets: asset_count,
+        templates: template_count,
+        size: bytes.len() as u64,
+    })
+}
+
+/// Relative path of an archive entry, rejecting anything that could escape the target folder
+fn safe_relative(name: &str) -> Result<PathBuf, String> {
+    let path = Path::new(name);
+    if name.contains('\\') || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Unsafe path in bundle: {}", name));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// Checks every entry against the manifest; returns the entries it lists and the names of
+/// those it doesn't
+fn verify_manifest(entries: Vec<(String, Vec<u8>)>) -> Result<(Entries, Vec<String>), String> {
+    let mut entries: Entries = entries.into_iter().collect();
+    let manifest = entries.remove(MANIFEST_NAME).ok_or("Bundle has no manifest")?;
+    let manifest: Value = serde_json::from_slice(&manifest).map_err(|e| format!("Invalid bundle manifest: {}", e))?;
+
+    if render::str_field(&manifest, "format") != Some(BUNDLE_FORMAT) {
+        return Err("Not a SAG-Excalidraw bundle".to_string());
+    }
+    let version = manifest.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if version > BUNDLE_VERSION {
+        return Err(format!("Bundle version {} is newer than this app supports", version));
+    }
+
+    let listed = manifest.get("files").and_then(|f| f.as_array()).ok_or("Bundle manifest lists no files")?;
+    let mut verified = Entries::new();
+    for file in listed {
+        let name = render::str_field(file, "path").ok_or("Bundle manifest entry has no path")?;
+        let bytes = entries
+            .remove(name)
+            .ok_or_else(|| format!("Bundle is missing {}", name))?;
+        if render::str_field(file, "sha256") != Some(assets::hash_bytes(&bytes).as_str()) {
+            return Err(format!("Hash mismatch for {}; the bundle is corrupted or was modified", name));
+        }
+        verified.insert(name.to_string(), bytes);
+    }
+    Ok((verified, entries.into_keys().collect()))
+}
+
+/// Checks that a bundled drawing or template holds a valid scene before it is written.
+///
+/// The manifest comes from the same archive, so its hashes only catch corruption.
+fn validate_drawing(name: &str, bytes: &[u8]) -> Result<(), String> {
+    let format = formats::drawing_format(Path::new(name))
+        .ok_or_else(|| format!("{} is not a drawing, expected one of {}", name, formats::supported_suffixes()))?;
+    formats::parse_scene_content(format, bytes)
+        .and_then(|content| security::validate_excalidraw_content(&content))
+        .map_err(|e| format!("Invalid drawing {}: {}", name, e))
+}
+
+/// Checks that an asset is named after the hash of its bytes, which lets an existing
+/// asset of the same name be kept as is
+fn validate_asset(name: &str, bytes: &[u8]) -> Result<(), String> {
+    let hash = name.split_once('.').map_or(name, |(hash, _)| hash);
+    if name.contains('/') || hash != assets::hash_bytes(bytes) {
+        return Err(format!("Asset {} does not match its content", name));
+    }
+    Ok(())
+}
+
+/// Writes `bytes` into `dir` under `name`, numbering the name like `create_new_file` when taken.
+/// Identical existing files are reused.
+fn place_file(dir: &Path, name: &str, bytes: &[u8]) -> Result<(PathBuf, bool), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let wanted = security::safe_path_join(dir, name)?;
+    if fs::read(&wanted).is_ok_and(|existing| existing == bytes) {
+        return Ok((wanted, false));
+    }
+    let path = file_ops::unique_file_path(dir, name)?;
+    fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok((path.clone(), path != wanted))
+}
+
+/// Unpacks a bundle into `directory` after checking its manifest hashes and every entry
+#[tauri::command]
+pub async fn import_bundle(
+    bundle_path: String,
+    directory: String,
+    state: State<'_, models::AppState>,
+) -> Result<models::BundleImportReport, String> {
+    let validated_path = security::validate_path(Path::new(&bundle_path), None)?;
+    let target = security::validate_path(Path::new(&directory), None)?;
+    if !target.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+
+    let bytes = fs::read(&validated_path).map_err(|e| format!("Failed to read bundle: {}", e))?;
+    let (entries, mut ignored) = verify_manifest(zip::read_zip(&bytes)?)?;
+
+    // Nothing is written until every entry has been checked
+    for (name, bytes) in &entries {
+        safe_relative(name)?;
+        if let Some(asset) = name.strip_prefix(ASSETS_PREFIX) {
+            validate_asset(asset, bytes)?;
+        } else if let Some(template) = name.strip_prefix(TEMPLATES_PREFIX) {
+            if !template.ends_with(formats::DrawingFormat::Json.suffix()) {
+                return Err(format!("Template {} is not an .excalidraw file", template));
+            }
+            validate_drawing(name, bytes)?;
+        } else if name.starts_with(DRAWINGS_PREFIX) {
+            validate_drawing(name, bytes)?;
+        } else {
+            ignored.push(name.clone());
+        }
+    }
+    ignored.sort();
+
+    let mut report = models::BundleImportReport {
+        drawings: Vec::new(),
+        templates: Vec::new(),
+        assets: 0,
+        ignored,
+    };
+
+    // Shared folders are reused only when they belong to the workspace the bundle goes into
+    let workspace = file_ops::workspace_dir(&state, &target);
+
+    // Assets first, so drawings that refer to them open right away
+    let assets_dir = file_ops::find_workspace_folder(&target, &workspace, assets::ASSETS_DIR)
+        .unwrap_or_else(|| target.join(assets::ASSETS_DIR));
+    for (name, bytes) in entries.iter().filter(|(n, _)| n.starts_with(ASSETS_PREFIX)) {
+        let asset_name = &name[ASSETS_PREFIX.len()..];
+        let path = security::safe_path_join(&assets_dir, asset_name)?;
+        // Assets are named by their hash, so an existing one already has these bytes
+        if !path.exists() {
+            fs::create_dir_all(&assets_dir).map_err(|e| format!("Failed to create asset folder: {}", e))?;
+            fs::write(&path, bytes).map_err(|e| format!("Failed to write asset {}: {}", asset_name, e))?;
+        }
+        report.assets += 1;
+    }
+
+    let templates_dir = file_ops::find_workspace_folder(&target, &workspace, templates::TEMPLATES_DIR)
+        .unwrap_or_else(|| target.join(templates::TEMPLATES_DIR));
+    for (name, bytes) in entries.iter().filter(|(n, _)| n.starts_with(TEMPLATES_PREFIX)) {
+        let (path, renamed) = place_file(&templates_dir, &name[TEMPLATES_PREFIX.len()..], bytes)?;
+        report.templates.push(models::BundleEntry {
+            source: name.clone(),
+            path: path.to_string_lossy().to_string(),
+            renamed,
+        });
+    }
+
+    for (name, bytes) in entries.iter().filter(|(n, _)| n.starts_with(DRAWINGS_PREFIX)) {
+        let relative = safe_relative(&name[DRAWINGS_PREFIX.len()..])?;
+        let file_name = relative.file_name().ok_or("Invalid file name")?.to_string_lossy();
+        let dir = relative.parent().map(|p| target.join(p)).unwrap_or_else(|| target.clone());
+        let (path, renamed) = place_file(&dir, &file_name, bytes)?;
+        report.drawings.push(models::BundleEntry {
+            source: name.clone(),
+            path: path.to_string_lossy().to_string(),
+            renamed,
+        });
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(files: Value) -> (String, Vec<u8>) {
+        let manifest = json!({ "format": BUNDLE_FORMAT, "version": BUNDLE_VERSION, "files": files });
+        (MANIFEST_NAME.to_string(), manifest.to_string().into_bytes())
+    }
+
+    #[test]
+    fn verifies_listed_files_and_returns_the_others() {
+        let drawing = b"{}".to_vec();
+        let entries = vec![
+            manifest(json!([{ "path": "a.excalidraw", "sha256": assets::hash_bytes(&drawing) }])),
+            ("a.excalidraw".to_string(), drawing),
+            ("notes.txt".to_string(), b"extra".to_vec()),
+        ];
+        let (verified, ignored) = verify_manifest(entries).unwrap();
+        assert_eq!(verified.keys().collect::<Vec<_>>(), ["a.excalidraw"]);
+        assert_eq!(ignored, ["notes.txt"]);
+    }
+
+    #[test]
+    fn rejects_modified_files() {
+        let entries = vec![
+            manifest(json!([{ "path": "a.excalidraw", "sha256": assets::hash_bytes(b"{}") }])),
+            ("a.excalidraw".to_string(), b"{\"changed\":1}".to_vec()),
+        ];
+        assert!(verify_manifest(entries).unwrap_err().starts_with("Hash mismatch for a.excalidraw"));
+    }
+
+    #[test]
+    fn validates_drawings_and_asset_names() {
+        let scene = br#"{"type":"excalidraw","version":2,"elements":[],"appState":{},"files":{}}"#;
+        assert!(validate_drawing("a.excalidraw", scene).is_ok());
+        assert!(validate_drawing("a.excalidraw", b"not json").unwrap_err().starts_with("Invalid drawing a.excalidraw"));
+        assert!(validate_drawing("a.exe", scene).unwrap_err().starts_with("a.exe is not a drawing"));
+
+        let png = b"png bytes";
+        let name = format!("{}.png", assets::hash_bytes(png));
+        assert!(validate_asset(&name, png).is_ok());
+        assert!(validate_asset(&name, b"other bytes").is_err());
+        assert!(validate_asset("x/../evil.png", png).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use serde_json::{Map, Value};
+
+/// Number of decimal places floats are rounded to in the canonical format
+const FLOAT_PRECISION: i32 = 3;
+
+/// Top-level keys are written in this order, any other keys follow alphabetically
+const TOP_LEVEL_ORDER: [&str; 6] = ["type", "version", "source", "elements", "appState", "files"];
+
+/// Whether `content` is already in the canonical form
+pub fn is_canonical(content: &str) -> bool {
+    canonicalize_scene(content).is_ok_and(|canonical| canonical == content)
+}
+
+/// Serializes a scene into a deterministic, diff-friendly form.
+///
+/// Keys are sorted, every element and every embedded file sits on its own line,
+/// floats are rounded to a fixed precision and elements are ordered by their
+/// fractional `index` when all of them carry one.
+pub fn canonicalize_scene(content: &str) -> Result<String, String> {
+    let scene: Value = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    let obj = scene.as_object().ok_or("Content is not a JSON object")?;
+
+    let mut keys: Vec<&String> = obj.keys().collect();
+    keys.sort_by_key(|k| {
+        let rank = TOP_LEVEL_ORDER
+            .iter()
+            .position(|t| t == k)
+            .unwrap_or(TOP_LEVEL_ORDER.len());
+        (rank, k.as_str())
+    });
+
+    let mut out = String::from("{\n");
+    for (i, key) in keys.iter().enumerate() {
+        let value = &obj[key.as_str()];
+        out.push_str("  ");
+        write_string(key, &mut out);
+        out.push_str(": ");
+
+        match (key.as_str(), value) {
+            ("elements", Value::Array(elements)) => write_elements(elements, &mut out),
+            ("files", Value::Object(files)) => write_files(files, &mut out),
+            _ => write_pretty(value, 1, &mut out),
+        }
+
+        if i + 1 < keys.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("}\n");
+
+    Ok(out)
+}
+
+fn write_elements(elements: &[Value], out: &mut String) {
+    if elements.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+
+    let mut ordered: Vec<&Value> = elements.iter().collect();
+    let all_indexed = ordered
+        .iter()
+        .all(|e| e.get("index").is_some_and(|i| i.is_string()));
+    if all_indexed {
+        // Fractional indices encode z-order, so sorting by them is lossless
+        ordered.sort_by(|a, b| {
+            let a = a.get("index").and_then(|i| i.as_str()).unwrap_or_default();
+            let b = b.get("index").and_then(|i| i.as_str()).unwrap_or_default();
+            a.cmp(b)
+        });
+    }
+
+    out.push_str("[\n");
+    for (i, element) in ordered.iter().enumerate() {
+        out.push_str("    ");
+        write_compact(element, out);
+        if i + 1 < ordered.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("  ]");
+}
+
+fn write_files(files: &Map<String, Value>, out: &mut String) {
+    if files.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    let mut ids: Vec<&String> = files.keys().collect();
+    ids.sort();
+
+    out.push_str("{\n");
+    for (i, id) in ids.iter().enumerate() {
+        out.push_str("    ");
+        write_string(id, out);
+        out.push_str(": ");
+        write_compact(&files[id.as_str()], out);
+        if i + 1 < ids.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("  }");
+}
+
+fn write_pretty(value: &Value, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth + 1);
+    let closing = "  ".repeat(depth);
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+
+            out.push_str("{\n");
+            for (i, key) in keys.iter().enumerate() {
+                out.push_str(&indent);
+                write_string(key, out);
+                out.push_str(": ");
+                write_pretty(&map[key.as_str()], depth + 1, out);
+                if i + 1 < keys.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&closing);
+            out.push('}');
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&indent);
+                write_pretty(item, depth + 1, out);
+                if i + 1 < items.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&closing);
+            out.push(']');
+        }
+        _ => write_compact(value, out),
+    }
+}
+
+fn write_compact(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(n, out),
+        Value::String(s) => write_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_compact(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+
+            out.push('{');
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write_compact(&map[key.as_str()], out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_number(n: &serde_json::Number, out: &mut String) {
+    if n.is_i64() || n.is_u64() {
+        out.push_str(&n.to_string());
+        return;
+    }
+
+    let f = n.as_f64().unwrap_or(0.0);
+    let factor = 10f64.powi(FLOAT_PRECISION);
+    let rounded = (f * factor).round() / factor;
+
+    if rounded == 0.0 {
+        // Also folds -0 into 0
+        out.push('0');
+    } else if rounded.fract() == 0.0 && rounded.abs() < 1e15 {
+        out.push_str(&format!("{}", rounded as i64));
+    } else {
+        out.push_str(&format!("{}", rounded));
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    // serde_json never fails to serialize a plain string
+    out.push_str(&serde_json::to_string(s).unwrap_or_default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"{"files":{"b":{"id":"b"},"a":{"id":"a","mimeType":"image/png"}},"appState":{"zoom":{"value":1.0},"gridSize":null},"elements":[{"type":"text","index":"a2","id":"t","x":1.23456},{"index":"a1","id":"r","y":-0.0001,"width":10.0}],"type":"excalidraw","version":2,"extra":true}"#;
+
+    #[test]
+    fn writes_keys_elements_and_files_in_a_fixed_order() {
+        let expected = r#"{
+  "type": "excalidraw",
+  "version": 2,
+  "elements": [
+    {"height":null,"id":"r","index":"a1","width":10,"y":0},
+    {"id":"t","index":"a2","type":"text","x":1.235}
+  ],
+  "appState": {
+    "gridSize": null,
+    "zoom": {
+      "value": 1
+    }
+  },
+  "files": {
+    "a": {"id":"a","mimeType":"image/png"},
+    "b": {"id":"b"}
+  },
+  "extra": true
+}
+"#;
+        let with_height = SCENE.replace(r#""width":10.0"#, r#""width":10.0,"height":null"#);
+        assert_eq!(canonicalize_scene(&with_height).unwrap(), expected);
+    }
+
+    #[test]
+    fn canonical_form_is_stable() {
+        let canonical = canonicalize_scene(SCENE).unwrap();
+        assert!(!is_canonical(SCENE));
+        assert!(is_canonical(&canonical));
+        assert_eq!(canonicalize_scene(&canonical).unwrap(), canonical);
+    }
+
+    #[test]
+    fn keeps_element_order_unless_every_element_has_an_index() {
+        let scene = r#"{"elements":[{"id":"b","index":"a2"},{"id":"a"}]}"#;
+        let canonical = canonicalize_scene(scene).unwrap();
+        assert!(canonical.find(r#""id":"b""#) < canonical.find(r#""id":"a""#));
+    }
+
+    #[test]
+    fn rejects_content_that_is_not_a_scene_object() {
+        assert!(canonicalize_scene("[1, 2]").is_err());
+        assert!(canonicalize_scene("{").unwrap_err().starts_with("Invalid JSON"));
+    }
+}
@@ -0,0 +1,242 @@
+use super::*;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+#[derive(Default)]
+struct CompactStats {
+    removed_elements: usize,
+    removed_files: usize,
+    fixed_bindings: usize,
+}
+
+/// Removes deleted elements and unreferenced files, and clears bindings that
+/// point at elements which no longer exist.
+fn compact_scene(scene: &mut Value) -> CompactStats {
+    let mut stats = CompactStats::default();
+
+    if let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) {
+        let before = elements.len();
+        elements.retain(|e| !e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false));
+        stats.removed_elements = before - elements.len();
+
+        let live_ids: HashSet<String> = elements
+            .iter()
+            .filter_map(|e| e.get("id").and_then(|id| id.as_str()))
+            .map(str::to_string)
+            .collect();
+
+        for element in elements.iter_mut() {
+            stats.fixed_bindings += fix_bindings(element, &live_ids);
+        }
+    }
+
+    let used_file_ids: HashSet<String> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|elements| {
+            elements
+                .iter()
+                .filter_map(|e| e.get("fileId").and_then(|id| id.as_str()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) {
+        let before = files.len();
+        files.retain(|id, _| used_file_ids.contains(id));
+        stats.removed_files = before - files.len();
+    }
+
+    stats
+}
+
+fn fix_bindings(element: &mut Value, live_ids: &HashSet<String>) -> usize {
+    let Some(obj) = element.as_object_mut() else {
+        return 0;
+    };
+    let mut fixed = 0;
+
+    if let Some(bound) = obj.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+        let before = bound.len();
+        bound.retain(|b| {
+            b.get("id")
+                .and_then(|id| id.as_str())
+                .is_some_and(|id| live_ids.contains(id))
+        });
+        fixed += before - bound.len();
+    }
+
+    for key in ["containerId", "frameId"] {
+        let dangling = obj
+            .get(key)
+            .and_then(|v| v.as_str())
+            .is_some_and(|id| !live_ids.contains(id));
+        if dangling {
+            obj.insert(key.to_string(), Value::Null);
+            fixed += 1;
+        }
+    }
+
+    for key in ["startBinding", "endBinding"] {
+        let dangling = obj
+            .get(key)
+            .and_then(|b| b.get("elementId"))
+            .and_then(|id| id.as_str())
+            .is_some_and(|id| !live_ids.contains(id));
+        if dangling {
+            obj.insert(key.to_string(), Value::Null);
+            fixed += 1;
+        }
+    }
+
+    fixed
+}
+
+fn compact_path(
+    path: &Path,
+    dry_run: bool,
+    font_dirs: &[PathBuf],
+) -> Result<models::CompactReport, String> {
+    let content = formats::read_scene_content(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    security::validate_excalidraw_content(&content)?;
+
+    let mut scene: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+    let stats = compact_scene(&mut scene);
+
+    // Sizes are those of the file on disk, whatever container the scene is stored in
+    let bytes_before = fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        .len();
+    let mut bytes_after = bytes_before;
+
+    let changed = stats.removed_elements + stats.removed_files + stats.fixed_bindings > 0;
+    if changed {
+        // A drawing saved in the canonical format stays in it
+        let new_content = if canonical::is_canonical(&content) {
+            canonical::canonicalize_scene(&scene.to_string())?
+        } else {
+            serde_json::to_string_pretty(&scene)
+                .map_err(|e| format!("Failed to serialize content: {}", e))?
+        };
+        let data = formats::encode_scene_content(path, &new_content, font_dirs)?;
+        if !dry_run {
+            formats::write_atomic(path, &data)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        bytes_after = data.len() as u64;
+    }
+
+    Ok(models::CompactReport {
+        file_path: path.to_string_lossy().to_string(),
+        bytes_before,
+        bytes_after,
+        bytes_saved: bytes_before.saturating_sub(bytes_after),
+        removed_elements: stats.removed_elements,
+        removed_files: stats.removed_files,
+        fixed_bindings: stats.fixed_bindings,
+    })
+}
+
+#[tauri::command]
+pub async fn compact_file(
+    app: AppHandle,
+    file_path: String,
+    dry_run: Option<bool>,
+) -> Result<models::CompactReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    security::validate_excalidraw_file(&validated_path)?;
+
+    compact_path(&validated_path, dry_run.unwrap_or(false), &export::font_dirs(&app))
+}
+
+#[tauri::command]
+pub async fn compact_workspace(
+    app: AppHandle,
+    directory: String,
+    dry_run: Option<bool>,
+) -> Result<models::WorkspaceCompactReport, String> {
+    let validated_dir = security::validate_path(Path::new(&directory), None)?;
+    let dry_run = dry_run.unwrap_or(false);
+    let font_dirs = export::font_dirs(&app);
+
+    let mut files = Vec::new();
+    file_ops::collect_excalidraw_files_recursive(&validated_dir, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut report = models::WorkspaceCompactReport {
+        files: Vec::new(),
+        failed: Vec::new(),
+        bytes_saved: 0,
+        removed_elements: 0,
+        removed_files: 0,
+        fixed_bindings: 0,
+    };
+
+    for file in files {
+        match compact_path(Path::new(&file.path), dry_run, &font_dirs) {
+            Ok(file_report) => {
+                report.bytes_saved += file_report.bytes_saved;
+                report.removed_elements += file_report.removed_elements;
+                report.removed_files += file_report.removed_files;
+                report.fixed_bindings += file_report.fixed_bindings;
+                report.files.push(file_report);
+            }
+            Err(error) => report.failed.push(models::SkippedFile { path: file.path, error }),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scene() -> Value {
+        json!({
+            "type": "excalidraw",
+            "version": 2,
+            "elements": [
+                { "id": "a", "type": "rectangle", "boundElements": [{ "id": "b", "type": "text" }] },
+                { "id": "b", "type": "text", "containerId": "a", "isDeleted": true },
+                { "id": "c", "type": "arrow", "startBinding": { "elementId": "b" }, "endBinding": null },
+            ],
+            "appState": {},
+            "files": { "unused": { "id": "unused", "dataURL": "data:image/png;base64," } },
+        })
+    }
+
+    #[test]
+    fn compact_scene_removes_deleted_elements_and_dangling_references() {
+        let mut scene = scene();
+        let stats = compact_scene(&mut scene);
+        assert_eq!((stats.removed_elements, stats.removed_files, stats.fixed_bindings), (1, 1, 2));
+        assert_eq!(scene["elements"].as_array().unwrap().len(), 2);
+        assert_eq!(scene["elements"][0]["boundElements"], json!([]));
+        assert_eq!(scene["elements"][1]["startBinding"], Value::Null);
+    }
+
+    #[test]
+    fn compact_path_reports_file_sizes_and_keeps_the_canonical_format() {
+        let dir = std::env::temp_dir().join(format!("compact-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("drawing.excalidraw");
+        let content = canonical::canonicalize_scene(&scene().to_string()).unwrap();
+        fs::write(&path, &content).unwrap();
+
+        let report = compact_path(&path, false, &[]).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.bytes_before, content.len() as u64);
+        assert_eq!(report.bytes_after, written.len() as u64);
+        assert!(canonical::is_canonical(&written));
+    }
+}
@@ -0,0 +1,875 @@
+use super::*;
+use elements::Style;
+use layout::{Direction, Graph, Vertex};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Label size used when the graph sets no `fontsize`
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+/// Graphviz sizes are in inches
+const POINTS_PER_INCH: f64 = 72.0;
+
+type Attributes = HashMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    /// An identifier, number, quoted string or HTML string; quoted ones are never keywords
+    Id(String, bool),
+    Punct(char),
+    EdgeOp,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn syntax_error(line: usize, column: usize, message: impl std::fmt::Display) -> String {
+    format!("DOT syntax error at line {}, column {}: {}", line, column, message)
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+    let mut line_start = true;
+
+    let advance = |i: &mut usize, line: &mut usize, column: &mut usize, count: usize| {
+        for _ in 0..count {
+            if chars.get(*i) == Some(&'\n') {
+                *line += 1;
+                *column = 1;
+            } else {
+                *column += 1;
+            }
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (start_line, start_column) = (line, column);
+
+        if c.is_whitespace() {
+            line_start |= c == '\n';
+            advance(&mut i, &mut line, &mut column, 1);
+            continue;
+        }
+        // `#` lines are C preprocessor output
+        if (c == '/' && next == Some('/')) || (c == '#' && line_start) {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            continue;
+        }
+        line_start = false;
+        if c == '/' && next == Some('*') {
+            advance(&mut i, &mut line, &mut column, 2);
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            if i >= chars.len() {
+                return Err(syntax_error(start_line, start_column, "Unclosed comment"));
+            }
+            advance(&mut i, &mut line, &mut column, 2);
+            continue;
+        }
+
+        let kind = if c == '"' {
+            let mut text = String::new();
+            advance(&mut i, &mut line, &mut column, 1);
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax_error(start_line, start_column, "Unclosed string")),
+                    Some('"') => break,
+                    Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                        text.push('"');
+                        advance(&mut i, &mut line, &mut column, 1);
+                    }
+                    // Line continuation
+                    Some('\\') if chars.get(i + 1) == Some(&'\n') => {
+                        advance(&mut i, &mut line, &mut column, 1);
+                    }
+                    Some(c) => text.push(*c),
+                }
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            advance(&mut i, &mut line, &mut column, 1);
+            TokenKind::Id(text, true)
+        } else if c == '<' {
+            let mut depth = 0;
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    None => return Err(syntax_error(start_line, start_column, "Unclosed HTML string")),
+                    Some('<') => depth += 1,
+                    Some('>') => depth -= 1,
+                    _ => {}
+                }
+                text.push(chars[i]);
+                advance(&mut i, &mut line, &mut column, 1);
+                if depth == 0 {
+                    break;
+                }
+            }
+            let inner = &text[1..text.len() - 1];
+            TokenKind::Id(drawio::html_to_text(inner), true)
+        } else if c == '-' && matches!(next, Some('>' | '-')) {
+            advance(&mut i, &mut line, &mut column, 2);
+            TokenKind::EdgeOp
+        } else if c.is_alphanumeric() || c == '_' || c == '.' || (c == '-' && next.is_some_and(|n| n.is_ascii_digit() || n == '.')) {
+            let numeral = c.is_ascii_digit() || c == '.' || c == '-';
+            let mut text = String::new();
+            while let Some(&c) = chars.get(i) {
+                let accepted = if numeral {
+                    c.is_ascii_digit() || c == '.' || (c == '-' && text.is_empty())
+                } else {
+                    c.is_alphanumeric() || c == '_'
+                };
+                if !accepted {
+                    break;
+                }
+                text.push(c);
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            TokenKind::Id(text, false)
+        } else if "{}[]=;,:+".contains(c) {
+            advance(&mut i, &mut line, &mut column, 1);
+            TokenKind::Punct(c)
+        } else {
+            return Err(syntax_error(start_line, start_column, format!("Unexpected character '{}'", c)));
+        };
+
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    Ok(tokens)
+}
+
+struct Node {
+    name: String,
+    attributes: Attributes,
+    subgraph: Option<usize>,
+}
+
+struct Subgraph {
+    name: String,
+    attributes: Attributes,
+    parent: Option<usize>,
+}
+
+impl Subgraph {
+    /// Graphviz only draws `cluster*` subgraphs; labelled ones are kept as frames too
+    fn is_frame(&self) -> bool {
+        self.name.starts_with("cluster") || self.attributes.contains_key("label")
+    }
+
+    /// Element id of the frame, numbered for anonymous subgraphs so each gets its own
+    fn frame_id(&self, index: usize) -> String {
+        if self.name.is_empty() {
+            format!("subgraph-{}", index)
+        } else {
+            format!("cluster-{}", self.name)
+        }
+    }
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    attributes: Attributes,
+}
+
+#[derive(Clone, Default)]
+struct Scope {
+    subgraph: Option<usize>,
+    node_defaults: Attributes,
+    edge_defaults: Attributes,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    directed: bool,
+    attributes: Attributes,
+    nodes: Vec<Node>,
+    node_index: HashMap<String, usize>,
+    subgraphs: Vec<Subgraph>,
+    edges: Vec<Edge>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|t| &t.kind)
+    }
+
+    fn error_here(&self, message: impl std::fmt::Display) -> String {
+        match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(token) => syntax_error(token.line, token.column, message),
+            None => syntax_error(1, 1, message),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Id(text, false)) if text.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&TokenKind::Punct(c))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.is_punct(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error_here(format!("Expected '{}'", c)))
+        }
+    }
+
+    /// An id, joining `"a" + "b"` concatenations
+    fn id(&mut self) -> Result<String, String> {
+        let Some(TokenKind::Id(text, _)) = self.peek().cloned() else {
+            return Err(self.error_here("Expected an identifier"));
+        };
+        self.pos += 1;
+        let mut text = text;
+        while self.is_punct('+') && matches!(self.peek_at(1), Some(TokenKind::Id(_, true))) {
+            if let Some(TokenKind::Id(more, _)) = self.peek_at(1).cloned() {
+                text.push_str(&more);
+            }
+            self.pos += 2;
+        }
+        Ok(text)
+    }
+
+    fn graph(&mut self) -> Result<(), String> {
+        if self.is_keyword("strict") {
+            self.pos += 1;
+        }
+        if self.is_keyword("digraph") {
+            self.directed = true;
+        } else if !self.is_keyword("graph") {
+            return Err(self.error_here("Expected 'graph' or 'digraph'"));
+        }
+        self.pos += 1;
+        if !self.is_punct('{') {
+            self.id()?;
+        }
+        self.expect('{')?;
+        let mut scope = Scope::default();
+        self.statements(&mut scope)?;
+        self.expect('}')?;
+        if self.pos < self.tokens.len() {
+            return Err(self.error_here("Unexpected content after the closing '}'"));
+        }
+        Ok(())
+    }
+
+    /// Parses statements up to the closing `}` and returns the nodes they mention
+    fn statements(&mut self, scope: &mut Scope) -> Result<Vec<usize>, String> {
+        let mut mentioned = Vec::new();
+        while self.peek().is_some() && !self.is_punct('}') {
+            mentioned.extend(self.statement(scope)?);
+            if self.is_punct(';') {
+                self.pos += 1;
+            }
+        }
+        Ok(mentioned)
+    }
+
+    fn attribute_list(&mut self) -> Result<Attributes, String> {
+        let mut attributes = Attributes::new();
+        while self.is_punct('[') {
+            self.pos += 1;
+            while !self.is_punct(']') {
+                let key = self.id()?;
+                self.expect('=')?;
+                let value = self.id()?;
+                attributes.insert(key, value);
+                if self.is_punct(',') || self.is_punct(';') {
+                    self.pos += 1;
+                }
+            }
+            self.pos += 1;
+        }
+        Ok(attributes)
+    }
+
+    fn scope_attributes(&mut self, scope: &Scope) -> &mut Attributes {
+        match scope.subgraph {
+            Some(index) => &mut self.subgraphs[index].attributes,
+            None => &mut self.attributes,
+        }
+    }
+
+    fn statement(&mut self, scope: &mut Scope) -> Result<Vec<usize>, String> {
+        if self.peek_at(1) == Some(&TokenKind::Punct('[')) {
+            for (keyword, target) in [("graph", 0), ("node", 1), ("edge", 2)] {
+                if self.is_keyword(keyword) {
+                    self.pos += 1;
+                    let attributes = self.attribute_list()?;
+                    match target {
+                        0 => self.scope_attributes(scope).extend(attributes),
+                        1 => scope.node_defaults.extend(attributes),
+                        _ => scope.edge_defaults.extend(attributes),
+                    }
+                    return Ok(Vec::new());
+                }
+            }
+        }
+        if matches!(self.peek(), Some(TokenKind::Id(..))) && self.peek_at(1) == Some(&TokenKind::Punct('=')) {
+            let key = self.id()?;
+            self.pos += 1;
+            let value = self.id()?;
+            self.scope_attributes(scope).insert(key, value);
+            return Ok(Vec::new());
+        }
+
+        let mut group = self.endpoint(scope)?;
+        let mut mentioned = group.clone();
+        if self.peek() != Some(&TokenKind::EdgeOp) {
+            // A node statement
+            if let [node] = group[..]
+                && self.is_punct('[')
+            {
+                let attributes = self.attribute_list()?;
+                self.nodes[node].attributes.extend(attributes);
+            }
+            return Ok(mentioned);
+        }
+
+        let mut links = Vec::new();
+        while self.peek() == Some(&TokenKind::EdgeOp) {
+            self.pos += 1;
+            let next = self.endpoint(scope)?;
+            links.push((group, next.clone()));
+            mentioned.extend(&next);
+            group = next;
+        }
+        let mut attributes = scope.edge_defaults.clone();
+        attributes.extend(self.attribute_list()?);
+        for (sources, targets) in links {
+            for from in &sources {
+                for to in &targets {
+                    self.edges.push(Edge {
+                        from: *from,
+                        to: *to,
+                        attributes: attributes.clone(),
+                    });
+                }
+            }
+        }
+        Ok(mentioned)
+    }
+
+    /// A node id or a subgraph, as the nodes it stands for
+    fn endpoint(&mut self, scope: &mut Scope) -> Result<Vec<usize>, String> {
+        if self.is_keyword("subgraph") || self.is_punct('{') {
+            return self.subgraph(scope);
+        }
+        if matches!(self.peek(), Some(TokenKind::Id(text, false)) if ["node", "edge", "graph"].contains(&text.to_lowercase().as_str()))
+        {
+            return Err(self.error_here("Expected '[' after the keyword"));
+        }
+
+        let name = self.id()?;
+        // Ports only say where an edge attaches
+        while self.is_punct(':') {
+            self.pos += 1;
+            self.id()?;
+        }
+        Ok(vec![self.node(&name, scope)])
+    }
+
+    fn node(&mut self, name: &str, scope: &Scope) -> usize {
+        let index = match self.node_index.get(name) {
+            Some(index) => *index,
+            None => {
+                self.nodes.push(Node {
+                    name: name.to_string(),
+                    attributes: scope.node_defaults.clone(),
+                    subgraph: scope.subgraph,
+                });
+                self.node_index.insert(name.to_string(), self.nodes.len() - 1);
+                return self.nodes.len() - 1;
+            }
+        };
+        // A node mentioned again in a nested subgraph moves into it
+        if let Some(current) = scope.subgraph
+            && self.nodes[index].subgraph.is_none_or(|existing| self.is_ancestor(existing, current))
+        {
+            self.nodes[index].subgraph = Some(current);
+        }
+        index
+    }
+
+    fn is_ancestor(&self, ancestor: usize, subgraph: usize) -> bool {
+        std::iter::successors(Some(subgraph), |s| self.subgraphs[*s].parent).any(|s| s == ancestor)
+    }
+
+    fn subgraph(&mut self, scope: &mut Scope) -> Result<Vec<usize>, String> {
+        let mut name = String::new();
+        if self.is_keyword("subgraph") {
+            self.pos += 1;
+            if !self.is_punct('{') {
+                name = self.id()?;
+            }
+        }
+
+        // Subgraphs with the same name are the same subgraph
+        let existing = (!name.is_empty())
+            .then(|| self.subgraphs.iter().position(|s| s.name == name))
+            .flatten();
+        let index = existing.unwrap_or_else(|| {
+            self.subgraphs.push(Subgraph {
+                name,
+                attributes: Attributes::new(),
+                parent: scope.subgraph,
+            });
+            self.subgraphs.len() - 1
+        });
+
+        self.expect('{')?;
+        let mut inner = Scope {
+            subgraph: Some(index),
+            ..scope.clone()
+        };
+        let mentioned = self.statements(&mut inner)?;
+        self.expect('}')?;
+        Ok(mentioned)
+    }
+}
+
+/// Label text with Graphviz escapes resolved; `\N` and `\G` stand for the node and graph names
+fn label_text(label: &str, name: &str) -> String {
+    let text = label
+        .replace("\\N", name)
+        .replace("\\G", name)
+        .replace("\\n", "\n")
+        .replace("\\l", "\n")
+        .replace("\\r", "\n")
+        .replace("\\\\", "\\");
+    text.trim_end_matches('\n').to_string()
+}
+
+/// Record labels like `{a|b}` shown as one field per line
+fn record_text(label: &str) -> String {
+    label
+        .replace(['{', '}'], "")
+        .split('|')
+        .map(|field| {
+            // `<port> text` names a field for edges
+            let field = field.trim();
+            match field.strip_prefix('<').and_then(|f| f.split_once('>')) {
+                Some((_, text)) => text.trim(),
+                None => field,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn number(attributes: &Attributes, key: &str) -> Option<f64> {
+    attributes.get(key).and_then(|v| v.trim().parse().ok())
+}
+
+fn has_style(attributes: &Attributes, style: &str) -> bool {
+    attributes
+        .get("style")
+        .is_some_and(|s| s.split(',').any(|part| part.trim() == style))
+}
+
+/// Graphviz colors are X11 names or hex, which canvas colors accept; lists keep the first
+fn color(value: &str) -> String {
+    value.split([':', ';']).next().unwrap_or(value).trim().to_string()
+}
+
+fn element_style(attributes: &Attributes, font_size: f64) -> (Style, String) {
+    let mut style = Style {
+        font_size: number(attributes, "fontsize").unwrap_or(font_size),
+        ..Style::default()
+    };
+    if let Some(value) = attributes.get("color") {
+        style.stroke_color = color(value);
+    }
+    if has_style(attributes, "filled") {
+        style.background_color = attributes
+            .get("fillcolor")
+            .or(attributes.get("color"))
+            .map(|c| color(c))
+            .unwrap_or_else(|| "lightgrey".to_string());
+    }
+    if has_style(attributes, "dashed") {
+        style.stroke_style = "dashed".to_string();
+    } else if has_style(attributes, "dotted") {
+        style.stroke_style = "dotted".to_string();
+    }
+    style.rounded = has_style(attributes, "rounded");
+    if let Some(width) = number(attributes, "penwidth") {
+        style.stroke_width = width * 2.0;
+    } else if has_style(attributes, "bold") {
+        style.stroke_width = 4.0;
+    }
+    let text_color = attributes
+        .get("fontcolor")
+        .map(|c| color(c))
+        .unwrap_or_else(|| style.stroke_color.clone());
+    (style, text_color)
+}
+
+fn arrowhead(value: &str) -> Option<&'static str> {
+    // Modifiers like `l`/`r` halves are dropped
+    match value.trim_start_matches(['l', 'r']) {
+        "none" => None,
+        "vee" | "open" => Some("arrow"),
+        "empty" | "onormal" | "inv" | "oinv" => Some("triangle_outline"),
+        "dot" => Some("circle"),
+        "odot" => Some("circle_outline"),
+        "diamond" => Some("diamond"),
+        "odiamond" | "ediamond" => Some("diamond_outline"),
+        "tee" => Some("bar"),
+        _ => Some("triangle"),
+    }
+}
+
+/// Element type of a node shape, or `text` for shapes drawn without a border
+fn shape_type(shape: &str) -> &'static str {
+    match shape {
+        "ellipse" | "oval" | "circle" | "doublecircle" | "point" => "ellipse",
+        "diamond" | "Mdiamond" => "diamond",
+        "plaintext" | "plain" | "none" | "underline" => "text",
+        _ => "rectangle",
+    }
+}
+
+/// Parses DOT source and lays it out as a scene
+pub fn dot_to_scene(source: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        directed: false,
+        attributes: Attributes::new(),
+        nodes: Vec::new(),
+        node_index: HashMap::new(),
+        subgraphs: Vec::new(),
+        edges: Vec::new(),
+    };
+    parser.graph()?;
+
+    let direction = parser
+        .attributes
+        .get("rankdir")
+        .and_then(|d| Direction::parse(d))
+        .unwrap_or(Direction::TopBottom);
+    let font_size = number(&parser.attributes, "fontsize").unwrap_or(DEFAULT_FONT_SIZE);
+    let mut graph = Graph::new(direction);
+    if let Some(gap) = number(&parser.attributes, "ranksep") {
+        graph.rank_gap = gap * POINTS_PER_INCH;
+    }
+    if let Some(gap) = number(&parser.attributes, "nodesep") {
+        graph.node_gap = gap * POINTS_PER_INCH;
+    }
+    // Edge labels sit between ranks, so leave room for the largest one
+    for edge in &parser.edges {
+        if let Some(label) = edge.attributes.get("label") {
+            let (width, height) = elements::text_size(&label_text(label, ""), font_size);
+            let across = match direction {
+                Direction::LeftRight | Direction::RightLeft => width,
+                _ => height,
+            };
+            graph.rank_gap = graph.rank_gap.max(across + 40.0);
+        }
+    }
+
+    // Subgraphs that are not frames only group nodes for the parser
+    let frame_of = |mut subgraph: Option<usize>| {
+        while let Some(index) = subgraph {
+            if parser.subgraphs[index].is_frame() {
+                return Some(index);
+            }
+            subgraph = parser.subgraphs[index].parent;
+        }
+        None
+    };
+    let mut cluster_index = HashMap::new();
+    let mut frames = Vec::new();
+    for (index, subgraph) in parser.subgraphs.iter().enumerate() {
+        if !subgraph.is_frame() {
+            continue;
+        }
+        let name = subgraph.attributes.get("label").map(|l| label_text(l, &subgraph.name)).unwrap_or_else(|| subgraph.name.clone());
+        let parent = frame_of(subgraph.parent).map(|p| cluster_index[&p]);
+        let width = elements::text_size(&name, font_size).0;
+        cluster_index.insert(index, graph.add_cluster(parent, (width, 0.0)));
+        frames.push((index, name));
+    }
+
+    let mut labels = Vec::new();
+    for node in &parser.nodes {
+        let shape = node.attributes.get("shape").map(String::as_str).unwrap_or("ellipse");
+        let raw = node.attributes.get("label").map(String::as_str).unwrap_or("\\N");
+        let mut label = label_text(raw, &node.name);
+        if matches!(shape, "record" | "Mrecord") {
+            label = record_text(&label);
+        }
+        let size = number(&node.attributes, "fontsize").unwrap_or(font_size);
+        let (text_width, text_height) = elements::text_size(&label, size);
+        let (mut width, mut height) = match shape_type(shape) {
+            "text" => (text_width, text_height),
+            "diamond" => (text_width * 1.5 + 40.0, text_height * 1.5 + 40.0),
+            "ellipse" => (text_width * 1.4 + 20.0, text_height * 1.4 + 20.0),
+            _ => (text_width + 40.0, text_height + 30.0),
+        };
+        if matches!(shape, "circle" | "doublecircle" | "square") {
+            width = width.max(height);
+            height = width;
+        }
+        // Graphviz width and height are minimums unless `fixedsize` is set
+        if let Some(w) = number(&node.attributes, "width") {
+            width = width.max(w * POINTS_PER_INCH);
+        }
+        if let Some(h) = number(&node.attributes, "height") {
+            height = height.max(h * POINTS_PER_INCH);
+        }
+        if shape == "point" {
+            (width, height) = (10.0, 10.0);
+            label.clear();
+        }
+        let cluster = frame_of(node.subgraph).map(|s| cluster_index[&s]);
+        graph.add_node((width, height), cluster);
+        labels.push(label);
+    }
+    for edge in &parser.edges {
+        graph.add_edge(Vertex::Node(edge.from), Vertex::Node(edge.to));
+    }
+    let placed = layout::layout(&graph);
+
+    let node_id = |index: usize| format!("node-{}", parser.nodes[index].name);
+    let frame_id = |subgraph: Option<usize>| frame_of(subgraph).map(|s| parser.subgraphs[s].frame_id(s));
+    let mut out = Vec::new();
+
+    for (index, node) in parser.nodes.iter().enumerate() {
+        if has_style(&node.attributes, "invis") {
+            continue;
+        }
+        let shape = node.attributes.get("shape").map(String::as_str).unwrap_or("ellipse");
+        let (mut style, text_color) = element_style(&node.attributes, font_size);
+        let kind = shape_type(shape);
+        let bounds = placed.nodes[index];
+        let id = node_id(index);
+        let frame = frame_id(node.subgraph);
+
+        if kind == "text" {
+            style.stroke_color = text_color;
+            let mut text = elements::text(&id, (bounds.0, bounds.1), &labels[index], &style);
+            text["frameId"] = json!(frame);
+            out.push(text);
+            continue;
+        }
+        if shape == "point" {
+            style.background_color = style.stroke_color.clone();
+        }
+        let mut element = elements::shape(kind, &id, bounds, &style);
+        element["frameId"] = json!(frame);
+        let label = (!labels[index].is_empty()).then(|| {
+            let mut label_style = style.clone();
+            label_style.stroke_color = text_color;
+            let mut label = elements::label(&format!("{}-label", id), &mut element, &labels[index], &label_style);
+            label["frameId"] = json!(frame);
+            label
+        });
+        out.push(element);
+        out.extend(label);
+    }
+
+    for (index, edge) in parser.edges.iter().enumerate() {
+        let attributes = &edge.attributes;
+        if has_style(attributes, "invis") {
+            continue;
+        }
+        let kind_of = |node: usize| {
+            let shape = parser.nodes[node].attributes.get("shape").map(String::as_str).unwrap_or("ellipse");
+            match shape_type(shape) {
+                "text" => "rectangle",
+                kind => kind,
+            }
+        };
+        let points = if edge.from == edge.to {
+            elements::self_loop(placed.nodes[edge.from])
+        } else {
+            elements::route(
+                (kind_of(edge.from), placed.nodes[edge.from]),
+                (kind_of(edge.to), placed.nodes[edge.to]),
+                &placed.edges[index],
+            )
+        };
+
+        let default_dir = if parser.directed { "forward" } else { "none" };
+        let dir = attributes.get("dir").map(String::as_str).unwrap_or(default_dir);
+        let head = || arrowhead(attributes.get("arrowhead").map(String::as_str).unwrap_or("normal"));
+        let tail = || arrowhead(attributes.get("arrowtail").map(String::as_str).unwrap_or("normal"));
+        let (start, end) = match dir {
+            "forward" => (None, head()),
+            "back" => (tail(), None),
+            "both" => (tail(), head()),
+            _ => (None, None),
+        };
+
+        let (style, text_color) = element_style(attributes, font_size);
+        let id = format!("edge-{}", index + 1);
+        let mut arrow = elements::linear("arrow", &id, &points, &style, start, end);
+        let (from_frame, to_frame) = (frame_id(parser.nodes[edge.from].subgraph), frame_id(parser.nodes[edge.to].subgraph));
+        if from_frame == to_frame {
+            arrow["frameId"] = json!(from_frame);
+        }
+        let label = attributes.get("label").or(attributes.get("xlabel")).map(|text| {
+            let mut label_style = style.clone();
+            label_style.stroke_color = text_color;
+            let mut label = elements::label(&format!("{}-label", id), &mut arrow, &label_text(text, ""), &label_style);
+            label["frameId"] = arrow["frameId"].clone();
+            label
+        });
+        out.push(arrow);
+        out.extend(label);
+
+        let bound = |node: usize| (!has_style(&parser.nodes[node].attributes, "invis")).then(|| node_id(node));
+        elements::bind_arrow(&mut out, &id, bound(edge.from).as_deref(), bound(edge.to).as_deref());
+    }
+
+    for (index, name) in &frames {
+        let subgraph = &parser.subgraphs[*index];
+        let (mut style, _) = element_style(&subgraph.attributes, font_size);
+        style.roughness = 0.0;
+        let mut frame = elements::shape("frame", &subgraph.frame_id(*index), placed.clusters[cluster_index[index]], &style);
+        frame["name"] = json!(name);
+        out.push(frame);
+    }
+
+    // The graph label goes above the drawing
+    if let Some(title) = parser.attributes.get("label") {
+        let title = label_text(title, "");
+        let all: Vec<&Value> = out.iter().collect();
+        let (x1, y1, _, _) = render::scene_bounds(&all).unwrap_or((0.0, 0.0, 0.0, 0.0));
+        let style = Style {
+            font_size: font_size * 1.25,
+            text_align: "left".to_string(),
+            ..Style::default()
+        };
+        let height = elements::text_size(&title, style.font_size).1;
+        out.push(elements::text("graph-label", (x1, y1 - height - 30.0), &title, &style));
+    }
+
+    Ok(elements::scene(out))
+}
+
+/// Imports a Graphviz DOT file as a laid out drawing
+#[tauri::command]
+pub async fn import_dot(file_path: String, output_dir: Option<String>) -> Result<String, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let source = fs::read_to_string(&validated_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let scene = dot_to_scene(&source)?;
+
+    let dir = match output_dir {
+        Some(dir) => security::validate_path(Path::new(&dir), None)?,
+        None => validated_path
+            .parent()
+            .ok_or("File has no parent directory")?
+            .to_path_buf(),
+    };
+    let stem = validated_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "graph".to_string());
+
+    let path = file_ops::unique_file_path(&dir, &format!("{}.excalidraw", stem))?;
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| format!("Failed to serialize content: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element<'a>(scene: &'a Value, id: &str) -> &'a Value {
+        scene["elements"].as_array().unwrap().iter().find(|e| e["id"] == id).unwrap()
+    }
+
+    #[test]
+    fn reports_where_syntax_errors_are() {
+        let cases = [
+            ("digraph {\n  a -> b /* open", "line 2, column 10: Unclosed comment"),
+            ("digraph { a [label=\"x]; }", "line 1, column 20: Unclosed string"),
+            ("digraph { a -> b; } extra", "line 1, column 21: Unexpected content after the closing '}'"),
+            ("graph { a -- b ; node a }", "line 1, column 18: Expected '[' after the keyword"),
+            ("strict { }", "line 1, column 8: Expected 'graph' or 'digraph'"),
+            ("digraph { a ; }\n  @", "line 2, column 3: Unexpected character '@'"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(dot_to_scene(source).unwrap_err(), format!("DOT syntax error at {}", expected));
+        }
+    }
+
+    #[test]
+    fn converts_nodes_and_edges() {
+        let source = r#"digraph G {
+            rankdir=LR;
+            node [shape=box];
+            a [label="Start"];
+            b [shape=ellipse];
+            a -> b [label="go"];
+        }"#;
+        let scene = dot_to_scene(source).unwrap();
+        let (a, b) = (element(&scene, "node-a"), element(&scene, "node-b"));
+        assert_eq!(a["type"], "rectangle");
+        assert_eq!(b["type"], "ellipse");
+        assert_eq!(element(&scene, "node-a-label")["text"], "Start");
+        assert!(b["x"].as_f64().unwrap() > a["x"].as_f64().unwrap());
+
+        let edge = element(&scene, "edge-1");
+        assert_eq!(edge["startBinding"]["elementId"], "node-a");
+        assert_eq!(edge["endBinding"]["elementId"], "node-b");
+        // DOT draws `normal`, a filled triangle, by default
+        assert_eq!(edge["endArrowhead"], "triangle");
+        assert_eq!(element(&scene, "edge-1-label")["text"], "go");
+    }
+
+    #[test]
+    fn anonymous_subgraphs_get_their_own_frames() {
+        let scene = dot_to_scene(r#"digraph { subgraph { label="A"; a } subgraph { label="B"; b } }"#).unwrap();
+        let frames: Vec<&Value> = scene["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|e| e["type"] == "frame")
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_ne!(frames[0]["id"], frames[1]["id"]);
+        for (node, frame) in [("node-a", "A"), ("node-b", "B")] {
+            let frame_id = &element(&scene, node)["frameId"];
+            let owner = frames.iter().find(|f| &f["id"] == frame_id).unwrap();
+            assert_eq!(owner["name"], frame);
+        }
+    }
+
+    #[test]
+    fn undirected_edges_have_no_arrowheads() {
+        let scene = dot_to_scene("graph { a -- b }").unwrap();
+        assert!(element(&scene, "edge-1")["endArrowhead"].is_null());
+    }
+}
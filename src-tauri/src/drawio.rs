@@ -0,0 +1,857 @@
+use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use elements::Style;
+use flate2::read::DeflateDecoder;
+use render::{Bounds, Point};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// draw.io's default label size
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+
+/// A page of a draw.io file converted to a scene
+#[derive(Debug)]
+pub struct DrawioPage {
+    pub name: String,
+    pub scene: Value,
+    /// Cells that could not be converted
+    pub skipped: Vec<models::SkippedElement>,
+}
+
+#[derive(Debug, Default)]
+struct Geometry {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    points: Vec<Point>,
+    source_point: Option<Point>,
+    target_point: Option<Point>,
+}
+
+#[derive(Debug)]
+struct Cell {
+    id: String,
+    parent: Option<String>,
+    value: String,
+    link: Option<String>,
+    style: HashMap<String, String>,
+    /// First style token without `=`, such as `ellipse` or `text`
+    shape: Option<String>,
+    vertex: bool,
+    edge: bool,
+    source: Option<String>,
+    target: Option<String>,
+    geometry: Geometry,
+}
+
+impl Cell {
+    fn style(&self, key: &str) -> Option<&str> {
+        self.style.get(key).map(|s| s.as_str())
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.style(key) == Some("1")
+    }
+
+    fn number(&self, key: &str) -> Option<f64> {
+        self.style(key).and_then(|v| v.parse().ok())
+    }
+
+    fn kind(&self) -> &str {
+        self.style("shape").or(self.shape.as_deref()).unwrap_or("")
+    }
+}
+
+/// Parses every page of a `.drawio` file, compressed or not
+pub fn parse_drawio(xml: &str) -> Result<Vec<DrawioPage>, String> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| format!("Invalid draw.io file: {}", e))?;
+    let root = doc.root_element();
+
+    match root.tag_name().name() {
+        "mxGraphModel" => Ok(vec![convert_model(root, "Page-1".to_string())?]),
+        "mxfile" => root
+            .children()
+            .filter(|n| n.has_tag_name("diagram"))
+            .enumerate()
+            .map(|(index, diagram)| {
+                let name = diagram
+                    .attribute("name")
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| format!("Page-{}", index + 1));
+                match diagram.children().find(|n| n.has_tag_name("mxGraphModel")) {
+                    Some(model) => convert_model(model, name),
+                    None => {
+                        let model_xml = decompress_diagram(diagram.text().unwrap_or_default())?;
+                        let model_doc = roxmltree::Document::parse(&model_xml)
+                            .map_err(|e| format!("Invalid diagram on page {}: {}", name, e))?;
+                        convert_model(model_doc.root_element(), name)
+                    }
+                }
+            })
+            .collect(),
+        other => Err(format!("Not a draw.io file: unexpected <{}> element", other)),
+    }
+}
+
+/// Compressed pages are base64 of raw deflate of the URI-encoded model XML
+fn decompress_diagram(text: &str) -> Result<String, String> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.is_empty() {
+        return Ok("<mxGraphModel><root/></mxGraphModel>".to_string());
+    }
+
+    let deflated = BASE64
+        .decode(compact)
+        .map_err(|e| format!("Invalid compressed diagram: {}", e))?;
+    let mut inflated = String::new();
+    DeflateDecoder::new(deflated.as_slice())
+        .read_to_string(&mut inflated)
+        .map_err(|e| format!("Failed to decompress diagram: {}", e))?;
+
+    if inflated.starts_with('<') {
+        Ok(inflated)
+    } else {
+        percent_decode(&inflated)
+    }
+}
+
+pub fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3).ok_or("Invalid escape in diagram")?;
+            out.push(u8::from_str_radix(hex, 16).map_err(|_| "Invalid escape in diagram")?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|e| format!("Invalid diagram text: {}", e))
+}
+
+fn parse_style(style: &str) -> (HashMap<String, String>, Option<String>) {
+    let mut map = HashMap::new();
+    let mut shape = None;
+    for token in style.split(';').filter(|t| !t.is_empty()) {
+        match token.split_once('=') {
+            Some((key, value)) => {
+                map.insert(key.to_string(), value.to_string());
+            }
+            None if shape.is_none() => shape = Some(token.to_string()),
+            None => {}
+        }
+    }
+    (map, shape)
+}
+
+fn parse_point(node: roxmltree::Node) -> Point {
+    let number = |name: &str| node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(0.0);
+    (number("x"), number("y"))
+}
+
+fn parse_geometry(cell: roxmltree::Node) -> Geometry {
+    let Some(node) = cell.children().find(|n| n.has_tag_name("mxGeometry")) else {
+        return Geometry::default();
+    };
+    let number = |name: &str| node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(0.0);
+
+    let mut geometry = Geometry {
+        x: number("x"),
+        y: number("y"),
+        width: number("width"),
+        height: number("height"),
+        ..Geometry::default()
+    };
+    for child in node.children().filter(|n| n.is_element()) {
+        match (child.tag_name().name(), child.attribute("as")) {
+            ("mxPoint", Some("sourcePoint")) => geometry.source_point = Some(parse_point(child)),
+            ("mxPoint", Some("targetPoint")) => geometry.target_point = Some(parse_point(child)),
+            ("Array", Some("points")) => {
+                geometry.points = child
+                    .children()
+                    .filter(|n| n.has_tag_name("mxPoint"))
+                    .map(parse_point)
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+    geometry
+}
+
+/// Reads `<mxCell>`s, including those wrapped in `<UserObject>` or `<object>`
+fn parse_cells(model: roxmltree::Node) -> Vec<Cell> {
+    let Some(root) = model.children().find(|n| n.has_tag_name("root")) else {
+        return Vec::new();
+    };
+
+    root.children()
+        .filter(|n| n.is_element())
+        .filter_map(|node| {
+            let (wrapper, cell) = if node.has_tag_name("mxCell") {
+                (None, node)
+            } else {
+                (Some(node), node.children().find(|n| n.has_tag_name("mxCell"))?)
+            };
+            let attribute = |name: &str| {
+                wrapper
+                    .and_then(|w| w.attribute(name))
+                    .or_else(|| cell.attribute(name))
+                    .map(|v| v.to_string())
+            };
+            let (style, shape) = parse_style(cell.attribute("style").unwrap_or_default());
+            let html = style.get("html").is_some_and(|v| v == "1");
+            let raw_value = attribute("label")
+                .or_else(|| cell.attribute("value").map(|v| v.to_string()))
+                .unwrap_or_default();
+
+            Some(Cell {
+                id: attribute("id")?,
+                parent: cell.attribute("parent").map(|v| v.to_string()),
+                value: if html { html_to_text(&raw_value) } else { raw_value },
+                link: wrapper.and_then(|w| w.attribute("link")).map(|v| v.to_string()),
+                style,
+                shape,
+                vertex: cell.attribute("vertex") == Some("1"),
+                edge: cell.attribute("edge") == Some("1"),
+                source: cell.attribute("source").map(|v| v.to_string()),
+                target: cell.attribute("target").map(|v| v.to_string()),
+                geometry: parse_geometry(cell),
+            })
+        })
+        .collect()
+}
+
+/// Plain text of an HTML label
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim_start_matches('/').to_lowercase();
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        if matches!(name, "br" | "div" | "p" | "li") && !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let decoded = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    decoded.trim_end_matches('\n').to_string()
+}
+
+fn color(value: Option<&str>, fallback: &str) -> String {
+    match value {
+        Some("none") => "transparent".to_string(),
+        Some(c) if c.starts_with('#') => c.to_string(),
+        _ => fallback.to_string(),
+    }
+}
+
+fn element_style(cell: &Cell) -> Style {
+    let sketch = cell.flag("sketch");
+    Style {
+        stroke_color: color(cell.style("strokeColor"), "#000000"),
+        background_color: color(cell.style("fillColor"), "transparent"),
+        fill_style: if sketch { "hachure".to_string() } else { "solid".to_string() },
+        stroke_width: cell.number("strokeWidth").unwrap_or(1.0),
+        stroke_style: if cell.flag("dashed") {
+            "dashed".to_string()
+        } else {
+            "solid".to_string()
+        },
+        roughness: if sketch { 1.0 } else { 0.0 },
+        opacity: cell.number("opacity").unwrap_or(100.0),
+        rounded: cell.flag("rounded") || (cell.edge && cell.flag("curved")),
+        angle: cell.number("rotation").unwrap_or(0.0).to_radians(),
+        font_size: cell.number("fontSize").unwrap_or(DEFAULT_FONT_SIZE),
+        font_family: match cell.style("fontFamily") {
+            Some(f) if f.contains("Courier") || f.contains("mono") => 3,
+            _ if sketch => 5,
+            _ => 2,
+        },
+        text_align: cell.style("align").unwrap_or("center").to_string(),
+        vertical_align: cell.style("verticalAlign").unwrap_or("middle").to_string(),
+    }
+}
+
+fn text_style(cell: &Cell) -> Style {
+    let mut style = element_style(cell);
+    style.stroke_color = color(cell.style("fontColor"), "#000000");
+    style.background_color = "transparent".to_string();
+    style.opacity = cell.number("textOpacity").unwrap_or(style.opacity);
+    style
+}
+
+fn arrowhead(value: Option<&str>, default: Option<&'static str>) -> Option<&'static str> {
+    match value {
+        None => default,
+        Some("none") | Some("") => None,
+        Some("block" | "blockThin") => Some("triangle"),
+        Some("oval") => Some("dot"),
+        Some("circle" | "circlePlus") => Some("circle"),
+        Some("diamond" | "diamondThin") => Some("diamond"),
+        Some("dash") => Some("bar"),
+        Some(_) => Some("arrow"),
+    }
+}
+
+/// Converts one `<mxGraphModel>` into a scene
+fn convert_model(model: roxmltree::Node, name: String) -> Result<DrawioPage, String> {
+    let cells = parse_cells(model);
+    let by_id: HashMap<&str, &Cell> = cells.iter().map(|c| (c.id.as_str(), c)).collect();
+
+    // Vertices nested in other vertices are positioned relative to them
+    let origin = |cell: &Cell| -> Point {
+        let mut offset = (0.0, 0.0);
+        let mut parent = cell.parent.as_deref();
+        while let Some(p) = parent.and_then(|id| by_id.get(id)).filter(|p| p.vertex) {
+            offset.0 += p.geometry.x;
+            offset.1 += p.geometry.y;
+            parent = p.parent.as_deref();
+        }
+        offset
+    };
+    let ancestors = |cell: &Cell| -> Vec<&str> {
+        let mut ids = Vec::new();
+        let mut parent = cell.parent.as_deref();
+        while let Some(p) = parent.and_then(|id| by_id.get(id)).filter(|p| p.vertex) {
+            ids.push(p.id.as_str());
+            parent = p.parent.as_deref();
+        }
+        ids
+    };
+
+    let mut out: Vec<Value> = Vec::new();
+    let mut skipped = Vec::new();
+    let mut shapes: HashMap<&str, (String, Bounds)> = HashMap::new();
+
+    for cell in cells.iter().filter(|c| c.vertex) {
+        // Labels attached to edges are handled with the edge
+        if cell.parent.as_deref().and_then(|p| by_id.get(p)).is_some_and(|p| p.edge) {
+            continue;
+        }
+        let (ox, oy) = origin(cell);
+        let x = ox + cell.geometry.x;
+        let y = oy + cell.geometry.y;
+        let bounds = (x, y, x + cell.geometry.width, y + cell.geometry.height);
+
+        let mut created = match cell.kind() {
+            "group" => Vec::new(),
+            "text" => {
+                if cell.value.trim().is_empty() {
+                    continue;
+                }
+                let mut style = text_style(cell);
+                style.vertical_align = "top".to_string();
+                let (width, height) = elements::text_size(&cell.value, style.font_size);
+                let tx = match style.text_align.as_str() {
+                    "left" => x,
+                    "right" => bounds.2 - width,
+                    _ => (x + bounds.2 - width) / 2.0,
+                };
+                let ty = (y + bounds.3 - height) / 2.0;
+                vec![elements::text(&cell.id, (tx, ty), &cell.value, &style)]
+            }
+            kind => {
+                let element_type = match kind {
+                    "ellipse" | "doubleEllipse" => "ellipse",
+                    "rhombus" => "diamond",
+                    _ => "rectangle",
+                };
+                let mut shape = elements::shape(element_type, &cell.id, bounds, &element_style(cell));
+                if let Some(link) = &cell.link {
+                    shape["link"] = Value::String(link.clone());
+                }
+                shapes.insert(cell.id.as_str(), (element_type.to_string(), bounds));
+                let mut created = Vec::new();
+                if !cell.value.trim().is_empty() {
+                    let label_id = format!("{}-label", cell.id);
+                    let mut style = text_style(cell);
+                    // Swimlanes and containers put their title at the top
+                    if kind == "swimlane" || cell.flag("container") {
+                        style.vertical_align = "top".to_string();
+                    }
+                    created.push(elements::label(&label_id, &mut shape, &cell.value, &style));
+                }
+                created.insert(0, shape);
+                created
+            }
+        };
+
+        for element in &mut created {
+            for group in ancestors(cell) {
+                elements::add_group(element, &format!("group-{}", group));
+            }
+        }
+        out.extend(created);
+    }
+
+    for cell in cells.iter().filter(|c| c.edge) {
+        let (ox, oy) = origin(cell);
+        let waypoints: Vec<Point> = cell
+            .geometry
+            .points
+            .iter()
+            .map(|(px, py)| (ox + px, oy + py))
+            .collect();
+        let source = cell.source.as_deref().and_then(|id| shapes.get(id));
+        let target = cell.target.as_deref().and_then(|id| shapes.get(id));
+        let center = |(x1, y1, x2, y2): Bounds| ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+
+        let start_toward = waypoints
+            .first()
+            .copied()
+            .or(target.map(|t| center(t.1)))
+            .or(cell.geometry.target_point.map(|(px, py)| (ox + px, oy + py)));
+        let end_toward = waypoints
+            .last()
+            .copied()
+            .or(source.map(|s| center(s.1)))
+            .or(cell.geometry.source_point.map(|(px, py)| (ox + px, oy + py)));
+
+        let start = match (source, start_toward) {
+            (Some((kind, bounds)), Some(toward)) => {
+                Some(elements::outline_point(kind, *bounds, toward, elements::BINDING_GAP))
+            }
+            _ => cell.geometry.source_point.map(|(px, py)| (ox + px, oy + py)),
+        };
+        let end = match (target, end_toward) {
+            (Some((kind, bounds)), Some(toward)) => {
+                Some(elements::outline_point(kind, *bounds, toward, elements::BINDING_GAP))
+            }
+            _ => cell.geometry.target_point.map(|(px, py)| (ox + px, oy + py)),
+        };
+        let (Some(start), Some(end)) = (start, end) else {
+            skipped.push(models::SkippedElement {
+                id: cell.id.clone(),
+                reason: format!("Edge on page {} has no end points", name),
+            });
+            continue;
+        };
+
+        let mut points = vec![start];
+        points.extend(waypoints);
+        points.push(end);
+
+        let style = element_style(cell);
+        let mut arrow = elements::linear(
+            "arrow",
+            &cell.id,
+            &points,
+            &style,
+            arrowhead(cell.style("startArrow"), None),
+            arrowhead(cell.style("endArrow"), Some("arrow")),
+        );
+
+        // The edge's own value plus any label cells attached to it
+        let labels: Vec<&str> = std::iter::once(cell.value.as_str())
+            .chain(
+                cells
+                    .iter()
+                    .filter(|c| c.vertex && c.parent.as_deref() == Some(cell.id.as_str()))
+                    .map(|c| c.value.as_str()),
+            )
+            .filter(|v| !v.trim().is_empty())
+            .collect();
+        let label = (!labels.is_empty()).then(|| {
+            elements::label(&format!("{}-label", cell.id), &mut arrow, &labels.join("\n"), &text_style(cell))
+        });
+
+        out.push(arrow);
+        out.extend(label);
+        elements::bind_arrow(
+            &mut out,
+            &cell.id,
+            source.and(cell.source.as_deref()),
+            target.and(cell.target.as_deref()),
+        );
+    }
+
+    Ok(DrawioPage {
+        name,
+        scene: elements::scene(out),
+        skipped,
+    })
+}
+
+/// Characters not allowed in file names on common platforms
+pub fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if "/\\:*?\"<>|".contains(c) { '-' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[tauri::command]
+pub async fn import_drawio(
+    file_path: String,
+    output_dir: Option<String>,
+) -> Result<models::DiagramImportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let xml = fs::read_to_string(&validated_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let pages = parse_drawio(&xml)?;
+
+    let dir = match output_dir {
+        Some(dir) => security::validate_path(Path::new(&dir), None)?,
+        None => validated_path
+            .parent()
+            .ok_or("File has no parent directory")?
+            .to_path_buf(),
+    };
+    let stem = validated_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "drawio".to_string());
+
+    let single = pages.len() == 1;
+    let mut report = models::DiagramImportReport {
+        created: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for page in pages {
+        let name = if single {
+            format!("{}.excalidraw", stem)
+        } else {
+            format!("{}-{}.excalidraw", stem, file_name_safe(&page.name))
+        };
+        let path = file_ops::unique_file_path(&dir, &name)?;
+        let content = serde_json::to_string_pretty(&page.scene)
+            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        report.created.push(path.to_string_lossy().to_string());
+        report.skipped.extend(page.skipped);
+    }
+
+    Ok(report)
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#xa;")
+}
+
+fn drawio_arrowhead(value: Option<&str>) -> &'static str {
+    match value {
+        None => "none",
+        Some("triangle" | "triangle_outline") => "block",
+        Some("dot" | "circle" | "circle_outline") => "oval",
+        Some("diamond" | "diamond_outline") => "diamond",
+        Some("bar") => "dash",
+        Some(_) => "classic",
+    }
+}
+
+fn common_style(element: &Value) -> String {
+    let mut style = String::new();
+    let stroke = render::str_field(element, "strokeColor").unwrap_or("#1e1e1e");
+    style.push_str(&format!("strokeColor={};", if stroke == "transparent" { "none" } else { stroke }));
+    style.push_str(&format!(
+        "strokeWidth={};",
+        render::num_field(element, "strokeWidth").unwrap_or(2.0)
+    ));
+    if matches!(render::str_field(element, "strokeStyle"), Some("dashed" | "dotted")) {
+        style.push_str("dashed=1;");
+    }
+    let opacity = render::num_field(element, "opacity").unwrap_or(100.0);
+    if opacity < 100.0 {
+        style.push_str(&format!("opacity={};", opacity));
+    }
+    let angle = render::num_field(element, "angle").unwrap_or(0.0);
+    if angle != 0.0 {
+        style.push_str(&format!("rotation={};", angle.to_degrees()));
+    }
+    if render::num_field(element, "roughness").unwrap_or(1.0) > 0.0 {
+        style.push_str("sketch=1;");
+    }
+    style
+}
+
+fn text_attributes(text: Option<&Value>) -> String {
+    let Some(text) = text else {
+        return String::new();
+    };
+    format!(
+        "fontSize={};fontColor={};align={};verticalAlign={};",
+        render::num_field(text, "fontSize").unwrap_or(20.0),
+        render::str_field(text, "strokeColor").unwrap_or("#1e1e1e"),
+        render::str_field(text, "textAlign").unwrap_or("center"),
+        render::str_field(text, "verticalAlign").unwrap_or("middle"),
+    )
+}
+
+/// Builds an uncompressed single-page `.drawio` document from the simple shapes of a
+/// scene. Returns the document and the elements that had no equivalent.
+pub fn scene_to_drawio(scene: &Value, page_name: &str) -> (String, Vec<models::SkippedElement>) {
+    let elements: Vec<&Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+        .unwrap_or_default();
+    let labels: HashMap<&str, &Value> = elements
+        .iter()
+        .filter_map(|e| Some((render::str_field(e, "containerId")?, *e)))
+        .collect();
+    let label_text = |id: &str| {
+        labels
+            .get(id)
+            .and_then(|t| render::str_field(t, "originalText").or(render::str_field(t, "text")))
+            .unwrap_or_default()
+    };
+
+    let mut cells = Vec::new();
+    let mut exported: Vec<&str> = Vec::new();
+    let mut skipped = Vec::new();
+    for element in &elements {
+        let id = render::str_field(element, "id").unwrap_or_default();
+        let kind = render::str_field(element, "type").unwrap_or_default();
+        let x = render::num_field(element, "x").unwrap_or(0.0);
+        let y = render::num_field(element, "y").unwrap_or(0.0);
+        let width = render::num_field(element, "width").unwrap_or(0.0);
+        let height = render::num_field(element, "height").unwrap_or(0.0);
+        let geometry = format!(
+            "<mxGeometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" as=\"geometry\"/>",
+            x, y, width, height
+        );
+
+        let shape_style = match kind {
+            "rectangle" => {
+                let rounded = element.get("roundness").is_some_and(|r| !r.is_null());
+                Some(format!("rounded={};whiteSpace=wrap;", u8::from(rounded)))
+            }
+            "ellipse" => Some("ellipse;whiteSpace=wrap;".to_string()),
+            "diamond" => Some("rhombus;whiteSpace=wrap;".to_string()),
+            _ => None,
+        };
+
+        if let Some(shape_style) = shape_style {
+            let fill = render::str_field(element, "backgroundColor").unwrap_or("transparent");
+            let style = format!(
+                "{}fillColor={};{}{}",
+                shape_style,
+                if fill == "transparent" { "none" } else { fill },
+                common_style(element),
+                text_attributes(labels.get(id).copied())
+            );
+            cells.push(format!(
+                "<mxCell id=\"{}\" value=\"{}\" style=\"{}\" vertex=\"1\" parent=\"1\">{}</mxCell>",
+                escape_attribute(id),
+                escape_attribute(label_text(id)),
+                escape_attribute(&style),
+                geometry
+            ));
+            exported.push(id);
+            continue;
+        }
+
+        match kind {
+            "text" if render::str_field(element, "containerId").is_none() => {
+                let content = render::str_field(element, "originalText")
+                    .or(render::str_field(element, "text"))
+                    .unwrap_or_default();
+                let style = format!("text;whiteSpace=wrap;{}", text_attributes(Some(element)));
+                cells.push(format!(
+                    "<mxCell id=\"{}\" value=\"{}\" style=\"{}\" vertex=\"1\" parent=\"1\">{}</mxCell>",
+                    escape_attribute(id),
+                    escape_attribute(content),
+                    escape_attribute(&style),
+                    geometry
+                ));
+            }
+            // Exported together with their container
+            "text" => {}
+            // Exported below, once every shape they may bind to is known
+            "arrow" | "line" => {}
+            _ => skipped.push(models::SkippedElement {
+                id: id.to_string(),
+                reason: format!("No draw.io equivalent for {}", kind),
+            }),
+        }
+    }
+
+    for element in elements.iter().filter(|e| elements::is_linear(e)) {
+        let id = render::str_field(element, "id").unwrap_or_default();
+        let points = elements::absolute_points(element);
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            skipped.push(models::SkippedElement {
+                id: id.to_string(),
+                reason: "Line without points".to_string(),
+            });
+            continue;
+        };
+        let binding = |field: &str| {
+            element
+                .get(field)
+                .and_then(|b| b.get("elementId"))
+                .and_then(|id| id.as_str())
+                .filter(|id| exported.contains(id))
+        };
+
+        let (start_arrow, end_arrow) = if render::str_field(element, "type") == Some("line") {
+            ("none", "none")
+        } else {
+            (
+                drawio_arrowhead(render::str_field(element, "startArrowhead")),
+                drawio_arrowhead(render::str_field(element, "endArrowhead")),
+            )
+        };
+        let rounded = element.get("roundness").is_some_and(|r| !r.is_null());
+        let style = format!(
+            "endArrow={};startArrow={};html=0;{}{}{}",
+            end_arrow,
+            start_arrow,
+            if rounded { "curved=1;" } else { "rounded=0;" },
+            common_style(element),
+            text_attributes(labels.get(id).copied())
+        );
+
+        let mut cell = format!(
+            "<mxCell id=\"{}\" value=\"{}\" style=\"{}\" edge=\"1\" parent=\"1\"",
+            escape_attribute(id),
+            escape_attribute(label_text(id)),
+            escape_attribute(&style)
+        );
+        if let Some(source) = binding("startBinding") {
+            cell.push_str(&format!(" source=\"{}\"", escape_attribute(source)));
+        }
+        if let Some(target) = binding("endBinding") {
+            cell.push_str(&format!(" target=\"{}\"", escape_attribute(target)));
+        }
+        cell.push_str("><mxGeometry relative=\"1\" as=\"geometry\">");
+        cell.push_str(&format!("<mxPoint x=\"{}\" y=\"{}\" as=\"sourcePoint\"/>", first.0, first.1));
+        cell.push_str(&format!("<mxPoint x=\"{}\" y=\"{}\" as=\"targetPoint\"/>", last.0, last.1));
+        if points.len() > 2 {
+            cell.push_str("<Array as=\"points\">");
+            for (px, py) in &points[1..points.len() - 1] {
+                cell.push_str(&format!("<mxPoint x=\"{}\" y=\"{}\"/>", px, py));
+            }
+            cell.push_str("</Array>");
+        }
+        cell.push_str("</mxGeometry></mxCell>");
+        cells.push(cell);
+    }
+
+    let xml = format!(
+        "<mxfile host=\"SAG-Excalidraw\">\n  <diagram id=\"page-1\" name=\"{}\">\n    <mxGraphModel grid=\"1\" gridSize=\"10\">\n      <root>\n        <mxCell id=\"0\"/>\n        <mxCell id=\"1\" parent=\"0\"/>\n{}      </root>\n    </mxGraphModel>\n  </diagram>\n</mxfile>\n",
+        escape_attribute(page_name),
+        cells
+            .iter()
+            .map(|c| format!("        {}\n", c))
+            .collect::<String>()
+    );
+    (xml, skipped)
+}
+
+#[tauri::command]
+pub async fn export_drawio(
+    file_path: String,
+    output_path: Option<String>,
+) -> Result<models::DiagramExportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+
+    let page_name = validated_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(formats::drawing_stem)
+        .unwrap_or("Page-1");
+    let (xml, skipped) = scene_to_drawio(&scene, page_name);
+
+    let output = render::output_path_for(&validated_path, output_path, "drawio")?;
+    fs::write(&output, xml).map_err(|e| format!("Failed to write draw.io file: {}", e))?;
+
+    Ok(models::DiagramExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = r#"<mxGraphModel><root><mxCell id="0"/><mxCell id="1" parent="0"/>
+        <mxCell id="a" value="Hi" vertex="1" parent="1"><mxGeometry x="10" y="20" width="100" height="40" as="geometry"/></mxCell>
+        <mxCell id="b" vertex="1" parent="1" style="ellipse;"><mxGeometry x="200" y="20" width="40" height="40" as="geometry"/></mxCell>
+        <mxCell id="e1" edge="1" parent="1" source="a" target="b"><mxGeometry relative="1" as="geometry"/></mxCell>
+        <mxCell id="e2" edge="1" parent="1" source="a"><mxGeometry relative="1" as="geometry"/></mxCell>
+        </root></mxGraphModel>"#;
+
+    fn element<'a>(scene: &'a Value, id: &str) -> Option<&'a Value> {
+        scene["elements"].as_array()?.iter().find(|e| e["id"] == id)
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(parse_drawio("<mxfile><diagram>").unwrap_err().starts_with("Invalid draw.io file"));
+        assert!(parse_drawio("<svg/>").unwrap_err().starts_with("Not a draw.io file"));
+        let bad_page = r#"<mxfile><diagram name="P">not base64!</diagram></mxfile>"#;
+        assert!(parse_drawio(bad_page).unwrap_err().starts_with("Invalid compressed diagram"));
+    }
+
+    #[test]
+    fn converts_cells_and_reports_edges_without_end_points() {
+        let pages = parse_drawio(MODEL).unwrap();
+        assert_eq!(pages.len(), 1);
+        let scene = &pages[0].scene;
+        assert_eq!(element(scene, "a").unwrap()["type"], "rectangle");
+        assert_eq!(element(scene, "b").unwrap()["type"], "ellipse");
+        assert_eq!(element(scene, "a-label").unwrap()["text"], "Hi");
+        assert_eq!(element(scene, "e1").unwrap()["endBinding"]["elementId"], "b");
+        assert!(element(scene, "e2").is_none());
+        let skipped: Vec<&str> = pages[0].skipped.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(skipped, ["e2"]);
+    }
+
+    #[test]
+    fn reads_compressed_pages() {
+        let xml = r#"<mxfile><diagram name="Compressed">jVBBDgIhDHxN7wj+AHW9+AgSmoUEZIOo7O8ltLjxsIkHks5MZ0oLSsc6ZbO4W7IYQJ1B6ZxSoSpWjSGAFN6COoGUoj2Qlx310FWxmIz38o/BkOFlwhOJuXqmMBesu7Gd4swJU8SS19YyDDRVrAT5E+LtbXGjgzmHfnYcemTOPAjP3+BthVbwFgNu1+razzE/</diagram></mxfile>"#;
+        let pages = parse_drawio(xml).unwrap();
+        assert_eq!(pages[0].name, "Compressed");
+        assert_eq!(element(&pages[0].scene, "a-label").unwrap()["text"], "Hi");
+    }
+
+    #[test]
+    fn exported_documents_import_again() {
+        let scene = parse_drawio(MODEL).unwrap().remove(0).scene;
+        let mut with_freedraw = scene.clone();
+        with_freedraw["elements"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "id": "f", "type": "freedraw", "points": [[0, 0], [1, 1]] }));
+
+        let (xml, skipped) = scene_to_drawio(&with_freedraw, "Page");
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].id, "f");
+
+        let again = parse_drawio(&xml).unwrap().remove(0).scene;
+        for id in ["a", "b", "a-label", "e1"] {
+            assert_eq!(element(&again, id).unwrap()["type"], element(&scene, id).unwrap()["type"]);
+        }
+        assert_eq!(element(&again, "e1").unwrap()["startBinding"]["elementId"], "a");
+    }
+}
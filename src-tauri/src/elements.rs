@@ -0,0 +1,510 @@
+use super::*;
+use render::{Bounds, Point};
+use serde_json::{Value, json};
+
+/// Distance kept between a bound arrow's end and the shape outline
+pub const BINDING_GAP: f64 = 4.0;
+
+const LINE_HEIGHT: f64 = 1.25;
+/// Space Excalidraw keeps between a container's edge and its label
+const BOUND_TEXT_PADDING: f64 = 5.0;
+
+/// Visual properties of generated elements, named after their Excalidraw fields
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub stroke_color: String,
+    pub background_color: String,
+    pub fill_style: String,
+    pub stroke_width: f64,
+    pub stroke_style: String,
+    pub roughness: f64,
+    pub opacity: f64,
+    pub rounded: bool,
+    pub angle: f64,
+    pub font_size: f64,
+    pub font_family: i64,
+    pub text_align: String,
+    pub vertical_align: String,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            stroke_color: "#1e1e1e".to_string(),
+            background_color: "transparent".to_string(),
+            fill_style: "solid".to_string(),
+            stroke_width: 2.0,
+            stroke_style: "solid".to_string(),
+            roughness: 1.0,
+            opacity: 100.0,
+            rounded: false,
+            angle: 0.0,
+            font_size: 20.0,
+            font_family: 5,
+            text_align: "center".to_string(),
+            vertical_align: "middle".to_string(),
+        }
+    }
+}
+
+/// Stable pseudo-random number derived from an id, for `seed` and `versionNonce`
+pub fn seed_for(id: &str) -> i64 {
+    // FNV-1a, so imports of the same file produce the same scene
+    let hash = id
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    (hash % 2_147_483_647) as i64
+}
+
+/// Short prefix that keeps the ids of generated elements unique when they are
+/// added to a scene that already has elements
+pub fn new_id_prefix() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    format!("{:x}", seed_for(&format!("{}:{}", nanos, count)))
+}
+
+/// Moves an element; points of linear elements are relative, so they stay as they are
+pub fn translate(element: &mut Value, dx: f64, dy: f64) {
+    for (key, delta) in [("x", dx), ("y", dy)] {
+        let value = render::num_field(element, key).unwrap_or(0.0);
+        element[key] = json!(value + delta);
+    }
+}
+
+/// Marks an edited element as a new version, so collaborators and reconciliation pick it up
+pub fn bump_version(element: &mut Value) {
+    let version = element.get("version").and_then(|v| v.as_i64()).unwrap_or(0);
+    element["version"] = json!(version + 1);
+    element["versionNonce"] = json!(seed_for(&new_id_prefix()));
+    element["updated"] = json!(chrono::Utc::now().timestamp_millis());
+}
+
+fn base(kind: &str, id: &str, (x1, y1, x2, y2): Bounds, style: &Style) -> Value {
+    json!({
+        "id": id,
+        "type": kind,
+        "x": x1,
+        "y": y1,
+        "width": x2 - x1,
+        "height": y2 - y1,
+        "angle": style.angle,
+        "strokeColor": style.stroke_color,
+        "backgroundColor": style.background_color,
+        "fillStyle": style.fill_style,
+        "strokeWidth": style.stroke_width,
+        "strokeStyle": style.stroke_style,
+        "roughness": style.roughness,
+        "opacity": style.opacity,
+        "groupIds": [],
+        "frameId": null,
+        "roundness": if style.rounded { json!({ "type": 3 }) } else { Value::Null },
+        "seed": seed_for(id),
+        "version": 1,
+        "versionNonce": seed_for(&format!("{}:nonce", id)),
+        "isDeleted": false,
+        "boundElements": [],
+        "updated": chrono::Utc::now().timestamp_millis(),
+        "link": null,
+        "locked": false,
+    })
+}
+
+/// A rectangle, ellipse, diamond or frame
+pub fn shape(kind: &str, id: &str, bounds: Bounds, style: &Style) -> Value {
+    let mut element = base(kind, id, bounds, style);
+    if kind == "frame" {
+        element["name"] = Value::Null;
+    }
+    element
+}
+
+/// East Asian wide and fullwidth characters, which take a full em in every font
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'     // Hangul Jamo
+        | '\u{2E80}'..='\u{303E}'   // CJK radicals, symbols and punctuation
+        | '\u{3041}'..='\u{33FF}'   // Kana, Bopomofo, CJK compatibility
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{A000}'..='\u{A4CF}'   // Yi
+        | '\u{AC00}'..='\u{D7A3}'   // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{FE30}'..='\u{FE4F}'   // CJK compatibility forms
+        | '\u{FF00}'..='\u{FF60}'   // Fullwidth forms
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}' // Emoji
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}' // CJK Extensions B and later
+    )
+}
+
+/// Rough advance of a character in em: a full em for wide characters, otherwise the
+/// average advance of Excalidraw's Latin fonts
+fn char_width(c: char) -> f64 {
+    if is_wide(c) { 1.0 } else { 0.6 }
+}
+
+/// Rough width of a single line of text
+fn line_width(line: &str, font_size: f64) -> f64 {
+    line.chars().map(char_width).sum::<f64>() * font_size
+}
+
+/// Rough size of a text block; Excalidraw remeasures it when the text is edited
+pub fn text_size(content: &str, font_size: f64) -> (f64, f64) {
+    let lines: Vec<&str> = content.split('\n').collect();
+    let widest = lines.iter().map(|l| line_width(l, font_size)).fold(0.0, f64::max);
+    (
+        widest.max(font_size * 0.5),
+        lines.len() as f64 * font_size * LINE_HEIGHT,
+    )
+}
+
+/// Free-standing text with its top left corner at `origin`
+pub fn text(id: &str, (x, y): Point, content: &str, style: &Style) -> Value {
+    let (width, height) = text_size(content, style.font_size);
+    let mut element = base("text", id, (x, y, x + width, y + height), style);
+    element["roundness"] = Value::Null;
+    element["text"] = json!(content);
+    element["originalText"] = json!(content);
+    element["fontSize"] = json!(style.font_size);
+    element["fontFamily"] = json!(style.font_family);
+    element["textAlign"] = json!(style.text_align);
+    element["verticalAlign"] = json!(style.vertical_align);
+    element["containerId"] = Value::Null;
+    element["autoResize"] = json!(true);
+    element["lineHeight"] = json!(LINE_HEIGHT);
+    element
+}
+
+/// Text bound to `container`, centered on it (or on the middle of an arrow)
+pub fn label(id: &str, container: &mut Value, content: &str, style: &Style) -> Value {
+    let (width, height) = text_size(content, style.font_size);
+    let (cx, cy) = if is_linear(container) {
+        let points = absolute_points(container);
+        polyline_midpoint(&points)
+    } else {
+        let (x1, y1, x2, y2) = render::element_bounds(container);
+        let cy = match style.vertical_align.as_str() {
+            "top" => y1 + height / 2.0,
+            "bottom" => y2 - height / 2.0,
+            _ => (y1 + y2) / 2.0,
+        };
+        ((x1 + x2) / 2.0, cy)
+    };
+
+    let mut element = text(id, (cx - width / 2.0, cy - height / 2.0), content, style);
+    element["containerId"] = json!(render::str_field(container, "id"));
+    element["angle"] = container.get("angle").cloned().unwrap_or(json!(0));
+    element["groupIds"] = container.get("groupIds").cloned().unwrap_or(json!([]));
+    add_bound_element(container, id, "text");
+    element
+}
+
+/// Breaks `content` into lines that fit `max_width`, at spaces where possible
+pub fn wrap_text(content: &str, font_size: f64, max_width: f64) -> String {
+    let mut lines = Vec::new();
+    for line in content.split('\n') {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+            if line_width(&candidate, font_size) <= max_width {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            // Words longer than a line, like CJK text without spaces, are split wherever the line ends
+            let mut width = 0.0;
+            for c in word.chars() {
+                let advance = char_width(c) * font_size;
+                if !current.is_empty() && width + advance > max_width {
+                    lines.push(std::mem::take(&mut current));
+                    width = 0.0;
+                }
+                current.push(c);
+                width += advance;
+            }
+        }
+        lines.push(current);
+    }
+    lines.join("\n")
+}
+
+/// Rewraps a text element from its `originalText` and resizes it. Labels are re-aligned in
+/// their container, which grows when the text no longer fits.
+pub fn refit_text(text: &mut Value, container: Option<&mut Value>) {
+    let font_size = render::num_field(text, "fontSize").unwrap_or(20.0);
+    let original = render::str_field(text, "originalText")
+        .or(render::str_field(text, "text"))
+        .unwrap_or_default()
+        .to_string();
+
+    let Some(container) = container else {
+        let fixed_width = render::bool_field(text, "autoResize") == Some(false);
+        let content = match render::num_field(text, "width") {
+            Some(width) if fixed_width => wrap_text(&original, font_size, width),
+            _ => original,
+        };
+        let (width, height) = text_size(&content, font_size);
+        if !fixed_width {
+            text["width"] = json!(width);
+        }
+        text["height"] = json!(height);
+        text["text"] = json!(content);
+        return;
+    };
+
+    if is_linear(container) {
+        let (width, height) = text_size(&original, font_size);
+        let (cx, cy) = polyline_midpoint(&absolute_points(container));
+        text["x"] = json!(cx - width / 2.0);
+        text["y"] = json!(cy - height / 2.0);
+        text["width"] = json!(width);
+        text["height"] = json!(height);
+        text["text"] = json!(original);
+        return;
+    }
+
+    let x = render::num_field(container, "x").unwrap_or(0.0);
+    let y = render::num_field(container, "y").unwrap_or(0.0);
+    let container_width = render::num_field(container, "width").unwrap_or(0.0);
+    let mut container_height = render::num_field(container, "height").unwrap_or(0.0);
+    let content = wrap_text(&original, font_size, container_width - 2.0 * BOUND_TEXT_PADDING);
+    let (width, height) = text_size(&content, font_size);
+    if height + 2.0 * BOUND_TEXT_PADDING > container_height {
+        container_height = height + 2.0 * BOUND_TEXT_PADDING;
+        container["height"] = json!(container_height);
+        bump_version(container);
+    }
+
+    let text_x = match render::str_field(text, "textAlign") {
+        Some("left") => x + BOUND_TEXT_PADDING,
+        Some("right") => x + container_width - BOUND_TEXT_PADDING - width,
+        _ => x + (container_width - width) / 2.0,
+    };
+    let text_y = match render::str_field(text, "verticalAlign") {
+        Some("top") => y + BOUND_TEXT_PADDING,
+        Some("bottom") => y + container_height - BOUND_TEXT_PADDING - height,
+        _ => y + (container_height - height) / 2.0,
+    };
+    text["x"] = json!(text_x);
+    text["y"] = json!(text_y);
+    text["width"] = json!(width);
+    text["height"] = json!(height);
+    text["text"] = json!(content);
+}
+
+/// An arrow or line through absolute `points`
+pub fn linear(
+    kind: &str,
+    id: &str,
+    points: &[Point],
+    style: &Style,
+    start_arrowhead: Option<&str>,
+    end_arrowhead: Option<&str>,
+) -> Value {
+    let (x, y) = points.first().copied().unwrap_or_default();
+    let bounds = render::points_bounds(points);
+    let mut element = base(kind, id, (x, y, x + bounds.2 - bounds.0, y + bounds.3 - bounds.1), style);
+    element["roundness"] = if style.rounded { json!({ "type": 2 }) } else { Value::Null };
+    element["points"] = points.iter().map(|(px, py)| json!([px - x, py - y])).collect();
+    element["lastCommittedPoint"] = Value::Null;
+    element["startBinding"] = Value::Null;
+    element["endBinding"] = Value::Null;
+    element["startArrowhead"] = json!(start_arrowhead);
+    element["endArrowhead"] = json!(end_arrowhead);
+    if kind == "arrow" {
+        element["elbowed"] = json!(false);
+    }
+    element
+}
+
+pub fn is_linear(element: &Value) -> bool {
+    matches!(render::str_field(element, "type"), Some("arrow" | "line"))
+}
+
+/// Points of a linear element in scene coordinates
+pub fn absolute_points(element: &Value) -> Vec<Point> {
+    let x = render::num_field(element, "x").unwrap_or(0.0);
+    let y = render::num_field(element, "y").unwrap_or(0.0);
+    render::element_points(element)
+        .into_iter()
+        .map(|(px, py)| (x + px, y + py))
+        .collect()
+}
+
+/// Point halfway along a polyline
+pub fn polyline_midpoint(points: &[Point]) -> Point {
+    let lengths: Vec<f64> = points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .collect();
+    let mut remaining = lengths.iter().sum::<f64>() / 2.0;
+    for (segment, length) in points.windows(2).zip(&lengths) {
+        if remaining <= *length && *length > 0.0 {
+            let t = remaining / length;
+            return (
+                segment[0].0 + (segment[1].0 - segment[0].0) * t,
+                segment[0].1 + (segment[1].1 - segment[0].1) * t,
+            );
+        }
+        remaining -= length;
+    }
+    points.first().copied().unwrap_or_default()
+}
+
+pub fn add_bound_element(element: &mut Value, id: &str, kind: &str) {
+    let entry = json!({ "id": id, "type": kind });
+    match element.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+        Some(bound) => bound.push(entry),
+        None => element["boundElements"] = json!([entry]),
+    }
+}
+
+pub fn add_group(element: &mut Value, group_id: &str) {
+    match element.get_mut("groupIds").and_then(|g| g.as_array_mut()) {
+        Some(groups) => groups.push(json!(group_id)),
+        None => element["groupIds"] = json!([group_id]),
+    }
+}
+
+/// Copies the elements whose ids are in `keep`, dropping bindings, frames, containers and
+/// bound elements that point at elements left behind
+pub fn copy_subset(all: &[Value], keep: &std::collections::HashSet<&str>) -> Vec<Value> {
+    all.iter()
+        .filter(|e| render::str_field(e, "id").is_some_and(|id| keep.contains(id)))
+        .map(|element| {
+            let mut element = element.clone();
+            for key in ["startBinding", "endBinding"] {
+                let target = element.get(key).and_then(|b| render::str_field(b, "elementId"));
+                if target.is_some_and(|t| !keep.contains(t)) {
+                    element[key] = Value::Null;
+                }
+            }
+            for key in ["frameId", "containerId"] {
+                if render::str_field(&element, key).is_some_and(|id| !keep.contains(id)) {
+                    element[key] = Value::Null;
+                }
+            }
+            if let Some(bound) = element.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+                bound.retain(|b| render::str_field(b, "id").is_some_and(|id| keep.contains(id)));
+            }
+            element
+        })
+        .collect()
+}
+
+/// Binds the ends of arrow `arrow_id` to the shapes with the given ids
+pub fn bind_arrow(elements: &mut [Value], arrow_id: &str, start_id: Option<&str>, end_id: Option<&str>) {
+    for (field, target) in [("startBinding", start_id), ("endBinding", end_id)] {
+        let Some(target) = target else { continue };
+        let Some(shape) = elements
+            .iter_mut()
+            .find(|e| render::str_field(e, "id") == Some(target))
+        else {
+            continue;
+        };
+        add_bound_element(shape, arrow_id, "arrow");
+
+        if let Some(arrow) = elements
+            .iter_mut()
+            .find(|e| render::str_field(e, "id") == Some(arrow_id))
+        {
+            arrow[field] = json!({ "elementId": target, "focus": 0, "gap": BINDING_GAP });
+        }
+    }
+}
+
+/// Where the ray from the center of a shape towards `toward` leaves its outline,
+/// pushed out by `gap`
+pub fn outline_point(kind: &str, (x1, y1, x2, y2): Bounds, toward: Point, gap: f64) -> Point {
+    let (cx, cy) = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let (hw, hh) = ((x2 - x1) / 2.0, (y2 - y1) / 2.0);
+    let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+    let length = dx.hypot(dy);
+    if length == 0.0 || hw <= 0.0 || hh <= 0.0 {
+        return (cx, cy);
+    }
+
+    // Distance along the unit direction to the outline
+    let (ux, uy) = (dx / length, dy / length);
+    let distance = match kind {
+        "ellipse" => 1.0 / ((ux / hw).powi(2) + (uy / hh).powi(2)).sqrt(),
+        "diamond" => 1.0 / (ux.abs() / hw + uy.abs() / hh),
+        _ => (hw / ux.abs()).min(hh / uy.abs()),
+    };
+    let distance = (distance + gap).min(length);
+    (cx + ux * distance, cy + uy * distance)
+}
+
+/// Arrow points from the outline of one shape through `bends` to the outline of another
+pub fn route(from: (&str, Bounds), to: (&str, Bounds), bends: &[Point]) -> Vec<Point> {
+    let center = |(x1, y1, x2, y2): Bounds| ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let start_toward = bends.first().copied().unwrap_or_else(|| center(to.1));
+    let end_toward = bends.last().copied().unwrap_or_else(|| center(from.1));
+
+    let mut points = vec![outline_point(from.0, from.1, start_toward, BINDING_GAP)];
+    points.extend_from_slice(bends);
+    points.push(outline_point(to.0, to.1, end_toward, BINDING_GAP));
+    points
+}
+
+/// Arrow points for an edge from a shape back to itself, looping out on the right
+pub fn self_loop((_, y1, x2, y2): Bounds) -> Vec<Point> {
+    let (top, bottom) = (y1 + (y2 - y1) / 4.0, y2 - (y2 - y1) / 4.0);
+    let out = x2 + 30.0;
+    vec![
+        (x2 + BINDING_GAP, top),
+        (out, top),
+        (out, bottom),
+        (x2 + BINDING_GAP, bottom),
+    ]
+}
+
+/// A scene holding `elements`, with the usual app state
+pub fn scene(elements: Vec<Value>) -> Value {
+    let mut scene = templates::blank_scene();
+    scene["elements"] = Value::Array(elements);
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_size_counts_cjk_as_a_full_em() {
+        assert_eq!(text_size("abcd", 20.0).0, 48.0);
+        assert_eq!(text_size("支付网关", 20.0).0, 80.0);
+        assert_eq!(text_size("a\n支付", 20.0), (40.0, 50.0));
+    }
+
+    #[test]
+    fn wrap_text_breaks_at_spaces() {
+        assert_eq!(wrap_text("one two three", 10.0, 50.0), "one two\nthree");
+    }
+
+    #[test]
+    fn wrap_text_splits_cjk_where_the_line_ends() {
+        assert_eq!(wrap_text("支付网关服务", 20.0, 60.0), "支付网\n关服务");
+        // Every line keeps at least one character
+        assert_eq!(wrap_text("支付", 20.0, 5.0), "支\n付");
+    }
+
+    #[test]
+    fn refit_text_grows_the_container_for_wrapped_cjk_labels() {
+        let mut container = json!({ "id": "box", "type": "rectangle", "x": 0.0, "y": 0.0, "width": 60.0, "height": 30.0 });
+        let mut label = json!({
+            "id": "label", "type": "text", "fontSize": 20.0, "containerId": "box",
+            "originalText": "支付网关服务", "text": "", "textAlign": "center", "verticalAlign": "middle",
+        });
+        refit_text(&mut label, Some(&mut container));
+        let lines = render::str_field(&label, "text").unwrap().lines().count();
+        assert!(lines > 1);
+        assert!(render::num_field(&container, "height").unwrap() >= lines as f64 * 25.0);
+        assert!(render::num_field(&label, "width").unwrap() <= 60.0 - 2.0 * BOUND_TEXT_PADDING);
+    }
+}
@@ -0,0 +1,303 @@
+use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Directories searched for Excalidraw font files to embed in exports: the user's own fonts
+/// first, then the fonts bundled with the app
+pub fn font_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    [app.path().app_data_dir(), app.path().resource_dir()]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("fonts"))
+        .collect()
+}
+
+pub fn render_options(options: &models::ExportOptions) -> render::RenderOptions {
+    render::RenderOptions {
+        padding: options.padding,
+        export_background: options.background,
+        background_color: options.background_color.clone(),
+        dark_mode: options.dark_mode,
+        hand_drawn: options.hand_drawn,
+        frame_ids: options.frame_ids.clone(),
+        element_ids: options.element_ids.clone(),
+    }
+}
+
+/// Renders a scene to an SVG document
+pub fn scene_to_svg(
+    scene: &serde_json::Value,
+    options: &models::ExportOptions,
+    font_dirs: &[PathBuf],
+) -> Result<String, String> {
+    let list = render::build_display_list(scene, &render_options(options))?;
+
+    let fonts = if options.embed_fonts {
+        list.fonts
+            .iter()
+            .filter_map(|id| {
+                let font = render::font_info(*id);
+                let path = render::find_font_file(font, font_dirs, &render::WEB_FONT_EXTENSIONS)?;
+                let data = fs::read(&path).ok()?;
+                let mime_type = match path.extension().and_then(|e| e.to_str()) {
+                    Some("woff2") => "font/woff2",
+                    Some("woff") => "font/woff",
+                    Some("otf") => "font/otf",
+                    _ => "font/ttf",
+                };
+                Some(render::svg::FontFace {
+                    family: font.family.to_string(),
+                    data_url: format!("data:{};base64,{}", mime_type, BASE64.encode(data)),
+                })
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let svg = render::svg::to_svg(&list, &fonts);
+    if options.embed_scene {
+        formats::embed_svg_scene(&svg, &scene.to_string())
+    } else {
+        Ok(svg)
+    }
+}
+
+/// Renders a scene to PNG bytes. With `fit`, scenes too large for the requested scale are
+/// rendered at the largest scale that fits instead of failing.
+pub fn scene_to_png(
+    scene: &serde_json::Value,
+    options: &models::ExportOptions,
+    fit: bool,
+    font_dirs: &[PathBuf],
+) -> Result<Vec<u8>, String> {
+    let svg_options = models::ExportOptions {
+        // resvg resolves fonts from its own database, @font-face rules are ignored
+        embed_fonts: false,
+        embed_scene: false,
+        ..options.clone()
+    };
+    let svg = scene_to_svg(scene, &svg_options, font_dirs)?;
+    let png = render::png::svg_to_png(&svg, options.scale, fit, font_dirs)?;
+
+    if options.embed_scene {
+        formats::embed_png_scene(&png, &scene.to_string())
+    } else {
+        Ok(png)
+    }
+}
+
+/// Export settings saved in the scene's appState by the editor
+pub fn options_from_app_state(scene: &serde_json::Value) -> models::ExportOptions {
+    let app_state = scene.get("appState");
+    let flag = |key: &str| app_state.and_then(|s| s.get(key)).and_then(|v| v.as_bool());
+    let defaults = models::ExportOptions::default();
+
+    models::ExportOptions {
+        background: flag("exportBackground").unwrap_or(defaults.background),
+        dark_mode: flag("exportWithDarkMode").unwrap_or(defaults.dark_mode),
+        scale: app_state
+            .and_then(|s| s.get("exportScale"))
+            .and_then(|v| v.as_f64())
+            .filter(|scale| *scale > 0.0)
+            .unwrap_or(defaults.scale),
+        ..defaults
+    }
+}
+
+#[tauri::command]
+pub async fn export_svg(
+    app: AppHandle,
+    file_path: String,
+    output_path: Option<String>,
+    options: Option<models::ExportOptions>,
+) -> Result<String, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+    let options = options.unwrap_or_default();
+
+    let svg = scene_to_svg(&scene, &options, &font_dirs(&app))?;
+
+    let output = render::output_path_for(&validated_path, output_path, "svg")?;
+    fs::write(&output, svg).map_err(|e| format!("Failed to write SVG: {}", e))?;
+
+    Ok(output.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn export_png(
+    app: AppHandle,
+    file_path: String,
+    output_path: Option<String>,
+    options: Option<models::ExportOptions>,
+) -> Result<models::PngExportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+    let options = options.unwrap_or_default();
+    let font_dirs = font_dirs(&app);
+
+    let png = scene_to_png(&scene, &options, false, &font_dirs)?;
+
+    let output = render::output_path_for(&validated_path, output_path, "png")?;
+    fs::write(&output, png).map_err(|e| format!("Failed to write PNG: {}", e))?;
+
+    Ok(models::PngExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        warnings: render::png::font_database(&font_dirs).warnings(),
+    })
+}
+
+/// Builds the PDF pages, one per frame when `frame_pages` is set
+pub fn pdf_pages(
+    scene: &serde_json::Value,
+    options: &models::ExportOptions,
+    frame_pages: bool,
+    page_order: &str,
+) -> Result<Vec<render::pdf::PdfPage>, String> {
+    let base = render_options(options);
+    let frames = if frame_pages {
+        ordered_frames(scene, &options.frame_ids, page_order)?
+    } else {
+        Vec::new()
+    };
+
+    // A scene without frames still exports as a single page
+    if frames.is_empty() {
+        return Ok(vec![render::pdf::PdfPage {
+            list: render::build_display_list(scene, &base)?,
+            title: None,
+        }]);
+    }
+
+    frames
+        .into_iter()
+        .map(|(id, name)| {
+            let mut page_options = base.clone();
+            page_options.frame_ids = vec![id];
+            page_options.element_ids.clear();
+            Ok(render::pdf::PdfPage {
+                list: render::build_display_list(scene, &page_options)?,
+                title: Some(name),
+            })
+        })
+        .collect()
+}
+
+/// Frame ids and names in page order: by name, or in reading order by position
+pub fn ordered_frames(
+    scene: &serde_json::Value,
+    only: &[String],
+    page_order: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let mut frames: Vec<(String, String, render::Bounds)> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|elements| elements.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|e| matches!(render::str_field(e, "type"), Some("frame" | "magicframe")))
+        .filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false))
+        .filter_map(|e| {
+            let id = render::str_field(e, "id")?.to_string();
+            if !only.is_empty() && !only.contains(&id) {
+                return None;
+            }
+            let name = render::str_field(e, "name").unwrap_or("Frame").to_string();
+            Some((id, name, render::element_bounds(e)))
+        })
+        .collect();
+
+    match page_order {
+        "name" => frames.sort_by(|a, b| natural_cmp(&a.1, &b.1)),
+        "position" => {
+            // Group frames whose tops fall within the first frame of a row, then go left to right
+            frames.sort_by(|a, b| a.2.1.total_cmp(&b.2.1));
+            let mut rows: Vec<Vec<(String, String, render::Bounds)>> = Vec::new();
+            for frame in frames {
+                match rows.last_mut() {
+                    Some(row) if frame.2.1 < row[0].2.3 => row.push(frame),
+                    _ => rows.push(vec![frame]),
+                }
+            }
+            frames = rows
+                .into_iter()
+                .flat_map(|mut row| {
+                    row.sort_by(|a, b| a.2.0.total_cmp(&b.2.0));
+                    row
+                })
+                .collect();
+        }
+        other => return Err(format!("Unknown page order: {}", other)),
+    }
+
+    Ok(frames.into_iter().map(|(id, name, _)| (id, name)).collect())
+}
+
+/// Compares names so that "Slide 2" sorts before "Slide 10"
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    fn chunks(s: &str) -> Vec<(bool, String)> {
+        let mut out: Vec<(bool, String)> = Vec::new();
+        for c in s.to_lowercase().chars() {
+            let digit = c.is_ascii_digit();
+            match out.last_mut() {
+                Some((is_digit, chunk)) if *is_digit == digit => chunk.push(c),
+                _ => out.push((digit, c.to_string())),
+            }
+        }
+        out
+    }
+
+    let (a, b) = (chunks(a), chunks(b));
+    for (x, y) in a.iter().zip(&b) {
+        let ordering = match (x, y) {
+            ((true, x), (true, y)) => {
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            ((_, x), (_, y)) => x.cmp(y),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[tauri::command]
+pub async fn export_pdf(
+    app: AppHandle,
+    file_path: String,
+    output_path: Option<String>,
+    options: Option<models::ExportOptions>,
+    frame_pages: Option<bool>,
+    page_order: Option<String>,
+) -> Result<models::PdfExportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+    let options = options.unwrap_or_default();
+
+    let pages = pdf_pages(
+        &scene,
+        &options,
+        frame_pages.unwrap_or(false),
+        page_order.as_deref().unwrap_or("position"),
+    )?;
+    let title = validated_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(formats::drawing_stem)
+        .unwrap_or("Drawing");
+    let pdf = render::pdf::to_pdf(&pages, title, &font_dirs(&app))?;
+
+    let output = render::output_path_for(&validated_path, output_path, "pdf")?;
+    fs::write(&output, pdf.data).map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    Ok(models::PdfExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        skipped: pdf.skipped,
+        warnings: pdf.warnings,
+    })
+}
@@ -1,5 +1,6 @@
 use super::*;
 use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
@@ -54,7 +55,7 @@ pub async fn get_file_tree(directory: String) -> Result<Vec<models::FileTreeNode
     Ok(tree)
 }
 
-fn collect_excalidraw_files_recursive(
+pub fn collect_excalidraw_files_recursive(
     dir: &Path,
     files: &mut Vec<models::ExcalidrawFile>,
 ) -> Result<(), String> {
@@ -63,16 +64,12 @@ fn collect_excalidraw_files_recursive(
             for entry in entries.flatten() {
                 let path = entry.path();
                 if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            if let Some(file_name) = path.file_name() {
-                                files.push(models::ExcalidrawFile {
-                                    name: file_name.to_string_lossy().to_string(),
-                                    path: path.to_string_lossy().to_string(),
-                                    modified: false,
-                                });
-                            }
-                        }
+                    if let Some(file_name) = path.file_name().filter(|_| formats::is_drawing_file(&path)) {
+                        files.push(models::ExcalidrawFile {
+                            name: file_name.to_string_lossy().to_string(),
+                            path: path.to_string_lossy().to_string(),
+                            modified: false,
+                        });
                     }
                 } else if path.is_dir() {
                     collect_excalidraw_files_recursive(&path, files)?;
@@ -96,6 +93,10 @@ fn build_file_tree(dir: &Path, tree: &mut Vec<models::FileTreeNode>) -> Result<(
                     .to_string();
 
                 if path.is_dir() {
+                    if name == templates::TEMPLATES_DIR || name == library::LIBRARIES_DIR {
+                        continue;
+                    }
+
                     let mut children = Vec::new();
                     build_file_tree(&path, &mut children)?;
 
@@ -114,18 +115,14 @@ fn build_file_tree(dir: &Path, tree: &mut Vec<models::FileTreeNode>) -> Result<(
                             children: Some(children),
                         });
                     }
-                } else if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            tree.push(models::FileTreeNode {
-                                name,
-                                path: path.to_string_lossy().to_string(),
-                                is_directory: false,
-                                modified: false,
-                                children: None,
-                            });
-                        }
-                    }
+                } else if path.is_file() && formats::is_drawing_file(&path) {
+                    tree.push(models::FileTreeNode {
+                        name,
+                        path: path.to_string_lossy().to_string(),
+                        is_directory: false,
+                        modified: false,
+                        children: None,
+                    });
                 }
             }
         }
@@ -140,10 +137,8 @@ fn has_excalidraw_files(dir: &Path) -> Result<bool, String> {
             for entry in entries.flatten() {
                 let path = entry.path();
                 if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            return Ok(true);
-                        }
+                    if formats::is_drawing_file(&path) {
+                        return Ok(true);
                     }
                 } else if path.is_dir() && has_excalidraw_files(&path)? {
                     return Ok(true);
@@ -159,36 +154,104 @@ fn has_excalidraw_files(dir: &Path) -> Result<bool, String> {
 pub async fn read_file(file_path: String) -> Result<String, String> {
     let path = Path::new(&file_path);
     let validated_path = security::validate_path(path, None)?;
+
+    read_drawing(&validated_path)
+}
+
+/// Reads and validates a drawing, with externalized assets inlined again
+pub fn read_drawing(validated_path: &Path) -> Result<String, String> {
+    security::validate_excalidraw_file(validated_path)?;
     
-    security::validate_excalidraw_file(&validated_path)?;
-    
-    let content = fs::read_to_string(&validated_path)
-        .map_err(|e| e.to_string())?;
+    let content = formats::read_scene_content(validated_path)?;
     
     security::validate_excalidraw_content(&content)?;
-    
-    Ok(content)
+
+    assets::inline_assets(&content, validated_path)
+}
+
+/// Reads a drawing and parses it into a scene
+pub fn load_scene(validated_path: &Path) -> Result<serde_json::Value, String> {
+    let content = read_drawing(validated_path)?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid JSON: {}", e))
 }
 
 #[tauri::command]
-pub async fn save_file(file_path: String, content: String) -> Result<(), String> {
+pub async fn save_file(
+    app: AppHandle,
+    file_path: String,
+    content: String,
+    canonical: Option<bool>,
+    externalize_assets: Option<bool>,
+    state: State<'_, models::AppState>,
+) -> Result<(), String> {
     let path = Path::new(&file_path);
     let validated_path = security::validate_path(path, None)?;
     
     security::validate_excalidraw_file(&validated_path)?;
     
     security::validate_excalidraw_content(&content)?;
+
+    // Image-backed drawings stay self-contained
+    let is_json = formats::drawing_format(&validated_path) == Some(formats::DrawingFormat::Json);
+    let content = if externalize_assets.unwrap_or(false) && is_json {
+        let workspace = workspace_root(&state, &validated_path);
+        assets::externalize_assets(&content, &workspace)?
+    } else {
+        content
+    };
+
+    let content = if canonical.unwrap_or(false) {
+        canonical::canonicalize_scene(&content)?
+    } else {
+        content
+    };
     
-    fs::write(&validated_path, content)
-        .map_err(|e| e.to_string())?;
+    formats::write_scene_content(&validated_path, &content, &export::font_dirs(&app))?;
     
     Ok(())
 }
 
+/// Returns the open workspace if it contains the file, otherwise the file's own directory
+pub fn workspace_root(state: &models::AppState, file_path: &Path) -> PathBuf {
+    let current = state.current_directory.lock().unwrap().clone();
+    current
+        .and_then(|dir| dir.canonicalize().ok())
+        .filter(|dir| file_path.starts_with(dir))
+        .or_else(|| file_path.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Returns the open workspace if it contains `dir`, otherwise `dir` itself
+pub fn workspace_dir(state: &models::AppState, dir: &Path) -> PathBuf {
+    let current = state.current_directory.lock().unwrap().clone();
+    current
+        .and_then(|workspace| workspace.canonicalize().ok())
+        .filter(|workspace| dir.starts_with(workspace))
+        .unwrap_or_else(|| dir.to_path_buf())
+}
+
+/// Finds the nearest `name` folder in `dir` or one of its ancestors, never looking above `workspace`
+pub fn find_workspace_folder(dir: &Path, workspace: &Path, name: &str) -> Option<PathBuf> {
+    dir.ancestors()
+        .take_while(|d| d.starts_with(workspace))
+        .map(|d| d.join(name))
+        .find(|d| d.is_dir())
+}
+
 #[tauri::command]
-pub async fn save_file_as(app: AppHandle, content: String) -> Result<Option<String>, String> {
+pub async fn save_file_as(
+    app: AppHandle,
+    content: String,
+    canonical: Option<bool>,
+) -> Result<Option<String>, String> {
     use tauri_plugin_dialog::DialogExt;
 
+    let content = if canonical.unwrap_or(false) {
+        canonical::canonicalize_scene(&content)?
+    } else {
+        content
+    };
+
     let (tx, rx) = mpsc::channel();
 
     app.dialog()
@@ -213,7 +276,13 @@ pub async fn save_file_as(app: AppHandle, content: String) -> Result<Option<Stri
 }
 
 #[tauri::command]
-pub async fn create_new_file(directory: String, file_name: String) -> Result<String, String> {
+pub async fn create_new_file(
+    app: AppHandle,
+    directory: String,
+    file_name: String,
+    template_id: Option<String>,
+    variables: Option<HashMap<String, String>>,
+) -> Result<String, String> {
     println!(
         "[create_new_file] Called with directory: {}, file_name: {}",
         directory, file_name
@@ -226,54 +295,24 @@ pub async fn create_new_file(directory: String, file_name: String) -> Result<Str
         return Err(format!("Path is not a directory: {}", directory));
     }
 
-    let mut path = security::safe_path_join(&validated_dir, &file_name)?;
-    println!("[create_new_file] Initial path: {:?}", path);
-
-    if path.exists() {
-        println!("[create_new_file] File already exists, finding unique name");
-        let mut counter = 1;
-
-        let stem = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or("Invalid file name")?
-            .to_string();
+    let path = unique_file_path(&validated_dir, &file_name)?;
+    println!("[create_new_file] Using path: {:?}", path);
 
-        let base_stem = if stem.ends_with(".excalidraw") {
-            stem.trim_end_matches(".excalidraw").to_string()
-        } else {
-            stem
-        };
+    let title = path
+        .file_name()
+        .map(|n| n.to_string_lossy().trim_end_matches(".excalidraw").to_string())
+        .unwrap_or_default();
 
-        loop {
-            let new_name = format!("{}-{}.excalidraw", base_stem, counter);
-            path = dir_path.join(&new_name);
-
-            if !path.exists() {
-                println!("[create_new_file] Found unique name: {:?}", path);
-                break;
-            }
-            counter += 1;
-
-            if counter > 100 {
-                return Err("Could not find unique file name".to_string());
-            }
-        }
-    }
+    let mut content = templates::resolve_template(
+        &app,
+        &validated_dir,
+        template_id.as_deref().unwrap_or(templates::BLANK_TEMPLATE_ID),
+    )?;
+    let mut template_variables = templates::default_variables(&title);
+    template_variables.extend(variables.unwrap_or_default());
+    templates::apply_variables(&mut content, &template_variables);
 
-    let default_content = serde_json::json!({
-        "type": "excalidraw",
-        "version": 2,
-        "source": "SAG-Excalidraw",
-        "elements": [],
-        "appState": {
-            "gridSize": null,
-            "viewBackgroundColor": "#ffffff"
-        },
-        "files": {}
-    });
-
-    let content_str = serde_json::to_string_pretty(&default_content)
+    let content_str = serde_json::to_string_pretty(&content)
         .map_err(|e| format!("Failed to serialize content: {}", e))?;
 
     println!("[create_new_file] Writing to path: {:?}", path);
@@ -310,6 +349,43 @@ pub async fn create_new_file(directory: String, file_name: String) -> Result<Str
     }
 }
 
+/// Joins `file_name` to `dir`, appending `-1`, `-2`, ... before the extension
+/// until the name is free
+pub fn unique_file_path(dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let path = security::safe_path_join(dir, file_name)?;
+    if !path.exists() {
+        return Ok(path);
+    }
+
+    let name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid file name")?
+        .to_string();
+
+    // Other files such as exported markdown keep their own extension
+    let extension = path.extension().and_then(|s| s.to_str()).map(|ext| format!(".{}", ext));
+    let (base_stem, suffix) = match name.find(".excalidraw") {
+        Some(index) => (name[..index].to_string(), name[index..].to_string()),
+        None => (
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or("Invalid file name")?
+                .to_string(),
+            extension.unwrap_or_else(|| ".excalidraw".to_string()),
+        ),
+    };
+
+    for counter in 1..=100 {
+        let candidate = dir.join(format!("{}-{}{}", base_stem, counter, suffix));
+        if !candidate.exists() {
+            return Ok(candidate);
+        }
+    }
+
+    Err("Could not find unique file name".to_string())
+}
+
 #[tauri::command]
 pub async fn rename_file(old_path: String, new_name: String) -> Result<String, String> {
     let old_path = Path::new(&old_path);
@@ -325,17 +401,15 @@ pub async fn rename_file(old_path: String, new_name: String) -> Result<String, S
     
     let new_path = security::safe_path_join(parent, &new_name)?;
     
-    let new_path = if new_path.extension() != Some(std::ffi::OsStr::new("excalidraw")) {
-        new_path.with_extension("excalidraw")
-    } else {
-        new_path
-    };
+    // Keep the drawing's format, an .excalidraw.png stays a PNG
+    let format = formats::drawing_format(&validated_old).ok_or("Invalid file path")?;
+    let new_path = formats::with_format_suffix(&new_path, format);
 
     if new_path.exists() && new_path != old_path {
         return Err("A file with that name already exists".to_string());
     }
 
-    let content = match fs::read_to_string(old_path) {
+    let content = match fs::read(old_path) {
         Ok(content) => content,
         Err(e) => return Err(format!("Failed to read original file: {}", e)),
     };
@@ -345,7 +419,7 @@ pub async fn rename_file(old_path: String, new_name: String) -> Result<String, S
         Err(e) => return Err(format!("Failed to create new file: {}", e)),
     }
 
-    match fs::read_to_string(&new_path) {
+    match fs::read(&new_path) {
         Ok(new_content) => {
             if new_content != content {
                 eprintln!("Warning: New file content doesn't match original!");
@@ -392,7 +466,8 @@ pub async fn watch_directory(
     directory: String,
     state: State<'_, models::AppState>,
 ) -> Result<(), String> {
-    let path = PathBuf::from(&directory);
+    // Events and the search index use canonical paths, so the workspace must match them
+    let path = security::validate_path(Path::new(&directory), None)?;
 
     {
         let mut current_dir = state.current_directory.lock().unwrap();
@@ -416,10 +491,11 @@ pub async fn watch_directory(
                 ..
             })) => {
                 for path in paths {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            let _ = app_handle.emit("file-system-change", &path);
-                        }
+                    search_index::update(&app_handle, &path);
+                    if formats::is_drawing_file(&path) {
+                        let _ = app_handle.emit("file-system-change", &path);
+                    } else if path.extension().is_some_and(|ext| ext == library::LIBRARY_EXTENSION) {
+                        let _ = app_handle.emit("library-change", &path);
                     }
                 }
             }
@@ -432,6 +508,10 @@ pub async fn watch_directory(
         }
     });
 
+    // The watcher stops when dropped, so it lives in the state until the next directory
+    *state.watcher.lock().unwrap() = Some(watcher);
+    search_index::start(&app, path);
+
     Ok(())
 }
 
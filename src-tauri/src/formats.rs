@@ -0,0 +1,416 @@
+use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde_json::{Value, json};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// MIME type Excalidraw uses to tag scene data embedded in images
+pub const EXCALIDRAW_MIME: &str = "application/vnd.excalidraw+json";
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const SVG_SOURCE_TAG: &str = "<!-- svg-source:excalidraw -->";
+const SVG_PAYLOAD_START: &str = "<!-- payload-start -->";
+const SVG_PAYLOAD_END: &str = "<!-- payload-end -->";
+const SVG_PAYLOAD_VERSION: &str = "<!-- payload-version:";
+
+/// Container a drawing is stored in, recognised by its file name suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawingFormat {
+    Json,
+    Png,
+    Svg,
+    Markdown,
+}
+
+/// Longer suffixes first, so `.excalidraw.png` is not mistaken for something else
+const FORMATS: [DrawingFormat; 4] = [
+    DrawingFormat::Png,
+    DrawingFormat::Svg,
+    DrawingFormat::Markdown,
+    DrawingFormat::Json,
+];
+
+impl DrawingFormat {
+    pub fn suffix(self) -> &'static str {
+        match self {
+            DrawingFormat::Json => ".excalidraw",
+            DrawingFormat::Png => ".excalidraw.png",
+            DrawingFormat::Svg => ".excalidraw.svg",
+            DrawingFormat::Markdown => ".excalidraw.md",
+        }
+    }
+}
+
+pub fn drawing_format(path: &Path) -> Option<DrawingFormat> {
+    let name = path.file_name()?.to_str()?;
+    FORMATS
+        .into_iter()
+        .find(|format| name.len() > format.suffix().len() && name.ends_with(format.suffix()))
+}
+
+pub fn is_drawing_file(path: &Path) -> bool {
+    drawing_format(path).is_some()
+}
+
+/// All supported suffixes, for error messages
+pub fn supported_suffixes() -> String {
+    let suffixes: Vec<&str> = FORMATS.iter().rev().map(|f| f.suffix()).collect();
+    suffixes.join(", ")
+}
+
+/// File name without its drawing suffix
+pub fn drawing_stem(name: &str) -> &str {
+    FORMATS
+        .iter()
+        .find_map(|format| name.strip_suffix(format.suffix()))
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or(name)
+}
+
+/// Gives `path` the suffix of `format`, replacing any other drawing suffix or extension
+pub fn with_format_suffix(path: &Path, format: DrawingFormat) -> PathBuf {
+    if drawing_format(path) == Some(format) {
+        return path.to_path_buf();
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = if drawing_format(path).is_some() {
+        drawing_stem(&name).to_string()
+    } else {
+        Path::new(&name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or(name)
+    };
+    path.with_file_name(format!("{}{}", stem, format.suffix()))
+}
+
+/// Reads the scene JSON stored in a drawing file of any supported format
+pub fn read_scene_content(path: &Path) -> Result<String, String> {
+    let format = drawing_format(path).ok_or_else(|| {
+        format!("Unsupported drawing format, expected one of {}", supported_suffixes())
+    })?;
+
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let content = parse_scene_content(format, &bytes)?;
+    if format == DrawingFormat::Markdown {
+        // Obsidian keeps the images of a drawing in the vault, next to the document
+        let md = std::str::from_utf8(&bytes).map_err(|e| e.to_string())?;
+        return obsidian::load_embedded_files(md, path, &content);
+    }
+    Ok(content)
+}
+
+/// Extracts the scene JSON from the contents of a drawing file
+pub fn parse_scene_content(format: DrawingFormat, bytes: &[u8]) -> Result<String, String> {
+    let text = || std::str::from_utf8(bytes).map_err(|e| e.to_string());
+    match format {
+        DrawingFormat::Json => text().map(str::to_string),
+        DrawingFormat::Png => extract_png_scene(bytes),
+        DrawingFormat::Svg => extract_svg_scene(text()?),
+        DrawingFormat::Markdown => obsidian::extract_scene(text()?),
+    }
+}
+
+/// Writes scene JSON back into a drawing file, re-rendering image formats and
+/// updating Obsidian documents in place
+pub fn write_scene_content(path: &Path, content: &str, font_dirs: &[PathBuf]) -> Result<(), String> {
+    let data = encode_scene_content(path, content, font_dirs)?;
+    write_atomic(path, &data)
+}
+
+/// The bytes `write_scene_content` would write to `path`
+pub fn encode_scene_content(path: &Path, content: &str, font_dirs: &[PathBuf]) -> Result<Vec<u8>, String> {
+    let format = drawing_format(path).ok_or_else(|| {
+        format!("Unsupported drawing format, expected one of {}", supported_suffixes())
+    })?;
+
+    let data = match format {
+        DrawingFormat::Json => content.as_bytes().to_vec(),
+        DrawingFormat::Png | DrawingFormat::Svg => {
+            let scene: Value =
+                serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+            let mut options = export::options_from_app_state(&scene);
+            options.embed_scene = true;
+            if format == DrawingFormat::Png {
+                // A drawing that outgrew its export scale must still save
+                export::scene_to_png(&scene, &options, true, font_dirs)?
+            } else {
+                export::scene_to_svg(&scene, &options, font_dirs)?.into_bytes()
+            }
+        }
+        DrawingFormat::Markdown => {
+            // Keep the front matter and notes of an existing Obsidian document
+            let existing = fs::read_to_string(path).ok();
+            obsidian::update_document(existing.as_deref(), content)?.into_bytes()
+        }
+    };
+
+    Ok(data)
+}
+
+/// Replaces `path` through a temporary file, so readers and watchers never see half a drawing
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let name = path.file_name().ok_or("Path has no file name")?.to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    fs::write(&temp, data).map_err(|e| e.to_string())?;
+    fs::rename(&temp, path).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        e.to_string()
+    })
+}
+
+/// Excalidraw's `EncodedData`: the scene JSON zlib-compressed into a byte string.
+/// Returned as Latin-1 bytes, which is how both PNG and SVG carry it.
+fn encode_payload(scene_json: &str) -> Result<Vec<u8>, String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(scene_json.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|deflated| {
+            let encoded = json!({
+                "version": "1",
+                "encoding": "bstring",
+                "compressed": true,
+                "encoded": byte_string(&deflated),
+            });
+            // Every char is at most U+00FF, so this maps one char to one byte
+            encoded.to_string().chars().map(|c| c as u32 as u8).collect()
+        })
+        .map_err(|e| format!("Failed to compress scene: {}", e))
+}
+
+fn decode_payload(payload: &str) -> Result<String, String> {
+    let data: Value =
+        serde_json::from_str(payload).map_err(|e| format!("Invalid embedded scene: {}", e))?;
+
+    let Some(encoded) = data.get("encoded").and_then(|e| e.as_str()) else {
+        // Early exports embedded the scene JSON as is
+        return Ok(payload.to_string());
+    };
+    match data.get("encoding").and_then(|e| e.as_str()) {
+        Some("bstring") => {}
+        other => return Err(format!("Unsupported scene encoding: {:?}", other)),
+    }
+
+    let bytes = encoded
+        .chars()
+        .map(|c| u8::try_from(c as u32))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| "Embedded scene is not a byte string".to_string())?;
+
+    if data.get("compressed").and_then(|c| c.as_bool()).unwrap_or(false) {
+        let mut text = String::new();
+        ZlibDecoder::new(bytes.as_slice())
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to decompress scene: {}", e))?;
+        Ok(text)
+    } else {
+        String::from_utf8(bytes).map_err(|e| format!("Invalid embedded scene: {}", e))
+    }
+}
+
+fn byte_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// PNG chunk type and data
+type PngChunk<'a> = ([u8; 4], &'a [u8]);
+
+fn png_chunks(png: &[u8]) -> Result<Vec<PngChunk<'_>>, String> {
+    if !png.starts_with(&PNG_SIGNATURE) {
+        return Err("Not a PNG file".to_string());
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 12 <= png.len() {
+        let length = u32::from_be_bytes([png[offset], png[offset + 1], png[offset + 2], png[offset + 3]]) as usize;
+        let kind = [png[offset + 4], png[offset + 5], png[offset + 6], png[offset + 7]];
+        let data = png
+            .get(offset + 8..offset + 8 + length)
+            .ok_or("Truncated PNG chunk")?;
+        chunks.push((kind, data));
+        offset += 12 + length;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+fn write_png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = flate2::Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+fn scene_text_chunk(data: &[u8]) -> Option<&[u8]> {
+    let separator = data.iter().position(|b| *b == 0)?;
+    (&data[..separator] == EXCALIDRAW_MIME.as_bytes()).then(|| &data[separator + 1..])
+}
+
+/// Reads the scene from the `tEXt` chunk Excalidraw writes into PNG exports
+pub fn extract_png_scene(png: &[u8]) -> Result<String, String> {
+    png_chunks(png)?
+        .into_iter()
+        .filter(|(kind, _)| kind == b"tEXt")
+        .find_map(|(_, data)| scene_text_chunk(data))
+        .ok_or_else(|| "No Excalidraw scene is embedded in this PNG".to_string())
+        .and_then(|text| decode_payload(&byte_string(text)))
+}
+
+/// Stores the scene in a `tEXt` chunk right before `IEND`, replacing any earlier one
+pub fn embed_png_scene(png: &[u8], scene_json: &str) -> Result<Vec<u8>, String> {
+    let mut text = EXCALIDRAW_MIME.as_bytes().to_vec();
+    text.push(0);
+    text.extend(encode_payload(scene_json)?);
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    for (kind, data) in png_chunks(png)? {
+        if &kind == b"tEXt" && scene_text_chunk(data).is_some() {
+            continue;
+        }
+        if &kind == b"IEND" {
+            write_png_chunk(&mut out, b"tEXt", &text);
+        }
+        write_png_chunk(&mut out, &kind, data);
+    }
+    Ok(out)
+}
+
+/// Reads the scene from the base64 payload in an SVG export's metadata
+pub fn extract_svg_scene(svg: &str) -> Result<String, String> {
+    let missing = || "No Excalidraw scene is embedded in this SVG".to_string();
+    if !svg.contains(&format!("payload-type:{}", EXCALIDRAW_MIME)) {
+        return Err(missing());
+    }
+
+    let start = svg.find(SVG_PAYLOAD_START).ok_or_else(missing)? + SVG_PAYLOAD_START.len();
+    let end = svg[start..].find(SVG_PAYLOAD_END).ok_or_else(missing)? + start;
+    let base64: String = svg[start..end].chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = BASE64
+        .decode(base64)
+        .map_err(|e| format!("Invalid embedded scene: {}", e))?;
+
+    // Version 1 payloads were UTF-8, later ones are byte strings
+    let version = svg
+        .find(SVG_PAYLOAD_VERSION)
+        .map(|i| &svg[i + SVG_PAYLOAD_VERSION.len()..])
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("1");
+    let payload = if version == "1" {
+        String::from_utf8(bytes).map_err(|e| format!("Invalid embedded scene: {}", e))?
+    } else {
+        byte_string(&bytes)
+    };
+
+    decode_payload(&payload)
+}
+
+/// Adds the scene payload as `<metadata>` after the source comment of an SVG export
+pub fn embed_svg_scene(svg: &str, scene_json: &str) -> Result<String, String> {
+    let metadata = format!(
+        "<metadata><!-- payload-type:{} --><!-- payload-version:2 -->{}{}{}</metadata>\n",
+        EXCALIDRAW_MIME,
+        SVG_PAYLOAD_START,
+        BASE64.encode(encode_payload(scene_json)?),
+        SVG_PAYLOAD_END
+    );
+
+    let insert_at = match svg.find(SVG_SOURCE_TAG) {
+        Some(i) => {
+            let after = i + SVG_SOURCE_TAG.len();
+            after + usize::from(svg[after..].starts_with('\n'))
+        }
+        None => svg.find('>').map(|i| i + 1).ok_or("Invalid SVG document")?,
+    };
+
+    let mut out = String::with_capacity(svg.len() + metadata.len());
+    out.push_str(&svg[..insert_at]);
+    out.push_str(&metadata);
+    out.push_str(&svg[insert_at..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"{"type":"excalidraw","elements":[{"id":"t","type":"text","text":"Grüße ✓"}]}"#;
+
+    fn tiny_png() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbaImage::new(1, 1)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn recognises_drawing_suffixes() {
+        assert_eq!(drawing_format(Path::new("a.excalidraw.png")), Some(DrawingFormat::Png));
+        assert_eq!(drawing_format(Path::new("a.excalidraw.md")), Some(DrawingFormat::Markdown));
+        assert_eq!(drawing_format(Path::new("a.excalidraw")), Some(DrawingFormat::Json));
+        assert_eq!(drawing_format(Path::new(".excalidraw")), None);
+        assert_eq!(drawing_format(Path::new("a.png")), None);
+        assert_eq!(drawing_stem("plan.v2.excalidraw.svg"), "plan.v2");
+        assert_eq!(
+            with_format_suffix(Path::new("dir/plan.excalidraw"), DrawingFormat::Png),
+            Path::new("dir/plan.excalidraw.png")
+        );
+    }
+
+    #[test]
+    fn png_scene_round_trips_and_replaces_an_earlier_one() {
+        let png = embed_png_scene(&tiny_png(), r#"{"old":true}"#).unwrap();
+        let png = embed_png_scene(&png, SCENE).unwrap();
+        assert_eq!(extract_png_scene(&png).unwrap(), SCENE);
+
+        let chunks = png_chunks(&png).unwrap();
+        let scenes = chunks.iter().filter(|(_, data)| scene_text_chunk(data).is_some()).count();
+        assert_eq!(scenes, 1);
+        assert_eq!(&chunks.last().unwrap().0, b"IEND");
+        assert!(image::load_from_memory(&png).is_ok());
+    }
+
+    #[test]
+    fn png_without_a_scene_is_an_error() {
+        assert!(extract_png_scene(&tiny_png()).unwrap_err().starts_with("No Excalidraw scene"));
+        assert_eq!(extract_png_scene(b"GIF89a").unwrap_err(), "Not a PNG file");
+    }
+
+    #[test]
+    fn svg_scene_round_trips() {
+        let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\">\n  {}\n<g/></svg>", SVG_SOURCE_TAG);
+        let embedded = embed_svg_scene(&svg, SCENE).unwrap();
+        assert!(embedded.contains(&format!("{}\n<metadata>", SVG_SOURCE_TAG)));
+        assert_eq!(extract_svg_scene(&embedded).unwrap(), SCENE);
+        assert!(extract_svg_scene(&svg).unwrap_err().starts_with("No Excalidraw scene"));
+    }
+
+    #[test]
+    fn reads_version_1_svg_payloads() {
+        let svg = format!(
+            "<svg><metadata><!-- payload-type:{} --><!-- payload-version:1 -->{}{}{}</metadata></svg>",
+            EXCALIDRAW_MIME,
+            SVG_PAYLOAD_START,
+            BASE64.encode(SCENE),
+            SVG_PAYLOAD_END
+        );
+        assert_eq!(extract_svg_scene(&svg).unwrap(), SCENE);
+    }
+}
@@ -0,0 +1,476 @@
+use super::*;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A shape, or any element an arrow is bound to
+struct Node<'a> {
+    id: &'a str,
+    kind: &'a str,
+    label: String,
+    frame: Option<&'a str>,
+}
+
+struct Edge<'a> {
+    id: &'a str,
+    from: &'a str,
+    to: &'a str,
+    label: String,
+    start_arrowhead: Option<&'a str>,
+    end_arrowhead: Option<&'a str>,
+    dashed: bool,
+}
+
+/// Boxes, their connections and what could not be placed in the graph
+struct SceneGraph<'a> {
+    nodes: Vec<Node<'a>>,
+    edges: Vec<Edge<'a>>,
+    frames: Vec<(&'a str, String)>,
+    unconnected_arrows: Vec<models::UnconnectedArrow>,
+    free_text: Vec<models::FreeText>,
+}
+
+const SHAPE_TYPES: [&str; 5] = ["rectangle", "ellipse", "diamond", "image", "embeddable"];
+
+fn text_of(element: &Value) -> String {
+    render::str_field(element, "originalText")
+        .or(render::str_field(element, "text"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn binding<'a>(element: &'a Value, field: &str) -> Option<&'a str> {
+    element.get(field).and_then(|b| render::str_field(b, "elementId"))
+}
+
+/// Element an arrow end is bound to, if it still exists
+fn endpoint<'a>(arrow: &'a Value, field: &str, by_id: &HashMap<&str, &Value>) -> Option<&'a str> {
+    binding(arrow, field).filter(|id| by_id.contains_key(id))
+}
+
+fn build_graph(scene: &Value) -> SceneGraph<'_> {
+    let live: Vec<&Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+        .unwrap_or_default();
+    let by_id: HashMap<&str, &Value> = live
+        .iter()
+        .filter_map(|e| render::str_field(e, "id").map(|id| (id, *e)))
+        .collect();
+
+    let mut bound_text: HashMap<&str, String> = HashMap::new();
+    for element in &live {
+        if render::str_field(element, "type") == Some("text")
+            && let Some(container) = render::str_field(element, "containerId")
+            && by_id.contains_key(container)
+        {
+            bound_text.insert(container, text_of(element));
+        }
+    }
+
+    let arrows: Vec<&Value> = live
+        .iter()
+        .filter(|e| render::str_field(e, "type") == Some("arrow"))
+        .copied()
+        .collect();
+    let bound_targets: Vec<&str> = arrows
+        .iter()
+        .flat_map(|a| [endpoint(a, "startBinding", &by_id), endpoint(a, "endBinding", &by_id)])
+        .flatten()
+        .collect();
+
+    let frames: Vec<(&str, String)> = live
+        .iter()
+        .filter(|e| matches!(render::str_field(e, "type"), Some("frame" | "magicframe")))
+        .filter_map(|e| {
+            let id = render::str_field(e, "id")?;
+            let name = render::str_field(e, "name").unwrap_or(id).to_string();
+            Some((id, name))
+        })
+        .collect();
+
+    let mut nodes = Vec::new();
+    let mut free_text = Vec::new();
+    for element in &live {
+        let Some(id) = render::str_field(element, "id") else { continue };
+        let kind = render::str_field(element, "type").unwrap_or_default();
+        let is_target = bound_targets.contains(&id);
+        let label = match kind {
+            "text" if render::str_field(element, "containerId").is_some_and(|c| by_id.contains_key(c)) => continue,
+            "text" if !is_target => {
+                free_text.push(models::FreeText {
+                    id: id.to_string(),
+                    text: text_of(element),
+                });
+                continue;
+            }
+            "text" => text_of(element),
+            "frame" | "magicframe" if is_target => {
+                render::str_field(element, "name").unwrap_or_default().to_string()
+            }
+            _ if SHAPE_TYPES.contains(&kind) || is_target && !elements::is_linear(element) => {
+                bound_text.get(id).cloned().unwrap_or_default()
+            }
+            _ => continue,
+        };
+        let frame = render::str_field(element, "frameId").filter(|f| frames.iter().any(|(id, _)| id == f));
+        nodes.push(Node { id, kind, label, frame });
+    }
+
+    // Arrows bound to elements that are not nodes, like other lines, have nothing to connect
+    let is_node = |id: &str| nodes.iter().any(|n: &Node| n.id == id);
+    let mut edges = Vec::new();
+    let mut unconnected_arrows = Vec::new();
+    for arrow in arrows {
+        let id = render::str_field(arrow, "id").unwrap_or_default();
+        let label = bound_text.get(id).cloned().unwrap_or_default();
+        match (endpoint(arrow, "startBinding", &by_id), endpoint(arrow, "endBinding", &by_id)) {
+            (Some(from), Some(to)) if is_node(from) && is_node(to) => edges.push(Edge {
+                id,
+                from,
+                to,
+                label,
+                start_arrowhead: render::str_field(arrow, "startArrowhead"),
+                end_arrowhead: render::str_field(arrow, "endArrowhead"),
+                dashed: render::str_field(arrow, "strokeStyle").is_some_and(|s| s != "solid"),
+            }),
+            (from, to) => unconnected_arrows.push(models::UnconnectedArrow {
+                id: id.to_string(),
+                label,
+                from: from.map(str::to_string),
+                to: to.map(str::to_string),
+            }),
+        }
+    }
+
+    SceneGraph {
+        nodes,
+        edges,
+        frames,
+        unconnected_arrows,
+        free_text,
+    }
+}
+
+/// Whether an edge points from its start to its end, both ways, or neither way
+fn direction(edge: &Edge) -> &'static str {
+    match (edge.start_arrowhead.is_some(), edge.end_arrowhead.is_some()) {
+        (true, true) => "both",
+        (true, false) => "back",
+        (false, true) => "forward",
+        (false, false) => "none",
+    }
+}
+
+fn unplaced_lines(graph: &SceneGraph) -> Vec<String> {
+    let mut lines = Vec::new();
+    for arrow in &graph.unconnected_arrows {
+        let describe = |end: &Option<String>| end.clone().unwrap_or_else(|| "nothing".to_string());
+        lines.push(format!(
+            "Unconnected arrow {} from {} to {}{}",
+            arrow.id,
+            describe(&arrow.from),
+            describe(&arrow.to),
+            if arrow.label.is_empty() { String::new() } else { format!(": {}", arrow.label) }
+        ));
+    }
+    for text in &graph.free_text {
+        lines.push(format!("Free text {}: {}", text.id, text.text.replace('\n', " ")));
+    }
+    lines
+}
+
+fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn to_dot(graph: &SceneGraph, name: &str) -> String {
+    let node_line = |node: &Node| {
+        let shape = match node.kind {
+            "ellipse" => "ellipse",
+            "diamond" => "diamond",
+            "text" => "plaintext",
+            _ => "box",
+        };
+        format!("{} [label={}, shape={}];", dot_string(node.id), dot_string(&node.label), shape)
+    };
+
+    let mut out = format!("digraph {} {{\n", dot_string(name));
+    for (frame, frame_name) in &graph.frames {
+        let members: Vec<&Node> = graph.nodes.iter().filter(|n| n.frame == Some(*frame)).collect();
+        if members.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("  subgraph {} {{\n", dot_string(&format!("cluster_{}", frame))));
+        out.push_str(&format!("    label={};\n", dot_string(frame_name)));
+        for node in members {
+            out.push_str(&format!("    {}\n", node_line(node)));
+        }
+        out.push_str("  }\n");
+    }
+    for node in graph.nodes.iter().filter(|n| n.frame.is_none()) {
+        out.push_str(&format!("  {}\n", node_line(node)));
+    }
+    for edge in &graph.edges {
+        let mut attributes = vec![format!("dir={}", direction(edge))];
+        if !edge.label.is_empty() {
+            attributes.insert(0, format!("label={}", dot_string(&edge.label)));
+        }
+        if edge.dashed {
+            attributes.push("style=dashed".to_string());
+        }
+        out.push_str(&format!(
+            "  {} -> {} [{}];\n",
+            dot_string(edge.from),
+            dot_string(edge.to),
+            attributes.join(", ")
+        ));
+    }
+    for line in unplaced_lines(graph) {
+        out.push_str(&format!("  // {}\n", line));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn mermaid_label(label: &str) -> String {
+    format!("\"{}\"", label.replace('"', "#quot;").replace('\n', "<br>"))
+}
+
+fn to_mermaid(graph: &SceneGraph, scene: &Value) -> String {
+    // Mermaid ids must be plain words
+    let ids: HashMap<&str, String> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id, format!("n{}", i + 1)))
+        .collect();
+
+    // Follow the way most connections run in the drawing
+    let centers: HashMap<&str, render::Point> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|e| {
+            let (x1, y1, x2, y2) = render::element_bounds(e);
+            Some((render::str_field(e, "id")?, ((x1 + x2) / 2.0, (y1 + y2) / 2.0)))
+        })
+        .collect();
+    let (horizontal, vertical) = graph.edges.iter().fold((0.0, 0.0), |(h, v), edge| {
+        match (centers.get(edge.from), centers.get(edge.to)) {
+            (Some(a), Some(b)) => (h + (b.0 - a.0).abs(), v + (b.1 - a.1).abs()),
+            _ => (h, v),
+        }
+    });
+    let direction_keyword = if horizontal > vertical { "LR" } else { "TD" };
+
+    let node_line = |node: &Node| {
+        let label = mermaid_label(&node.label);
+        let shape = match node.kind {
+            "ellipse" => format!("(({}))", label),
+            "diamond" => format!("{{{}}}", label),
+            _ => format!("[{}]", label),
+        };
+        format!("{}{}", ids[node.id], shape)
+    };
+
+    let mut out = format!("flowchart {}\n", direction_keyword);
+    for (index, (frame, frame_name)) in graph.frames.iter().enumerate() {
+        let members: Vec<&Node> = graph.nodes.iter().filter(|n| n.frame == Some(*frame)).collect();
+        if members.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("    subgraph f{}[{}]\n", index + 1, mermaid_label(frame_name)));
+        for node in members {
+            out.push_str(&format!("        {}\n", node_line(node)));
+        }
+        out.push_str("    end\n");
+    }
+    for node in graph.nodes.iter().filter(|n| n.frame.is_none()) {
+        out.push_str(&format!("    {}\n", node_line(node)));
+    }
+    for edge in &graph.edges {
+        let (from, to) = match direction(edge) {
+            "back" => (edge.to, edge.from),
+            _ => (edge.from, edge.to),
+        };
+        let link = match (direction(edge), edge.dashed) {
+            ("both", false) => "<-->",
+            ("both", true) => "<-.->",
+            ("none", false) => "---",
+            ("none", true) => "-.-",
+            (_, false) => "-->",
+            (_, true) => "-.->",
+        };
+        let (Some(from), Some(to)) = (ids.get(from), ids.get(to)) else {
+            continue;
+        };
+        let label = if edge.label.is_empty() {
+            String::new()
+        } else {
+            format!("|{}|", mermaid_label(&edge.label))
+        };
+        out.push_str(&format!("    {} {}{} {}\n", from, link, label, to));
+    }
+    for line in unplaced_lines(graph) {
+        out.push_str(&format!("    %% {}\n", line));
+    }
+    out
+}
+
+fn to_json(graph: &SceneGraph) -> Result<String, String> {
+    let frame_name = |frame: Option<&str>| {
+        frame.and_then(|f| graph.frames.iter().find(|(id, _)| *id == f).map(|(_, name)| name.clone()))
+    };
+    let value = json!({
+        "nodes": graph.nodes.iter().map(|node| json!({
+            "id": node.id,
+            "type": node.kind,
+            "label": node.label,
+            "frame": frame_name(node.frame),
+        })).collect::<Vec<_>>(),
+        "edges": graph.edges.iter().map(|edge| json!({
+            "id": edge.id,
+            "from": edge.from,
+            "to": edge.to,
+            "label": edge.label,
+            "direction": direction(edge),
+            "startArrowhead": edge.start_arrowhead,
+            "endArrowhead": edge.end_arrowhead,
+        })).collect::<Vec<_>>(),
+        "unconnectedArrows": graph.unconnected_arrows,
+        "freeText": graph.free_text,
+    });
+    serde_json::to_string_pretty(&value).map_err(|e| format!("Failed to serialize graph: {}", e))
+}
+
+/// Writes the boxes and connections of a drawing as DOT, a Mermaid flowchart or JSON
+#[tauri::command]
+pub async fn export_graph(
+    file_path: String,
+    format: String,
+    output_path: Option<String>,
+) -> Result<models::GraphExportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+    let graph = build_graph(&scene);
+
+    let name = validated_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(formats::drawing_stem)
+        .unwrap_or("graph");
+    let (content, extension) = match format.to_lowercase().as_str() {
+        "dot" => (to_dot(&graph, name), "dot"),
+        "mermaid" => (to_mermaid(&graph, &scene), "mmd"),
+        // Keeps the graph apart from `.json` drawings
+        "json" => (to_json(&graph)?, "graph.json"),
+        other => {
+            return Err(format!(
+                "Unsupported graph format: {} (expected dot, mermaid or json)",
+                other
+            ));
+        }
+    };
+
+    let output = render::output_path_for(&validated_path, output_path, extension)?;
+    fs::write(&output, content).map_err(|e| format!("Failed to write graph: {}", e))?;
+
+    Ok(models::GraphExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        nodes: graph.nodes.len(),
+        edges: graph.edges.len(),
+        unconnected_arrows: graph.unconnected_arrows,
+        free_text: graph.free_text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two connected boxes, one in a frame, plus an arrow with a loose end and a note
+    fn scene() -> Value {
+        json!({
+            "type": "excalidraw",
+            "elements": [
+                { "id": "frame", "type": "frame", "name": "Backend", "x": 300, "y": -50, "width": 200, "height": 200 },
+                { "id": "a", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50,
+                  "boundElements": [{ "id": "a-text", "type": "text" }, { "id": "ab", "type": "arrow" }] },
+                { "id": "a-text", "type": "text", "containerId": "a", "text": "Client", "originalText": "Client" },
+                { "id": "b", "type": "ellipse", "x": 350, "y": 0, "width": 100, "height": 50, "frameId": "frame" },
+                { "id": "ab", "type": "arrow", "x": 100, "y": 25, "width": 250, "height": 0,
+                  "points": [[0, 0], [250, 0]], "strokeStyle": "dashed",
+                  "startArrowhead": null, "endArrowhead": "arrow",
+                  "startBinding": { "elementId": "a" }, "endBinding": { "elementId": "b" } },
+                { "id": "ab-text", "type": "text", "containerId": "ab", "text": "calls", "originalText": "calls" },
+                { "id": "loose", "type": "arrow", "x": 0, "y": 100, "width": 50, "height": 0,
+                  "points": [[0, 0], [50, 0]], "startBinding": { "elementId": "a" }, "endBinding": null },
+                { "id": "note", "type": "text", "x": 0, "y": 200, "text": "TODO", "originalText": "TODO" },
+                { "id": "gone", "type": "rectangle", "isDeleted": true },
+            ],
+        })
+    }
+
+    #[test]
+    fn builds_nodes_edges_and_leftovers() {
+        let scene = scene();
+        let graph = build_graph(&scene);
+
+        let nodes: Vec<(&str, &str, Option<&str>)> =
+            graph.nodes.iter().map(|n| (n.id, n.label.as_str(), n.frame)).collect();
+        assert_eq!(nodes, [("a", "Client", None), ("b", "", Some("frame"))]);
+
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        assert_eq!((edge.from, edge.to, edge.label.as_str()), ("a", "b", "calls"));
+        assert_eq!(direction(edge), "forward");
+        assert!(edge.dashed);
+
+        assert_eq!(graph.unconnected_arrows.len(), 1);
+        let loose = &graph.unconnected_arrows[0];
+        assert_eq!((loose.id.as_str(), loose.from.as_deref(), loose.to.as_deref()), ("loose", Some("a"), None));
+        let free: Vec<&str> = graph.free_text.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(free, ["TODO"]);
+    }
+
+    #[test]
+    fn writes_dot() {
+        let scene = scene();
+        let dot = to_dot(&build_graph(&scene), "diagram");
+        assert!(dot.starts_with("digraph \"diagram\" {\n"));
+        assert!(dot.contains("  subgraph \"cluster_frame\" {\n    label=\"Backend\";\n    \"b\" [label=\"\", shape=ellipse];\n  }"));
+        assert!(dot.contains("  \"a\" [label=\"Client\", shape=box];"));
+        assert!(dot.contains("  \"a\" -> \"b\" [label=\"calls\", dir=forward, style=dashed];"));
+        assert!(dot.contains("  // Unconnected arrow loose from a to nothing"));
+        assert!(dot.contains("  // Free text note: TODO"));
+    }
+
+    #[test]
+    fn writes_mermaid() {
+        let scene = scene();
+        let mermaid = to_mermaid(&build_graph(&scene), &scene);
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("    subgraph f1[\"Backend\"]\n        n2((\"\"))\n    end"));
+        assert!(mermaid.contains("    n1[\"Client\"]"));
+        assert!(mermaid.contains("    n1 -.->|\"calls\"| n2"));
+        assert!(mermaid.contains("    %% Unconnected arrow loose from a to nothing"));
+        assert!(mermaid.contains("    %% Free text note: TODO"));
+    }
+
+    #[test]
+    fn writes_json_with_camel_case_keys() {
+        let scene = scene();
+        let value: Value = serde_json::from_str(&to_json(&build_graph(&scene)).unwrap()).unwrap();
+        assert_eq!(value["nodes"][1]["frame"], "Backend");
+        let edge = &value["edges"][0];
+        assert_eq!(edge["direction"], "forward");
+        assert!(edge["startArrowhead"].is_null());
+        assert_eq!(edge["endArrowhead"], "arrow");
+        assert_eq!(value["unconnectedArrows"][0]["id"], "loose");
+        assert_eq!(value["freeText"][0]["text"], "TODO");
+    }
+}
@@ -0,0 +1,342 @@
+use super::*;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, GenericImageView, ImageEncoder, imageops};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+struct Optimized {
+    mime_type: String,
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+#[tauri::command]
+pub async fn optimize_images(
+    app: AppHandle,
+    file_path: String,
+    options: Option<models::ImageOptimizeOptions>,
+) -> Result<models::ImageOptimizeReport, String> {
+    let options = options.unwrap_or_default();
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    security::validate_excalidraw_file(&validated_path)?;
+
+    let content = formats::read_scene_content(&validated_path)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    security::validate_excalidraw_content(&content)?;
+
+    let mut scene: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let mut results = Vec::new();
+    // File id -> resize factor, used to keep image crops in sync
+    let mut scale_factors: HashMap<String, f64> = HashMap::new();
+
+    if let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) {
+        let mut ids: Vec<String> = files.keys().cloned().collect();
+        ids.sort();
+
+        for id in ids {
+            let Some(entry) = files.get_mut(&id) else {
+                continue;
+            };
+            let result = optimize_entry(&id, entry, &validated_path, &options)?;
+            if result.action == models::ImageAction::Optimized && result.width_before > 0 {
+                let factor = result.width_after as f64 / result.width_before as f64;
+                if (factor - 1.0).abs() > f64::EPSILON {
+                    scale_factors.insert(id.clone(), factor);
+                }
+            }
+            results.push(result);
+        }
+    }
+
+    if let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) {
+        for element in elements.iter_mut() {
+            let factor = element
+                .get("fileId")
+                .and_then(|id| id.as_str())
+                .and_then(|id| scale_factors.get(id))
+                .copied();
+            if let (Some(factor), Some(crop)) = (
+                factor,
+                element.get_mut("crop").and_then(|c| c.as_object_mut()),
+            ) {
+                for key in ["x", "y", "width", "height", "naturalWidth", "naturalHeight"] {
+                    if let Some(v) = crop.get(key).and_then(|v| v.as_f64()) {
+                        crop.insert(key.to_string(), serde_json::json!(v * factor));
+                    }
+                }
+            }
+        }
+    }
+
+    let bytes_before: u64 = results.iter().map(|r| r.bytes_before).sum();
+    let bytes_after: u64 = results.iter().map(|r| r.bytes_after).sum();
+    let changed = results.iter().any(|r| r.action == models::ImageAction::Optimized);
+
+    if changed && !options.dry_run {
+        let new_content = serde_json::to_string_pretty(&scene)
+            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        formats::write_scene_content(&validated_path, &new_content, &export::font_dirs(&app))
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+    }
+
+    Ok(models::ImageOptimizeReport {
+        file_path: validated_path.to_string_lossy().to_string(),
+        images: results,
+        bytes_before,
+        bytes_after,
+    })
+}
+
+fn optimize_entry(
+    id: &str,
+    entry: &mut Value,
+    file_path: &Path,
+    options: &models::ImageOptimizeOptions,
+) -> Result<models::ImageOptimizeResult, String> {
+    let mut result = models::ImageOptimizeResult {
+        file_id: id.to_string(),
+        action: models::ImageAction::Skipped,
+        reason: None,
+        mime_before: String::new(),
+        mime_after: String::new(),
+        bytes_before: 0,
+        bytes_after: 0,
+        width_before: 0,
+        height_before: 0,
+        width_after: 0,
+        height_after: 0,
+    };
+
+    let asset_ref = entry.get("assetRef").and_then(|a| a.as_str()).map(str::to_string);
+    // The optimized asset goes next to the original, which may be in an ancestor's asset folder
+    let mut asset_dir = None;
+    let (mime_type, bytes) = if let Some(asset_name) = &asset_ref {
+        let asset_path = match assets::find_asset(file_path, asset_name) {
+            Ok(path) => path,
+            Err(e) => {
+                result.reason = Some(e);
+                return Ok(result);
+            }
+        };
+        let bytes = fs::read(&asset_path)
+            .map_err(|e| format!("Failed to read asset {}: {}", asset_name, e))?;
+        asset_dir = asset_path.parent().map(Path::to_path_buf);
+        let mime_type = entry
+            .get("mimeType")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default()
+            .to_string();
+        (mime_type, bytes)
+    } else {
+        match entry
+            .get("dataURL")
+            .and_then(|d| d.as_str())
+            .and_then(assets::decode_data_url)
+        {
+            Some(decoded) => decoded,
+            None => {
+                result.reason = Some("Image has no data".to_string());
+                return Ok(result);
+            }
+        }
+    };
+
+    result.mime_before = mime_type.clone();
+    result.mime_after = mime_type.clone();
+    result.bytes_before = bytes.len() as u64;
+    result.bytes_after = bytes.len() as u64;
+
+    // Vector and animated images would be damaged by a raster re-encode
+    if matches!(mime_type.as_str(), "image/svg+xml" | "image/gif") {
+        result.reason = Some("Vector and animated images are not re-encoded".to_string());
+        return Ok(result);
+    }
+
+    let image = match image::load_from_memory(&bytes) {
+        Ok(image) => image,
+        Err(e) => {
+            result.reason = Some(format!("Failed to decode image: {}", e));
+            return Ok(result);
+        }
+    };
+    let (width, height) = image.dimensions();
+    result.width_before = width;
+    result.height_before = height;
+    result.width_after = width;
+    result.height_after = height;
+
+    let target_mime = match options.format.as_deref() {
+        Some(format) => mime_for_format(format)?,
+        None => mime_type.clone(),
+    };
+    let fits = width.max(height) <= options.max_dimension;
+    let converting = target_mime != mime_type;
+    if fits && bytes.len() as u64 <= options.min_bytes && !converting {
+        result.action = models::ImageAction::Kept;
+        result.reason = Some("Already smaller than the minimum size".to_string());
+        return Ok(result);
+    }
+
+    // A requested format change is applied even when it makes the image larger
+    let optimized = reencode(image, &target_mime, options)?;
+    if optimized.bytes.len() >= bytes.len() && fits && !converting {
+        result.action = models::ImageAction::Kept;
+        result.reason = Some("Re-encoding would not make it smaller".to_string());
+        return Ok(result);
+    }
+
+    result.action = models::ImageAction::Optimized;
+    result.mime_after = optimized.mime_type.clone();
+    result.bytes_after = optimized.bytes.len() as u64;
+    result.width_after = optimized.width;
+    result.height_after = optimized.height;
+
+    if options.dry_run {
+        return Ok(result);
+    }
+
+    let Some(obj) = entry.as_object_mut() else {
+        return Ok(result);
+    };
+    obj.insert("mimeType".to_string(), Value::String(optimized.mime_type.clone()));
+
+    if asset_ref.is_some() {
+        // Old asset stays in place for other drawings, gc_assets cleans it up
+        let dir = asset_dir.ok_or("Asset folder not found")?;
+        let asset_name = format!(
+            "{}.{}",
+            assets::hash_bytes(&optimized.bytes),
+            assets::extension_for_mime(&optimized.mime_type)
+        );
+        let asset_path = dir.join(&asset_name);
+        if !asset_path.exists() {
+            fs::write(&asset_path, &optimized.bytes)
+                .map_err(|e| format!("Failed to write asset {}: {}", asset_name, e))?;
+        }
+        obj.insert("assetRef".to_string(), Value::String(asset_name));
+    } else {
+        obj.insert(
+            "dataURL".to_string(),
+            Value::String(assets::encode_data_url(&optimized.mime_type, &optimized.bytes)),
+        );
+    }
+
+    Ok(result)
+}
+
+fn reencode(
+    image: DynamicImage,
+    mime_type: &str,
+    options: &models::ImageOptimizeOptions,
+) -> Result<Optimized, String> {
+    let (width, height) = image.dimensions();
+    let image = if width.max(height) > options.max_dimension {
+        image.resize(
+            options.max_dimension,
+            options.max_dimension,
+            imageops::FilterType::Lanczos3,
+        )
+    } else {
+        image
+    };
+    let (width, height) = image.dimensions();
+
+    let mut bytes = Vec::new();
+    match mime_type {
+        "image/jpeg" => {
+            let rgb = flatten_onto_white(&image);
+            JpegEncoder::new_with_quality(&mut bytes, options.quality.clamp(1, 100))
+                .write_image(rgb.as_raw(), width, height, image::ExtendedColorType::Rgb8)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        }
+        "image/webp" => {
+            let rgba = image.to_rgba8();
+            WebPEncoder::new_lossless(&mut bytes)
+                .write_image(rgba.as_raw(), width, height, image::ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+        }
+        _ => {
+            let rgba = image.to_rgba8();
+            PngEncoder::new_with_quality(&mut bytes, CompressionType::Best, FilterType::Adaptive)
+                .write_image(rgba.as_raw(), width, height, image::ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        }
+    }
+
+    let mime_type = match mime_type {
+        "image/jpeg" | "image/webp" => mime_type,
+        _ => "image/png",
+    };
+
+    Ok(Optimized {
+        mime_type: mime_type.to_string(),
+        bytes,
+        width,
+        height,
+    })
+}
+
+fn flatten_onto_white(image: &DynamicImage) -> image::RgbImage {
+    let rgba = image.to_rgba8();
+    image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let alpha = a as u32;
+        let blend = |c: u8| ((c as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+fn mime_for_format(format: &str) -> Result<String, String> {
+    match format.to_lowercase().as_str() {
+        "png" => Ok("image/png".to_string()),
+        "jpg" | "jpeg" => Ok("image/jpeg".to_string()),
+        "webp" => Ok("image/webp".to_string()),
+        other => Err(format!("Unsupported image format: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tiny_png() -> Value {
+        let image = DynamicImage::new_rgba8(2, 2);
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        json!({ "id": "img", "mimeType": "image/png", "dataURL": assets::encode_data_url("image/png", &bytes) })
+    }
+
+    #[test]
+    fn small_images_are_kept_when_no_format_is_requested() {
+        let mut entry = tiny_png();
+        let result = optimize_entry("img", &mut entry, Path::new("drawing.excalidraw"), &Default::default()).unwrap();
+        assert_eq!(result.action, models::ImageAction::Kept);
+        assert!(result.reason.is_some());
+    }
+
+    #[test]
+    fn requested_format_changes_are_applied_even_when_larger() {
+        let mut entry = tiny_png();
+        let options = models::ImageOptimizeOptions { format: Some("jpeg".to_string()), ..Default::default() };
+        let result = optimize_entry("img", &mut entry, Path::new("drawing.excalidraw"), &options).unwrap();
+        assert_eq!(result.action, models::ImageAction::Optimized);
+        assert_eq!(result.mime_after, "image/jpeg");
+        assert!(entry["dataURL"].as_str().unwrap().starts_with("data:image/jpeg;base64,"));
+    }
+
+    #[test]
+    fn actions_serialize_in_lowercase() {
+        assert_eq!(serde_json::to_value(models::ImageAction::Kept).unwrap(), json!("kept"));
+    }
+}
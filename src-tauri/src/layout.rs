@@ -0,0 +1,567 @@
+use super::*;
+use render::{Bounds, Point};
+use std::cmp::Reverse;
+
+/// Direction ranks flow in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    TopBottom,
+    BottomTop,
+    LeftRight,
+    RightLeft,
+}
+
+impl Direction {
+    /// Accepts the Mermaid and DOT spellings: TB, TD, BT, LR and RL
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "TB" | "TD" => Some(Self::TopBottom),
+            "BT" => Some(Self::BottomTop),
+            "LR" => Some(Self::LeftRight),
+            "RL" => Some(Self::RightLeft),
+            _ => None,
+        }
+    }
+
+    fn is_horizontal(self) -> bool {
+        matches!(self, Self::LeftRight | Self::RightLeft)
+    }
+}
+
+/// Either end of an edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vertex {
+    Node(usize),
+    Cluster(usize),
+}
+
+/// A box drawn around a set of nodes and nested clusters
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    pub parent: Option<usize>,
+    /// Size of the title shown above the members
+    pub label: (f64, f64),
+}
+
+/// Input of [`layout`]: node sizes, cluster nesting and edges
+#[derive(Debug, Clone)]
+pub struct Graph {
+    pub direction: Direction,
+    pub nodes: Vec<(f64, f64)>,
+    pub node_clusters: Vec<Option<usize>>,
+    pub clusters: Vec<Cluster>,
+    pub edges: Vec<(Vertex, Vertex)>,
+    pub rank_gap: f64,
+    pub node_gap: f64,
+    pub cluster_padding: f64,
+}
+
+impl Graph {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            nodes: Vec::new(),
+            node_clusters: Vec::new(),
+            clusters: Vec::new(),
+            edges: Vec::new(),
+            rank_gap: 60.0,
+            node_gap: 40.0,
+            cluster_padding: 20.0,
+        }
+    }
+
+    pub fn add_node(&mut self, size: (f64, f64), cluster: Option<usize>) -> usize {
+        self.nodes.push(size);
+        self.node_clusters.push(cluster);
+        self.nodes.len() - 1
+    }
+
+    pub fn add_cluster(&mut self, parent: Option<usize>, label: (f64, f64)) -> usize {
+        self.clusters.push(Cluster { parent, label });
+        self.clusters.len() - 1
+    }
+
+    pub fn add_edge(&mut self, from: Vertex, to: Vertex) {
+        self.edges.push((from, to));
+    }
+
+    fn parent(&self, vertex: Vertex) -> Option<usize> {
+        match vertex {
+            Vertex::Node(i) => self.node_clusters[i],
+            Vertex::Cluster(c) => self.clusters[c].parent,
+        }
+    }
+
+    fn depth(&self, cluster: usize) -> usize {
+        std::iter::successors(self.clusters[cluster].parent, |c| self.clusters[*c].parent).count()
+    }
+
+    /// The member of `container` that holds `vertex`, if `vertex` is inside it
+    fn representative(&self, vertex: Vertex, container: Option<usize>) -> Option<Vertex> {
+        let mut current = vertex;
+        loop {
+            let parent = self.parent(current);
+            if parent == container {
+                return Some(current);
+            }
+            current = Vertex::Cluster(parent?);
+        }
+    }
+}
+
+/// Result of [`layout`], in absolute coordinates
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub nodes: Vec<Bounds>,
+    pub clusters: Vec<Bounds>,
+    /// Bend points of every edge, from its source to its target
+    pub edges: Vec<Vec<Point>>,
+}
+
+/// Layered layout: every cluster is laid out on its own, innermost first, and then
+/// placed in its parent as a single node
+pub fn layout(graph: &Graph) -> Layout {
+    let mut containers: Vec<Option<usize>> = (0..graph.clusters.len()).map(Some).collect();
+    containers.sort_by_key(|c| Reverse(c.map(|c| graph.depth(c))));
+    containers.push(None);
+
+    let mut cluster_sizes = vec![(0.0, 0.0); graph.clusters.len()];
+    // Positions relative to the top left corner of the enclosing cluster
+    let mut node_offsets = vec![(0.0, 0.0); graph.nodes.len()];
+    let mut cluster_offsets = vec![(0.0, 0.0); graph.clusters.len()];
+    let mut edge_bends: Vec<(Option<usize>, Vec<Point>)> = vec![(None, Vec::new()); graph.edges.len()];
+
+    for container in containers {
+        let mut members: Vec<Vertex> = (0..graph.nodes.len())
+            .filter(|i| graph.node_clusters[*i] == container)
+            .map(Vertex::Node)
+            .collect();
+        members.extend(
+            (0..graph.clusters.len())
+                .filter(|c| graph.clusters[*c].parent == container)
+                .map(Vertex::Cluster),
+        );
+        let sizes: Vec<(f64, f64)> = members
+            .iter()
+            .map(|m| match m {
+                Vertex::Node(i) => graph.nodes[*i],
+                Vertex::Cluster(c) => cluster_sizes[*c],
+            })
+            .collect();
+
+        let mut local_edges = Vec::new();
+        let mut local_edge_ids = Vec::new();
+        for (id, (from, to)) in graph.edges.iter().enumerate() {
+            let from = graph.representative(*from, container);
+            let to = graph.representative(*to, container);
+            if let (Some(from), Some(to)) = (from, to)
+                && from != to
+            {
+                let index = |v: Vertex| members.iter().position(|m| *m == v).unwrap_or(0);
+                local_edges.push((index(from), index(to)));
+                local_edge_ids.push(id);
+            }
+        }
+
+        let (positions, bends, size) = layered(
+            &sizes,
+            &local_edges,
+            graph.direction,
+            graph.rank_gap,
+            graph.node_gap,
+        );
+
+        let (inset_x, inset_y, size) = match container {
+            Some(c) => {
+                let padding = graph.cluster_padding;
+                let label = graph.clusters[c].label;
+                let header = if label.1 > 0.0 { label.1 + padding / 2.0 } else { 0.0 };
+                let width = (size.0 + padding * 2.0).max(label.0 + padding * 2.0).max(60.0);
+                let height = size.1 + padding * 2.0 + header;
+                // Center the members when the title is wider than they are
+                let inset_x = (width - size.0) / 2.0;
+                (inset_x, padding + header, (width, height))
+            }
+            None => (0.0, 0.0, size),
+        };
+
+        for (member, (x, y)) in members.iter().zip(positions) {
+            match member {
+                Vertex::Node(i) => node_offsets[*i] = (x + inset_x, y + inset_y),
+                Vertex::Cluster(c) => cluster_offsets[*c] = (x + inset_x, y + inset_y),
+            }
+        }
+        for (edge, points) in local_edge_ids.into_iter().zip(bends) {
+            let points = points.into_iter().map(|(x, y)| (x + inset_x, y + inset_y)).collect();
+            edge_bends[edge] = (container, points);
+        }
+        if let Some(c) = container {
+            cluster_sizes[c] = size;
+        }
+    }
+
+    // Clusters sorted parents first, so each origin builds on its parent's
+    let mut origins = vec![(0.0, 0.0); graph.clusters.len()];
+    let mut by_depth: Vec<usize> = (0..graph.clusters.len()).collect();
+    by_depth.sort_by_key(|c| graph.depth(*c));
+    let origin_of = |origins: &[Point], container: Option<usize>| container.map(|c| origins[c]).unwrap_or_default();
+    for c in by_depth {
+        let (px, py) = origin_of(&origins, graph.clusters[c].parent);
+        origins[c] = (px + cluster_offsets[c].0, py + cluster_offsets[c].1);
+    }
+
+    let nodes = (0..graph.nodes.len())
+        .map(|i| {
+            let (ox, oy) = origin_of(&origins, graph.node_clusters[i]);
+            let (x, y) = (ox + node_offsets[i].0, oy + node_offsets[i].1);
+            (x, y, x + graph.nodes[i].0, y + graph.nodes[i].1)
+        })
+        .collect();
+    let clusters = (0..graph.clusters.len())
+        .map(|c| {
+            let (x, y) = origins[c];
+            (x, y, x + cluster_sizes[c].0, y + cluster_sizes[c].1)
+        })
+        .collect();
+    let edges = edge_bends
+        .into_iter()
+        .map(|(container, points)| {
+            let (ox, oy) = origin_of(&origins, container);
+            points.into_iter().map(|(x, y)| (ox + x, oy + y)).collect()
+        })
+        .collect();
+
+    Layout { nodes, clusters, edges }
+}
+
+/// Sugiyama-style layout of a flat graph. Returns the top left corner of every node,
+/// the bend points of every edge and the size of the drawing.
+fn layered(
+    sizes: &[(f64, f64)],
+    edges: &[(usize, usize)],
+    direction: Direction,
+    rank_gap: f64,
+    node_gap: f64,
+) -> (Vec<Point>, Vec<Vec<Point>>, (f64, f64)) {
+    let n = sizes.len();
+    if n == 0 {
+        return (Vec::new(), vec![Vec::new(); edges.len()], (0.0, 0.0));
+    }
+
+    // Work as if ranks go top to bottom: "across" runs along a rank, "along" between ranks
+    let (across, along): (Vec<f64>, Vec<f64>) = sizes
+        .iter()
+        .map(|(w, h)| if direction.is_horizontal() { (*h, *w) } else { (*w, *h) })
+        .unzip();
+
+    let reversed = break_cycles(n, edges);
+    let dag: Vec<(usize, usize)> = edges
+        .iter()
+        .zip(&reversed)
+        .map(|(&(u, v), rev)| if *rev { (v, u) } else { (u, v) })
+        .collect();
+    let mut rank = assign_ranks(n, &dag);
+
+    // Long edges get a dummy node on every rank they cross
+    let mut v_across = across.clone();
+    let mut v_real = vec![true; n];
+    let mut chains: Vec<Vec<usize>> = Vec::with_capacity(dag.len());
+    for &(u, v) in &dag {
+        let mut chain = vec![u];
+        if u != v {
+            for r in rank[u] + 1..rank[v] {
+                v_across.push(0.0);
+                v_real.push(false);
+                rank.push(r);
+                chain.push(rank.len() - 1);
+            }
+            chain.push(v);
+        }
+        chains.push(chain);
+    }
+    let total = rank.len();
+    let mut up: Vec<Vec<usize>> = vec![Vec::new(); total];
+    let mut down: Vec<Vec<usize>> = vec![Vec::new(); total];
+    for chain in &chains {
+        for pair in chain.windows(2) {
+            down[pair[0]].push(pair[1]);
+            up[pair[1]].push(pair[0]);
+        }
+    }
+
+    let layer_count = rank.iter().max().map(|r| r + 1).unwrap_or(1);
+    let mut layers = vec![Vec::new(); layer_count];
+    for (v, r) in rank.iter().enumerate() {
+        layers[*r].push(v);
+    }
+    order_layers(&mut layers, &up, &down);
+
+    // Coordinates along each rank
+    let gap = |a: usize, b: usize| {
+        let sep = if v_real[a] && v_real[b] { node_gap } else { node_gap / 2.0 };
+        (v_across[a] + v_across[b]) / 2.0 + sep
+    };
+    let mut x = vec![0.0; total];
+    for layer in &layers {
+        for i in 1..layer.len() {
+            x[layer[i]] = x[layer[i - 1]] + gap(layer[i - 1], layer[i]);
+        }
+    }
+    for _ in 0..8 {
+        for layer in layers.iter().skip(1) {
+            let desired = desired_positions(layer, &up, &x);
+            place_layer(layer, &desired, &gap, &mut x);
+        }
+        for layer in layers.iter().rev().skip(1) {
+            let desired = desired_positions(layer, &down, &x);
+            place_layer(layer, &desired, &gap, &mut x);
+        }
+    }
+
+    // Coordinates between ranks
+    let thickness: Vec<f64> = layers
+        .iter()
+        .map(|layer| layer.iter().filter(|v| **v < n).map(|v| along[*v]).fold(0.0, f64::max))
+        .collect();
+    let mut rank_center = Vec::with_capacity(layer_count);
+    let mut top = 0.0;
+    for t in &thickness {
+        rank_center.push(top + t / 2.0);
+        top += t + rank_gap;
+    }
+    let total_along = top - rank_gap;
+
+    let min_x = (0..total).map(|v| x[v] - v_across[v] / 2.0).fold(f64::INFINITY, f64::min);
+    let max_x = (0..total).map(|v| x[v] + v_across[v] / 2.0).fold(f64::NEG_INFINITY, f64::max);
+    let total_across = max_x - min_x;
+
+    let place = |a: f64, b: f64| -> Point {
+        let a = a - min_x;
+        match direction {
+            Direction::TopBottom => (a, b),
+            Direction::BottomTop => (a, total_along - b),
+            Direction::LeftRight => (b, a),
+            Direction::RightLeft => (total_along - b, a),
+        }
+    };
+
+    let positions = (0..n)
+        .map(|v| {
+            let (cx, cy) = place(x[v], rank_center[rank[v]]);
+            (cx - sizes[v].0 / 2.0, cy - sizes[v].1 / 2.0)
+        })
+        .collect();
+    let bends = chains
+        .iter()
+        .zip(&reversed)
+        .map(|(chain, rev)| {
+            let mut points: Vec<Point> = chain
+                .iter()
+                .filter(|v| **v >= n)
+                .map(|v| place(x[*v], rank_center[rank[*v]]))
+                .collect();
+            if *rev {
+                points.reverse();
+            }
+            points
+        })
+        .collect();
+    let size = if direction.is_horizontal() {
+        (total_along, total_across)
+    } else {
+        (total_across, total_along)
+    };
+
+    (positions, bends, size)
+}
+
+/// Depth-first search; every edge back to a node on the stack gets reversed
+fn break_cycles(n: usize, edges: &[(usize, usize)]) -> Vec<bool> {
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (i, (u, v)) in edges.iter().enumerate() {
+        if u != v {
+            outgoing[*u].push(i);
+        }
+    }
+
+    // 0 = unvisited, 1 = on the stack, 2 = done
+    let mut state = vec![0u8; n];
+    let mut reversed = vec![false; edges.len()];
+    for start in 0..n {
+        if state[start] != 0 {
+            continue;
+        }
+        let mut stack = vec![(start, 0)];
+        state[start] = 1;
+        while let Some((node, next)) = stack.pop() {
+            let Some(&edge) = outgoing[node].get(next) else {
+                state[node] = 2;
+                continue;
+            };
+            stack.push((node, next + 1));
+            let target = edges[edge].1;
+            match state[target] {
+                0 => {
+                    state[target] = 1;
+                    stack.push((target, 0));
+                }
+                1 => reversed[edge] = true,
+                _ => {}
+            }
+        }
+    }
+    reversed
+}
+
+/// Longest path ranking, with sources pulled down next to their first successor
+fn assign_ranks(n: usize, dag: &[(usize, usize)]) -> Vec<usize> {
+    let mut incoming = vec![0; n];
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for &(u, v) in dag.iter().filter(|(u, v)| u != v) {
+        incoming[v] += 1;
+        successors[u].push(v);
+    }
+
+    let mut rank = vec![0usize; n];
+    let mut has_predecessor = vec![false; n];
+    let mut queue: Vec<usize> = (0..n).filter(|v| incoming[*v] == 0).collect();
+    let mut topological = Vec::with_capacity(n);
+    let mut head = 0;
+    while head < queue.len() {
+        let u = queue[head];
+        head += 1;
+        topological.push(u);
+        for &v in &successors[u] {
+            rank[v] = rank[v].max(rank[u] + 1);
+            has_predecessor[v] = true;
+            incoming[v] -= 1;
+            if incoming[v] == 0 {
+                queue.push(v);
+            }
+        }
+    }
+
+    for &u in topological.iter().rev() {
+        if !has_predecessor[u]
+            && let Some(closest) = successors[u].iter().map(|v| rank[*v]).min()
+        {
+            rank[u] = closest.saturating_sub(1);
+        }
+    }
+    rank
+}
+
+/// Barycenter sweeps, keeping the ordering with the fewest crossings
+fn order_layers(layers: &mut [Vec<usize>], up: &[Vec<usize>], down: &[Vec<usize>]) {
+    let total: usize = layers.iter().map(|l| l.len()).sum();
+    let mut position = vec![0.0; total];
+    let index = |layers: &[Vec<usize>], position: &mut [f64]| {
+        for layer in layers {
+            for (i, v) in layer.iter().enumerate() {
+                position[*v] = i as f64;
+            }
+        }
+    };
+    index(layers, &mut position);
+
+    let mut best = layers.to_vec();
+    let mut best_crossings = crossings(layers, down, &position);
+    for iteration in 0..12 {
+        let sweep_down = iteration % 2 == 0;
+        let ranks: Vec<usize> = if sweep_down {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+        for r in ranks {
+            let neighbors = if sweep_down { up } else { down };
+            let mut keyed: Vec<(f64, usize)> = layers[r]
+                .iter()
+                .map(|v| {
+                    let adjacent = &neighbors[*v];
+                    let key = if adjacent.is_empty() {
+                        position[*v]
+                    } else {
+                        adjacent.iter().map(|a| position[*a]).sum::<f64>() / adjacent.len() as f64
+                    };
+                    (key, *v)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[r] = keyed.into_iter().map(|(_, v)| v).collect();
+            for (i, v) in layers[r].iter().enumerate() {
+                position[*v] = i as f64;
+            }
+        }
+
+        let count = crossings(layers, down, &position);
+        if count < best_crossings {
+            best_crossings = count;
+            best = layers.to_vec();
+        }
+    }
+    layers.clone_from_slice(&best);
+}
+
+fn crossings(layers: &[Vec<usize>], down: &[Vec<usize>], position: &[f64]) -> usize {
+    let mut count = 0;
+    for layer in layers {
+        let segments: Vec<(f64, f64)> = layer
+            .iter()
+            .flat_map(|u| down[*u].iter().map(move |v| (position[*u], position[*v])))
+            .collect();
+        for (i, a) in segments.iter().enumerate() {
+            for b in &segments[i + 1..] {
+                if (a.0 - b.0) * (a.1 - b.1) < 0.0 {
+                    count += 1;
+                }
+            }
+        }
+    }
+    count
+}
+
+fn desired_positions(layer: &[usize], neighbors: &[Vec<usize>], x: &[f64]) -> Vec<f64> {
+    layer
+        .iter()
+        .map(|v| {
+            let adjacent = &neighbors[*v];
+            if adjacent.is_empty() {
+                x[*v]
+            } else {
+                adjacent.iter().map(|a| x[*a]).sum::<f64>() / adjacent.len() as f64
+            }
+        })
+        .collect()
+}
+
+/// Moves the nodes of a rank as close to `desired` as their order and spacing allow.
+/// With the spacing subtracted out this is isotonic regression, solved by pooling
+/// adjacent violators.
+fn place_layer(layer: &[usize], desired: &[f64], gap: &impl Fn(usize, usize) -> f64, x: &mut [f64]) {
+    let mut offsets = vec![0.0; layer.len()];
+    for i in 1..layer.len() {
+        offsets[i] = offsets[i - 1] + gap(layer[i - 1], layer[i]);
+    }
+
+    // Blocks of (mean, count)
+    let mut blocks: Vec<(f64, usize)> = Vec::new();
+    for (target, offset) in desired.iter().zip(&offsets) {
+        blocks.push((target - offset, 1));
+        while blocks.len() > 1 && blocks[blocks.len() - 2].0 > blocks[blocks.len() - 1].0 {
+            let (mean_b, count_b) = blocks.pop().unwrap_or_default();
+            if let Some((mean_a, count_a)) = blocks.last_mut() {
+                *mean_a = (*mean_a * *count_a as f64 + mean_b * count_b as f64) / (*count_a + count_b) as f64;
+                *count_a += count_b;
+            }
+        }
+    }
+
+    let mut i = 0;
+    for (mean, count) in blocks {
+        for _ in 0..count {
+            x[layer[i]] = mean + offsets[i];
+            i += 1;
+        }
+    }
+}
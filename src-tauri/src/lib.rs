@@ -3,6 +3,33 @@ mod models;
 mod file_ops;
 mod preferences;
 mod ai_logs;
+mod canonical;
+mod assets;
+mod compact;
+mod image_opt;
+mod templates;
+mod render;
+mod export;
+mod formats;
+mod lz_string;
+mod obsidian;
+mod elements;
+mod drawio;
+mod layout;
+mod mermaid;
+mod dot;
+mod graph_export;
+mod tldraw;
+mod library;
+mod zip;
+mod bundle;
+mod site;
+mod markdown;
+mod split_merge;
+mod search;
+mod search_index;
+mod replace;
+mod links;
 
 use tauri::{ Emitter, Manager };
 use std::sync::Mutex;
@@ -19,7 +46,10 @@ pub fn run() {
             app.manage(models::AppState {
                 current_directory: Mutex::new(None),
                 modified_files: Mutex::new(Vec::new()),
+                watcher: Mutex::new(None),
             });
+            app.manage(search_index::SearchIndexState::default());
+            app.manage(links::LinkIndexState::default());
 
             let window = app.get_webview_window("main").unwrap();
             let window_clone = window.clone();
@@ -45,6 +75,45 @@ pub fn run() {
             file_ops::delete_file,
             file_ops::watch_directory,
             file_ops::force_close_app,
+            assets::gc_assets,
+            compact::compact_file,
+            compact::compact_workspace,
+            image_opt::optimize_images,
+            templates::list_templates,
+            library::list_libraries,
+            library::load_library,
+            library::import_library,
+            library::merge_libraries,
+            library::dedupe_library,
+            library::export_library,
+            library::add_to_library,
+            library::extract_library_items,
+            bundle::export_bundle,
+            bundle::import_bundle,
+            site::export_site,
+            markdown::export_markdown,
+            split_merge::split_by_frames,
+            split_merge::merge_files,
+            search::search_workspace,
+            search_index::search_index,
+            search_index::search_index_status,
+            replace::replace_in_workspace,
+            replace::undo_replace,
+            links::get_backlinks,
+            links::get_link_graph,
+            links::find_broken_links,
+            links::rename_with_links,
+            links::move_with_links,
+            export::export_svg,
+            export::export_png,
+            export::export_pdf,
+            drawio::import_drawio,
+            drawio::export_drawio,
+            mermaid::mermaid_to_excalidraw,
+            dot::import_dot,
+            graph_export::export_graph,
+            tldraw::import_tldraw,
+            tldraw::export_tldraw,
             preferences::get_preferences,
             preferences::save_preferences,
             ai_logs::save_ai_log,
@@ -0,0 +1,533 @@
+use super::*;
+use serde_json::{Map, Value, json};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Workspace folder holding per-workspace libraries
+pub const LIBRARIES_DIR: &str = ".libraries";
+
+pub const LIBRARY_EXTENSION: &str = "excalidrawlib";
+
+const WORKSPACE_SOURCE: &str = "workspace";
+const APP_SOURCE: &str = "app";
+
+/// Keys that differ between copies of the same shapes and are ignored when deduplicating
+const VOLATILE_KEYS: [&str; 12] = [
+    "id",
+    "seed",
+    "version",
+    "versionNonce",
+    "updated",
+    "index",
+    "groupIds",
+    "frameId",
+    "boundElements",
+    "containerId",
+    "startBinding",
+    "endBinding",
+];
+
+fn app_libraries_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("libraries"))
+}
+
+/// Finds the nearest `.libraries` folder in `dir` or one of its ancestors, stopping at the
+/// open workspace like workspace templates do
+fn workspace_libraries_dir(state: &models::AppState, dir: &Path) -> Option<PathBuf> {
+    let workspace = file_ops::workspace_dir(state, dir);
+    file_ops::find_workspace_folder(dir, &workspace, LIBRARIES_DIR)
+}
+
+/// Folder for a source, created on first write
+fn libraries_dir(app: &AppHandle, directory: Option<&Path>, source: &str) -> Result<PathBuf, String> {
+    match source {
+        WORKSPACE_SOURCE => {
+            let directory = directory.ok_or("A workspace directory is required for workspace libraries")?;
+            let state = app.state::<models::AppState>();
+            Ok(workspace_libraries_dir(&state, directory).unwrap_or_else(|| directory.join(LIBRARIES_DIR)))
+        }
+        APP_SOURCE => app_libraries_dir(app).ok_or_else(|| "App data directory is not available".to_string()),
+        _ => Err(format!("Unknown library source: {}", source)),
+    }
+}
+
+/// Path of a library id such as `workspace/icons` or `app/flowchart`
+fn library_path(app: &AppHandle, directory: Option<&Path>, library_id: &str) -> Result<PathBuf, String> {
+    let (source, name) = library_id
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid library id: {}", library_id))?;
+    let dir = libraries_dir(app, directory, source)?;
+    security::safe_path_join(&dir, &format!("{}.{}", name, LIBRARY_EXTENSION))
+}
+
+fn validated_directory(directory: Option<String>) -> Result<Option<PathBuf>, String> {
+    directory
+        .map(|dir| security::validate_path(Path::new(&dir), None))
+        .transpose()
+}
+
+/// Reads a library in either the current format (`libraryItems`) or the original one,
+/// where `library` is a list of element lists
+pub fn parse_library(content: &str) -> Result<Vec<Value>, String> {
+    let library: Value = serde_json::from_str(content).map_err(|e| format!("Invalid library JSON: {}", e))?;
+    if let Some(kind) = render::str_field(&library, "type")
+        && kind != "excalidrawlib"
+    {
+        return Err(format!("Not an Excalidraw library: type is \"{}\"", kind));
+    }
+
+    if let Some(items) = library.get("libraryItems").and_then(|i| i.as_array()) {
+        return Ok(items
+            .iter()
+            .filter(|item| item.get("elements").is_some_and(|e| e.is_array()))
+            .cloned()
+            .collect());
+    }
+    if let Some(items) = library.get("library").and_then(|i| i.as_array()) {
+        let created = chrono::Utc::now().timestamp_millis();
+        return Ok(items
+            .iter()
+            .filter(|elements| elements.is_array())
+            .map(|elements| {
+                json!({
+                    "id": elements::new_id_prefix(),
+                    "status": "unpublished",
+                    "elements": elements,
+                    "created": created,
+                })
+            })
+            .collect());
+    }
+    Err("Library has no items".to_string())
+}
+
+fn library_document(items: Vec<Value>) -> Value {
+    json!({
+        "type": "excalidrawlib",
+        "version": 2,
+        "source": "SAG-Excalidraw",
+        "libraryItems": items,
+    })
+}
+
+fn read_library(path: &Path) -> Result<Vec<Value>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read library {}: {}", path.display(), e))?;
+    parse_library(&content)
+}
+
+/// Writes through a temporary file so a watching library panel never sees half a file
+fn write_library(app: &AppHandle, path: &Path, items: Vec<Value>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create library folder: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&library_document(items))
+        .map_err(|e| format!("Failed to serialize library: {}", e))?;
+    let temp = path.with_extension(format!("{}.tmp", LIBRARY_EXTENSION));
+    fs::write(&temp, content).map_err(|e| format!("Failed to write library: {}", e))?;
+    fs::rename(&temp, path).map_err(|e| format!("Failed to write library: {}", e))?;
+    let _ = app.emit("library-change", path);
+    Ok(())
+}
+
+/// Fingerprint of an item's shapes, independent of ids, position and edit history
+fn item_fingerprint(item: &Value) -> String {
+    let elements: Vec<&Value> = item
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+        .unwrap_or_default();
+    let (min_x, min_y, ..) = render::scene_bounds(&elements).unwrap_or_default();
+
+    let normalized: Vec<Value> = elements
+        .iter()
+        .map(|element| {
+            let mut element = (*element).clone();
+            if let Some(object) = element.as_object_mut() {
+                for key in VOLATILE_KEYS {
+                    object.remove(key);
+                }
+            }
+            let x = render::num_field(&element, "x").unwrap_or(0.0) - min_x;
+            let y = render::num_field(&element, "y").unwrap_or(0.0) - min_y;
+            element["x"] = json!((x * 100.0).round() / 100.0);
+            element["y"] = json!((y * 100.0).round() / 100.0);
+            element
+        })
+        .collect();
+    let mut lines: Vec<String> = normalized.iter().map(|e| e.to_string()).collect();
+    lines.sort();
+    assets::hash_bytes(lines.join("\n").as_bytes())
+}
+
+/// Appends `new_items` to `items`, skipping shapes that are already there;
+/// returns the number added and the number skipped
+fn merge_items(items: &mut Vec<Value>, new_items: Vec<Value>) -> (usize, usize) {
+    let mut seen: HashSet<String> = items.iter().map(item_fingerprint).collect();
+    let mut ids: HashSet<String> = items
+        .iter()
+        .filter_map(|i| render::str_field(i, "id").map(str::to_string))
+        .collect();
+    let (mut added, mut duplicates) = (0, 0);
+
+    for mut item in new_items {
+        if !seen.insert(item_fingerprint(&item)) {
+            duplicates += 1;
+            continue;
+        }
+        let id = render::str_field(&item, "id").map(str::to_string);
+        if id.as_ref().is_none_or(|id| ids.contains(id)) {
+            item["id"] = json!(elements::new_id_prefix());
+        }
+        ids.insert(render::str_field(&item, "id").unwrap_or_default().to_string());
+        items.push(item);
+        added += 1;
+    }
+    (added, duplicates)
+}
+
+fn library_info(library_id: &str, path: &Path, items: &[Value]) -> models::LibraryInfo {
+    let (source, name) = library_id.split_once('/').unwrap_or((APP_SOURCE, library_id));
+    models::LibraryInfo {
+        id: library_id.to_string(),
+        name: name.to_string(),
+        source: source.to_string(),
+        path: path.to_string_lossy().to_string(),
+        item_count: items.len(),
+    }
+}
+
+fn collect_libraries(dir: &Path, source: &str, list: &mut models::LibraryList) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut found = Vec::new();
+    let paths = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == LIBRARY_EXTENSION));
+    for path in paths {
+        let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+            continue;
+        };
+        match read_library(&path) {
+            Ok(items) => found.push(library_info(&format!("{}/{}", source, name), &path, &items)),
+            Err(error) => list.unreadable.push(models::SkippedFile {
+                path: path.to_string_lossy().to_string(),
+                error,
+            }),
+        }
+    }
+
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    list.libraries.extend(found);
+}
+
+/// Workspace and app-wide libraries, with the library files that could not be read
+#[tauri::command]
+pub async fn list_libraries(app: AppHandle, directory: Option<String>) -> Result<models::LibraryList, String> {
+    let mut list = models::LibraryList {
+        libraries: Vec::new(),
+        unreadable: Vec::new(),
+    };
+
+    if let Some(directory) = validated_directory(directory)?
+        && let Some(dir) = workspace_libraries_dir(&app.state::<models::AppState>(), &directory)
+    {
+        collect_libraries(&dir, WORKSPACE_SOURCE, &mut list);
+    }
+    if let Some(dir) = app_libraries_dir(&app) {
+        collect_libraries(&dir, APP_SOURCE, &mut list);
+    }
+
+    Ok(list)
+}
+
+/// Returns a library as a current-format `.excalidrawlib` document
+#[tauri::command]
+pub async fn load_library(app: AppHandle, directory: Option<String>, library_id: String) -> Result<Value, String> {
+    let directory = validated_directory(directory)?;
+    let path = library_path(&app, directory.as_deref(), &library_id)?;
+    Ok(library_document(read_library(&path)?))
+}
+
+/// Copies a `.excalidrawlib` file into the workspace or app libraries.
+/// Importing over a library of the same name merges into it.
+#[tauri::command]
+pub async fn import_library(
+    app: AppHandle,
+    file_path: String,
+    directory: Option<String>,
+    source: String,
+) -> Result<models::LibraryUpdate, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let new_items = read_library(&validated_path)?;
+    let name = validated_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or("Invalid library file name")?;
+
+    let directory = validated_directory(directory)?;
+    let library_id = format!("{}/{}", source, name);
+    let path = library_path(&app, directory.as_deref(), &library_id)?;
+    let mut items = if path.exists() { read_library(&path)? } else { Vec::new() };
+
+    let (added, duplicates) = merge_items(&mut items, new_items);
+    let library = library_info(&library_id, &path, &items);
+    write_library(&app, &path, items)?;
+
+    Ok(models::LibraryUpdate { library, added, duplicates })
+}
+
+/// Merges several libraries into `target_id`, which is created if needed; the sources are kept
+#[tauri::command]
+pub async fn merge_libraries(
+    app: AppHandle,
+    directory: Option<String>,
+    library_ids: Vec<String>,
+    target_id: String,
+) -> Result<models::LibraryUpdate, String> {
+    let directory = validated_directory(directory)?;
+    let path = library_path(&app, directory.as_deref(), &target_id)?;
+    let mut items = if path.exists() { read_library(&path)? } else { Vec::new() };
+
+    let (mut added, mut duplicates) = (0, 0);
+    for library_id in library_ids.iter().filter(|id| **id != target_id) {
+        let source = read_library(&library_path(&app, directory.as_deref(), library_id)?)?;
+        let (a, d) = merge_items(&mut items, source);
+        added += a;
+        duplicates += d;
+    }
+
+    let library = library_info(&target_id, &path, &items);
+    write_library(&app, &path, items)?;
+    Ok(models::LibraryUpdate { library, added, duplicates })
+}
+
+/// Removes items whose shapes repeat an earlier item
+#[tauri::command]
+pub async fn dedupe_library(
+    app: AppHandle,
+    directory: Option<String>,
+    library_id: String,
+) -> Result<models::LibraryUpdate, String> {
+    let directory = validated_directory(directory)?;
+    let path = library_path(&app, directory.as_deref(), &library_id)?;
+
+    let mut items = Vec::new();
+    let (_, duplicates) = merge_items(&mut items, read_library(&path)?);
+    let library = library_info(&library_id, &path, &items);
+    if duplicates > 0 {
+        write_library(&app, &path, items)?;
+    }
+
+    Ok(models::LibraryUpdate { library, added: 0, duplicates })
+}
+
+/// Writes a library to `output_path` in the current `.excalidrawlib` format
+#[tauri::command]
+pub async fn export_library(
+    app: AppHandle,
+    directory: Option<String>,
+    library_id: String,
+    output_path: String,
+) -> Result<String, String> {
+    let directory = validated_directory(directory)?;
+    let items = read_library(&library_path(&app, directory.as_deref(), &library_id)?)?;
+
+    let output = PathBuf::from(&output_path);
+    let parent = output
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or("Output path has no parent directory")?;
+    let parent = security::validate_path(parent, None)?;
+    let file_name = output.file_name().ok_or("Output path has no file name")?.to_string_lossy();
+    let output = security::safe_path_join(&parent, &file_name)?;
+
+    let content = serde_json::to_string_pretty(&library_document(items))
+        .map_err(|e| format!("Failed to serialize library: {}", e))?;
+    fs::write(&output, content).map_err(|e| format!("Failed to write library: {}", e))?;
+
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Elements to copy for a selection: whole groups, bound text, arrow labels and frame children
+fn selection_closure(all: &[Value], element_ids: &[String]) -> Vec<Value> {
+    let mut selected: HashSet<&str> = element_ids.iter().map(String::as_str).collect();
+    if selected.is_empty() {
+        selected = all.iter().filter_map(|e| render::str_field(e, "id")).collect();
+    }
+
+    loop {
+        let before = selected.len();
+        let groups: HashSet<&str> = all
+            .iter()
+            .filter(|e| render::str_field(e, "id").is_some_and(|id| selected.contains(id)))
+            .filter_map(|e| e.get("groupIds")?.as_array()?.last()?.as_str())
+            .collect();
+        for element in all {
+            let Some(id) = render::str_field(element, "id") else { continue };
+            let in_group = element
+                .get("groupIds")
+                .and_then(|g| g.as_array())
+                .is_some_and(|g| g.iter().any(|g| g.as_str().is_some_and(|g| groups.contains(g))));
+            let linked = ["containerId", "frameId"]
+                .iter()
+                .any(|key| render::str_field(element, key).is_some_and(|c| selected.contains(c)));
+            let contains_selected = element
+                .get("boundElements")
+                .and_then(|b| b.as_array())
+                .is_some_and(|b| {
+                    b.iter().any(|b| {
+                        render::str_field(b, "type") == Some("text")
+                            && render::str_field(b, "id").is_some_and(|t| selected.contains(t))
+                    })
+                });
+            if in_group || linked || contains_selected {
+                selected.insert(id);
+            }
+        }
+        if selected.len() == before {
+            break;
+        }
+    }
+
+    elements::copy_subset(all, &selected)
+}
+
+/// Adds elements of a drawing to a library as one item, creating the library if needed.
+/// With no `element_ids` the whole drawing becomes the item. Images are left out because
+/// library files cannot carry image data.
+#[tauri::command]
+pub async fn add_to_library(
+    app: AppHandle,
+    directory: Option<String>,
+    library_id: String,
+    file_path: String,
+    element_ids: Vec<String>,
+    name: Option<String>,
+) -> Result<models::LibraryUpdate, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+    let live: Vec<Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| {
+            e.iter()
+                .filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false))
+                .filter(|e| render::str_field(e, "type") != Some("image"))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let item_elements = selection_closure(&live, &element_ids);
+    if item_elements.is_empty() {
+        return Err("No elements to add to the library".to_string());
+    }
+
+    let mut item = Map::new();
+    item.insert("id".to_string(), json!(elements::new_id_prefix()));
+    item.insert("status".to_string(), json!("unpublished"));
+    item.insert("elements".to_string(), json!(item_elements));
+    item.insert("created".to_string(), json!(chrono::Utc::now().timestamp_millis()));
+    if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+        item.insert("name".to_string(), json!(name.trim()));
+    }
+
+    let directory = validated_directory(directory)?;
+    let path = library_path(&app, directory.as_deref(), &library_id)?;
+    let mut items = if path.exists() { read_library(&path)? } else { Vec::new() };
+    let (added, duplicates) = merge_items(&mut items, vec![Value::Object(item)]);
+    let library = library_info(&library_id, &path, &items);
+    if added > 0 {
+        write_library(&app, &path, items)?;
+    }
+
+    Ok(models::LibraryUpdate { library, added, duplicates })
+}
+
+/// Saves library items as standalone drawings in `output_dir`; all items when `item_ids` is empty
+#[tauri::command]
+pub async fn extract_library_items(
+    app: AppHandle,
+    directory: Option<String>,
+    library_id: String,
+    item_ids: Vec<String>,
+    output_dir: String,
+) -> Result<Vec<String>, String> {
+    let directory = validated_directory(directory)?;
+    let path = library_path(&app, directory.as_deref(), &library_id)?;
+    let items = read_library(&path)?;
+    let dir = security::validate_path(Path::new(&output_dir), None)?;
+    let library_name = library_id.split_once('/').map(|(_, n)| n).unwrap_or(&library_id);
+
+    let mut created = Vec::new();
+    for (n, item) in items.iter().enumerate() {
+        let id = render::str_field(item, "id").unwrap_or_default();
+        if !item_ids.is_empty() && !item_ids.iter().any(|i| i == id) {
+            continue;
+        }
+        let item_elements = item.get("elements").and_then(|e| e.as_array()).cloned().unwrap_or_default();
+        let name = match render::str_field(item, "name") {
+            Some(name) if !name.trim().is_empty() => drawio::file_name_safe(name),
+            _ => format!("{}-{}", library_name, n + 1),
+        };
+        let path = file_ops::unique_file_path(&dir, &format!("{}.excalidraw", name))?;
+        let content = serde_json::to_string_pretty(&elements::scene(item_elements))
+            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        created.push(path.to_string_lossy().to_string());
+    }
+
+    if created.is_empty() {
+        return Err("No matching library items".to_string());
+    }
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_readable_libraries_and_reports_the_others() {
+        let dir = std::env::temp_dir().join(format!("library-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let items = vec![json!({ "id": "item", "elements": [] })];
+        fs::write(dir.join("shapes.excalidrawlib"), library_document(items).to_string()).unwrap();
+        fs::write(dir.join("broken.excalidrawlib"), "{").unwrap();
+
+        let mut list = models::LibraryList {
+            libraries: Vec::new(),
+            unreadable: Vec::new(),
+        };
+        collect_libraries(&dir, WORKSPACE_SOURCE, &mut list);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(list.libraries.len(), 1);
+        assert_eq!(list.libraries[0].id, "workspace/shapes");
+        assert_eq!(list.libraries[0].item_count, 1);
+        assert_eq!(list.unreadable.len(), 1);
+        assert!(list.unreadable[0].path.ends_with("broken.excalidrawlib"));
+        assert!(list.unreadable[0].error.starts_with("Invalid library JSON"));
+    }
+
+    #[test]
+    fn merging_skips_duplicate_shapes() {
+        let shape = json!([{ "id": "r", "type": "rectangle", "width": 10 }]);
+        let mut items = vec![json!({ "id": "a", "elements": shape })];
+        let new_items = vec![
+            json!({ "id": "a", "elements": shape }),
+            json!({ "id": "a", "elements": [{ "id": "e", "type": "ellipse" }] }),
+        ];
+        assert_eq!(merge_items(&mut items, new_items), (1, 1));
+        assert_eq!(items.len(), 2);
+        assert_ne!(items[1]["id"], "a");
+    }
+}
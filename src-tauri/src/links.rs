@@ -0,0 +1,466 @@
+use super::*;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+
+/// Element ids and raw links of one drawing, as of its modification time
+struct DrawingLinks {
+    modified: u64,
+    element_ids: HashSet<String>,
+    /// (element id, link)
+    links: Vec<(String, String)>,
+}
+
+/// Links of every drawing seen so far; a drawing is only read again after it changes
+#[derive(Default)]
+pub struct LinkIndexState {
+    drawings: Mutex<HashMap<PathBuf, DrawingLinks>>,
+}
+
+/// Resolves `.` and `..` without touching the file system, since link targets may not exist
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Element id in `#id`, `#^id` or Excalidraw's `?element=id` form
+fn anchor_of(rest: &str) -> Option<String> {
+    let id = match rest.find("element=") {
+        Some(i) => rest[i + "element=".len()..].split(['&', '#']).next().unwrap_or_default(),
+        None => rest.trim_start_matches(['#', '?', '/', '^']),
+    };
+    (!id.is_empty()).then(|| drawio::percent_decode(id).unwrap_or_else(|_| id.to_string()))
+}
+
+/// File and element a link of `source` points to. Web and other URLs give None;
+/// `file://` URLs, absolute and relative paths, and bare anchors into `source` itself resolve.
+pub fn resolve_link(source: &Path, link: &str) -> Option<(PathBuf, Option<String>)> {
+    let link = link.trim();
+    let scheme = link.split_once(':').map(|(scheme, _)| scheme).filter(|scheme| {
+        // A single letter is a Windows drive rather than a scheme
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'))
+    });
+    let link = match scheme {
+        Some("file") => link.trim_start_matches("file://"),
+        Some(_) => return None,
+        None => link,
+    };
+
+    let (path, anchor) = match link.find(['#', '?']) {
+        Some(i) => (&link[..i], anchor_of(&link[i..])),
+        None => (link, None),
+    };
+    if path.is_empty() {
+        return anchor.map(|anchor| (source.to_path_buf(), Some(anchor)));
+    }
+    let path = drawio::percent_decode(path).unwrap_or_else(|_| path.to_string());
+    let path = Path::new(&path);
+    let target = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        source.parent()?.join(path)
+    };
+    Some((normalize(&target), anchor))
+}
+
+/// Brings the cached links of every drawing under `root` up to date and returns those drawings,
+/// with the ones that could not be read
+fn refresh(state: &LinkIndexState, root: &Path) -> Result<(Vec<PathBuf>, Vec<models::SkippedFile>), String> {
+    let drawings = search::workspace_drawings(root)?;
+    let mut cache = state.drawings.lock().unwrap();
+    let current: HashSet<&PathBuf> = drawings.iter().collect();
+    cache.retain(|path, _| !path.starts_with(root) || current.contains(path));
+
+    let mut skipped = Vec::new();
+    for path in &drawings {
+        let modified = search_index::modified_millis(path).unwrap_or_default();
+        if cache.get(path).is_some_and(|d| d.modified == modified) {
+            continue;
+        }
+        let scene = match search::read_scene(path) {
+            Ok(scene) => scene,
+            Err(error) => {
+                cache.remove(path);
+                skipped.push(models::SkippedFile {
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                });
+                continue;
+            }
+        };
+        let mut entry = DrawingLinks { modified, element_ids: HashSet::new(), links: Vec::new() };
+        let elements = scene.get("elements").and_then(|e| e.as_array()).into_iter().flatten();
+        for element in elements.filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)) {
+            let Some(id) = render::str_field(element, "id") else { continue };
+            entry.element_ids.insert(id.to_string());
+            if let Some(link) = render::str_field(element, "link").filter(|l| !l.trim().is_empty()) {
+                entry.links.push((id.to_string(), link.to_string()));
+            }
+        }
+        cache.insert(path.clone(), entry);
+    }
+    Ok((drawings, skipped))
+}
+
+/// Every link between files of the workspace, with the reason for each broken one.
+/// Links leaving the workspace are not part of it and are left out.
+fn workspace_links(state: &LinkIndexState, root: &Path) -> Result<(Vec<PathBuf>, Vec<models::DrawingLink>), String> {
+    let (drawings, _) = refresh(state, root)?;
+    let cache = state.drawings.lock().unwrap();
+
+    let mut links = Vec::new();
+    for source in &drawings {
+        let Some(entry) = cache.get(source) else { continue };
+        for (element_id, link) in &entry.links {
+            let Some((target, anchor)) = resolve_link(source, link) else { continue };
+            if !target.starts_with(root) {
+                continue;
+            }
+            let broken = if !target.exists() {
+                Some("Target file does not exist".to_string())
+            } else {
+                match (&anchor, cache.get(&target)) {
+                    (Some(anchor), Some(target_entry)) if !target_entry.element_ids.contains(anchor) => {
+                        Some(format!("Element {} not found in target", anchor))
+                    }
+                    _ => None,
+                }
+            };
+            links.push(models::DrawingLink {
+                source: source.to_string_lossy().to_string(),
+                element_id: element_id.clone(),
+                link: link.clone(),
+                target: target.to_string_lossy().to_string(),
+                anchor,
+                broken,
+            });
+        }
+    }
+    Ok((drawings, links))
+}
+
+/// Links in other drawings of the workspace that point to `file_path`
+#[tauri::command]
+pub async fn get_backlinks(
+    state: State<'_, LinkIndexState>,
+    directory: String,
+    file_path: String,
+) -> Result<Vec<models::DrawingLink>, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let file = security::validate_path(Path::new(&file_path), None)?;
+    let (_, links) = workspace_links(&state, &root)?;
+    Ok(links
+        .into_iter()
+        .filter(|link| Path::new(&link.target) == file && Path::new(&link.source) != file)
+        .collect())
+}
+
+/// Drawings and linked files of the workspace, with one edge per linking file pair.
+/// Broken links are not edges; `find_broken_links` lists them.
+#[tauri::command]
+pub async fn get_link_graph(state: State<'_, LinkIndexState>, directory: String) -> Result<models::LinkGraph, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let (drawings, links) = workspace_links(&state, &root)?;
+
+    let mut nodes: BTreeSet<String> = drawings.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for link in links.into_iter().filter(|l| l.broken.is_none() && l.source != l.target) {
+        nodes.insert(link.target.clone());
+        *edges.entry((link.source, link.target)).or_default() += 1;
+    }
+
+    Ok(models::LinkGraph {
+        nodes: nodes.into_iter().collect(),
+        edges: edges
+            .into_iter()
+            .map(|((source, target), links)| models::LinkGraphEdge { source, target, links })
+            .collect(),
+    })
+}
+
+/// Links whose file is missing or whose element anchor no longer exists in the target
+#[tauri::command]
+pub async fn find_broken_links(
+    state: State<'_, LinkIndexState>,
+    directory: String,
+) -> Result<Vec<models::DrawingLink>, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let (_, links) = workspace_links(&state, &root)?;
+    Ok(links.into_iter().filter(|link| link.broken.is_some()).collect())
+}
+
+/// Path part of a link and the anchor or query after it
+fn split_link(link: &str) -> (&str, &str) {
+    match link.find(['#', '?']) {
+        Some(i) => (&link[..i], &link[i..]),
+        None => (link, ""),
+    }
+}
+
+/// `to` relative to the folder `from`, with `/` separators as links use them
+fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()));
+    parts.join("/")
+}
+
+/// `link` pointing to `target` from a drawing at `source`, written the way the original was:
+/// relative or absolute, as a `file://` URL or with escaped spaces, keeping its anchor
+fn rewrite_link(link: &str, source: &Path, target: &Path) -> String {
+    let (path, suffix) = split_link(link.trim());
+    let file_url = path.starts_with("file://");
+    let plain = path.trim_start_matches("file://");
+    let new_path = if file_url || Path::new(plain).is_absolute() {
+        target.to_string_lossy().to_string()
+    } else {
+        let relative = relative_path(source.parent().unwrap_or(Path::new("")), target);
+        if plain.starts_with("./") && !relative.starts_with("..") {
+            format!("./{}", relative)
+        } else {
+            relative
+        }
+    };
+    let new_path = if file_url || path.contains('%') { new_path.replace(' ', "%20") } else { new_path };
+    format!("{}{}{}", if file_url { "file://" } else { "" }, new_path, suffix)
+}
+
+/// A drawing, by its path before the move, and the changes to its links
+type AffectedDrawing = (PathBuf, Vec<models::LinkChange>);
+
+/// Drawings whose links change when `old` moves to `new`, and the drawings that could not be
+/// read to find out
+fn affected_drawings(
+    state: &LinkIndexState,
+    root: &Path,
+    old: &Path,
+    new: &Path,
+) -> Result<(Vec<AffectedDrawing>, Vec<models::SkippedFile>), String> {
+    let (drawings, skipped) = refresh(state, root)?;
+    let cache = state.drawings.lock().unwrap();
+
+    let mut affected = Vec::new();
+    for source in drawings {
+        let Some(entry) = cache.get(&source) else { continue };
+        // Relative links of the moved drawing itself now start from its new folder
+        let moved = source == old;
+        let new_source = if moved { new } else { source.as_path() };
+        let mut changes = Vec::new();
+        for (element_id, link) in &entry.links {
+            if split_link(link.trim()).0.is_empty() {
+                continue;
+            }
+            let Some((target, _)) = resolve_link(&source, link) else { continue };
+            if target != old && !moved {
+                continue;
+            }
+            let new_target = if target == old { new } else { target.as_path() };
+            let after = rewrite_link(link, new_source, new_target);
+            if after != link.trim() {
+                changes.push(models::LinkChange {
+                    element_id: element_id.clone(),
+                    before: link.clone(),
+                    after,
+                });
+            }
+        }
+        if !changes.is_empty() {
+            affected.push((source, changes));
+        }
+    }
+    Ok((affected, skipped))
+}
+
+/// Moves a drawing and rewrites every link to it, and its own relative links.
+/// Either the move and all link updates happen or, after a failed write, none of them.
+fn relocate(
+    app: &AppHandle,
+    state: &LinkIndexState,
+    root: &Path,
+    old: &Path,
+    new: PathBuf,
+    dry_run: bool,
+) -> Result<models::RelocateReport, String> {
+    if new.exists() && new != old {
+        return Err("A file with that name already exists".to_string());
+    }
+    let (affected, skipped) = affected_drawings(state, root, old, &new)?;
+    let new_location = |path: &Path| if path == old { new.clone() } else { path.to_path_buf() };
+    let mut report = models::RelocateReport {
+        old_path: old.to_string_lossy().to_string(),
+        new_path: new.to_string_lossy().to_string(),
+        files: affected
+            .iter()
+            .map(|(path, changes)| models::RelinkedFile {
+                path: new_location(path).to_string_lossy().to_string(),
+                changes: changes.clone(),
+            })
+            .collect(),
+        skipped,
+        applied: false,
+    };
+    if dry_run || new == old {
+        return Ok(report);
+    }
+
+    let mut updates = Vec::new();
+    for (path, changes) in &affected {
+        let before = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut scene = search::read_scene(path)?;
+        for element in scene.get_mut("elements").and_then(|e| e.as_array_mut()).into_iter().flatten() {
+            let id = render::str_field(element, "id").unwrap_or_default();
+            if let Some(change) = changes.iter().find(|c| c.element_id == id) {
+                element["link"] = json!(change.after);
+                elements::bump_version(element);
+            }
+        }
+        let content = serde_json::to_string_pretty(&scene).map_err(|e| format!("Failed to serialize content: {}", e))?;
+        updates.push((new_location(path), before, content));
+    }
+
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    fs::rename(old, &new).map_err(|e| format!("Failed to move file: {}", e))?;
+    let fonts = export::font_dirs(app);
+    for (i, (path, _, content)) in updates.iter().enumerate() {
+        if let Err(e) = formats::write_scene_content(path, content, &fonts) {
+            let error = format!("Failed to update links in {}: {}", path.display(), e);
+            return Err(restore(&updates[..i], &new, old, error));
+        }
+    }
+
+    report.applied = true;
+    Ok(report)
+}
+
+/// Puts back the drawings already rewritten and moves the file back to `old`, like
+/// `replace::restore`. Returns `error` with the files that could not be put back, if any.
+fn restore(written: &[(PathBuf, Vec<u8>, String)], new: &Path, old: &Path, error: String) -> String {
+    let mut failed: Vec<String> = written
+        .iter()
+        .filter_map(|(path, before, _)| {
+            let result = formats::write_atomic(path, before);
+            result.err().map(|e| format!("{} ({})", path.display(), e))
+        })
+        .collect();
+    if let Err(e) = fs::rename(new, old) {
+        failed.push(format!("{} ({})", old.display(), e));
+    }
+    if failed.is_empty() {
+        error
+    } else {
+        format!("{}; failed to restore {}", error, failed.join(", "))
+    }
+}
+
+/// Renames a drawing and updates the links pointing to it across the workspace.
+/// With `dry_run`, only reports which drawings would change.
+#[tauri::command]
+pub async fn rename_with_links(
+    app: AppHandle,
+    state: State<'_, LinkIndexState>,
+    directory: String,
+    file_path: String,
+    new_name: String,
+    dry_run: Option<bool>,
+) -> Result<models::RelocateReport, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let old = security::validate_path(Path::new(&file_path), Some(&root))?;
+    security::validate_excalidraw_file(&old)?;
+    let parent = old.parent().ok_or("Invalid file path")?;
+    // Keep the drawing's format, an .excalidraw.png stays a PNG
+    let format = formats::drawing_format(&old).ok_or("Invalid file path")?;
+    let new = formats::with_format_suffix(&security::safe_path_join(parent, &new_name)?, format);
+    relocate(&app, &state, &root, &old, new, dry_run.unwrap_or(false))
+}
+
+/// Moves a drawing into another folder of the workspace and updates the links pointing to it,
+/// along with its own relative links. With `dry_run`, only reports which drawings would change.
+#[tauri::command]
+pub async fn move_with_links(
+    app: AppHandle,
+    state: State<'_, LinkIndexState>,
+    directory: String,
+    file_path: String,
+    target_directory: String,
+    dry_run: Option<bool>,
+) -> Result<models::RelocateReport, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let old = security::validate_path(Path::new(&file_path), Some(&root))?;
+    security::validate_excalidraw_file(&old)?;
+    let target = security::validate_path(Path::new(&target_directory), Some(&root))?;
+    if !target.is_dir() {
+        return Err("Target is not a folder".to_string());
+    }
+    let new = target.join(old.file_name().ok_or("Invalid file path")?);
+    relocate(&app, &state, &root, &old, new, dry_run.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(link: &str) -> Option<(String, Option<String>)> {
+        resolve_link(Path::new("/w/notes/a.excalidraw"), link)
+            .map(|(path, anchor)| (path.to_string_lossy().to_string(), anchor))
+    }
+
+    #[test]
+    fn resolves_paths_and_anchors() {
+        let target = |path: &str, anchor: Option<&str>| Some((path.to_string(), anchor.map(str::to_string)));
+        assert_eq!(resolved("b.excalidraw"), target("/w/notes/b.excalidraw", None));
+        assert_eq!(resolved("../plans/My%20Plan.excalidraw#box"), target("/w/plans/My Plan.excalidraw", Some("box")));
+        assert_eq!(resolved("./b.excalidraw#^box"), target("/w/notes/b.excalidraw", Some("box")));
+        assert_eq!(resolved("/w/b.excalidraw?element=x%2Fy&x=1"), target("/w/b.excalidraw", Some("x/y")));
+        assert_eq!(resolved("file:///w/b.excalidraw"), target("/w/b.excalidraw", None));
+        assert_eq!(resolved("#box"), target("/w/notes/a.excalidraw", Some("box")));
+    }
+
+    #[test]
+    fn leaves_other_urls_alone() {
+        assert_eq!(resolved("https://example.com/a.excalidraw"), None);
+        assert_eq!(resolved("mailto:someone@example.com"), None);
+        assert_eq!(resolved(""), None);
+    }
+
+    #[test]
+    fn rewrites_links_the_way_they_were_written() {
+        let source = Path::new("/w/notes/a.excalidraw");
+        let target = Path::new("/w/plans/My Plan.excalidraw");
+        let rewrite = |link: &str| rewrite_link(link, source, target);
+        assert_eq!(rewrite("b.excalidraw#box"), "../plans/My Plan.excalidraw#box");
+        assert_eq!(rewrite("./b%20c.excalidraw"), "../plans/My%20Plan.excalidraw");
+        assert_eq!(rewrite("/w/b.excalidraw?element=x"), "/w/plans/My Plan.excalidraw?element=x");
+        assert_eq!(rewrite("file:///w/b.excalidraw"), "file:///w/plans/My%20Plan.excalidraw");
+
+        let sibling = Path::new("/w/notes/sub/c.excalidraw");
+        assert_eq!(rewrite_link("./b.excalidraw", source, sibling), "./sub/c.excalidraw");
+    }
+
+    #[test]
+    fn rewritten_links_resolve_to_the_target() {
+        let source = Path::new("/w/notes/a.excalidraw");
+        let target = Path::new("/w/plans/My Plan.excalidraw");
+        for link in ["b.excalidraw#box", "./b%20c.excalidraw", "file:///w/b.excalidraw"] {
+            let (path, _) = resolve_link(source, &rewrite_link(link, source, target)).unwrap();
+            assert_eq!(path, target);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use super::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Folder next to the markdown files that holds the exported SVGs
+const IMAGES_DIR: &str = "images";
+
+/// Escapes characters that would otherwise start markdown syntax
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Lower-case file name fragment for a frame name
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|p| !p.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() { "frame".to_string() } else { slug }
+}
+
+fn element_text(element: &Value) -> Option<String> {
+    let text = render::str_field(element, "originalText").or(render::str_field(element, "text"))?;
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Text of the elements in `frame` (or outside every frame), one bullet each in reading order.
+/// Shape labels are listed as the shape's text and arrow labels name the shapes they connect.
+fn text_outline(elements: &[&Value], frame: Option<&str>) -> Vec<String> {
+    let by_id: HashMap<&str, &Value> = elements
+        .iter()
+        .filter_map(|e| render::str_field(e, "id").map(|id| (id, *e)))
+        .collect();
+    let label_of = |id: &str| {
+        elements
+            .iter()
+            .find(|e| render::str_field(e, "containerId") == Some(id))
+            .and_then(|e| element_text(e))
+    };
+
+    let mut entries: Vec<(render::Bounds, String)> = Vec::new();
+    for element in elements {
+        if render::str_field(element, "type") != Some("text") {
+            continue;
+        }
+        let Some(text) = element_text(element) else { continue };
+        let container = render::str_field(element, "containerId").and_then(|id| by_id.get(id));
+        // Bound text belongs to the frame of its container
+        let owner = container.copied().unwrap_or(element);
+        if render::str_field(owner, "frameId") != frame {
+            continue;
+        }
+
+        let line = match container {
+            Some(arrow) if elements::is_linear(arrow) => {
+                let end = |key: &str| {
+                    arrow
+                        .get(key)
+                        .and_then(|b| render::str_field(b, "elementId"))
+                        .and_then(label_of)
+                };
+                match (end("startBinding"), end("endBinding")) {
+                    (Some(from), Some(to)) => format!("{} ({} → {})", escape_markdown(&text), escape_markdown(&from), escape_markdown(&to)),
+                    _ => escape_markdown(&text),
+                }
+            }
+            _ => escape_markdown(&text),
+        };
+        entries.push((render::element_bounds(owner), line));
+    }
+
+    // Rows of overlapping text top to bottom, each left to right
+    entries.sort_by(|a, b| a.0.1.total_cmp(&b.0.1));
+    let mut rows: Vec<Vec<(render::Bounds, String)>> = Vec::new();
+    for entry in entries {
+        match rows.last_mut() {
+            Some(row) if entry.0.1 < (row[0].0.1 + row[0].0.3) / 2.0 => row.push(entry),
+            _ => rows.push(vec![entry]),
+        }
+    }
+    rows.into_iter()
+        .flat_map(|mut row| {
+            row.sort_by(|a, b| a.0.0.total_cmp(&b.0.0));
+            row.into_iter().map(|(_, line)| format!("- {}", line))
+        })
+        .collect()
+}
+
+/// Image link with a percent-encoded target, so spaces and parentheses in names keep it intact
+fn image_markdown(alt: &str, file_name: &str) -> String {
+    let alt = alt.replace(['[', ']'], "");
+    format!("![{}]({}/{})", alt, IMAGES_DIR, site::url_encode(file_name))
+}
+
+/// Render settings shared by every drawing of an export, and the SVGs written so far
+struct SvgWriter {
+    options: models::ExportOptions,
+    fonts: Vec<PathBuf>,
+    images: Vec<String>,
+}
+
+/// Writes the SVGs of one drawing and returns its markdown, with headings starting at `level`
+fn drawing_markdown(
+    path: &Path,
+    title: &str,
+    image_stem: &str,
+    images_dir: &Path,
+    level: usize,
+    writer: &mut SvgWriter,
+) -> Result<String, String> {
+    let scene = file_ops::load_scene(path)?;
+    let live: Vec<&Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+        .unwrap_or_default();
+
+    fs::create_dir_all(images_dir).map_err(|e| format!("Failed to create image folder: {}", e))?;
+    // Existing files are never overwritten, so each image gets the first free name
+    let mut write_svg = |file_name: &str, frame_ids: Vec<String>| -> Result<String, String> {
+        let svg_options = models::ExportOptions {
+            frame_ids,
+            element_ids: Vec::new(),
+            embed_scene: false,
+            ..writer.options.clone()
+        };
+        let svg = export::scene_to_svg(&scene, &svg_options, &writer.fonts)?;
+        let svg_path = file_ops::unique_file_path(images_dir, file_name)?;
+        fs::write(&svg_path, svg).map_err(|e| format!("Failed to write SVG: {}", e))?;
+        writer.images.push(svg_path.to_string_lossy().to_string());
+        Ok(svg_path.file_name().unwrap_or_default().to_string_lossy().to_string())
+    };
+
+    let heading = "#".repeat(level);
+    let mut out = format!("{} {}\n\n", heading, escape_markdown(title));
+
+    let overview = write_svg(&format!("{}.svg", image_stem), Vec::new())?;
+    out.push_str(&image_markdown(title, &overview));
+    out.push_str("\n\n");
+    let outline = text_outline(&live, None);
+    if !outline.is_empty() {
+        out.push_str(&outline.join("\n"));
+        out.push_str("\n\n");
+    }
+
+    let frames = export::ordered_frames(&scene, &[], "position")?;
+    let mut used = HashMap::new();
+    for (id, name) in frames {
+        // Frames may share a name, so repeated slugs get a counter
+        let base = slug(&name);
+        let count = used.entry(base.clone()).and_modify(|c| *c += 1).or_insert(1);
+        let file_name = if *count == 1 {
+            format!("{}-{}.svg", image_stem, base)
+        } else {
+            format!("{}-{}-{}.svg", image_stem, base, count)
+        };
+        let file_name = write_svg(&file_name, vec![id.clone()])?;
+
+        out.push_str(&format!("{}# {}\n\n", heading, escape_markdown(&name)));
+        out.push_str(&image_markdown(&name, &file_name));
+        out.push_str("\n\n");
+        let outline = text_outline(&live, Some(&id));
+        if !outline.is_empty() {
+            out.push_str(&outline.join("\n"));
+            out.push_str("\n\n");
+        }
+    }
+
+    Ok(out)
+}
+
+/// Exports a drawing, or every drawing in a folder, as markdown that embeds SVG renderings.
+///
+/// Frames become sections with their own image, and the text of each part is listed under its
+/// image as an outline for readers that cannot see it. With `combined`, a folder becomes one
+/// document with a section per drawing.
+#[tauri::command]
+pub async fn export_markdown(
+    app: AppHandle,
+    path: String,
+    output_dir: Option<String>,
+    combined: Option<bool>,
+    options: Option<models::ExportOptions>,
+) -> Result<models::MarkdownExportReport, String> {
+    let source = security::validate_path(Path::new(&path), None)?;
+    let root = if source.is_dir() {
+        source.clone()
+    } else {
+        security::validate_excalidraw_file(&source)?;
+        source.parent().ok_or("File has no parent directory")?.to_path_buf()
+    };
+    let output = match output_dir {
+        Some(dir) => render::output_dir_for(&dir)?,
+        None => root.clone(),
+    };
+
+    let mut drawings: Vec<PathBuf> = if source.is_dir() {
+        let mut files = Vec::new();
+        file_ops::collect_excalidraw_files_recursive(&source, &mut files)?;
+        files
+            .into_iter()
+            .map(|f| PathBuf::from(f.path))
+            .filter(|p| {
+                !p.strip_prefix(&root).unwrap_or(p).components().any(|c| {
+                    let name = c.as_os_str().to_string_lossy();
+                    bundle::is_workspace_folder(&name)
+                })
+            })
+            .collect()
+    } else {
+        vec![source.clone()]
+    };
+    drawings.sort();
+    if drawings.is_empty() {
+        return Err("No drawings found to export".to_string());
+    }
+
+    let mut writer = SvgWriter {
+        options: options.unwrap_or_default(),
+        fonts: export::font_dirs(&app),
+        images: Vec::new(),
+    };
+    let mut files = Vec::new();
+
+    let combined = combined.unwrap_or(false) && source.is_dir();
+    let mut combined_doc = String::new();
+    if combined {
+        let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        combined_doc = format!("# {}\n\n", escape_markdown(&name));
+    }
+
+    for path in &drawings {
+        let relative = path.strip_prefix(&root).unwrap_or(path);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let title = formats::drawing_stem(&file_name).to_string();
+        // Named after the whole file name, so `a.excalidraw` and `a.excalidraw.png` stay apart
+        let base = file_name.replace('.', "_");
+
+        if combined {
+            // All images share one folder, so the relative path keeps their names apart
+            let image_stem = relative
+                .with_file_name(&base)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("-");
+            let section = drawing_markdown(path, &title, &image_stem, &output.join(IMAGES_DIR), 2, &mut writer)?;
+            combined_doc.push_str(&section);
+        } else {
+            let dir = relative.parent().map(|p| output.join(p)).unwrap_or_else(|| output.clone());
+            let doc = drawing_markdown(path, &title, &base, &dir.join(IMAGES_DIR), 1, &mut writer)?;
+            let md_path = file_ops::unique_file_path(&dir, &format!("{}.md", base))?;
+            fs::write(&md_path, doc.trim_end().to_string() + "\n").map_err(|e| format!("Failed to write markdown: {}", e))?;
+            files.push(md_path.to_string_lossy().to_string());
+        }
+    }
+
+    if combined {
+        let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "drawings".to_string());
+        let md_path = file_ops::unique_file_path(&output, &format!("{}.md", name))?;
+        fs::write(&md_path, combined_doc.trim_end().to_string() + "\n").map_err(|e| format!("Failed to write markdown: {}", e))?;
+        files.push(md_path.to_string_lossy().to_string());
+    }
+
+    Ok(models::MarkdownExportReport {
+        files,
+        images: writer.images,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_markdown_encodes_the_target() {
+        assert_eq!(
+            image_markdown("Flow [v2]", "Flow (v2) 网关.svg"),
+            format!("![Flow v2]({}/Flow%20%28v2%29%20%E7%BD%91%E5%85%B3.svg)", IMAGES_DIR)
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use super::*;
+use layout::{Direction, Graph, Vertex};
+use render::Point;
+use serde_json::json;
+use std::collections::HashMap;
+
+const PADDING: f64 = 10.0;
+const NOTE_COLOR: &str = "#fff5ad";
+
+struct Class {
+    id: String,
+    label: String,
+    annotations: Vec<String>,
+    attributes: Vec<String>,
+    methods: Vec<String>,
+    namespace: Option<usize>,
+    note: bool,
+}
+
+struct Relation {
+    from: usize,
+    to: usize,
+    start: Option<&'static str>,
+    end: Option<&'static str>,
+    dashed: bool,
+    label: Option<String>,
+    start_cardinality: Option<String>,
+    end_cardinality: Option<String>,
+}
+
+enum Scope {
+    Body(usize),
+    Namespace(usize),
+}
+
+#[derive(Default)]
+struct Parser {
+    direction: Option<Direction>,
+    classes: Vec<Class>,
+    index: HashMap<String, usize>,
+    namespaces: Vec<(String, Option<usize>)>,
+    scopes: Vec<Scope>,
+    relations: Vec<Relation>,
+}
+
+/// `List~int~` as `List<int>`
+fn generics(text: &str) -> String {
+    let mut out = String::new();
+    let mut open = false;
+    for c in text.chars() {
+        if c == '~' {
+            out.push(if open { '>' } else { '<' });
+            open = !open;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '~' | '.' | '-' | '`' | '$')
+}
+
+/// Head of a relation written before its line, read from the end of `text`
+fn left_head(text: &str) -> (Option<&'static str>, &str) {
+    if let Some(rest) = text.strip_suffix("<|") {
+        return (Some("triangle_outline"), rest);
+    }
+    let boundary = |rest: &str| rest.is_empty() || rest.ends_with(char::is_whitespace) || rest.ends_with('"');
+    for (suffix, head) in [("*", "diamond"), ("<", "arrow"), ("o", "diamond_outline")] {
+        if let Some(rest) = text.strip_suffix(suffix)
+            && (suffix != "o" || boundary(rest))
+        {
+            return (Some(head), rest);
+        }
+    }
+    (None, text)
+}
+
+/// Head of a relation written after its line, read from the start of `text`
+fn right_head(text: &str) -> (Option<&'static str>, &str) {
+    if let Some(rest) = text.strip_prefix("|>") {
+        return (Some("triangle_outline"), rest);
+    }
+    let boundary = |rest: &str| rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with('"');
+    for (prefix, head) in [("*", "diamond"), (">", "arrow"), ("o", "diamond_outline")] {
+        if let Some(rest) = text.strip_prefix(prefix)
+            && (prefix != "o" || boundary(rest))
+        {
+            return (Some(head), rest);
+        }
+    }
+    (None, text)
+}
+
+/// Splits `"1" Name` into the cardinality and the rest
+fn cardinality(text: &str, from_end: bool) -> (Option<String>, &str) {
+    let text = text.trim();
+    if from_end {
+        if let Some(rest) = text.strip_suffix('"')
+            && let Some(start) = rest.rfind('"')
+        {
+            return (Some(rest[start + 1..].to_string()), rest[..start].trim_end());
+        }
+    } else if let Some(rest) = text.strip_prefix('"')
+        && let Some(end) = rest.find('"')
+    {
+        return (Some(rest[..end].to_string()), rest[end + 1..].trim_start());
+    }
+    (None, text)
+}
+
+/// Byte offset of the first `--` or `..` outside quotes
+fn relation_line(text: &str) -> Option<(usize, bool)> {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '-' if !quoted && text[i..].starts_with("--") => return Some((i, false)),
+            '.' if !quoted && text[i..].starts_with("..") => return Some((i, true)),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Parser {
+    fn class(&mut self, name: &str) -> usize {
+        let name = name.trim().trim_matches('`');
+        let id = name.split('~').next().unwrap_or(name).to_string();
+        if let Some(index) = self.index.get(&id) {
+            return *index;
+        }
+        let namespace = self.scopes.iter().rev().find_map(|s| match s {
+            Scope::Namespace(n) => Some(*n),
+            Scope::Body(_) => None,
+        });
+        self.classes.push(Class {
+            id: id.clone(),
+            label: generics(name),
+            annotations: Vec::new(),
+            attributes: Vec::new(),
+            methods: Vec::new(),
+            namespace,
+            note: false,
+        });
+        self.index.insert(id, self.classes.len() - 1);
+        self.classes.len() - 1
+    }
+
+    fn member(&mut self, class: usize, member: &str) {
+        let member = member.trim();
+        if member.is_empty() {
+            return;
+        }
+        let class = &mut self.classes[class];
+        if let Some(annotation) = member.strip_prefix("<<").and_then(|m| m.strip_suffix(">>")) {
+            class.annotations.push(annotation.trim().to_string());
+        } else if member.contains('(') {
+            class.methods.push(generics(member));
+        } else {
+            class.attributes.push(generics(member));
+        }
+    }
+
+    fn statement(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text.trim_end_matches(';');
+
+        if let Some(Scope::Body(class)) = self.scopes.last() {
+            let class = *class;
+            match text.strip_suffix('}') {
+                Some(rest) => {
+                    self.member(class, rest);
+                    self.scopes.pop();
+                }
+                None => self.member(class, text),
+            }
+            return Ok(());
+        }
+        if text == "}" {
+            return match self.scopes.pop() {
+                Some(_) => Ok(()),
+                None => Err(line.error(0, "'}' without a matching '{'")),
+            };
+        }
+
+        let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let rest = rest.trim();
+        match keyword {
+            "direction" => {
+                let direction = Direction::parse(rest)
+                    .ok_or_else(|| line.error(keyword.len() + 1, format!("Unknown direction '{}'", rest)))?;
+                self.direction = Some(direction);
+                Ok(())
+            }
+            "class" => self.class_statement(line.slice(keyword.len())),
+            "namespace" => {
+                let name = rest.trim_end_matches('{').trim();
+                if name.is_empty() {
+                    return Err(line.error(keyword.len(), "Expected a namespace name"));
+                }
+                let parent = self.scopes.iter().rev().find_map(|s| match s {
+                    Scope::Namespace(n) => Some(*n),
+                    Scope::Body(_) => None,
+                });
+                self.namespaces.push((name.to_string(), parent));
+                self.scopes.push(Scope::Namespace(self.namespaces.len() - 1));
+                Ok(())
+            }
+            "note" => self.note(line, rest),
+            "click" | "callback" | "link" | "cssClass" | "style" | "classDef" | "accTitle" | "accTitle:"
+            | "accDescr" | "accDescr:" => Ok(()),
+            _ if text.starts_with("<<") => {
+                let Some((annotation, name)) = text[2..].split_once(">>") else {
+                    return Err(line.error(0, "Unclosed '<<' in annotation"));
+                };
+                let class = self.class(name);
+                self.member(class, &format!("<<{}>>", annotation));
+                Ok(())
+            }
+            _ => self.relation_or_member(line),
+        }
+    }
+
+    /// `class Name~T~["Label"]:::style {`
+    fn class_statement(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text;
+        let end = text.find(|c: char| !is_name_char(c)).unwrap_or(text.len());
+        if end == 0 {
+            return Err(line.error(0, "Expected a class name"));
+        }
+        let class = self.class(&text[..end]);
+
+        let mut rest = text[end..].trim_start();
+        if let Some(label) = rest.strip_prefix('[') {
+            let close = label.find(']').ok_or_else(|| line.error(end, "Unclosed '[' in class label"))?;
+            self.classes[class].label = label_text(&label[..close]);
+            rest = label[close + 1..].trim_start();
+        }
+        if let Some(style) = rest.strip_prefix(":::") {
+            rest = style.trim_start_matches(is_name_char).trim_start();
+        }
+        if let Some(body) = rest.strip_prefix('{') {
+            match body.trim().strip_suffix('}') {
+                Some(members) => members.split(';').for_each(|m| self.member(class, m)),
+                None => {
+                    self.member(class, body);
+                    self.scopes.push(Scope::Body(class));
+                }
+            }
+        } else if !rest.is_empty() {
+            let offset = text.len() - rest.len();
+            return Err(line.error(offset, format!("Unexpected '{}' after the class name", rest)));
+        }
+        Ok(())
+    }
+
+    /// `note for Name "text"` or a free `note "text"`
+    fn note(&mut self, line: Line, rest: &str) -> Result<(), ParseError> {
+        let (target, text) = match rest.strip_prefix("for ") {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (Some(self.class(&rest[..end])), rest[end..].trim())
+            }
+            None => (None, rest),
+        };
+        if !text.starts_with('"') {
+            return Err(line.error(line.text.len() - text.len(), "Expected the note text in quotes"));
+        }
+
+        let id = format!("note-{}", self.classes.len() + 1);
+        let note = self.class(&id);
+        self.classes[note].label = label_text(text);
+        self.classes[note].note = true;
+        if let Some(target) = target {
+            self.relations.push(Relation {
+                from: note,
+                to: target,
+                start: None,
+                end: None,
+                dashed: true,
+                label: None,
+                start_cardinality: None,
+                end_cardinality: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn relation_or_member(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text.trim_end_matches(';');
+        let (relation, label) = match relation_line(text) {
+            Some((at, _)) => match text[at..].find(':') {
+                Some(colon) => (&text[..at + colon], Some(label_text(&text[at + colon + 1..]))),
+                None => (text, None),
+            },
+            None => {
+                // `Name : member`
+                let Some((name, member)) = text.split_once(':') else {
+                    return Err(line.error(0, format!("Unrecognized statement '{}'", text)));
+                };
+                let class = self.class(name);
+                self.member(class, member);
+                return Ok(());
+            }
+        };
+
+        let (at, dashed) = relation_line(relation).unwrap_or((0, false));
+        let line_end = at + 2;
+        let (start, before) = left_head(relation[..at].trim_end());
+        let (end, after) = right_head(&relation[line_end..]);
+        let (start_cardinality, from_name) = cardinality(before, true);
+        let (end_cardinality, to_name) = cardinality(after, false);
+
+        if from_name.is_empty() || !from_name.chars().all(is_name_char) {
+            return Err(line.error(0, "Expected a class name before the relation"));
+        }
+        if to_name.is_empty() || !to_name.chars().all(is_name_char) {
+            return Err(line.error(line_end, "Expected a class name after the relation"));
+        }
+
+        let from = self.class(from_name);
+        let to = self.class(to_name);
+        self.relations.push(Relation {
+            from,
+            to,
+            start,
+            end,
+            dashed,
+            label: label.filter(|l| !l.is_empty()),
+            start_cardinality,
+            end_cardinality,
+        });
+        Ok(())
+    }
+}
+
+pub fn convert(lines: &[Line], prefix: &str) -> Result<Vec<Value>, ParseError> {
+    let mut parser = Parser::default();
+    for line in &lines[1..] {
+        parser.statement(*line)?;
+    }
+    if !parser.scopes.is_empty() {
+        let last = lines.last().copied().unwrap_or(lines[0]);
+        return Err(last.error(last.text.len(), "Missing '}'"));
+    }
+    Ok(build(&parser, prefix))
+}
+
+/// Title and member sections of a class box
+fn sections(class: &Class) -> Vec<String> {
+    if class.note {
+        return vec![class.label.clone()];
+    }
+    let mut title: Vec<String> = class.annotations.iter().map(|a| format!("«{}»", a)).collect();
+    title.push(class.label.clone());
+    vec![title.join("\n"), class.attributes.join("\n"), class.methods.join("\n")]
+}
+
+fn section_height(text: &str) -> f64 {
+    if text.is_empty() {
+        PADDING
+    } else {
+        elements::text_size(text, FONT_SIZE).1 + PADDING * 2.0
+    }
+}
+
+fn box_size(class: &Class) -> (f64, f64) {
+    let sections = sections(class);
+    let width = sections
+        .iter()
+        .map(|s| elements::text_size(s, FONT_SIZE).0)
+        .fold(0.0, f64::max);
+    (
+        (width + PADDING * 4.0).max(120.0),
+        sections.iter().map(|s| section_height(s)).sum(),
+    )
+}
+
+/// Point `along` from the end of a polyline towards its previous point, nudged sideways
+fn near_end(points: &[Point], along: f64) -> Point {
+    let (end, previous) = (points[points.len() - 1], points[points.len() - 2]);
+    let (dx, dy) = (previous.0 - end.0, previous.1 - end.1);
+    let length = dx.hypot(dy).max(1.0);
+    let (ux, uy) = (dx / length, dy / length);
+    (end.0 + ux * along - uy * 12.0, end.1 + uy * along + ux * 12.0)
+}
+
+fn build(parser: &Parser, prefix: &str) -> Vec<Value> {
+    let element_id = |id: &str| format!("{}-{}", prefix, id);
+    let style = label_style();
+
+    let mut graph = Graph::new(parser.direction.unwrap_or(Direction::TopBottom));
+    for (name, parent) in &parser.namespaces {
+        graph.add_cluster(*parent, (elements::text_size(name, FONT_SIZE).0, 0.0));
+    }
+    for class in &parser.classes {
+        graph.add_node(box_size(class), class.namespace);
+    }
+    // Parents and wholes go above the classes that point at them
+    let reversed: Vec<bool> = parser
+        .relations
+        .iter()
+        .map(|r| r.start.is_none() && matches!(r.end, Some("triangle_outline" | "diamond" | "diamond_outline")))
+        .collect();
+    for (relation, reversed) in parser.relations.iter().zip(&reversed) {
+        let (from, to) = if *reversed { (relation.to, relation.from) } else { (relation.from, relation.to) };
+        graph.add_edge(Vertex::Node(from), Vertex::Node(to));
+    }
+    let placed = layout::layout(&graph);
+
+    let mut out = Vec::new();
+    for (i, class) in parser.classes.iter().enumerate() {
+        let id = element_id(&class.id);
+        let group = format!("{}-group", id);
+        let bounds = placed.nodes[i];
+        let frame_id = class.namespace.map(|n| element_id(&format!("namespace-{}", n + 1)));
+        let mut box_style = style.clone();
+        if class.note {
+            box_style.background_color = NOTE_COLOR.to_string();
+        }
+
+        let mut shape = elements::shape("rectangle", &id, bounds, &box_style);
+        let sections = sections(class);
+        let mut title_style = style.clone();
+        title_style.vertical_align = "top".to_string();
+        let title = elements::label(&format!("{}-label", id), &mut shape, &sections[0], &title_style);
+        let mut parts = vec![shape, title];
+
+        let mut member_style = style.clone();
+        member_style.text_align = "left".to_string();
+        let mut y = bounds.1 + section_height(&sections[0]);
+        for (index, section) in sections.iter().enumerate().skip(1) {
+            let separator = format!("{}-separator-{}", id, index);
+            parts.push(elements::linear("line", &separator, &[(bounds.0, y), (bounds.2, y)], &style, None, None));
+            if !section.is_empty() {
+                let member_id = format!("{}-members-{}", id, index);
+                parts.push(elements::text(&member_id, (bounds.0 + PADDING, y + PADDING), section, &member_style));
+            }
+            y += section_height(section);
+        }
+
+        for mut part in parts {
+            elements::add_group(&mut part, &group);
+            part["frameId"] = json!(frame_id);
+            out.push(part);
+        }
+    }
+
+    for (index, (relation, reversed)) in parser.relations.iter().zip(&reversed).enumerate() {
+        let id = element_id(&format!("relation-{}", index + 1));
+        let mut bends = placed.edges[index].clone();
+        if *reversed {
+            bends.reverse();
+        }
+        let (from, to) = (&parser.classes[relation.from], &parser.classes[relation.to]);
+        let points = if relation.from == relation.to {
+            elements::self_loop(placed.nodes[relation.from])
+        } else {
+            elements::route(("rectangle", placed.nodes[relation.from]), ("rectangle", placed.nodes[relation.to]), &bends)
+        };
+
+        let mut arrow_style = style.clone();
+        if relation.dashed {
+            arrow_style.stroke_style = "dashed".to_string();
+        }
+        // Plain associations are arrows without heads, since lines cannot be bound
+        let mut arrow = elements::linear("arrow", &id, &points, &arrow_style, relation.start, relation.end);
+        let label = relation
+            .label
+            .as_ref()
+            .map(|text| elements::label(&format!("{}-label", id), &mut arrow, text, &style));
+
+        let mut small = style.clone();
+        small.font_size = 14.0;
+        let reversed_points: Vec<Point> = points.iter().rev().copied().collect();
+        let cardinalities = [
+            (&relation.start_cardinality, &reversed_points, "start"),
+            (&relation.end_cardinality, &points, "end"),
+        ];
+        out.push(arrow);
+        out.extend(label);
+        for (cardinality, points, end) in cardinalities {
+            if let Some(text) = cardinality {
+                let (width, height) = elements::text_size(text, small.font_size);
+                let (x, y) = near_end(points, 20.0);
+                let text_id = format!("{}-{}-cardinality", id, end);
+                out.push(elements::text(&text_id, (x - width / 2.0, y - height / 2.0), text, &small));
+            }
+        }
+        elements::bind_arrow(&mut out, &id, Some(&element_id(&from.id)), Some(&element_id(&to.id)));
+    }
+
+    for (n, (name, _)) in parser.namespaces.iter().enumerate() {
+        let mut frame = elements::shape("frame", &element_id(&format!("namespace-{}", n + 1)), placed.clusters[n], &style);
+        frame["name"] = json!(name);
+        frame["roughness"] = json!(0);
+        out.push(frame);
+    }
+
+    out
+}
@@ -0,0 +1,764 @@
+use super::*;
+use elements::Style;
+use layout::{Direction, Graph, Vertex};
+use serde_json::json;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Rectangle,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    Trapezoid,
+}
+
+impl Shape {
+    fn element_type(self) -> &'static str {
+        match self {
+            Shape::Circle | Shape::DoubleCircle => "ellipse",
+            Shape::Rhombus => "diamond",
+            _ => "rectangle",
+        }
+    }
+
+    fn rounded(self) -> bool {
+        matches!(
+            self,
+            Shape::Round | Shape::Stadium | Shape::Cylinder | Shape::Hexagon
+        )
+    }
+
+    /// Size that leaves room around a label of the given size
+    fn size(self, (width, height): (f64, f64)) -> (f64, f64) {
+        match self {
+            Shape::Circle | Shape::DoubleCircle => {
+                let side = width.max(height) + 40.0;
+                (side, side)
+            }
+            Shape::Rhombus => (width * 1.5 + 40.0, height * 1.5 + 40.0),
+            _ => ((width + 40.0).max(80.0), (height + 30.0).max(50.0)),
+        }
+    }
+}
+
+/// Node delimiters, longest openings first
+const SHAPES: [(&str, &str, Shape); 14] = [
+    ("(((", ")))", Shape::DoubleCircle),
+    ("((", "))", Shape::Circle),
+    ("([", "])", Shape::Stadium),
+    ("[[", "]]", Shape::Subroutine),
+    ("[(", ")]", Shape::Cylinder),
+    ("{{", "}}", Shape::Hexagon),
+    ("[/", "/]", Shape::Parallelogram),
+    ("[/", "\\]", Shape::Trapezoid),
+    ("[\\", "\\]", Shape::Parallelogram),
+    ("[\\", "/]", Shape::Trapezoid),
+    ("[", "]", Shape::Rectangle),
+    ("(", ")", Shape::Round),
+    ("{", "}", Shape::Rhombus),
+    (">", "]", Shape::Asymmetric),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stroke {
+    Solid,
+    Thick,
+    Dotted,
+    Invisible,
+}
+
+#[derive(Debug, Clone)]
+struct Link {
+    start: Option<&'static str>,
+    end: Option<&'static str>,
+    stroke: Stroke,
+    label: Option<String>,
+}
+
+struct Node {
+    id: String,
+    label: String,
+    shape: Shape,
+    classes: Vec<String>,
+    styles: Vec<(String, String)>,
+    cluster: Option<usize>,
+}
+
+struct Subgraph {
+    id: String,
+    title: String,
+    parent: Option<usize>,
+}
+
+struct Edge {
+    from: Vertex,
+    to: Vertex,
+    link: Link,
+}
+
+#[derive(Default)]
+struct Parser {
+    direction: Option<Direction>,
+    nodes: Vec<Node>,
+    node_index: HashMap<String, usize>,
+    subgraphs: Vec<Subgraph>,
+    open: Vec<usize>,
+    edges: Vec<Edge>,
+    class_defs: HashMap<String, Vec<(String, String)>>,
+}
+
+fn is_id_char(text: &str, index: usize) -> bool {
+    let mut chars = text[index..].chars();
+    match chars.next() {
+        Some(c) if c.is_alphanumeric() || c == '_' => true,
+        // Hyphens belong to the id unless they start an arrow
+        Some('-') => chars
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+fn skip_whitespace(text: &str, pos: usize) -> usize {
+    pos + text[pos..].len() - text[pos..].trim_start().len()
+}
+
+/// Splits a line at `;` outside quotes and brackets
+fn split_statements(line: Line<'_>) -> Vec<Line<'_>> {
+    let mut statements = Vec::new();
+    let mut depth = 0i32;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in line.text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '(' | '{' if !quoted => depth += 1,
+            ']' | ')' | '}' if !quoted => depth -= 1,
+            ';' if !quoted && depth <= 0 => {
+                statements.push(Line {
+                    text: line.text[start..i].trim_end(),
+                    ..line.slice(start)
+                });
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(line.slice(start));
+    statements
+        .into_iter()
+        .filter(|s| !s.text.is_empty())
+        .collect()
+}
+
+/// `fill:#f9f,stroke:#333` into pairs, keeping commas inside `rgb(...)`
+fn parse_styles(text: &str) -> Vec<(String, String)> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+        .into_iter()
+        .filter_map(|part| {
+            let (key, value) = part.split_once(':')?;
+            Some((
+                key.trim().to_string(),
+                value.trim().trim_end_matches(';').to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn apply_styles(style: &mut Style, text_color: &mut Option<String>, styles: &[(String, String)]) {
+    for (key, value) in styles {
+        match key.as_str() {
+            "fill" => style.background_color = value.clone(),
+            "stroke" => style.stroke_color = value.clone(),
+            "stroke-width" => {
+                if let Ok(width) = value.trim_end_matches("px").parse() {
+                    style.stroke_width = width;
+                }
+            }
+            "stroke-dasharray" => style.stroke_style = "dashed".to_string(),
+            "color" => *text_color = Some(value.clone()),
+            _ => {}
+        }
+    }
+}
+
+impl Parser {
+    fn statement(&mut self, line: Line) -> Result<(), ParseError> {
+        let (keyword, rest) = line
+            .text
+            .split_once(char::is_whitespace)
+            .unwrap_or((line.text, ""));
+        let rest_line = line.slice(keyword.len());
+
+        match keyword {
+            "subgraph" => self.open_subgraph(rest_line),
+            "end" if rest.trim().is_empty() => self
+                .open
+                .pop()
+                .map(|_| ())
+                .ok_or_else(|| line.error(0, "'end' without a matching 'subgraph'")),
+            "direction" => {
+                let direction = Direction::parse(rest.trim()).ok_or_else(|| {
+                    rest_line.error(0, format!("Unknown direction '{}'", rest.trim()))
+                })?;
+                // Clusters are laid out in the diagram's direction
+                if self.open.is_empty() {
+                    self.direction = Some(direction);
+                }
+                Ok(())
+            }
+            "classDef" => {
+                let (names, styles) = rest
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| rest_line.error(0, "Expected a class name and styles"))?;
+                for name in names.split(',') {
+                    self.class_defs
+                        .insert(name.trim().to_string(), parse_styles(styles));
+                }
+                Ok(())
+            }
+            "class" => {
+                let (ids, class_name) = rest
+                    .trim()
+                    .rsplit_once(char::is_whitespace)
+                    .ok_or_else(|| rest_line.error(0, "Expected node ids and a class name"))?;
+                for id in ids.split(',') {
+                    let node = self.node_mut(id.trim());
+                    node.classes.push(class_name.trim().to_string());
+                }
+                Ok(())
+            }
+            "style" => {
+                let (id, styles) = rest
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| rest_line.error(0, "Expected a node id and styles"))?;
+                let styles = parse_styles(styles);
+                self.node_mut(id).styles.extend(styles);
+                Ok(())
+            }
+            "linkStyle" | "click" | "callback" | "accTitle" | "accTitle:" | "accDescr"
+            | "accDescr:" => Ok(()),
+            _ => self.chain(line),
+        }
+    }
+
+    fn open_subgraph(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text;
+        if text.is_empty() {
+            return Err(line.error(0, "Expected a subgraph name"));
+        }
+
+        let (id, title) = if text.starts_with('"') {
+            (
+                format!("subgraph-{}", self.subgraphs.len() + 1),
+                label_text(text),
+            )
+        } else {
+            let id_end = text
+                .char_indices()
+                .find(|(i, _)| !is_id_char(text, *i))
+                .map(|(i, _)| i)
+                .unwrap_or(text.len());
+            let after = text[id_end..].trim_start();
+            if let Some(title) = after.strip_prefix('[') {
+                let title = title
+                    .strip_suffix(']')
+                    .ok_or_else(|| line.error(id_end, "Unclosed '[' in subgraph title"))?;
+                (text[..id_end].to_string(), label_text(title))
+            } else {
+                (text.to_string(), label_text(text))
+            }
+        };
+
+        self.subgraphs.push(Subgraph {
+            id,
+            title,
+            parent: self.open.last().copied(),
+        });
+        self.open.push(self.subgraphs.len() - 1);
+        Ok(())
+    }
+
+    fn node_mut(&mut self, id: &str) -> &mut Node {
+        let index = match self.node_index.get(id) {
+            Some(index) => *index,
+            None => {
+                self.nodes.push(Node {
+                    id: id.to_string(),
+                    label: id.to_string(),
+                    shape: Shape::Rectangle,
+                    classes: Vec::new(),
+                    styles: Vec::new(),
+                    cluster: None,
+                });
+                self.node_index.insert(id.to_string(), self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        &mut self.nodes[index]
+    }
+
+    fn is_ancestor(&self, ancestor: usize, mut cluster: usize) -> bool {
+        loop {
+            if cluster == ancestor {
+                return true;
+            }
+            match self.subgraphs[cluster].parent {
+                Some(parent) => cluster = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// A node reference with an optional shape and `:::class`
+    fn node(&mut self, line: Line, pos: &mut usize) -> Result<Vertex, ParseError> {
+        let text = line.text;
+        let start = *pos;
+        while *pos < text.len() && is_id_char(text, *pos) {
+            *pos += text[*pos..]
+                .chars()
+                .next()
+                .map(|c| c.len_utf8())
+                .unwrap_or(1);
+        }
+        if *pos == start {
+            let found = text[start..]
+                .chars()
+                .next()
+                .map(|c| c.to_string())
+                .unwrap_or_default();
+            return Err(line.error(start, format!("Expected a node id but found '{}'", found)));
+        }
+        let id = text[start..*pos].to_string();
+
+        let shape = self.shape(line, pos)?;
+        let mut classes = Vec::new();
+        if let Some(rest) = text[*pos..].strip_prefix(":::") {
+            let length = rest
+                .char_indices()
+                .find(|(i, _)| !is_id_char(rest, *i))
+                .map(|(i, _)| i)
+                .unwrap_or(rest.len());
+            classes.push(rest[..length].to_string());
+            *pos += 3 + length;
+        }
+
+        // Links may point at a whole subgraph
+        if shape.is_none()
+            && !self.node_index.contains_key(&id)
+            && let Some(cluster) = self.subgraphs.iter().position(|s| s.id == id)
+        {
+            return Ok(Vertex::Cluster(cluster));
+        }
+
+        let current = self.open.last().copied();
+        let existing_cluster = self
+            .node_index
+            .get(&id)
+            .and_then(|i| self.nodes[*i].cluster);
+        let move_into = match (current, existing_cluster) {
+            (Some(current), Some(existing)) => self.is_ancestor(existing, current),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        let node = self.node_mut(&id);
+        if let Some((shape, label)) = shape {
+            node.shape = shape;
+            node.label = label;
+        }
+        node.classes.extend(classes);
+        if move_into {
+            node.cluster = current;
+        }
+        Ok(Vertex::Node(self.node_index[&id]))
+    }
+
+    fn shape(&self, line: Line, pos: &mut usize) -> Result<Option<(Shape, String)>, ParseError> {
+        let text = line.text;
+        let rest = &text[*pos..];
+        let Some(&(open, _, _)) = SHAPES.iter().find(|(open, _, _)| rest.starts_with(open)) else {
+            return Ok(None);
+        };
+
+        let body_start = *pos + open.len();
+        let body = &text[body_start..];
+        // Quoted labels may contain the closing delimiter
+        let search_from = if body.trim_start().starts_with('"') {
+            let quote = body.find('"').unwrap_or(0);
+            body[quote + 1..]
+                .find('"')
+                .map(|end| quote + end + 2)
+                .ok_or_else(|| line.error(body_start + quote, "Unclosed '\"' in node label"))?
+        } else {
+            0
+        };
+
+        let (end, close, shape) = SHAPES
+            .iter()
+            .filter(|(o, _, _)| *o == open)
+            .filter_map(|(_, close, shape)| {
+                body[search_from..]
+                    .find(close)
+                    .map(|i| (search_from + i, *close, *shape))
+            })
+            .min_by_key(|(end, _, _)| *end)
+            .ok_or_else(|| line.error(*pos, format!("Unclosed '{}' in node definition", open)))?;
+
+        *pos = body_start + end + close.len();
+        Ok(Some((shape, label_text(&body[..end]))))
+    }
+
+    /// `A --> B & C -- text --> D`
+    fn chain(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text;
+        let mut pos = 0;
+        let mut previous: Option<(Vec<Vertex>, Link)> = None;
+
+        loop {
+            pos = skip_whitespace(text, pos);
+            let mut group = vec![self.node(line, &mut pos)?];
+            loop {
+                let after = skip_whitespace(text, pos);
+                if !text[after..].starts_with('&') {
+                    break;
+                }
+                pos = skip_whitespace(text, after + 1);
+                group.push(self.node(line, &mut pos)?);
+            }
+
+            if let Some((sources, link)) = previous.take() {
+                for from in &sources {
+                    for to in &group {
+                        self.edges.push(Edge {
+                            from: *from,
+                            to: *to,
+                            link: link.clone(),
+                        });
+                    }
+                }
+            }
+
+            pos = skip_whitespace(text, pos);
+            if pos >= text.len() {
+                return Ok(());
+            }
+            let link = parse_link(line, &mut pos)?;
+            pos = skip_whitespace(text, pos);
+            if pos >= text.len() {
+                return Err(line.error(pos, "Expected a node after the link"));
+            }
+            previous = Some((group, link));
+        }
+    }
+}
+
+fn head(c: char) -> Option<&'static str> {
+    match c {
+        '>' | '<' => Some("arrow"),
+        'o' => Some("circle_outline"),
+        'x' => Some("bar"),
+        _ => None,
+    }
+}
+
+/// Parses `-->`, `---`, `-.->`, `==>`, `~~~`, their head variants and labels
+fn parse_link(line: Line, pos: &mut usize) -> Result<Link, ParseError> {
+    let text = line.text;
+    let start = *pos;
+    let rest = &text[start..];
+    let unexpected = || {
+        let found: String = rest.chars().take(3).collect();
+        line.error(
+            start,
+            format!("Expected a link like '-->' but found '{}'", found),
+        )
+    };
+
+    if let Some(after) = rest.strip_prefix("~~~") {
+        *pos = start + 3 + after.len() - after.trim_start_matches('~').len();
+        return Ok(Link {
+            start: None,
+            end: None,
+            stroke: Stroke::Invisible,
+            label: None,
+        });
+    }
+
+    // Optional head at the start: `<-->`, `o--o`, `x--x`
+    let mut i = start;
+    let mut start_head = None;
+    if let Some(c) = rest.chars().next().filter(|c| matches!(c, '<' | 'o' | 'x')) {
+        let next = &rest[1..];
+        if next.starts_with("--") || next.starts_with("==") || next.starts_with("-.") {
+            start_head = head(c);
+            i += 1;
+        }
+    }
+
+    let body = &text[i..];
+    let (stroke, fill) = if body.starts_with("-.") {
+        (Stroke::Dotted, '.')
+    } else if body.starts_with("--") {
+        (Stroke::Solid, '-')
+    } else if body.starts_with("==") {
+        (Stroke::Thick, '=')
+    } else {
+        return Err(unexpected());
+    };
+
+    // Consume the line characters, then an optional head
+    let consume = |from: usize| -> (usize, Option<&'static str>) {
+        let mut j = from;
+        let bytes = text.as_bytes();
+        while j < text.len()
+            && (bytes[j] == fill as u8
+                || (bytes[j] == b'-' && matches!(stroke, Stroke::Dotted | Stroke::Solid)))
+        {
+            j += 1;
+        }
+        match text[j..].chars().next().and_then(head) {
+            Some(h) if text.as_bytes()[j] != b'<' => (j + 1, Some(h)),
+            _ => (j, None),
+        }
+    };
+
+    let (end, end_head) = consume(i);
+    let line_length = end - i - usize::from(end_head.is_some());
+    let mut label = None;
+    let mut end = end;
+    let mut end_head = end_head;
+
+    // `-- text -->`, `== text ==>` and `-. text .->` put the label inside the link
+    let opens_label = end_head.is_none()
+        && match stroke {
+            Stroke::Dotted => line_length == 2,
+            _ => line_length == 2 && text[end..].starts_with(char::is_whitespace),
+        };
+    if opens_label {
+        let closing = match stroke {
+            Stroke::Dotted => ".-",
+            Stroke::Thick => "==",
+            _ => "--",
+        };
+        let close_at = text[end..]
+            .find(closing)
+            .map(|k| end + k)
+            .ok_or_else(|| line.error(start, "Unterminated link label"))?;
+        label = Some(label_text(&text[end..close_at]));
+        let (close_end, close_head) = consume(close_at);
+        end = close_end;
+        end_head = close_head;
+    }
+
+    // `-->|text|`
+    let after = skip_whitespace(text, end);
+    if label.is_none() && text[after..].starts_with('|') {
+        let close = text[after + 1..]
+            .find('|')
+            .ok_or_else(|| line.error(after, "Unclosed '|' in link label"))?;
+        label = Some(label_text(&text[after + 1..after + 1 + close]));
+        end = after + close + 2;
+    }
+
+    *pos = end;
+    Ok(Link {
+        start: start_head,
+        end: end_head,
+        stroke,
+        label: label.filter(|l| !l.is_empty()),
+    })
+}
+
+pub fn convert(lines: &[Line], prefix: &str) -> Result<Vec<Value>, ParseError> {
+    let header = lines[0];
+    let mut parser = Parser::default();
+    let mut words = header.text.split_whitespace();
+    words.next();
+    let direction = match words.next().map(|w| w.trim_end_matches(';')) {
+        Some(word) => Direction::parse(word).ok_or_else(|| {
+            header.error(
+                header.text.find(word).unwrap_or(0),
+                format!("Unknown direction '{}'", word),
+            )
+        })?,
+        None => Direction::TopBottom,
+    };
+
+    for line in &lines[1..] {
+        for statement in split_statements(*line) {
+            parser.statement(statement)?;
+        }
+    }
+    if let Some(open) = parser.open.last() {
+        let last = lines.last().copied().unwrap_or(header);
+        return Err(last.error(
+            last.text.len(),
+            format!(
+                "Subgraph '{}' is missing its 'end'",
+                parser.subgraphs[*open].id
+            ),
+        ));
+    }
+
+    Ok(build(
+        &parser,
+        parser.direction.unwrap_or(direction),
+        prefix,
+    ))
+}
+
+fn build(parser: &Parser, direction: Direction, prefix: &str) -> Vec<Value> {
+    let element_id = |id: &str| format!("{}-{}", prefix, id);
+    let mut graph = Graph::new(direction);
+    for subgraph in &parser.subgraphs {
+        // Frames show their name above the border, so only the width matters
+        let (width, _) = elements::text_size(&subgraph.title, FONT_SIZE);
+        graph.add_cluster(subgraph.parent, (width, 0.0));
+    }
+    for node in &parser.nodes {
+        let size = node.shape.size(elements::text_size(&node.label, FONT_SIZE));
+        graph.add_node(size, node.cluster);
+    }
+    for edge in &parser.edges {
+        graph.add_edge(edge.from, edge.to);
+    }
+    let placed = layout::layout(&graph);
+
+    let frame_id = |cluster: Option<usize>| cluster.map(|c| element_id(&parser.subgraphs[c].id));
+    let mut out = Vec::new();
+
+    for (i, node) in parser.nodes.iter().enumerate() {
+        let mut style = label_style();
+        style.rounded = node.shape.rounded();
+        let mut text_color = None;
+        let default_styles = parser
+            .class_defs
+            .get("default")
+            .map(|s| s.as_slice())
+            .unwrap_or_default();
+        apply_styles(&mut style, &mut text_color, default_styles);
+        for class in &node.classes {
+            if let Some(styles) = parser.class_defs.get(class) {
+                apply_styles(&mut style, &mut text_color, styles);
+            }
+        }
+        apply_styles(&mut style, &mut text_color, &node.styles);
+
+        let id = element_id(&node.id);
+        let mut shape = elements::shape(node.shape.element_type(), &id, placed.nodes[i], &style);
+        shape["frameId"] = json!(frame_id(node.cluster));
+        let label = (!node.label.is_empty()).then(|| {
+            let mut label_style = style.clone();
+            label_style.stroke_color = text_color.unwrap_or_else(|| style.stroke_color.clone());
+            let mut label = elements::label(
+                &format!("{}-label", id),
+                &mut shape,
+                &node.label,
+                &label_style,
+            );
+            label["frameId"] = shape["frameId"].clone();
+            label
+        });
+        out.push(shape);
+        out.extend(label);
+    }
+
+    let endpoint = |vertex: Vertex| match vertex {
+        Vertex::Node(i) => (
+            parser.nodes[i].shape.element_type(),
+            placed.nodes[i],
+            Some(element_id(&parser.nodes[i].id)),
+            parser.nodes[i].cluster,
+        ),
+        Vertex::Cluster(c) => ("rectangle", placed.clusters[c], None, Some(c)),
+    };
+    for (index, edge) in parser.edges.iter().enumerate() {
+        if edge.link.stroke == Stroke::Invisible {
+            continue;
+        }
+        let (from_kind, from_bounds, from_id, from_cluster) = endpoint(edge.from);
+        let (to_kind, to_bounds, to_id, to_cluster) = endpoint(edge.to);
+        let points = if edge.from == edge.to {
+            elements::self_loop(from_bounds)
+        } else {
+            elements::route(
+                (from_kind, from_bounds),
+                (to_kind, to_bounds),
+                &placed.edges[index],
+            )
+        };
+
+        let mut style = label_style();
+        match edge.link.stroke {
+            Stroke::Thick => style.stroke_width = 4.0,
+            Stroke::Dotted => style.stroke_style = "dotted".to_string(),
+            _ => {}
+        }
+        let id = element_id(&format!("edge-{}", index + 1));
+        let mut arrow = elements::linear(
+            "arrow",
+            &id,
+            &points,
+            &style,
+            edge.link.start,
+            edge.link.end,
+        );
+        if from_cluster == to_cluster
+            && matches!(edge.from, Vertex::Node(_))
+            && matches!(edge.to, Vertex::Node(_))
+        {
+            arrow["frameId"] = json!(frame_id(from_cluster));
+        }
+        let label = edge.link.label.as_ref().map(|text| {
+            let mut label =
+                elements::label(&format!("{}-label", id), &mut arrow, text, &label_style());
+            label["frameId"] = arrow["frameId"].clone();
+            label
+        });
+        out.push(arrow);
+        out.extend(label);
+        elements::bind_arrow(&mut out, &id, from_id.as_deref(), to_id.as_deref());
+    }
+
+    // Frames go after their children, like in the editor
+    for (c, subgraph) in parser.subgraphs.iter().enumerate() {
+        let mut frame = elements::shape(
+            "frame",
+            &element_id(&subgraph.id),
+            placed.clusters[c],
+            &label_style(),
+        );
+        frame["name"] = json!(subgraph.title);
+        frame["roughness"] = json!(0);
+        out.push(frame);
+    }
+
+    out
+}
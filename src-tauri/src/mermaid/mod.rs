@@ -0,0 +1,279 @@
+//! Converts Mermaid flowcharts, sequence diagrams and class diagrams into Excalidraw
+//! elements, so diagrams from the AI chat do not depend on a browser renderer.
+
+use super::*;
+use serde_json::Value;
+use std::path::Path;
+use tauri::AppHandle;
+
+pub mod class;
+pub mod flowchart;
+pub mod sequence;
+
+/// Font size of generated labels, Mermaid's default
+pub const FONT_SIZE: f64 = 16.0;
+
+/// A syntax error with the 1-based position it was found at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Mermaid syntax error at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// A trimmed source line and where it starts
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    pub number: usize,
+    pub column: usize,
+    pub text: &'a str,
+}
+
+impl Line<'_> {
+    /// Error at `offset` bytes into the trimmed text
+    pub fn error(&self, offset: usize, message: impl Into<String>) -> ParseError {
+        let offset = offset.min(self.text.len());
+        ParseError {
+            line: self.number,
+            column: self.column + self.text[..offset].chars().count(),
+            message: message.into(),
+        }
+    }
+
+    /// Part of the line starting `offset` bytes in, keeping its position
+    pub fn slice(&self, offset: usize) -> Self {
+        let offset = offset.min(self.text.len());
+        let rest = &self.text[offset..];
+        let trimmed = rest.trim_start();
+        Line {
+            number: self.number,
+            column: self.column + self.text[..offset].chars().count() + rest.len() - trimmed.len(),
+            text: trimmed,
+        }
+    }
+}
+
+/// Non-empty lines without front matter, `%%` comments and `%%{init}%%` directives
+pub fn source_lines(code: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut in_front_matter = false;
+    for (index, raw) in code.lines().enumerate() {
+        let text = raw.trim();
+        if index == 0 && text == "---" {
+            in_front_matter = true;
+            continue;
+        }
+        if in_front_matter {
+            in_front_matter = text != "---";
+            continue;
+        }
+        if text.is_empty() || text.starts_with("%%") {
+            continue;
+        }
+        lines.push(Line {
+            number: index + 1,
+            column: raw.len() - raw.trim_start().len() + 1,
+            text,
+        });
+    }
+    lines
+}
+
+/// Label text with Mermaid's line breaks, quotes and entity codes resolved
+pub fn label_text(text: &str) -> String {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text);
+    let text = text
+        .strip_prefix('`')
+        .and_then(|t| t.strip_suffix('`'))
+        .unwrap_or(text);
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .replace("\\n", "\n")
+        .replace("#quot;", "\"")
+        .replace("#amp;", "&")
+        .replace("#lt;", "<")
+        .replace("#gt;", ">")
+        .replace("#35;", "#")
+}
+
+pub fn label_style() -> elements::Style {
+    elements::Style {
+        font_size: FONT_SIZE,
+        ..elements::Style::default()
+    }
+}
+
+/// Elements generated from a diagram
+#[derive(Debug)]
+pub struct Diagram {
+    pub kind: &'static str,
+    pub elements: Vec<Value>,
+}
+
+pub fn convert(code: &str) -> Result<Diagram, ParseError> {
+    let lines = source_lines(code);
+    let Some(header) = lines.first() else {
+        return Err(ParseError {
+            line: 1,
+            column: 1,
+            message: "Diagram is empty".to_string(),
+        });
+    };
+
+    let keyword = header.text.split_whitespace().next().unwrap_or_default();
+    let prefix = elements::new_id_prefix();
+    match keyword {
+        "flowchart" | "flowchart-elk" | "graph" => Ok(Diagram {
+            kind: "flowchart",
+            elements: flowchart::convert(&lines, &prefix)?,
+        }),
+        "sequenceDiagram" => Ok(Diagram {
+            kind: "sequence",
+            elements: sequence::convert(&lines, &prefix)?,
+        }),
+        "classDiagram" | "classDiagram-v2" => Ok(Diagram {
+            kind: "class",
+            elements: class::convert(&lines, &prefix)?,
+        }),
+        other => Err(header.error(
+            0,
+            format!(
+                "Unsupported diagram type '{}', expected flowchart, sequenceDiagram or classDiagram",
+                other
+            ),
+        )),
+    }
+}
+
+#[tauri::command]
+pub async fn mermaid_to_excalidraw(
+    app: AppHandle,
+    code: String,
+    file_path: Option<String>,
+) -> Result<models::MermaidConversion, String> {
+    let diagram = convert(&code).map_err(|e| e.to_string())?;
+    let mut new_elements = diagram.elements;
+
+    let file_path = match file_path {
+        Some(file_path) => {
+            let validated_path = security::validate_path(Path::new(&file_path), None)?;
+            // Raw scene, so externalized images stay in the asset folder when it is saved back
+            let mut scene = search::read_scene(&validated_path)?;
+
+            // Place the diagram to the right of what is already there
+            let existing: Vec<&Value> = scene
+                .get("elements")
+                .and_then(|e| e.as_array())
+                .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+                .unwrap_or_default();
+            let added: Vec<&Value> = new_elements.iter().collect();
+            if let (Some(current), Some(incoming)) = (render::scene_bounds(&existing), render::scene_bounds(&added)) {
+                let (dx, dy) = (current.2 + 100.0 - incoming.0, current.1 - incoming.1);
+                for element in &mut new_elements {
+                    elements::translate(element, dx, dy);
+                }
+            }
+
+            match scene.get_mut("elements").and_then(|e| e.as_array_mut()) {
+                Some(list) => list.extend(new_elements.iter().cloned()),
+                None => scene["elements"] = Value::Array(new_elements.clone()),
+            }
+            let content = serde_json::to_string_pretty(&scene)
+                .map_err(|e| format!("Failed to serialize content: {}", e))?;
+            formats::write_scene_content(&validated_path, &content, &export::font_dirs(&app))?;
+            Some(validated_path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    Ok(models::MermaidConversion {
+        diagram_type: diagram.kind.to_string(),
+        elements: new_elements,
+        file_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(diagram: &Diagram) -> Vec<&str> {
+        diagram
+            .elements
+            .iter()
+            .filter(|e| e["type"] == "text")
+            .filter_map(|e| e["text"].as_str())
+            .collect()
+    }
+
+    fn count(diagram: &Diagram, kind: &str) -> usize {
+        diagram.elements.iter().filter(|e| e["type"] == kind).count()
+    }
+
+    #[test]
+    fn converts_flowcharts() {
+        let code = "---\ntitle: Demo\n---\n%% comment\nflowchart LR\n  A[Start] --> B{Ok?}\n  B -->|yes| C((Done))\n  B -.-> A";
+        let diagram = convert(code).unwrap();
+        assert_eq!(diagram.kind, "flowchart");
+        assert_eq!(count(&diagram, "rectangle"), 1);
+        assert_eq!(count(&diagram, "diamond"), 1);
+        assert_eq!(count(&diagram, "ellipse"), 1);
+        assert_eq!(count(&diagram, "arrow"), 3);
+        for label in ["Start", "Ok?", "Done", "yes"] {
+            assert!(texts(&diagram).contains(&label), "missing {}", label);
+        }
+    }
+
+    #[test]
+    fn converts_sequence_diagrams() {
+        let code = "sequenceDiagram\n  participant A as Alice\n  Alice->>Bob: Hello<br/>there\n  Bob-->>Alice: Hi";
+        let diagram = convert(code).unwrap();
+        assert_eq!(diagram.kind, "sequence");
+        assert!(texts(&diagram).contains(&"Hello\nthere"));
+        assert!(texts(&diagram).contains(&"Bob"));
+    }
+
+    #[test]
+    fn converts_class_diagrams() {
+        let code = "classDiagram\n  class Animal {\n    +String name\n    +eat()\n  }\n  Animal <|-- Dog";
+        let diagram = convert(code).unwrap();
+        assert_eq!(diagram.kind, "class");
+        assert!(texts(&diagram).iter().any(|t| t.contains("Animal")));
+        assert!(texts(&diagram).iter().any(|t| t.contains("+eat()")));
+        assert!(count(&diagram, "arrow") >= 1);
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        let empty = convert("%% only a comment\n").unwrap_err();
+        assert_eq!((empty.line, empty.column), (1, 1));
+
+        let unsupported = convert("\n  pie title Pets").unwrap_err();
+        assert_eq!((unsupported.line, unsupported.column), (2, 3));
+        assert!(unsupported.to_string().starts_with("Mermaid syntax error at line 2, column 3"));
+
+        let unclosed = convert("flowchart TD\n  A[Start --> B").unwrap_err();
+        assert_eq!((unclosed.line, unclosed.column), (2, 4));
+        assert_eq!(unclosed.message, "Unclosed '[' in node definition");
+    }
+
+    #[test]
+    fn resolves_label_escapes() {
+        assert_eq!(label_text("\"a #quot;b#quot;<br>c #35;1\""), "a \"b\"\nc #1");
+    }
+}
@@ -0,0 +1,495 @@
+use super::*;
+use std::collections::HashMap;
+
+const BOX_HEIGHT: f64 = 50.0;
+const MESSAGE_GAP: f64 = 20.0;
+const NOTE_COLOR: &str = "#fff5ad";
+
+/// Message operators, longest first so `-->>` is not read as `-->`
+const ARROWS: [(&str, Option<&str>, Option<&str>, bool); 10] = [
+    ("<<-->>", Some("triangle"), Some("triangle"), true),
+    ("<<->>", Some("triangle"), Some("triangle"), false),
+    ("-->>", None, Some("triangle"), true),
+    ("->>", None, Some("triangle"), false),
+    ("--x", None, Some("bar"), true),
+    ("-x", None, Some("bar"), false),
+    ("--)", None, Some("arrow"), true),
+    ("-)", None, Some("arrow"), false),
+    ("-->", None, None, true),
+    ("->", None, None, false),
+];
+
+struct Participant {
+    id: String,
+    label: String,
+    actor: bool,
+}
+
+enum NotePlacement {
+    LeftOf(usize),
+    RightOf(usize),
+    Over(usize, usize),
+}
+
+enum Event {
+    Message {
+        from: usize,
+        to: usize,
+        y: f64,
+        text: String,
+        start: Option<&'static str>,
+        end: Option<&'static str>,
+        dashed: bool,
+    },
+    Note {
+        placement: NotePlacement,
+        y: f64,
+        text: String,
+    },
+    Block {
+        kind: String,
+        label: String,
+        depth: usize,
+        top: f64,
+        bottom: f64,
+        sections: Vec<(f64, String)>,
+        fill: Option<String>,
+    },
+    Activation {
+        participant: usize,
+        top: f64,
+        bottom: f64,
+    },
+}
+
+struct OpenBlock {
+    kind: String,
+    label: String,
+    top: f64,
+    sections: Vec<(f64, String)>,
+    fill: Option<String>,
+}
+
+#[derive(Default)]
+struct Parser {
+    participants: Vec<Participant>,
+    index: HashMap<String, usize>,
+    events: Vec<Event>,
+    open: Vec<Option<OpenBlock>>,
+    activations: Vec<(usize, f64)>,
+    title: Option<String>,
+    autonumber: Option<usize>,
+    y: f64,
+}
+
+fn text_height(text: &str) -> f64 {
+    elements::text_size(text, FONT_SIZE).1
+}
+
+impl Parser {
+    fn participant(&mut self, id: &str) -> usize {
+        if let Some(index) = self.index.get(id) {
+            return *index;
+        }
+        self.participants.push(Participant {
+            id: id.to_string(),
+            label: id.to_string(),
+            actor: false,
+        });
+        self.index.insert(id.to_string(), self.participants.len() - 1);
+        self.participants.len() - 1
+    }
+
+    fn statement(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text.trim_end_matches(';');
+        let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let rest = rest.trim();
+
+        match keyword {
+            "participant" | "actor" => {
+                let (id, alias) = match rest.split_once(" as ") {
+                    Some((id, alias)) => (id.trim(), Some(label_text(alias))),
+                    None => (rest, None),
+                };
+                // `participant A@{ "type": "database" }` only changes how the box is drawn
+                let id = id.split("@{").next().unwrap_or(id).trim();
+                if id.is_empty() {
+                    return Err(line.error(keyword.len(), format!("Expected a name after '{}'", keyword)));
+                }
+                let index = self.participant(id);
+                let participant = &mut self.participants[index];
+                participant.actor = keyword == "actor";
+                if let Some(alias) = alias {
+                    participant.label = alias;
+                }
+                Ok(())
+            }
+            "create" => self.statement(line.slice(keyword.len())),
+            "destroy" | "links" | "link" | "properties" | "details" => Ok(()),
+            "autonumber" => {
+                self.autonumber = Some(rest.split_whitespace().next().and_then(|n| n.parse().ok()).unwrap_or(1));
+                Ok(())
+            }
+            "title" | "title:" => {
+                self.title = Some(label_text(rest));
+                Ok(())
+            }
+            "activate" => {
+                let participant = self.participant(rest);
+                self.activations.push((participant, self.y - MESSAGE_GAP / 2.0));
+                Ok(())
+            }
+            "deactivate" => {
+                let participant = self.participant(rest);
+                self.deactivate(participant, line)
+            }
+            "loop" | "alt" | "opt" | "par" | "critical" | "break" | "rect" => {
+                let fill = (keyword == "rect").then(|| rest.to_string());
+                let label = if keyword == "rect" { String::new() } else { label_text(rest) };
+                self.open.push(Some(OpenBlock {
+                    kind: keyword.to_string(),
+                    label,
+                    top: self.y,
+                    sections: Vec::new(),
+                    fill,
+                }));
+                self.y += if keyword == "rect" { 10.0 } else { 40.0 };
+                Ok(())
+            }
+            "else" | "and" | "option" => {
+                let y = self.y;
+                match self.open.last_mut() {
+                    Some(Some(block)) => {
+                        block.sections.push((y, label_text(rest)));
+                        self.y += 40.0;
+                        Ok(())
+                    }
+                    _ => Err(line.error(0, format!("'{}' outside of a block", keyword))),
+                }
+            }
+            "box" => {
+                self.open.push(None);
+                Ok(())
+            }
+            "end" => match self.open.pop() {
+                Some(Some(block)) => {
+                    self.y += 10.0;
+                    self.events.push(Event::Block {
+                        kind: block.kind,
+                        label: block.label,
+                        depth: self.open.iter().filter(|b| b.is_some()).count(),
+                        top: block.top,
+                        bottom: self.y,
+                        sections: block.sections,
+                        fill: block.fill,
+                    });
+                    self.y += 10.0;
+                    Ok(())
+                }
+                Some(None) => Ok(()),
+                None => Err(line.error(0, "'end' without a matching block")),
+            },
+            _ if keyword.eq_ignore_ascii_case("note") => self.note(line, rest),
+            _ => self.message(line),
+        }
+    }
+
+    fn deactivate(&mut self, participant: usize, line: Line) -> Result<(), ParseError> {
+        let Some(position) = self.activations.iter().rposition(|(p, _)| *p == participant) else {
+            return Err(line.error(
+                0,
+                format!("'{}' is not active", self.participants[participant].id),
+            ));
+        };
+        let (_, top) = self.activations.remove(position);
+        self.events.push(Event::Activation {
+            participant,
+            top,
+            bottom: self.y - MESSAGE_GAP / 2.0,
+        });
+        Ok(())
+    }
+
+    fn note(&mut self, line: Line, rest: &str) -> Result<(), ParseError> {
+        let Some((target, text)) = rest.split_once(':') else {
+            return Err(line.error(line.text.len(), "Expected ':' followed by the note text"));
+        };
+        let target = target.trim();
+        let placement = if let Some(id) = target.strip_prefix("left of ") {
+            NotePlacement::LeftOf(self.participant(id.trim()))
+        } else if let Some(id) = target.strip_prefix("right of ") {
+            NotePlacement::RightOf(self.participant(id.trim()))
+        } else if let Some(ids) = target.strip_prefix("over ") {
+            let mut ids = ids.split(',').map(str::trim);
+            let first = self.participant(ids.next().unwrap_or_default());
+            let last = ids.next().map(|id| self.participant(id)).unwrap_or(first);
+            NotePlacement::Over(first.min(last), first.max(last))
+        } else {
+            return Err(line.error(5, "Expected 'left of', 'right of' or 'over' after 'Note'"));
+        };
+
+        let text = label_text(text);
+        let height = text_height(&text) + 20.0;
+        self.events.push(Event::Note {
+            placement,
+            y: self.y,
+            text,
+        });
+        self.y += height + MESSAGE_GAP;
+        Ok(())
+    }
+
+    /// `A->>+B: text`
+    fn message(&mut self, line: Line) -> Result<(), ParseError> {
+        let text = line.text.trim_end_matches(';');
+        let (left, message) = text.split_once(':').unwrap_or((text, ""));
+
+        // Names may contain hyphens, so `-x` only counts when no other arrow is found
+        let find = |cross: bool| {
+            left.char_indices()
+                .filter(|(_, c)| matches!(c, '-' | '<'))
+                .find_map(|(i, _)| {
+                    ARROWS
+                        .iter()
+                        .filter(|(op, ..)| op.ends_with('x') == cross)
+                        .find(|(op, ..)| left[i..].starts_with(op))
+                        .map(|arrow| (i, arrow))
+                })
+        };
+        let found = find(false).or_else(|| find(true));
+        let Some((at, &(op, start, end, dashed))) = found else {
+            let word = text.split_whitespace().next().unwrap_or_default();
+            return Err(line.error(0, format!("Unrecognized statement '{}'", word)));
+        };
+
+        let from_id = left[..at].trim();
+        let mut target = left[at + op.len()..].trim();
+        let mut activate = false;
+        let mut deactivate = false;
+        if let Some(rest) = target.strip_prefix('+') {
+            activate = true;
+            target = rest.trim();
+        } else if let Some(rest) = target.strip_prefix('-') {
+            deactivate = true;
+            target = rest.trim();
+        }
+        if from_id.is_empty() {
+            return Err(line.error(at, "Expected a participant before the arrow"));
+        }
+        if target.is_empty() {
+            return Err(line.error(at + op.len(), "Expected a participant after the arrow"));
+        }
+
+        let from = self.participant(from_id);
+        let to = self.participant(target);
+        let mut text = label_text(message);
+        if let Some(number) = self.autonumber.as_mut() {
+            text = format!("{}. {}", number, text).trim_end().to_string();
+            *number += 1;
+        }
+
+        self.y += if text.is_empty() { 0.0 } else { text_height(&text) };
+        self.events.push(Event::Message {
+            from,
+            to,
+            y: self.y,
+            text,
+            start,
+            end,
+            dashed,
+        });
+        self.y += if from == to { 30.0 } else { 0.0 } + MESSAGE_GAP;
+
+        if activate {
+            self.activations.push((to, self.y - MESSAGE_GAP));
+        }
+        if deactivate {
+            self.deactivate(from, line)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn convert(lines: &[Line], prefix: &str) -> Result<Vec<Value>, ParseError> {
+    let mut parser = Parser {
+        y: BOX_HEIGHT + MESSAGE_GAP,
+        ..Parser::default()
+    };
+    for line in &lines[1..] {
+        parser.statement(*line)?;
+    }
+    if !parser.open.is_empty() {
+        let last = lines.last().copied().unwrap_or(lines[0]);
+        return Err(last.error(last.text.len(), "Block is missing its 'end'"));
+    }
+    // Activations left open run to the end of the diagram
+    while let Some((participant, top)) = parser.activations.pop() {
+        parser.events.push(Event::Activation {
+            participant,
+            top,
+            bottom: parser.y,
+        });
+    }
+    Ok(build(&parser, prefix))
+}
+
+fn build(parser: &Parser, prefix: &str) -> Vec<Value> {
+    let element_id = |id: &str| format!("{}-{}", prefix, id);
+    let style = label_style();
+    let count = parser.participants.len();
+
+    // Columns are spaced so that box titles and message labels fit
+    let widths: Vec<f64> = parser
+        .participants
+        .iter()
+        .map(|p| (elements::text_size(&p.label, FONT_SIZE).0 + 40.0).max(120.0))
+        .collect();
+    let mut gaps = vec![60.0f64; count.saturating_sub(1)];
+    for event in &parser.events {
+        if let Event::Message { from, to, text, .. } = event
+            && from != to
+        {
+            let (left, right) = (*from.min(to), *from.max(to));
+            let needed = elements::text_size(text, FONT_SIZE).0 + 40.0;
+            let span: f64 = (left..right).map(|i| widths[i] / 2.0 + widths[i + 1] / 2.0).sum();
+            let share = (needed - span) / (right - left) as f64;
+            for gap in &mut gaps[left..right] {
+                *gap = gap.max(share);
+            }
+        }
+    }
+    let mut centers = Vec::with_capacity(count);
+    let mut x = 0.0;
+    for i in 0..count {
+        if i > 0 {
+            x += widths[i - 1] / 2.0 + gaps[i - 1] + widths[i] / 2.0;
+        }
+        centers.push(x + widths[0] / 2.0);
+    }
+    let left_edge = -20.0;
+    let right_edge = centers.last().zip(widths.last()).map(|(c, w)| c + w / 2.0 + 20.0).unwrap_or(0.0);
+    let bottom = parser.y + 10.0;
+
+    let mut out = Vec::new();
+    if let Some(title) = &parser.title {
+        let (width, height) = elements::text_size(title, FONT_SIZE);
+        let id = element_id("title");
+        out.push(elements::text(&id, ((left_edge + right_edge - width) / 2.0, -height - 20.0), title, &style));
+    }
+
+    // Blocks first, so messages are drawn over them
+    for (index, event) in parser.events.iter().enumerate() {
+        let Event::Block { kind, label, depth, top, bottom: block_bottom, sections, fill } = event else {
+            continue;
+        };
+        let inset = *depth as f64 * 10.0;
+        let id = element_id(&format!("block-{}", index + 1));
+        let mut block_style = style.clone();
+        if let Some(fill) = fill {
+            block_style.background_color = fill.clone();
+            block_style.stroke_color = "transparent".to_string();
+        }
+        let bounds = (left_edge + inset, *top, right_edge - inset, *block_bottom);
+        out.push(elements::shape("rectangle", &id, bounds, &block_style));
+        if kind != "rect" {
+            let title = if label.is_empty() { kind.clone() } else { format!("{} [{}]", kind, label) };
+            out.push(elements::text(&format!("{}-label", id), (bounds.0 + 10.0, top + 8.0), &title, &style));
+        }
+        for (section, (y, label)) in sections.iter().enumerate() {
+            let mut divider_style = style.clone();
+            divider_style.stroke_style = "dashed".to_string();
+            let divider_id = format!("{}-section-{}", id, section + 1);
+            out.push(elements::linear("line", &divider_id, &[(bounds.0, *y), (bounds.2, *y)], &divider_style, None, None));
+            if !label.is_empty() {
+                let text = format!("[{}]", label);
+                let (width, _) = elements::text_size(&text, FONT_SIZE);
+                let position = ((bounds.0 + bounds.2 - width) / 2.0, y + 8.0);
+                out.push(elements::text(&format!("{}-label", divider_id), position, &text, &style));
+            }
+        }
+    }
+
+    for (i, participant) in parser.participants.iter().enumerate() {
+        let kind = if participant.actor { "ellipse" } else { "rectangle" };
+        let (x1, x2) = (centers[i] - widths[i] / 2.0, centers[i] + widths[i] / 2.0);
+        let mut lifeline_style = style.clone();
+        lifeline_style.stroke_style = "dashed".to_string();
+        lifeline_style.stroke_width = 1.0;
+        let lifeline_id = element_id(&format!("{}-lifeline", participant.id));
+        out.push(elements::linear(
+            "line",
+            &lifeline_id,
+            &[(centers[i], BOX_HEIGHT), (centers[i], bottom)],
+            &lifeline_style,
+            None,
+            None,
+        ));
+
+        for (suffix, y) in [("", 0.0), ("-bottom", bottom)] {
+            let id = element_id(&format!("{}{}", participant.id, suffix));
+            let mut shape = elements::shape(kind, &id, (x1, y, x2, y + BOX_HEIGHT), &style);
+            let label = elements::label(&format!("{}-label", id), &mut shape, &participant.label, &style);
+            out.push(shape);
+            out.push(label);
+        }
+    }
+
+    for (index, event) in parser.events.iter().enumerate() {
+        match event {
+            Event::Activation { participant, top, bottom } => {
+                let id = element_id(&format!("activation-{}", index + 1));
+                let mut activation_style = style.clone();
+                activation_style.background_color = "#e9ecef".to_string();
+                let x = centers[*participant];
+                out.push(elements::shape("rectangle", &id, (x - 5.0, *top, x + 5.0, *bottom), &activation_style));
+            }
+            Event::Message { from, to, y, text, start, end, dashed } => {
+                let id = element_id(&format!("message-{}", index + 1));
+                let (x1, x2) = (centers[*from], centers[*to]);
+                let points = if from == to {
+                    vec![(x1, *y), (x1 + 40.0, *y), (x1 + 40.0, y + 30.0), (x1, y + 30.0)]
+                } else {
+                    vec![(x1, *y), (x2, *y)]
+                };
+                let mut arrow_style = style.clone();
+                if *dashed {
+                    arrow_style.stroke_style = "dashed".to_string();
+                }
+                out.push(elements::linear("arrow", &id, &points, &arrow_style, *start, *end));
+                if !text.is_empty() {
+                    let (width, height) = elements::text_size(text, FONT_SIZE);
+                    let position = if from == to {
+                        (x1 + 50.0, y + 15.0 - height / 2.0)
+                    } else {
+                        ((x1 + x2 - width) / 2.0, y - height - 4.0)
+                    };
+                    out.push(elements::text(&format!("{}-label", id), position, text, &style));
+                }
+            }
+            Event::Note { placement, y, text } => {
+                let id = element_id(&format!("note-{}", index + 1));
+                let (width, height) = elements::text_size(text, FONT_SIZE);
+                let width = (width + 20.0).max(100.0);
+                let (x1, x2) = match placement {
+                    NotePlacement::LeftOf(p) => (centers[*p] - 10.0 - width, centers[*p] - 10.0),
+                    NotePlacement::RightOf(p) => (centers[*p] + 10.0, centers[*p] + 10.0 + width),
+                    NotePlacement::Over(first, last) => {
+                        let (left, right) = (centers[*first] - 40.0, centers[*last] + 40.0);
+                        let middle = (left + right) / 2.0;
+                        let width = width.max(right - left);
+                        (middle - width / 2.0, middle + width / 2.0)
+                    }
+                };
+                let mut note_style = style.clone();
+                note_style.background_color = NOTE_COLOR.to_string();
+                let mut note = elements::shape("rectangle", &id, (x1, *y, x2, y + height + 20.0), &note_style);
+                let label = elements::label(&format!("{}-label", id), &mut note, text, &style);
+                out.push(note);
+                out.push(label);
+            }
+            Event::Block { .. } => {}
+        }
+    }
+
+    out
+}
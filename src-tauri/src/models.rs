@@ -52,7 +52,495 @@ impl Default for Preferences {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AssetGcReport {
+    pub referenced: usize,
+    /// Removed assets, relative to the workspace
+    pub removed: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompactReport {
+    pub file_path: String,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub bytes_saved: u64,
+    pub removed_elements: usize,
+    pub removed_files: usize,
+    pub fixed_bindings: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceCompactReport {
+    pub files: Vec<CompactReport>,
+    pub failed: Vec<SkippedFile>,
+    pub bytes_saved: u64,
+    pub removed_elements: usize,
+    pub removed_files: usize,
+    pub fixed_bindings: usize,
+}
+
+fn default_max_dimension() -> u32 {
+    1920
+}
+
+fn default_image_quality() -> u8 {
+    82
+}
+
+fn default_min_bytes() -> u64 {
+    200 * 1024
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageOptimizeOptions {
+    /// Longest side in pixels after resizing
+    #[serde(default = "default_max_dimension")]
+    pub max_dimension: u32,
+    /// Target format ("png", "jpeg" or "webp"); keeps the original format when unset
+    #[serde(default)]
+    pub format: Option<String>,
+    /// JPEG quality from 1 to 100
+    #[serde(default = "default_image_quality")]
+    pub quality: u8,
+    /// Images smaller than this that already fit `max_dimension` are left alone
+    #[serde(default = "default_min_bytes")]
+    pub min_bytes: u64,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Default for ImageOptimizeOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: default_max_dimension(),
+            format: None,
+            quality: default_image_quality(),
+            min_bytes: default_min_bytes(),
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageAction {
+    Optimized,
+    /// Already small enough, or re-encoding would not make it smaller
+    Kept,
+    /// Not an image that can be re-encoded
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageOptimizeResult {
+    pub file_id: String,
+    pub action: ImageAction,
+    /// Why the image was kept or skipped
+    pub reason: Option<String>,
+    pub mime_before: String,
+    pub mime_after: String,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub width_before: u32,
+    pub height_before: u32,
+    pub width_after: u32,
+    pub height_after: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageOptimizeReport {
+    pub file_path: String,
+    pub images: Vec<ImageOptimizeResult>,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    /// "builtin", "workspace" or "app"
+    pub source: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryInfo {
+    pub id: String,
+    pub name: String,
+    /// "workspace" or "app"
+    pub source: String,
+    pub path: String,
+    pub item_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryList {
+    /// Workspace libraries followed by the app-wide ones
+    pub libraries: Vec<LibraryInfo>,
+    /// Library files that could not be read
+    pub unreadable: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryUpdate {
+    pub library: LibraryInfo,
+    pub added: usize,
+    /// Items skipped or removed because the library already had the same shapes
+    pub duplicates: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleExportReport {
+    pub output_path: String,
+    pub drawings: usize,
+    pub assets: usize,
+    pub templates: usize,
+    /// Size of the zip file in bytes
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleEntry {
+    /// Path inside the bundle
+    pub source: String,
+    pub path: String,
+    /// Whether the name was taken and a numbered one was used
+    pub renamed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleImportReport {
+    pub drawings: Vec<BundleEntry>,
+    pub templates: Vec<BundleEntry>,
+    pub assets: usize,
+    /// Entries that were not imported: missing from the manifest or outside the known folders
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SiteExportReport {
+    pub output_dir: String,
+    pub index_path: String,
+    pub drawings: usize,
+    pub folders: usize,
+    /// Drawings that could not be rendered and were left out
+    pub failed: Vec<SkippedFile>,
+    /// Fonts that could not be loaded for the thumbnails
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarkdownExportReport {
+    /// Markdown documents written
+    pub files: Vec<String>,
+    /// SVGs the documents refer to
+    pub images: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SearchOptions {
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only match whole words
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Treat the query as a regular expression
+    #[serde(default)]
+    pub regex: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchMatch {
+    pub path: String,
+    /// None when the file name matched
+    pub element_id: Option<String>,
+    /// "text", "frame", "link" or "file_name"
+    pub field: String,
+    pub snippet: String,
+    /// Character offsets of the match within the snippet
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    /// Drawings that could not be read and were not searched
+    pub skipped: Vec<SkippedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexSearchHit {
+    pub path: String,
+    pub score: f64,
+    /// Element whose text matches best; None when only the file name matched
+    pub element_id: Option<String>,
+    pub field: String,
+    pub snippet: String,
+    pub match_start: usize,
+    pub match_end: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchIndexStatus {
+    pub root: Option<String>,
+    /// "idle", "loading", "building", "ready" or "error"
+    pub state: String,
+    pub indexed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+    /// Drawings left out of the index because they could not be read
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl Default for SearchIndexStatus {
+    fn default() -> Self {
+        Self {
+            root: None,
+            state: "idle".to_string(),
+            indexed: 0,
+            total: 0,
+            error: None,
+            skipped: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplaceChange {
+    pub path: String,
+    pub element_id: String,
+    /// Shape or arrow the text is a label of
+    pub container_id: Option<String>,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplaceReport {
+    pub changes: Vec<ReplaceChange>,
+    pub files: usize,
+    pub applied: bool,
+    /// Reverts the whole batch with `undo_replace`
+    pub undo_id: Option<String>,
+    /// Drawings that could not be read, so nothing in them was replaced
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// A file a batch command left out, with the reason
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SkippedFile {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplaceUndoReport {
+    pub restored: Vec<String>,
+    /// Drawings edited since the replacement, left as they are
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DrawingLink {
+    pub source: String,
+    pub element_id: String,
+    /// The element's `link` as written
+    pub link: String,
+    pub target: String,
+    /// Element the link jumps to inside the target
+    pub anchor: Option<String>,
+    /// Why the link does not resolve; None for working links
+    pub broken: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkGraphEdge {
+    pub source: String,
+    pub target: String,
+    /// Number of linking elements
+    pub links: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkGraph {
+    pub nodes: Vec<String>,
+    pub edges: Vec<LinkGraphEdge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkChange {
+    pub element_id: String,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelinkedFile {
+    /// Where the drawing is after the move
+    pub path: String,
+    pub changes: Vec<LinkChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelocateReport {
+    pub old_path: String,
+    pub new_path: String,
+    pub files: Vec<RelinkedFile>,
+    /// Drawings that could not be read, so links in them are not updated
+    pub skipped: Vec<SkippedFile>,
+    /// False for a dry run
+    pub applied: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeReport {
+    pub output_path: String,
+    pub drawings: usize,
+    pub elements: usize,
+    /// Element, group and file ids that collided and were regenerated
+    pub renamed_ids: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MermaidConversion {
+    /// "flowchart", "sequence" or "class"
+    pub diagram_type: String,
+    pub elements: Vec<serde_json::Value>,
+    /// Drawing the elements were added to, if any
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnconnectedArrow {
+    pub id: String,
+    /// Bound text of the arrow
+    pub label: String,
+    /// Element the start is bound to, if any
+    pub from: Option<String>,
+    /// Element the end is bound to, if any
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FreeText {
+    pub id: String,
+    pub text: String,
+}
+
+/// An element a conversion had to leave out
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SkippedElement {
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagramImportReport {
+    /// One drawing per page
+    pub created: Vec<String>,
+    pub skipped: Vec<SkippedElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagramExportReport {
+    pub output_path: String,
+    pub skipped: Vec<SkippedElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PngExportReport {
+    pub output_path: String,
+    /// Fonts that could not be loaded, so text may be drawn in another font
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PdfExportReport {
+    pub output_path: String,
+    /// Images that could not be decoded and are missing from the PDF
+    pub skipped: Vec<SkippedElement>,
+    /// Fonts that had to be replaced, so text may look different or show as boxes
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphExportReport {
+    pub output_path: String,
+    pub nodes: usize,
+    pub edges: usize,
+    pub unconnected_arrows: Vec<UnconnectedArrow>,
+    /// Text that is neither inside a shape or arrow nor connected by an arrow
+    pub free_text: Vec<FreeText>,
+}
+
+fn default_export_padding() -> f64 {
+    10.0
+}
+
+fn default_export_scale() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportOptions {
+    #[serde(default = "default_export_padding")]
+    pub padding: f64,
+    /// Paint the scene's background color instead of leaving it transparent
+    #[serde(default = "default_true")]
+    pub background: bool,
+    #[serde(default)]
+    pub dark_mode: bool,
+    /// Apply each element's roughness for the hand-drawn look
+    #[serde(default = "default_true")]
+    pub hand_drawn: bool,
+    /// Embed Excalidraw font files found in the app data `fonts` folder
+    #[serde(default = "default_true")]
+    pub embed_fonts: bool,
+    /// Pixel density for raster exports
+    #[serde(default = "default_export_scale")]
+    pub scale: f64,
+    /// Overrides the scene's background color
+    #[serde(default)]
+    pub background_color: Option<String>,
+    /// Crop the export to these frames
+    #[serde(default)]
+    pub frame_ids: Vec<String>,
+    /// Crop the export to these elements
+    #[serde(default)]
+    pub element_ids: Vec<String>,
+    /// Embed the scene so the image can be opened and edited again
+    #[serde(default)]
+    pub embed_scene: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            padding: default_export_padding(),
+            background: true,
+            dark_mode: false,
+            hand_drawn: true,
+            embed_fonts: true,
+            scale: default_export_scale(),
+            background_color: None,
+            frame_ids: Vec::new(),
+            element_ids: Vec::new(),
+            embed_scene: false,
+        }
+    }
+}
+
 pub struct AppState {
     pub current_directory: Mutex<Option<PathBuf>>,
     pub modified_files: Mutex<Vec<String>>,
+    /// Watcher of the current directory; replacing it stops the previous one
+    pub watcher: Mutex<Option<notify::RecommendedWatcher>>,
 }
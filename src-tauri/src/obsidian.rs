@@ -0,0 +1,429 @@
+use super::*;
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Width of the base64 lines Obsidian's Excalidraw plugin writes
+const COMPRESSED_LINE_WIDTH: usize = 256;
+
+const NEW_DOCUMENT_HEADER: &str = "---\n\nexcalidraw-plugin: parsed\ntags: [excalidraw]\n\n---\n==⚠  Switch to EXCALIDRAW VIEW in the MORE OPTIONS menu of this document. ⚠==\n\n\n# Excalidraw Data\n\n";
+
+/// A line with the offsets of its start and of the line after it
+struct Line<'a> {
+    start: usize,
+    next: usize,
+    text: &'a str,
+}
+
+fn lines(md: &str) -> Vec<Line<'_>> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    for raw in md.split_inclusive('\n') {
+        let text = raw.trim_end_matches(['\n', '\r']);
+        out.push(Line {
+            start,
+            next: start + raw.len(),
+            text,
+        });
+        start += raw.len();
+    }
+    out
+}
+
+/// `## Drawing` -> `Some("Drawing")`
+fn heading_title(line: &str) -> Option<&str> {
+    let title = line.trim_start_matches('#');
+    (title.len() < line.len() && title.starts_with(' ')).then(|| title.trim())
+}
+
+/// The fenced block under the `Drawing` heading that holds the scene
+struct DrawingBlock {
+    content_start: usize,
+    content_end: usize,
+    compressed: bool,
+}
+
+fn find_drawing_block(md: &str) -> Option<DrawingBlock> {
+    let lines = lines(md);
+    let heading = lines
+        .iter()
+        .position(|line| heading_title(line.text) == Some("Drawing"))?;
+
+    let (fence, compressed) = lines[heading + 1..].iter().enumerate().find_map(|(i, line)| {
+        match line.text.trim() {
+            "```compressed-json" => Some((heading + 1 + i, true)),
+            "```json" => Some((heading + 1 + i, false)),
+            _ => None,
+        }
+    })?;
+    let close = lines[fence + 1..]
+        .iter()
+        .find(|line| line.text.trim() == "```")?;
+
+    Some(DrawingBlock {
+        content_start: lines[fence].next,
+        content_end: close.start,
+        compressed,
+    })
+}
+
+/// Body of the `Text Elements` section, up to the next heading or `%%` marker
+fn find_text_section(md: &str) -> Option<(usize, usize)> {
+    let lines = lines(md);
+    let heading = lines
+        .iter()
+        .position(|line| heading_title(line.text) == Some("Text Elements"))?;
+    let start = lines[heading].next;
+    let end = lines[heading + 1..]
+        .iter()
+        .find(|line| line.text.starts_with('#') || line.text.trim() == "%%")
+        .map(|line| line.start)
+        .unwrap_or(md.len());
+    Some((start, end))
+}
+
+/// Where a missing `Text Elements` section goes: before the `%%` that opens the drawing
+/// part, or before the `Drawing` heading when there is none
+fn text_section_insert_point(md: &str) -> Option<usize> {
+    let lines = lines(md);
+    let heading = lines
+        .iter()
+        .position(|line| heading_title(line.text) == Some("Drawing"))?;
+    let marker = lines[..heading]
+        .iter()
+        .rev()
+        .find(|line| !line.text.trim().is_empty())
+        .filter(|line| line.text.trim() == "%%");
+    Some(marker.unwrap_or(&lines[heading]).start)
+}
+
+/// `<fileId>: [[image.png]]` entries of the `Embedded Files` section
+fn embedded_files(md: &str) -> Vec<(String, String)> {
+    let lines = lines(md);
+    let Some(heading) = lines
+        .iter()
+        .position(|line| heading_title(line.text) == Some("Embedded Files"))
+    else {
+        return Vec::new();
+    };
+
+    lines[heading + 1..]
+        .iter()
+        .take_while(|line| !line.text.starts_with('#') && line.text.trim() != "%%")
+        .filter_map(|line| {
+            let (id, target) = line.text.split_once(": ")?;
+            let link = target.trim().strip_prefix("[[")?.strip_suffix("]]")?;
+            // `[[image.png|alias]]` and `[[image.png#^block]]` still name the file
+            let link = link.split(['|', '#']).next().unwrap_or(link).trim();
+            let id = id.trim();
+            (!id.is_empty() && !link.is_empty()).then(|| (id.to_string(), link.to_string()))
+        })
+        .collect()
+}
+
+/// The vault the document belongs to: the nearest folder with an `.obsidian` folder,
+/// or the document's own folder outside a vault
+fn vault_root(doc_path: &Path) -> Option<&Path> {
+    let dir = doc_path.parent()?;
+    Some(
+        dir.ancestors()
+            .find(|dir| dir.join(".obsidian").is_dir())
+            .unwrap_or(dir),
+    )
+}
+
+fn find_by_name(dir: &Path, name: &OsStr) -> Option<PathBuf> {
+    let entries = fs::read_dir(dir).ok()?;
+    let (files, dirs): (Vec<PathBuf>, Vec<PathBuf>) = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .partition(|path| path.is_file());
+    files
+        .into_iter()
+        .find(|path| path.file_name() == Some(name))
+        .or_else(|| dirs.iter().find_map(|dir| find_by_name(dir, name)))
+}
+
+/// Resolves a wiki link the way Obsidian does: relative to the document, then to the
+/// vault root, then by file name anywhere in the vault
+fn resolve_link(doc_path: &Path, link: &str) -> Option<PathBuf> {
+    let vault = vault_root(doc_path)?.canonicalize().ok()?;
+    let dir = doc_path.parent()?;
+    let found = [dir.join(link), vault.join(link)]
+        .into_iter()
+        .find(|path| path.is_file())
+        .or_else(|| find_by_name(&vault, Path::new(link).file_name()?))?;
+
+    // A link must not read files from outside the vault
+    let canonical = found.canonicalize().ok()?;
+    canonical.starts_with(&vault).then_some(canonical)
+}
+
+/// Adds the images listed in the document's `Embedded Files` section to the scene's
+/// `files`. Images that cannot be found are left out, like the plugin shows them missing.
+pub fn load_embedded_files(md: &str, doc_path: &Path, scene_json: &str) -> Result<String, String> {
+    let embedded = embedded_files(md);
+    if embedded.is_empty() {
+        return Ok(scene_json.to_string());
+    }
+
+    let mut scene: Value =
+        serde_json::from_str(scene_json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let Some(root) = scene.as_object_mut() else {
+        return Ok(scene_json.to_string());
+    };
+    let files = root
+        .entry("files")
+        .or_insert_with(|| Value::Object(Default::default()));
+    let Some(files) = files.as_object_mut() else {
+        return Ok(scene_json.to_string());
+    };
+
+    for (id, link) in embedded {
+        if files.get(&id).and_then(|f| f.get("dataURL")).is_some() {
+            continue;
+        }
+        let Some(path) = resolve_link(doc_path, &link) else {
+            continue;
+        };
+        let Some(mime_type) = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(assets::mime_for_extension)
+        else {
+            continue;
+        };
+        let Ok(bytes) = fs::read(&path) else {
+            continue;
+        };
+        files.insert(
+            id.clone(),
+            json!({
+                "id": id,
+                "mimeType": mime_type,
+                "dataURL": assets::encode_data_url(mime_type, &bytes),
+                "created": chrono::Utc::now().timestamp_millis(),
+            }),
+        );
+    }
+
+    serde_json::to_string(&scene).map_err(|e| e.to_string())
+}
+
+/// Drops the files the document lists as embedded, which the vault already holds.
+/// Returns whether any were dropped.
+fn strip_embedded_files(scene: &mut Value, md: &str) -> bool {
+    let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return false;
+    };
+    let before = files.len();
+    for (id, _) in embedded_files(md) {
+        files.remove(&id);
+    }
+    files.len() != before
+}
+
+fn block_id(line: &str) -> Option<(&str, &str)> {
+    let (text, id) = line.trim_end().rsplit_once(" ^")?;
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then_some((text, id))
+}
+
+/// `text ^id` entries of the text section; an entry may span several lines
+fn parse_text_entries(section: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut pending: Vec<&str> = Vec::new();
+    for line in section.lines() {
+        if pending.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        match block_id(line) {
+            Some((text, id)) => {
+                pending.push(text);
+                entries.push((id.to_string(), pending.join("\n")));
+                pending.clear();
+            }
+            None => pending.push(line),
+        }
+    }
+    entries
+}
+
+/// Text elements of the scene the way the plugin lists them
+fn scene_text_entries(scene: &Value) -> Vec<(String, String)> {
+    scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|elements| elements.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter(|e| e.get("type").and_then(|t| t.as_str()) == Some("text"))
+        .filter(|e| !e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false))
+        .filter_map(|e| {
+            let id = e.get("id")?.as_str()?;
+            let text = ["rawText", "originalText", "text"]
+                .iter()
+                .find_map(|key| e.get(*key).and_then(|t| t.as_str()))?;
+            Some((id.to_string(), text.to_string()))
+        })
+        .collect()
+}
+
+fn format_text_entries(entries: &[(String, String)]) -> String {
+    entries
+        .iter()
+        .map(|(id, text)| format!("{} ^{}\n\n", text, id))
+        .collect()
+}
+
+fn compress(scene_json: &str) -> String {
+    let compressed = lz_string::compress_to_base64(scene_json);
+    let lines: Vec<&str> = compressed
+        .as_bytes()
+        .chunks(COMPRESSED_LINE_WIDTH)
+        // Base64 is ASCII, so chunks are always valid UTF-8
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    lines.join("\n\n")
+}
+
+/// Reads the scene JSON from an Obsidian `.excalidraw.md` document
+pub fn extract_scene(md: &str) -> Result<String, String> {
+    let block = find_drawing_block(md)
+        .ok_or_else(|| "No Excalidraw drawing found in this Markdown file".to_string())?;
+    let content = &md[block.content_start..block.content_end];
+
+    if block.compressed {
+        lz_string::decompress_from_base64(content)
+            .map_err(|e| format!("Failed to decompress drawing: {}", e))
+    } else {
+        Ok(content.trim().to_string())
+    }
+}
+
+/// Replaces the drawing in `existing`, keeping front matter and every other section.
+/// The text section is only rewritten when the scene's text actually changed.
+/// Images listed in the `Embedded Files` section stay in the vault, not in the scene.
+pub fn update_document(existing: Option<&str>, scene_json: &str) -> Result<String, String> {
+    let mut scene: Value =
+        serde_json::from_str(scene_json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let entries = scene_text_entries(&scene);
+
+    let Some(md) = existing else {
+        return Ok(format!(
+            "{}## Text Elements\n{}%%\n## Drawing\n```compressed-json\n{}\n```\n%%",
+            NEW_DOCUMENT_HEADER,
+            format_text_entries(&entries),
+            compress(scene_json)
+        ));
+    };
+
+    let block = find_drawing_block(md)
+        .ok_or_else(|| "No Excalidraw drawing found in this Markdown file".to_string())?;
+    let stripped;
+    let scene_json = if strip_embedded_files(&mut scene, md) {
+        stripped = serde_json::to_string(&scene).map_err(|e| e.to_string())?;
+        stripped.as_str()
+    } else {
+        scene_json
+    };
+    let unchanged = extract_scene(md)
+        .ok()
+        .and_then(|old| serde_json::from_str::<Value>(&old).ok())
+        .is_some_and(|old| old == scene);
+    if unchanged {
+        return Ok(md.to_string());
+    }
+
+    let data = if block.compressed {
+        compress(scene_json)
+    } else {
+        scene_json.trim().to_string()
+    };
+    let mut out = format!(
+        "{}{}\n{}",
+        &md[..block.content_start],
+        data,
+        &md[block.content_end..]
+    );
+
+    match find_text_section(&out) {
+        Some((start, end)) => {
+            if parse_text_entries(&out[start..end]) != entries {
+                out.replace_range(start..end, &format_text_entries(&entries));
+            }
+        }
+        None => {
+            if let Some(at) = text_section_insert_point(&out) {
+                out.insert_str(at, &format!("## Text Elements\n{}", format_text_entries(&entries)));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(scene: &Value) -> String {
+        format!(
+            "{}## Text Elements\n## Embedded Files\nimg1: [[pasted.png|200]]\n\n%%\n## Drawing\n```compressed-json\n{}\n```\n%%",
+            NEW_DOCUMENT_HEADER,
+            compress(&scene.to_string())
+        )
+    }
+
+    #[test]
+    fn new_documents_round_trip() {
+        let scene = json!({ "type": "excalidraw", "elements": [{ "id": "t", "type": "text", "text": "Hi" }] });
+        let md = update_document(None, &scene.to_string()).unwrap();
+        assert!(md.contains("Hi ^t"));
+        let read: Value = serde_json::from_str(&extract_scene(&md).unwrap()).unwrap();
+        assert_eq!(read, scene);
+    }
+
+    #[test]
+    fn missing_text_section_is_added_before_the_drawing() {
+        let old = json!({ "type": "excalidraw", "elements": [] });
+        let md = format!("---\nexcalidraw-plugin: parsed\n---\n%%\n## Drawing\n```json\n{}\n```\n%%", old);
+        let scene = json!({ "type": "excalidraw", "elements": [{ "id": "t", "type": "text", "text": "Hi" }] });
+
+        let updated = update_document(Some(&md), &scene.to_string()).unwrap();
+        assert!(updated.starts_with("---\nexcalidraw-plugin: parsed\n---\n## Text Elements\nHi ^t\n\n%%\n## Drawing\n"));
+        let read: Value = serde_json::from_str(&extract_scene(&updated).unwrap()).unwrap();
+        assert_eq!(read, scene);
+
+        // Without a `%%` marker the section goes right before the heading
+        let md = format!("# Drawing\n```json\n{}\n```\n", old);
+        let updated = update_document(Some(&md), &scene.to_string()).unwrap();
+        assert!(updated.starts_with("## Text Elements\nHi ^t\n\n# Drawing\n"));
+    }
+
+    #[test]
+    fn embedded_files_load_from_the_vault_and_stay_there_on_save() {
+        let vault = std::env::temp_dir().join(format!("obsidian-test-{}", std::process::id()));
+        fs::create_dir_all(vault.join(".obsidian")).unwrap();
+        fs::create_dir_all(vault.join("attachments")).unwrap();
+        fs::create_dir_all(vault.join("notes")).unwrap();
+        fs::write(vault.join("attachments").join("pasted.png"), b"png").unwrap();
+
+        let scene = json!({ "type": "excalidraw", "elements": [], "files": {} });
+        let md = document(&scene);
+        let doc_path = vault.join("notes").join("drawing.excalidraw.md");
+        let loaded = load_embedded_files(&md, &doc_path, &extract_scene(&md).unwrap()).unwrap();
+        fs::remove_dir_all(&vault).unwrap();
+
+        let loaded: Value = serde_json::from_str(&loaded).unwrap();
+        assert_eq!(loaded["files"]["img1"]["mimeType"], "image/png");
+        assert_eq!(loaded["files"]["img1"]["dataURL"], assets::encode_data_url("image/png", b"png"));
+
+        // Saving the loaded scene leaves the document as it was
+        assert_eq!(update_document(Some(&md), &loaded.to_string()).unwrap(), md);
+    }
+}
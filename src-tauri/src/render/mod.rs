@@ -0,0 +1,1291 @@
+//! Headless rendering of Excalidraw scenes.
+//!
+//! A scene is first turned into a backend-neutral display list of paths, text
+//! runs and images, which the output backends then serialize.
+
+pub mod pdf;
+pub mod png;
+pub mod rough;
+pub mod svg;
+
+use crate::assets;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+pub type Point = (f64, f64);
+
+/// Bounding box as (min x, min y, max x, max y)
+pub type Bounds = (f64, f64, f64, f64);
+
+const DEFAULT_STROKE_COLOR: &str = "#1e1e1e";
+const DEFAULT_BACKGROUND_COLOR: &str = "#ffffff";
+const FRAME_STROKE_COLOR: &str = "#bbb";
+const FRAME_NAME_COLOR: &str = "#999999";
+const FRAME_NAME_FONT_SIZE: f64 = 14.0;
+const FRAME_NAME_OFFSET: f64 = 6.0;
+const FRAME_RADIUS: f64 = 8.0;
+const HACHURE_ANGLE: f64 = -41.0;
+/// Bézier handle length for approximating a quarter circle
+const KAPPA: f64 = 0.552_284_749_8;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Segment {
+    MoveTo(Point),
+    LineTo(Point),
+    CubicTo(Point, Point, Point),
+    Close,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathData {
+    pub segments: Vec<Segment>,
+}
+
+impl PathData {
+    pub fn move_to(&mut self, p: Point) {
+        self.segments.push(Segment::MoveTo(p));
+    }
+
+    pub fn line_to(&mut self, p: Point) {
+        self.segments.push(Segment::LineTo(p));
+    }
+
+    pub fn cubic_to(&mut self, c1: Point, c2: Point, p: Point) {
+        self.segments.push(Segment::CubicTo(c1, c2, p));
+    }
+
+    pub fn close(&mut self) {
+        self.segments.push(Segment::Close);
+    }
+
+    pub fn extend(&mut self, other: PathData) {
+        self.segments.extend(other.segments);
+    }
+
+    pub fn polyline(points: &[Point], closed: bool) -> Self {
+        let mut path = PathData::default();
+        for (i, p) in points.iter().enumerate() {
+            if i == 0 {
+                path.move_to(*p);
+            } else {
+                path.line_to(*p);
+            }
+        }
+        if closed && points.len() > 2 {
+            path.close();
+        }
+        path
+    }
+
+    pub fn rect(x: f64, y: f64, width: f64, height: f64) -> Self {
+        PathData::polyline(
+            &[(x, y), (x + width, y), (x + width, y + height), (x, y + height)],
+            true,
+        )
+    }
+
+    pub fn rounded_rect(x: f64, y: f64, width: f64, height: f64, radius: f64) -> Self {
+        let r = radius.min(width.abs() / 2.0).min(height.abs() / 2.0);
+        if r <= 0.0 {
+            return PathData::rect(x, y, width, height);
+        }
+
+        let k = r * (1.0 - KAPPA);
+        let (x2, y2) = (x + width, y + height);
+        let mut path = PathData::default();
+        path.move_to((x + r, y));
+        path.line_to((x2 - r, y));
+        path.cubic_to((x2 - k, y), (x2, y + k), (x2, y + r));
+        path.line_to((x2, y2 - r));
+        path.cubic_to((x2, y2 - k), (x2 - k, y2), (x2 - r, y2));
+        path.line_to((x + r, y2));
+        path.cubic_to((x + k, y2), (x, y2 - k), (x, y2 - r));
+        path.line_to((x, y + r));
+        path.cubic_to((x, y + k), (x + k, y), (x + r, y));
+        path.close();
+        path
+    }
+
+    pub fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Self {
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let mut path = PathData::default();
+        path.move_to((cx + rx, cy));
+        path.cubic_to((cx + rx, cy + ky), (cx + kx, cy + ry), (cx, cy + ry));
+        path.cubic_to((cx - kx, cy + ry), (cx - rx, cy + ky), (cx - rx, cy));
+        path.cubic_to((cx - rx, cy - ky), (cx - kx, cy - ry), (cx, cy - ry));
+        path.cubic_to((cx + kx, cy - ry), (cx + rx, cy - ky), (cx + rx, cy));
+        path.close();
+        path
+    }
+
+    /// Smooth curve through all points, using Catmull-Rom splines
+    pub fn curve_through(points: &[Point]) -> Self {
+        if points.len() < 3 {
+            return PathData::polyline(points, false);
+        }
+
+        let mut path = PathData::default();
+        path.move_to(points[0]);
+        for i in 0..points.len() - 1 {
+            let p0 = points[i.saturating_sub(1)];
+            let p1 = points[i];
+            let p2 = points[i + 1];
+            let p3 = points[(i + 2).min(points.len() - 1)];
+            let c1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+            let c2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+            path.cubic_to(c1, c2, p2);
+        }
+        path
+    }
+
+    pub fn transformed(&self, m: &Transform) -> PathData {
+        let segments = self
+            .segments
+            .iter()
+            .map(|s| match *s {
+                Segment::MoveTo(p) => Segment::MoveTo(m.apply(p)),
+                Segment::LineTo(p) => Segment::LineTo(m.apply(p)),
+                Segment::CubicTo(c1, c2, p) => {
+                    Segment::CubicTo(m.apply(c1), m.apply(c2), m.apply(p))
+                }
+                Segment::Close => Segment::Close,
+            })
+            .collect();
+        PathData { segments }
+    }
+}
+
+/// Affine transform `[a, b, c, d, e, f]`, mapping (x, y) to (ax + cy + e, bx + dy + f)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform(pub [f64; 6]);
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform([1.0, 0.0, 0.0, 1.0, 0.0, 0.0])
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Transform([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Transform([sx, 0.0, 0.0, sy, 0.0, 0.0])
+    }
+
+    pub fn rotate_about(angle: f64, cx: f64, cy: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Transform::translate(-cx, -cy)
+            .then(&Transform([cos, sin, -sin, cos, 0.0, 0.0]))
+            .then(&Transform::translate(cx, cy))
+    }
+
+    pub fn scale_about(sx: f64, sy: f64, cx: f64, cy: f64) -> Self {
+        Transform::translate(-cx, -cy)
+            .then(&Transform::scale(sx, sy))
+            .then(&Transform::translate(cx, cy))
+    }
+
+    /// Applies `self` first, then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        let [a1, b1, c1, d1, e1, f1] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = next.0;
+        Transform([
+            a2 * a1 + c2 * b1,
+            b2 * a1 + d2 * b1,
+            a2 * c1 + c2 * d1,
+            b2 * c1 + d2 * d1,
+            a2 * e1 + c2 * f1 + e2,
+            b2 * e1 + d2 * f1 + f2,
+        ])
+    }
+
+    pub fn apply(&self, (x, y): Point) -> Point {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    pub color: String,
+    pub width: f64,
+    pub dash: Option<Vec<f64>>,
+    pub round_cap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub x: f64,
+    /// Alphabetic baseline
+    pub y: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Primitive {
+    Path {
+        path: PathData,
+        fill: Option<String>,
+        stroke: Option<Stroke>,
+    },
+    Text {
+        runs: Vec<TextRun>,
+        font: &'static FontInfo,
+        font_size: f64,
+        anchor: TextAnchor,
+        color: String,
+    },
+    Image {
+        /// Element the image is drawn for
+        element_id: String,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        mime_type: String,
+        data: Vec<u8>,
+    },
+    /// `clip` is expressed in the group's coordinates, after `transform`
+    Group {
+        transform: Option<Transform>,
+        clip: Option<PathData>,
+        opacity: f64,
+        children: Vec<Primitive>,
+    },
+}
+
+/// Metrics match the fonts shipped with Excalidraw, so baselines line up with the editor
+#[derive(Debug)]
+pub struct FontInfo {
+    pub id: i64,
+    pub family: &'static str,
+    pub fallback: &'static str,
+    pub units_per_em: f64,
+    pub ascender: f64,
+    pub descender: f64,
+}
+
+const FONTS: [FontInfo; 9] = [
+    FontInfo { id: 1, family: "Virgil", fallback: "Segoe UI Emoji, cursive", units_per_em: 1000.0, ascender: 886.0, descender: -374.0 },
+    FontInfo { id: 2, family: "Helvetica", fallback: "Arial, sans-serif", units_per_em: 2048.0, ascender: 1577.0, descender: -471.0 },
+    FontInfo { id: 3, family: "Cascadia", fallback: "Cascadia Code, monospace", units_per_em: 2048.0, ascender: 1900.0, descender: -480.0 },
+    FontInfo { id: 4, family: "Assistant", fallback: "sans-serif", units_per_em: 1000.0, ascender: 1021.0, descender: -287.0 },
+    FontInfo { id: 5, family: "Excalifont", fallback: "Xiaolai, Segoe UI Emoji, cursive", units_per_em: 1000.0, ascender: 886.0, descender: -374.0 },
+    FontInfo { id: 6, family: "Nunito", fallback: "Segoe UI Emoji, sans-serif", units_per_em: 1000.0, ascender: 1011.0, descender: -353.0 },
+    FontInfo { id: 7, family: "Lilita One", fallback: "Segoe UI Emoji, sans-serif", units_per_em: 1000.0, ascender: 923.0, descender: -220.0 },
+    FontInfo { id: 8, family: "Comic Shanns", fallback: "Segoe UI Emoji, cursive", units_per_em: 1000.0, ascender: 750.0, descender: -250.0 },
+    FontInfo { id: 9, family: "Liberation Sans", fallback: "Arial, sans-serif", units_per_em: 2048.0, ascender: 1854.0, descender: -434.0 },
+];
+
+pub fn font_info(id: i64) -> &'static FontInfo {
+    FONTS.iter().find(|f| f.id == id).unwrap_or(&FONTS[0])
+}
+
+/// Font formats a browser can load from an SVG `@font-face`, smallest first
+pub const WEB_FONT_EXTENSIONS: [&str; 4] = ["woff2", "woff", "ttf", "otf"];
+
+/// Font formats fontdb, ttf-parser and subsetter can read, for PNG and PDF
+pub const OUTLINE_FONT_EXTENSIONS: [&str; 4] = ["ttf", "otf", "ttc", "otc"];
+
+/// Looks for a font file named after the family in the given directories, trying
+/// `extensions` in order
+pub fn find_font_file(font: &FontInfo, dirs: &[PathBuf], extensions: &[&str]) -> Option<PathBuf> {
+    let compact = font.family.replace(' ', "");
+    let stems = [
+        font.family.to_string(),
+        compact.clone(),
+        format!("{}-Regular", compact),
+    ];
+
+    dirs.iter().find_map(|dir| {
+        stems.iter().find_map(|stem| {
+            extensions
+                .iter()
+                .map(|ext| dir.join(format!("{}.{}", stem, ext)))
+                .find(|p| p.is_file())
+        })
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub padding: f64,
+    pub export_background: bool,
+    /// Replaces the scene's `viewBackgroundColor`
+    pub background_color: Option<String>,
+    pub dark_mode: bool,
+    pub hand_drawn: bool,
+    /// Crop to these frames, drawing only their content
+    pub frame_ids: Vec<String>,
+    /// Crop to these elements (and the text bound to them)
+    pub element_ids: Vec<String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            padding: 10.0,
+            export_background: true,
+            background_color: None,
+            dark_mode: false,
+            hand_drawn: true,
+            frame_ids: Vec::new(),
+            element_ids: Vec::new(),
+        }
+    }
+}
+
+pub struct DisplayList {
+    pub width: f64,
+    pub height: f64,
+    pub background: Option<String>,
+    pub primitives: Vec<Primitive>,
+    /// Font ids used by text, for backends that embed fonts
+    pub fonts: BTreeSet<i64>,
+}
+
+struct RenderContext<'a> {
+    options: &'a RenderOptions,
+    files: Option<&'a Map<String, Value>>,
+    fonts: BTreeSet<i64>,
+}
+
+/// Builds the display list for every visible element of a scene
+pub fn build_display_list(scene: &Value, options: &RenderOptions) -> Result<DisplayList, String> {
+    let visible: Vec<&Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .ok_or("Scene has no elements array")?
+        .iter()
+        .filter(|e| is_visible(e))
+        .collect();
+
+    let by_id: HashMap<&str, &Value> = visible
+        .iter()
+        .filter_map(|e| Some((str_field(e, "id")?, *e)))
+        .collect();
+
+    let elements = select_elements(&visible, options);
+    if elements.is_empty() && !(options.frame_ids.is_empty() && options.element_ids.is_empty()) {
+        return Err("None of the requested frames or elements exist in the scene".to_string());
+    }
+
+    // Frame exports crop to the frame itself, like Excalidraw's "export frame"
+    let cropped_frames: Vec<&Value> = options
+        .frame_ids
+        .iter()
+        .filter_map(|id| by_id.get(id.as_str()).copied())
+        .collect();
+    let bounds = if cropped_frames.is_empty() {
+        scene_bounds(&elements)
+    } else {
+        let frame_bounds = cropped_frames.iter().map(|f| element_bounds(f));
+        let other = elements
+            .iter()
+            .filter(|e| {
+                str_field(e, "frameId").is_none_or(|id| !options.frame_ids.iter().any(|f| f == id))
+                    && !cropped_frames.iter().any(|f| std::ptr::eq(*f, **e))
+            })
+            .map(|e| visual_bounds(e));
+        frame_bounds.chain(other).reduce(union_bounds)
+    };
+    let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let padding = options.padding.max(0.0);
+
+    let mut ctx = RenderContext {
+        options,
+        files: scene.get("files").and_then(|f| f.as_object()),
+        fonts: BTreeSet::new(),
+    };
+
+    let mut children = Vec::new();
+    for element in &elements {
+        if cropped_frames.iter().any(|f| std::ptr::eq(*f, *element)) {
+            continue;
+        }
+        let Some(primitive) = render_element(element, &mut ctx) else {
+            continue;
+        };
+
+        let frame = str_field(element, "frameId").and_then(|id| by_id.get(id));
+        match frame {
+            Some(frame) => children.push(Primitive::Group {
+                transform: None,
+                clip: Some(frame_clip(frame)),
+                opacity: 1.0,
+                children: vec![primitive],
+            }),
+            None => children.push(primitive),
+        }
+    }
+
+    let background = if options.export_background {
+        let color = options.background_color.as_deref().unwrap_or_else(|| {
+            scene
+                .get("appState")
+                .and_then(|s| s.get("viewBackgroundColor"))
+                .and_then(|c| c.as_str())
+                .unwrap_or(DEFAULT_BACKGROUND_COLOR)
+        });
+        resolve_color(color, options.dark_mode)
+    } else {
+        None
+    };
+
+    Ok(DisplayList {
+        width: (max_x - min_x) + padding * 2.0,
+        height: (max_y - min_y) + padding * 2.0,
+        background,
+        primitives: vec![Primitive::Group {
+            transform: Some(Transform::translate(padding - min_x, padding - min_y)),
+            clip: None,
+            opacity: 1.0,
+            children,
+        }],
+        fonts: ctx.fonts,
+    })
+}
+
+/// Picks the elements to draw: everything, or the requested frames with their
+/// children and the requested elements with their bound text
+fn select_elements<'a>(elements: &[&'a Value], options: &RenderOptions) -> Vec<&'a Value> {
+    if options.frame_ids.is_empty() && options.element_ids.is_empty() {
+        return elements.to_vec();
+    }
+
+    let wanted = |id: Option<&str>| {
+        id.is_some_and(|id| {
+            options.frame_ids.iter().any(|f| f == id) || options.element_ids.iter().any(|e| e == id)
+        })
+    };
+    let in_frame = |e: &Value| {
+        str_field(e, "frameId").is_some_and(|id| options.frame_ids.iter().any(|f| f == id))
+    };
+
+    let selected: Vec<&str> = elements
+        .iter()
+        .filter(|e| wanted(str_field(e, "id")) || in_frame(e))
+        .filter_map(|e| str_field(e, "id"))
+        .collect();
+
+    elements
+        .iter()
+        .filter(|e| {
+            str_field(e, "id").is_some_and(|id| selected.contains(&id))
+                || str_field(e, "containerId").is_some_and(|id| selected.contains(&id))
+        })
+        .copied()
+        .collect()
+}
+
+fn is_visible(element: &Value) -> bool {
+    !bool_field(element, "isDeleted").unwrap_or(false)
+        && !matches!(str_field(element, "type"), Some("selection") | None)
+}
+
+pub fn str_field<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|v| v.as_str())
+}
+
+pub fn num_field(element: &Value, key: &str) -> Option<f64> {
+    element.get(key).and_then(|v| v.as_f64())
+}
+
+pub fn bool_field(element: &Value, key: &str) -> Option<bool> {
+    element.get(key).and_then(|v| v.as_bool())
+}
+
+pub fn element_points(element: &Value) -> Vec<Point> {
+    element
+        .get("points")
+        .and_then(|p| p.as_array())
+        .map(|points| {
+            points
+                .iter()
+                .filter_map(|p| {
+                    let p = p.as_array()?;
+                    Some((p.first()?.as_f64()?, p.get(1)?.as_f64()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn is_linear(element_type: &str) -> bool {
+    matches!(element_type, "line" | "arrow" | "freedraw")
+}
+
+/// Box in the element's own coordinates that rotation is applied around
+fn local_box(element: &Value) -> Bounds {
+    let element_type = str_field(element, "type").unwrap_or_default();
+    if is_linear(element_type) {
+        let points = element_points(element);
+        if !points.is_empty() {
+            return points_bounds(&points);
+        }
+    }
+
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let height = num_field(element, "height").unwrap_or(0.0);
+    (0.0, 0.0, width, height)
+}
+
+pub fn element_transform(element: &Value) -> Transform {
+    let x = num_field(element, "x").unwrap_or(0.0);
+    let y = num_field(element, "y").unwrap_or(0.0);
+    let angle = num_field(element, "angle").unwrap_or(0.0);
+    let (x1, y1, x2, y2) = local_box(element);
+
+    let translate = Transform::translate(x, y);
+    if angle == 0.0 {
+        return translate;
+    }
+    Transform::rotate_about(angle, (x1 + x2) / 2.0, (y1 + y2) / 2.0).then(&translate)
+}
+
+/// Axis-aligned bounds of an element in scene coordinates
+pub fn element_bounds(element: &Value) -> Bounds {
+    let (x1, y1, x2, y2) = local_box(element);
+    let transform = element_transform(element);
+    let corners: Vec<Point> = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)]
+        .iter()
+        .map(|p| transform.apply(*p))
+        .collect();
+    points_bounds(&corners)
+}
+
+/// Element bounds including decorations drawn outside of it
+pub fn visual_bounds(element: &Value) -> Bounds {
+    let (min_x, min_y, max_x, max_y) = element_bounds(element);
+    if matches!(str_field(element, "type"), Some("frame" | "magicframe")) {
+        // Leave room for the frame name drawn above the frame
+        return (min_x, min_y - FRAME_NAME_FONT_SIZE - FRAME_NAME_OFFSET, max_x, max_y);
+    }
+    (min_x, min_y, max_x, max_y)
+}
+
+pub fn scene_bounds(elements: &[&Value]) -> Option<Bounds> {
+    elements
+        .iter()
+        .map(|e| visual_bounds(e))
+        .reduce(union_bounds)
+}
+
+pub fn union_bounds(a: Bounds, b: Bounds) -> Bounds {
+    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+}
+
+pub fn points_bounds(points: &[Point]) -> Bounds {
+    points.iter().fold(
+        (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        |(x1, y1, x2, y2), &(x, y)| (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+    )
+}
+
+fn frame_clip(frame: &Value) -> PathData {
+    let width = num_field(frame, "width").unwrap_or(0.0);
+    let height = num_field(frame, "height").unwrap_or(0.0);
+    PathData::rounded_rect(0.0, 0.0, width, height, FRAME_RADIUS).transformed(&element_transform(frame))
+}
+
+/// Maps an Excalidraw color to the output color, `None` for transparent
+pub fn resolve_color(color: &str, dark_mode: bool) -> Option<String> {
+    let color = color.trim();
+    if color.is_empty() || color == "transparent" {
+        return None;
+    }
+    if !dark_mode {
+        return Some(color.to_string());
+    }
+
+    match parse_color(color) {
+        Some((r, g, b, a)) => {
+            let (r, g, b) = dark_mode_rgb(r, g, b);
+            Some(format_color(r, g, b, a))
+        }
+        None => Some(color.to_string()),
+    }
+}
+
+/// Same result as Excalidraw's `invert(93%) hue-rotate(180deg)` dark mode filter
+fn dark_mode_rgb(r: f64, g: f64, b: f64) -> (f64, f64, f64) {
+    let invert = |c: f64| 0.93 - 0.86 * c;
+    let (r, g, b) = (invert(r), invert(g), invert(b));
+    (
+        (-0.574 * r + 1.43 * g + 0.144 * b).clamp(0.0, 1.0),
+        (0.426 * r + 0.43 * g + 0.144 * b).clamp(0.0, 1.0),
+        (0.426 * r + 1.43 * g - 0.856 * b).clamp(0.0, 1.0),
+    )
+}
+
+fn format_color(r: f64, g: f64, b: f64, a: f64) -> String {
+    let byte = |c: f64| (c * 255.0).round() as u8;
+    if a < 1.0 {
+        format!("#{:02x}{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b), byte(a))
+    } else {
+        format!("#{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b))
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb`, `#rrggbbaa` and a few named colors into 0..1 components
+pub fn parse_color(color: &str) -> Option<(f64, f64, f64, f64)> {
+    let color = color.trim().to_lowercase();
+    let hex = match color.as_str() {
+        "black" => "000000".to_string(),
+        "white" => "ffffff".to_string(),
+        "red" => "ff0000".to_string(),
+        "green" => "008000".to_string(),
+        "blue" => "0000ff".to_string(),
+        "yellow" => "ffff00".to_string(),
+        "orange" => "ffa500".to_string(),
+        "gray" | "grey" => "808080".to_string(),
+        "transparent" => "00000000".to_string(),
+        other => other.strip_prefix('#')?.to_string(),
+    };
+
+    let expanded: String = match hex.len() {
+        3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 | 8 => hex,
+        _ => return None,
+    };
+
+    let component = |i: usize| {
+        u8::from_str_radix(expanded.get(i..i + 2)?, 16)
+            .ok()
+            .map(|v| v as f64 / 255.0)
+    };
+    let alpha = if expanded.len() == 8 { component(6)? } else { 1.0 };
+    Some((component(0)?, component(2)?, component(4)?, alpha))
+}
+
+fn render_element(element: &Value, ctx: &mut RenderContext) -> Option<Primitive> {
+    let element_type = str_field(element, "type")?;
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let height = num_field(element, "height").unwrap_or(0.0);
+    let opacity = num_field(element, "opacity").unwrap_or(100.0) / 100.0;
+    let mut rng = rough::Rng::new(num_field(element, "seed").unwrap_or(1.0) as i64);
+
+    let children = match element_type {
+        "rectangle" => {
+            let radius = corner_radius(element, width.min(height));
+            let path = PathData::rounded_rect(0.0, 0.0, width, height, radius);
+            render_shape(element, path, ctx, &mut rng)
+        }
+        "diamond" => {
+            let points = [
+                (width / 2.0, 0.0),
+                (width, height / 2.0),
+                (width / 2.0, height),
+                (0.0, height / 2.0),
+            ];
+            render_shape(element, PathData::polyline(&points, true), ctx, &mut rng)
+        }
+        "ellipse" => {
+            let path = PathData::ellipse(width / 2.0, height / 2.0, width / 2.0, height / 2.0);
+            render_shape(element, path, ctx, &mut rng)
+        }
+        "line" | "arrow" => render_linear(element, ctx, &mut rng),
+        "freedraw" => render_freedraw(element, ctx),
+        "text" => render_text(element, ctx),
+        "image" => render_image(element, ctx),
+        "frame" | "magicframe" => render_frame(element, ctx),
+        "embeddable" | "iframe" => render_embeddable(element, ctx, &mut rng),
+        _ => return None,
+    };
+
+    if children.is_empty() {
+        return None;
+    }
+
+    Some(Primitive::Group {
+        transform: Some(element_transform(element)),
+        clip: None,
+        opacity,
+        children,
+    })
+}
+
+fn corner_radius(element: &Value, size: f64) -> f64 {
+    let Some(roundness) = element.get("roundness").filter(|r| r.is_object()) else {
+        return 0.0;
+    };
+
+    match roundness.get("type").and_then(|t| t.as_i64()) {
+        // Adaptive radius: fixed radius unless the shape is too small for it
+        Some(3) => {
+            let radius = roundness.get("value").and_then(|v| v.as_f64()).unwrap_or(32.0);
+            if size <= radius / 0.25 { size * 0.25 } else { radius }
+        }
+        _ => size * 0.25,
+    }
+}
+
+fn element_stroke(element: &Value, ctx: &RenderContext) -> Option<Stroke> {
+    let color = resolve_color(
+        str_field(element, "strokeColor").unwrap_or(DEFAULT_STROKE_COLOR),
+        ctx.options.dark_mode,
+    )?;
+    let width = num_field(element, "strokeWidth").unwrap_or(2.0);
+
+    let dash = match str_field(element, "strokeStyle") {
+        Some("dashed") => Some(vec![8.0, 8.0 + width]),
+        Some("dotted") => Some(vec![1.5, 6.0 + width]),
+        _ => None,
+    };
+
+    Some(Stroke {
+        color,
+        width,
+        round_cap: !matches!(str_field(element, "strokeStyle"), Some("dashed")),
+        dash,
+    })
+}
+
+fn element_fill(element: &Value, ctx: &RenderContext) -> Option<String> {
+    resolve_color(
+        str_field(element, "backgroundColor").unwrap_or("transparent"),
+        ctx.options.dark_mode,
+    )
+}
+
+fn roughness(element: &Value, ctx: &RenderContext) -> f64 {
+    if ctx.options.hand_drawn {
+        num_field(element, "roughness").unwrap_or(1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Fill and outline of a closed shape
+fn render_shape(
+    element: &Value,
+    path: PathData,
+    ctx: &RenderContext,
+    rng: &mut rough::Rng,
+) -> Vec<Primitive> {
+    let mut out = Vec::new();
+    let roughness = roughness(element, ctx);
+
+    if let Some(fill) = element_fill(element, ctx) {
+        out.push(render_fill(element, &path, fill, roughness, rng));
+    }
+
+    if let Some(stroke) = element_stroke(element, ctx) {
+        let outline = if roughness > 0.0 {
+            rough::roughen(&path, roughness, rng)
+        } else {
+            path
+        };
+        out.push(Primitive::Path {
+            path: outline,
+            fill: None,
+            stroke: Some(stroke),
+        });
+    }
+
+    out
+}
+
+fn render_fill(
+    element: &Value,
+    path: &PathData,
+    color: String,
+    roughness: f64,
+    rng: &mut rough::Rng,
+) -> Primitive {
+    let fill_style = str_field(element, "fillStyle").unwrap_or("solid");
+    if fill_style == "solid" {
+        return Primitive::Path {
+            path: path.clone(),
+            fill: Some(color),
+            stroke: None,
+        };
+    }
+
+    let stroke_width = num_field(element, "strokeWidth").unwrap_or(2.0);
+    let gap = (stroke_width * 4.0).max(4.0);
+    let bounds = path_bounds(path);
+
+    let mut lines = hatch_lines(bounds, gap, HACHURE_ANGLE);
+    if fill_style == "cross-hatch" {
+        lines.extend(hatch_lines(bounds, gap, HACHURE_ANGLE + 90.0));
+    }
+    if roughness > 0.0 {
+        lines = rough::roughen_single(&lines, roughness, rng);
+    }
+
+    Primitive::Group {
+        transform: None,
+        clip: Some(path.clone()),
+        opacity: 1.0,
+        children: vec![Primitive::Path {
+            path: lines,
+            fill: None,
+            stroke: Some(Stroke {
+                color,
+                width: stroke_width / 2.0,
+                dash: None,
+                round_cap: true,
+            }),
+        }],
+    }
+}
+
+fn path_bounds(path: &PathData) -> Bounds {
+    let points: Vec<Point> = path
+        .segments
+        .iter()
+        .flat_map(|s| match *s {
+            Segment::MoveTo(p) | Segment::LineTo(p) => vec![p],
+            Segment::CubicTo(c1, c2, p) => vec![c1, c2, p],
+            Segment::Close => vec![],
+        })
+        .collect();
+    points_bounds(&points)
+}
+
+/// Parallel lines at `angle` degrees covering `bounds`, spaced `gap` apart
+fn hatch_lines((x1, y1, x2, y2): Bounds, gap: f64, angle: f64) -> PathData {
+    let (cx, cy) = ((x1 + x2) / 2.0, (y1 + y2) / 2.0);
+    let radius = ((x2 - x1).hypot(y2 - y1)) / 2.0 + gap;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let steps = (radius / gap).ceil() as i64;
+
+    let mut path = PathData::default();
+    for k in -steps..=steps {
+        let offset = k as f64 * gap;
+        let (px, py) = (cx - sin * offset, cy + cos * offset);
+        path.move_to((px - cos * radius, py - sin * radius));
+        path.line_to((px + cos * radius, py + sin * radius));
+    }
+    path
+}
+
+fn render_linear(element: &Value, ctx: &RenderContext, rng: &mut rough::Rng) -> Vec<Primitive> {
+    let points = element_points(element);
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let roughness = roughness(element, ctx);
+    let elbowed = bool_field(element, "elbowed").unwrap_or(false);
+    let curved = !elbowed && points.len() > 2 && element.get("roundness").is_some_and(|r| r.is_object());
+    let path = if curved {
+        PathData::curve_through(&points)
+    } else {
+        PathData::polyline(&points, false)
+    };
+
+    let mut out = Vec::new();
+    let closed = bool_field(element, "polygon").unwrap_or(false)
+        || (str_field(element, "type") == Some("line")
+            && points.len() > 2
+            && distance(points[0], points[points.len() - 1]) < 1.0);
+    if let Some(fill) = element_fill(element, ctx).filter(|_| closed) {
+        let mut area = path.clone();
+        area.close();
+        out.push(render_fill(element, &area, fill, roughness, rng));
+    }
+
+    let Some(stroke) = element_stroke(element, ctx) else {
+        return out;
+    };
+
+    let outline = if roughness > 0.0 {
+        rough::roughen(&path, roughness, rng)
+    } else {
+        path.clone()
+    };
+    out.push(Primitive::Path {
+        path: outline,
+        fill: None,
+        stroke: Some(stroke.clone()),
+    });
+
+    // Arrowheads follow the direction of the path at its ends
+    let (start_from, end_from) = match (path.segments.get(1), path.segments.last()) {
+        (Some(Segment::CubicTo(c1, _, _)), Some(Segment::CubicTo(_, c2, _))) => (*c1, *c2),
+        _ => (points[1], points[points.len() - 2]),
+    };
+    let last = points.len() - 1;
+    let heads = [
+        (str_field(element, "startArrowhead"), points[0], start_from, distance(points[0], points[1])),
+        (str_field(element, "endArrowhead"), points[last], end_from, distance(points[last], points[last - 1])),
+    ];
+    for (kind, tip, from, segment_length) in heads {
+        if let Some(kind) = kind {
+            let fill = element_fill(element, ctx);
+            out.extend(render_arrowhead(kind, tip, from, segment_length, &stroke, fill));
+        }
+    }
+
+    out
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// `from` gives the direction the head points in, `segment_length` limits its size
+fn render_arrowhead(
+    kind: &str,
+    tip: Point,
+    from: Point,
+    segment_length: f64,
+    stroke: &Stroke,
+    fill: Option<String>,
+) -> Vec<Primitive> {
+    let length = distance(tip, from);
+    if length < f64::EPSILON {
+        return Vec::new();
+    }
+
+    let base_size: f64 = match kind {
+        "arrow" => 25.0,
+        "diamond" | "diamond_outline" => 12.0,
+        kind if kind.starts_with("crowfoot") => 20.0,
+        _ => 15.0,
+    };
+    let multiplier = if kind.starts_with("diamond") { 0.25 } else { 0.5 };
+    let size = base_size.min(segment_length * multiplier);
+    let (dx, dy) = ((tip.0 - from.0) / length, (tip.1 - from.1) / length);
+
+    let wing = |angle: f64, size: f64| {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (rx, ry) = (dx * cos - dy * sin, dx * sin + dy * cos);
+        (tip.0 - rx * size, tip.1 - ry * size)
+    };
+
+    let solid_stroke = Stroke { dash: None, ..stroke.clone() };
+    let outline = |path: PathData, fill: Option<String>| Primitive::Path {
+        path,
+        fill,
+        stroke: Some(solid_stroke.clone()),
+    };
+
+    let primitive = match kind {
+        "bar" => {
+            let half = size / 2.0;
+            let a = (tip.0 - dy * half, tip.1 + dx * half);
+            let b = (tip.0 + dy * half, tip.1 - dx * half);
+            outline(PathData::polyline(&[a, b], false), None)
+        }
+        "dot" | "circle" | "circle_outline" => {
+            let r = size / 2.0;
+            let fill = if kind == "circle_outline" { fill } else { Some(stroke.color.clone()) };
+            outline(PathData::ellipse(tip.0 - dx * r, tip.1 - dy * r, r, r), fill)
+        }
+        "triangle" | "triangle_outline" => {
+            let fill = if kind == "triangle" { Some(stroke.color.clone()) } else { fill };
+            outline(PathData::polyline(&[tip, wing(25.0, size), wing(-25.0, size)], true), fill)
+        }
+        "diamond" | "diamond_outline" => {
+            let fill = if kind == "diamond" { Some(stroke.color.clone()) } else { fill };
+            let back = (tip.0 - dx * size * 2.0, tip.1 - dy * size * 2.0);
+            let points = [tip, wing(25.0, size * 1.1), back, wing(-25.0, size * 1.1)];
+            outline(PathData::polyline(&points, true), fill)
+        }
+        kind if kind.starts_with("crowfoot") => {
+            // Crow's foot opens towards the shape the arrow points at
+            let base = (tip.0 - dx * size, tip.1 - dy * size);
+            let spread = |sign: f64| (tip.0 - dy * sign * size / 2.0, tip.1 + dx * sign * size / 2.0);
+            let mut path = PathData::polyline(&[base, spread(1.0)], false);
+            path.extend(PathData::polyline(&[base, tip], false));
+            path.extend(PathData::polyline(&[base, spread(-1.0)], false));
+            outline(path, None)
+        }
+        _ => {
+            let mut path = PathData::polyline(&[wing(20.0, size), tip], false);
+            path.line_to(wing(-20.0, size));
+            outline(path, None)
+        }
+    };
+
+    vec![primitive]
+}
+
+fn render_freedraw(element: &Value, ctx: &RenderContext) -> Vec<Primitive> {
+    let points = element_points(element);
+    let Some(stroke) = element_stroke(element, ctx) else {
+        return Vec::new();
+    };
+    let width = num_field(element, "strokeWidth").unwrap_or(2.0) * 2.0;
+
+    if points.len() == 1 {
+        let (x, y) = points[0];
+        return vec![Primitive::Path {
+            path: PathData::ellipse(x, y, width / 2.0, width / 2.0),
+            fill: Some(stroke.color),
+            stroke: None,
+        }];
+    }
+
+    // Quadratic smoothing through segment midpoints, as used for freehand strokes
+    let mut path = PathData::default();
+    let Some(&first) = points.first() else {
+        return Vec::new();
+    };
+    path.move_to(first);
+    let mut current = first;
+    for window in points.windows(2).skip(1) {
+        let (control, next) = (window[0], window[1]);
+        let mid = ((control.0 + next.0) / 2.0, (control.1 + next.1) / 2.0);
+        path.cubic_to(
+            (current.0 + 2.0 / 3.0 * (control.0 - current.0), current.1 + 2.0 / 3.0 * (control.1 - current.1)),
+            (mid.0 + 2.0 / 3.0 * (control.0 - mid.0), mid.1 + 2.0 / 3.0 * (control.1 - mid.1)),
+            mid,
+        );
+        current = mid;
+    }
+    if let Some(&last) = points.last() {
+        path.line_to(last);
+    }
+
+    vec![Primitive::Path {
+        path,
+        fill: None,
+        stroke: Some(Stroke {
+            width,
+            dash: None,
+            round_cap: true,
+            ..stroke
+        }),
+    }]
+}
+
+fn render_text(element: &Value, ctx: &mut RenderContext) -> Vec<Primitive> {
+    let text = str_field(element, "text").unwrap_or_default();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    let Some(color) = resolve_color(
+        str_field(element, "strokeColor").unwrap_or(DEFAULT_STROKE_COLOR),
+        ctx.options.dark_mode,
+    ) else {
+        return Vec::new();
+    };
+
+    let font = font_info(element.get("fontFamily").and_then(|f| f.as_i64()).unwrap_or(1));
+    let font_size = num_field(element, "fontSize").unwrap_or(20.0);
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let line_height = num_field(element, "lineHeight").unwrap_or(1.25) * font_size;
+    ctx.fonts.insert(font.id);
+
+    let (anchor, x) = match str_field(element, "textAlign") {
+        Some("center") => (TextAnchor::Middle, width / 2.0),
+        Some("right") => (TextAnchor::End, width),
+        _ => (TextAnchor::Start, 0.0),
+    };
+
+    let ascent = font.ascender / font.units_per_em * font_size;
+    let content_height = (font.ascender - font.descender) / font.units_per_em * font_size;
+    let baseline = ascent + (line_height - content_height) / 2.0;
+
+    let runs = text
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| TextRun {
+            x,
+            y: i as f64 * line_height + baseline,
+            text: line.to_string(),
+        })
+        .collect();
+
+    vec![Primitive::Text {
+        runs,
+        font,
+        font_size,
+        anchor,
+        color,
+    }]
+}
+
+fn render_image(element: &Value, ctx: &RenderContext) -> Vec<Primitive> {
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let height = num_field(element, "height").unwrap_or(0.0);
+
+    let decoded = str_field(element, "fileId")
+        .and_then(|id| ctx.files?.get(id))
+        .and_then(|file| str_field(file, "dataURL"))
+        .and_then(assets::decode_data_url);
+
+    let Some((mime_type, data)) = decoded else {
+        // Missing image data, draw a placeholder like the editor does
+        return vec![Primitive::Path {
+            path: PathData::rect(0.0, 0.0, width, height),
+            fill: Some("#e9ecef".to_string()),
+            stroke: None,
+        }];
+    };
+
+    let (mut x, mut y, mut image_width, mut image_height) = (0.0, 0.0, width, height);
+    let crop = element.get("crop").filter(|c| c.is_object());
+    if let Some(crop) = crop {
+        let crop_width = num_field(crop, "width").unwrap_or(1.0).max(f64::EPSILON);
+        let crop_height = num_field(crop, "height").unwrap_or(1.0).max(f64::EPSILON);
+        let (sx, sy) = (width / crop_width, height / crop_height);
+        x = -num_field(crop, "x").unwrap_or(0.0) * sx;
+        y = -num_field(crop, "y").unwrap_or(0.0) * sy;
+        image_width = num_field(crop, "naturalWidth").unwrap_or(crop_width) * sx;
+        image_height = num_field(crop, "naturalHeight").unwrap_or(crop_height) * sy;
+    }
+
+    let image = Primitive::Image {
+        element_id: str_field(element, "id").unwrap_or_default().to_string(),
+        x,
+        y,
+        width: image_width,
+        height: image_height,
+        mime_type,
+        data,
+    };
+
+    let radius = corner_radius(element, width.min(height));
+    let clip = (crop.is_some() || radius > 0.0)
+        .then(|| PathData::rounded_rect(0.0, 0.0, width, height, radius));
+
+    let scale = element.get("scale").and_then(|s| s.as_array());
+    let flip_x = scale.and_then(|s| s.first()?.as_f64()).unwrap_or(1.0) < 0.0;
+    let flip_y = scale.and_then(|s| s.get(1)?.as_f64()).unwrap_or(1.0) < 0.0;
+    let transform = (flip_x || flip_y).then(|| {
+        Transform::scale_about(
+            if flip_x { -1.0 } else { 1.0 },
+            if flip_y { -1.0 } else { 1.0 },
+            width / 2.0,
+            height / 2.0,
+        )
+    });
+
+    vec![Primitive::Group {
+        transform,
+        clip,
+        opacity: 1.0,
+        children: vec![image],
+    }]
+}
+
+fn render_frame(element: &Value, ctx: &mut RenderContext) -> Vec<Primitive> {
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let height = num_field(element, "height").unwrap_or(0.0);
+    let dark_mode = ctx.options.dark_mode;
+
+    let mut out = Vec::new();
+    if let Some(color) = resolve_color(FRAME_STROKE_COLOR, dark_mode) {
+        out.push(Primitive::Path {
+            path: PathData::rounded_rect(0.0, 0.0, width, height, FRAME_RADIUS),
+            fill: None,
+            stroke: Some(Stroke {
+                color,
+                width: 2.0,
+                dash: None,
+                round_cap: true,
+            }),
+        });
+    }
+
+    let name = str_field(element, "name").unwrap_or(match str_field(element, "type") {
+        Some("magicframe") => "AI Frame",
+        _ => "Frame",
+    });
+    let font = font_info(2);
+    ctx.fonts.insert(font.id);
+    if let Some(color) = resolve_color(FRAME_NAME_COLOR, dark_mode) {
+        out.push(Primitive::Text {
+            runs: vec![TextRun {
+                x: 0.0,
+                y: -FRAME_NAME_OFFSET,
+                text: name.to_string(),
+            }],
+            font,
+            font_size: FRAME_NAME_FONT_SIZE,
+            anchor: TextAnchor::Start,
+            color,
+        });
+    }
+
+    out
+}
+
+fn render_embeddable(element: &Value, ctx: &mut RenderContext, rng: &mut rough::Rng) -> Vec<Primitive> {
+    let width = num_field(element, "width").unwrap_or(0.0);
+    let height = num_field(element, "height").unwrap_or(0.0);
+    let radius = corner_radius(element, width.min(height));
+    let mut out = render_shape(
+        element,
+        PathData::rounded_rect(0.0, 0.0, width, height, radius),
+        ctx,
+        rng,
+    );
+
+    if let (Some(link), Some(stroke)) = (str_field(element, "link"), element_stroke(element, ctx)) {
+        let font = font_info(2);
+        ctx.fonts.insert(font.id);
+        out.push(Primitive::Text {
+            runs: vec![TextRun {
+                x: width / 2.0,
+                y: height / 2.0,
+                text: link.to_string(),
+            }],
+            font,
+            font_size: 16.0,
+            anchor: TextAnchor::Middle,
+            color: stroke.color,
+        });
+    }
+
+    out
+}
+
+/// Resolves where an export is written: next to the drawing unless `output_path` is given
+pub fn output_path_for(
+    source: &Path,
+    output_path: Option<String>,
+    extension: &str,
+) -> Result<PathBuf, String> {
+    if let Some(output) = output_path {
+        let output = PathBuf::from(output);
+        let parent = output.parent().ok_or("Invalid output path")?;
+        let parent = crate::security::validate_path(parent, None)?;
+        let file_name = output
+            .file_name()
+            .ok_or("Invalid output path")?
+            .to_string_lossy()
+            .to_string();
+        return crate::security::safe_path_join(&parent, &file_name);
+    }
+
+    let name = source
+        .file_name()
+        .ok_or("Invalid file path")?
+        .to_string_lossy()
+        .to_string();
+    let stem = crate::formats::drawing_stem(&name);
+    let parent = source.parent().ok_or("Invalid file path")?;
+    Ok(parent.join(format!("{}.{}", stem, extension)))
+}
+
+/// Creates an export folder once its parent has been validated, and returns its canonical path
+pub fn output_dir_for(output_dir: &str) -> Result<PathBuf, String> {
+    let output = PathBuf::from(output_dir);
+    let parent = match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent = crate::security::validate_path(parent, None)?;
+    let name = output.file_name().ok_or("Invalid output folder")?.to_string_lossy().to_string();
+    let output = crate::security::safe_path_join(&parent, &name)?;
+    std::fs::create_dir_all(&output).map_err(|e| format!("Failed to create output folder: {}", e))?;
+    crate::security::validate_path(&output, None)
+}
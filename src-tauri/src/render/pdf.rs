@@ -0,0 +1,841 @@
+//! Vector PDF backend. Text is drawn with embedded, subset OpenType fonts and a
+//! ToUnicode map, so it stays selectable and searchable in viewers. Characters the
+//! Excalidraw font lacks, like CJK, are drawn with an installed font that has them.
+
+use super::{DisplayList, FontInfo, PathData, Primitive, Segment, TextAnchor, parse_color};
+use crate::models;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use pdf_writer::types::{
+    CidFontType, FontFlags, LineCapStyle, LineJoinStyle, SystemInfo, UnicodeCmap,
+};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use resvg::usvg::fontdb;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Largest page side most viewers accept, in points
+const MAX_PAGE_SIZE: f64 = 14_400.0;
+
+/// Average advance of base-14 Helvetica, used when no TrueType font is available
+const FALLBACK_ADVANCE: f64 = 0.55;
+
+/// Fonts tried first for characters missing from the Excalidraw fonts
+const FALLBACK_FAMILIES: [&str; 10] = [
+    "Xiaolai",
+    "Noto Sans CJK SC",
+    "Source Han Sans SC",
+    "PingFang SC",
+    "Microsoft YaHei",
+    "WenQuanYi Micro Hei",
+    "Noto Sans SC",
+    "SimHei",
+    "Hiragino Sans GB",
+    "Arial Unicode MS",
+];
+
+/// Resource name of the fallback font
+const FALLBACK_FONT_NAME: &str = "FB";
+
+const IDENTITY_SYSTEM_INFO: SystemInfo = SystemInfo {
+    registry: Str(b"Adobe"),
+    ordering: Str(b"Identity"),
+    supplement: 0,
+};
+
+pub struct PdfPage {
+    pub list: DisplayList,
+    /// Bookmark shown in the viewer's outline
+    pub title: Option<String>,
+}
+
+/// A PDF document and what could not be drawn as it is in the scene
+pub struct PdfOutput {
+    pub data: Vec<u8>,
+    /// Images that could not be decoded
+    pub skipped: Vec<models::SkippedElement>,
+    /// Fonts that were replaced
+    pub warnings: Vec<String>,
+}
+
+enum FontKind {
+    /// Text is encoded as 2-byte glyph ids; the map holds glyph id and advance in em
+    Cid {
+        glyphs: HashMap<char, (u16, f64)>,
+        missing_advance: f64,
+    },
+    /// Base-14 Helvetica with WinAnsi encoding
+    Helvetica,
+}
+
+struct EmbeddedFont {
+    name: String,
+    reference: Ref,
+    kind: FontKind,
+}
+
+impl EmbeddedFont {
+    fn covers(&self, c: char) -> bool {
+        match &self.kind {
+            FontKind::Cid { glyphs, .. } => glyphs.contains_key(&c),
+            FontKind::Helvetica => u8::try_from(c as u32).is_ok(),
+        }
+    }
+
+    /// Encoded string and its width in em
+    fn encode(&self, text: &str) -> (Vec<u8>, f64) {
+        match &self.kind {
+            FontKind::Cid {
+                glyphs,
+                missing_advance,
+            } => {
+                let mut bytes = Vec::with_capacity(text.len() * 2);
+                let mut width = 0.0;
+                for c in text.chars() {
+                    let (glyph, advance) = glyphs.get(&c).copied().unwrap_or((0, *missing_advance));
+                    bytes.extend_from_slice(&glyph.to_be_bytes());
+                    width += advance;
+                }
+                (bytes, width)
+            }
+            FontKind::Helvetica => {
+                let bytes: Vec<u8> = text
+                    .chars()
+                    .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+                    .collect();
+                let width = bytes.len() as f64 * FALLBACK_ADVANCE;
+                (bytes, width)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct PageResources {
+    fonts: BTreeSet<i64>,
+    fallback: bool,
+    images: BTreeSet<usize>,
+    states: BTreeSet<usize>,
+}
+
+struct Document<'a> {
+    pdf: Pdf,
+    next_ref: i32,
+    font_dirs: &'a [PathBuf],
+    fonts: HashMap<i64, EmbeddedFont>,
+    /// Draws the characters the Excalidraw fonts lack
+    fallback: Option<EmbeddedFont>,
+    /// Image XObjects keyed by a hash of their source bytes
+    images: HashMap<u64, usize>,
+    image_refs: Vec<Ref>,
+    /// Graphics states keyed by fill and stroke alpha in thousandths
+    states: HashMap<(u16, u16), usize>,
+    state_refs: Vec<Ref>,
+    skipped: Vec<models::SkippedElement>,
+    warnings: Vec<String>,
+}
+
+/// Writes one page per display list into a PDF document
+pub fn to_pdf(pages: &[PdfPage], title: &str, font_dirs: &[PathBuf]) -> Result<PdfOutput, String> {
+    if pages.is_empty() {
+        return Err("Nothing to export".to_string());
+    }
+
+    let mut doc = Document {
+        pdf: Pdf::new(),
+        next_ref: 0,
+        font_dirs,
+        fonts: HashMap::new(),
+        fallback: None,
+        images: HashMap::new(),
+        image_refs: Vec::new(),
+        states: HashMap::new(),
+        state_refs: Vec::new(),
+        skipped: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let catalog_id = doc.alloc();
+    let tree_id = doc.alloc();
+    let info_id = doc.alloc();
+
+    // Fonts are subset to the glyphs in use, so all text has to be known up front
+    let mut chars: BTreeMap<i64, BTreeSet<char>> = BTreeMap::new();
+    for page in pages {
+        collect_chars(&page.list.primitives, &mut chars);
+    }
+    let fonts = super::png::font_database(font_dirs);
+    doc.warnings.extend(fonts.warnings());
+    let db = fonts.db;
+    for (index, (id, used)) in chars.iter().enumerate() {
+        let font = doc.embed_font(super::font_info(*id), used, &db, format!("F{}", index + 1));
+        doc.fonts.insert(*id, font);
+    }
+    let missing: BTreeSet<char> = chars
+        .iter()
+        .flat_map(|(id, used)| used.iter().filter(|c| !doc.fonts[id].covers(**c)))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        doc.fallback = doc.embed_fallback(&missing, &db);
+    }
+
+    let mut page_ids = Vec::new();
+    for page in pages {
+        let page_id = doc.alloc();
+        let content_id = doc.alloc();
+        page_ids.push(page_id);
+
+        let list = &page.list;
+        let scale = (MAX_PAGE_SIZE / list.width.max(list.height)).min(1.0);
+        let (width, height) = (list.width * scale, list.height * scale);
+
+        let mut content = Content::new();
+        let mut used = PageResources::default();
+        // PDF's y axis points up, the display list's points down
+        content.transform([scale as f32, 0.0, 0.0, -scale as f32, 0.0, height as f32]);
+        if let Some(background) = &list.background {
+            let (r, g, b, a) = rgba(background);
+            content.save_state();
+            doc.set_alpha(&mut content, a, 1.0, &mut used);
+            content.set_fill_rgb(r, g, b);
+            content.rect(0.0, 0.0, list.width as f32, list.height as f32);
+            content.fill_nonzero();
+            content.restore_state();
+        }
+        for primitive in &list.primitives {
+            doc.draw(primitive, &mut content, 1.0, &mut used)?;
+        }
+
+        let data = deflate(&content.finish());
+        doc.pdf.stream(content_id, &data).filter(Filter::FlateDecode);
+
+        let mut pdf_page = doc.pdf.page(page_id);
+        pdf_page
+            .media_box(Rect::new(0.0, 0.0, width as f32, height as f32))
+            .parent(tree_id)
+            .contents(content_id);
+        let mut resources = pdf_page.resources();
+        let mut fonts = resources.fonts();
+        for id in &used.fonts {
+            let font = &doc.fonts[id];
+            fonts.pair(Name(font.name.as_bytes()), font.reference);
+        }
+        if let Some(fallback) = doc.fallback.as_ref().filter(|_| used.fallback) {
+            fonts.pair(Name(fallback.name.as_bytes()), fallback.reference);
+        }
+        fonts.finish();
+        let mut x_objects = resources.x_objects();
+        for index in &used.images {
+            let name = format!("Im{}", index);
+            x_objects.pair(Name(name.as_bytes()), doc.image_refs[*index]);
+        }
+        x_objects.finish();
+        let mut states = resources.ext_g_states();
+        for index in &used.states {
+            let name = format!("Gs{}", index);
+            states.pair(Name(name.as_bytes()), doc.state_refs[*index]);
+        }
+        states.finish();
+        resources.finish();
+        pdf_page.finish();
+    }
+
+    doc.pdf
+        .pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+
+    let titled: Vec<(Ref, &str)> = pages
+        .iter()
+        .zip(&page_ids)
+        .filter_map(|(page, id)| Some((*id, page.title.as_deref()?)))
+        .collect();
+    let mut catalog_outline = None;
+    if titled.len() > 1 {
+        let outline_id = doc.alloc();
+        let item_ids: Vec<Ref> = titled.iter().map(|_| doc.alloc()).collect();
+        for (i, (page_id, page_title)) in titled.iter().enumerate() {
+            let mut item = doc.pdf.outline_item(item_ids[i]);
+            item.parent(outline_id).title(TextStr(page_title));
+            if i > 0 {
+                item.prev(item_ids[i - 1]);
+            }
+            if let Some(next) = item_ids.get(i + 1) {
+                item.next(*next);
+            }
+            item.dest().page(*page_id).fit();
+        }
+        doc.pdf
+            .outline(outline_id)
+            .first(item_ids[0])
+            .last(item_ids[item_ids.len() - 1])
+            .count(item_ids.len() as i32);
+        catalog_outline = Some(outline_id);
+    }
+
+    let mut catalog = doc.pdf.catalog(catalog_id);
+    catalog.pages(tree_id);
+    if let Some(outline_id) = catalog_outline {
+        catalog.outlines(outline_id);
+    }
+    catalog.finish();
+    doc.pdf.document_info(info_id).title(TextStr(title));
+
+    Ok(PdfOutput {
+        data: doc.pdf.finish(),
+        skipped: doc.skipped,
+        warnings: doc.warnings,
+    })
+}
+
+impl Document<'_> {
+    fn alloc(&mut self) -> Ref {
+        self.next_ref += 1;
+        Ref::new(self.next_ref)
+    }
+
+    fn draw(
+        &mut self,
+        primitive: &Primitive,
+        content: &mut Content,
+        opacity: f64,
+        used: &mut PageResources,
+    ) -> Result<(), String> {
+        match primitive {
+            Primitive::Path { path, fill, stroke } => {
+                if path.segments.is_empty() || (fill.is_none() && stroke.is_none()) {
+                    return Ok(());
+                }
+                let fill = fill.as_deref().map(rgba);
+                let stroke_color = stroke.as_ref().map(|s| rgba(&s.color));
+
+                content.save_state();
+                self.set_alpha(
+                    content,
+                    fill.map_or(1.0, |c| c.3) * opacity as f32,
+                    stroke_color.map_or(1.0, |c| c.3) * opacity as f32,
+                    used,
+                );
+                if let Some((r, g, b, _)) = fill {
+                    content.set_fill_rgb(r, g, b);
+                }
+                if let (Some(stroke), Some((r, g, b, _))) = (stroke, stroke_color) {
+                    content.set_stroke_rgb(r, g, b);
+                    content.set_line_width(stroke.width as f32);
+                    if stroke.round_cap {
+                        content.set_line_cap(LineCapStyle::RoundCap);
+                        content.set_line_join(LineJoinStyle::RoundJoin);
+                    }
+                    if let Some(dash) = &stroke.dash {
+                        content.set_dash_pattern(dash.iter().map(|d| *d as f32), 0.0);
+                    }
+                }
+                write_path(content, path);
+                match (fill.is_some(), stroke.is_some()) {
+                    (true, true) => content.fill_nonzero_and_stroke(),
+                    (true, false) => content.fill_nonzero(),
+                    _ => content.stroke(),
+                };
+                content.restore_state();
+            }
+            Primitive::Text {
+                runs,
+                font,
+                font_size,
+                anchor,
+                color,
+            } => {
+                if !self.fonts.contains_key(&font.id) {
+                    return Ok(());
+                }
+                let (r, g, b, a) = rgba(color);
+
+                content.save_state();
+                self.set_alpha(content, a * opacity as f32, 1.0, used);
+                content.set_fill_rgb(r, g, b);
+                content.begin_text();
+                for run in runs.iter().filter(|run| !run.text.is_empty()) {
+                    let spans = self.spans(font.id, &run.text);
+                    let width: f64 = spans.iter().map(|(_, _, width)| width).sum();
+                    let x = match anchor {
+                        TextAnchor::Start => run.x,
+                        TextAnchor::Middle => run.x - width * font_size / 2.0,
+                        TextAnchor::End => run.x - width * font_size,
+                    };
+                    // Flip the glyphs back upright inside the y-down page transform
+                    content.set_text_matrix([1.0, 0.0, 0.0, -1.0, x as f32, run.y as f32]);
+                    // Each span advances the text position by its own width
+                    for (embedded, bytes, _) in &spans {
+                        content.set_font(Name(embedded.name.as_bytes()), *font_size as f32);
+                        content.show(Str(bytes));
+                        used.fallback |= embedded.name == FALLBACK_FONT_NAME;
+                    }
+                }
+                content.end_text();
+                content.restore_state();
+                used.fonts.insert(font.id);
+            }
+            Primitive::Image {
+                element_id,
+                x,
+                y,
+                width,
+                height,
+                mime_type,
+                data,
+            } => {
+                let index = match self.image(mime_type, data) {
+                    Ok(index) => index,
+                    Err(reason) => {
+                        self.skipped.push(models::SkippedElement {
+                            id: element_id.clone(),
+                            reason,
+                        });
+                        return Ok(());
+                    }
+                };
+                let name = format!("Im{}", index);
+
+                content.save_state();
+                self.set_alpha(content, opacity as f32, opacity as f32, used);
+                // Images fill the unit square with their first row at the top
+                content.transform([
+                    *width as f32,
+                    0.0,
+                    0.0,
+                    -*height as f32,
+                    *x as f32,
+                    (*y + *height) as f32,
+                ]);
+                content.x_object(Name(name.as_bytes()));
+                content.restore_state();
+                used.images.insert(index);
+            }
+            Primitive::Group {
+                transform,
+                clip,
+                opacity: group_opacity,
+                children,
+            } => {
+                content.save_state();
+                if let Some(transform) = transform.filter(|t| !t.is_identity()) {
+                    content.transform(transform.0.map(|v| v as f32));
+                }
+                if let Some(clip) = clip {
+                    write_path(content, clip);
+                    content.clip_nonzero();
+                    content.end_path();
+                }
+                for child in children {
+                    self.draw(child, content, opacity * group_opacity, used)?;
+                }
+                content.restore_state();
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits text into runs of characters drawn with the same font, encoded, with their
+    /// widths in em. Characters no font has are drawn with the Excalidraw font's notdef.
+    fn spans(&self, font_id: i64, text: &str) -> Vec<(&EmbeddedFont, Vec<u8>, f64)> {
+        let primary = &self.fonts[&font_id];
+        let mut runs: Vec<(&EmbeddedFont, String)> = Vec::new();
+        for c in text.chars() {
+            let font = match &self.fallback {
+                Some(fallback) if !primary.covers(c) && fallback.covers(c) => fallback,
+                _ => primary,
+            };
+            match runs.last_mut() {
+                Some((last, run)) if std::ptr::eq(*last, font) => run.push(c),
+                _ => runs.push((font, c.to_string())),
+            }
+        }
+        runs.into_iter()
+            .map(|(font, run)| {
+                let (bytes, width) = font.encode(&run);
+                (font, bytes, width)
+            })
+            .collect()
+    }
+
+    /// Selects a graphics state with the given fill and stroke alpha
+    fn set_alpha(&mut self, content: &mut Content, fill: f32, stroke: f32, used: &mut PageResources) {
+        if fill >= 0.999 && stroke >= 0.999 {
+            return;
+        }
+        let key = (
+            (fill.clamp(0.0, 1.0) * 1000.0).round() as u16,
+            (stroke.clamp(0.0, 1.0) * 1000.0).round() as u16,
+        );
+        let index = match self.states.get(&key) {
+            Some(index) => *index,
+            None => {
+                let id = self.alloc();
+                self.pdf
+                    .ext_graphics(id)
+                    .non_stroking_alpha(key.0 as f32 / 1000.0)
+                    .stroking_alpha(key.1 as f32 / 1000.0);
+                self.state_refs.push(id);
+                self.states.insert(key, self.state_refs.len() - 1);
+                self.state_refs.len() - 1
+            }
+        };
+        let name = format!("Gs{}", index);
+        content.set_parameters(Name(name.as_bytes()));
+        used.states.insert(index);
+    }
+
+    /// Writes an image XObject once per distinct image and returns its index
+    fn image(&mut self, mime_type: &str, data: &[u8]) -> Result<usize, String> {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        let key = Hasher::finish(&hasher);
+        if let Some(index) = self.images.get(&key) {
+            return Ok(*index);
+        }
+
+        let decoded = if mime_type == "image/svg+xml" {
+            let svg = std::str::from_utf8(data).map_err(|e| format!("Invalid SVG image: {}", e))?;
+            let png = super::png::svg_to_png(svg, 2.0, false, self.font_dirs)?;
+            image::load_from_memory(&png)
+        } else {
+            image::load_from_memory(data)
+        }
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+        let (width, height) = (decoded.width() as i32, decoded.height() as i32);
+        let id = self.alloc();
+
+        let passthrough_jpeg = mime_type == "image/jpeg"
+            && matches!(decoded.color(), image::ColorType::Rgb8 | image::ColorType::L8);
+        if passthrough_jpeg {
+            // JPEG data can be embedded as is
+            let mut xobject = self.pdf.image_xobject(id, data);
+            xobject.filter(Filter::DctDecode);
+            xobject
+                .width(width)
+                .height(height)
+                .bits_per_component(8);
+            if decoded.color() == image::ColorType::L8 {
+                xobject.color_space().device_gray();
+            } else {
+                xobject.color_space().device_rgb();
+            }
+        } else {
+            let pixels = decoded.to_rgba8();
+            let rgb: Vec<u8> = pixels.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+            let alpha: Vec<u8> = pixels.pixels().map(|p| p[3]).collect();
+            let mask_id = alpha.iter().any(|a| *a < 255).then(|| self.alloc());
+
+            let rgb = deflate(&rgb);
+            let mut xobject = self.pdf.image_xobject(id, &rgb);
+            xobject.filter(Filter::FlateDecode);
+            xobject
+                .width(width)
+                .height(height)
+                .bits_per_component(8);
+            xobject.color_space().device_rgb();
+            if let Some(mask_id) = mask_id {
+                xobject.s_mask(mask_id);
+            }
+            xobject.finish();
+
+            if let Some(mask_id) = mask_id {
+                let alpha = deflate(&alpha);
+                let mut mask = self.pdf.image_xobject(mask_id, &alpha);
+                mask.filter(Filter::FlateDecode);
+                mask.width(width).height(height).bits_per_component(8);
+                mask.color_space().device_gray();
+            }
+        }
+
+        self.image_refs.push(id);
+        self.images.insert(key, self.image_refs.len() - 1);
+        Ok(self.image_refs.len() - 1)
+    }
+
+    /// Embeds the closest installed OpenType font, or falls back to Helvetica
+    fn embed_font(
+        &mut self,
+        font: &FontInfo,
+        chars: &BTreeSet<char>,
+        db: &fontdb::Database,
+        name: String,
+    ) -> EmbeddedFont {
+        let families = font_families(font);
+        let face_id = db.query(&fontdb::Query {
+            families: &families,
+            ..fontdb::Query::default()
+        });
+        if let Some((reference, kind)) =
+            face_id.and_then(|id| self.embed_face(id, chars, db, font.family))
+        {
+            return EmbeddedFont {
+                name,
+                reference,
+                kind,
+            };
+        }
+
+        self.warnings
+            .push(format!("No TrueType font found for {}, using Helvetica", font.family));
+        let reference = self.alloc();
+        self.pdf
+            .type1_font(reference)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        EmbeddedFont {
+            name,
+            reference,
+            kind: FontKind::Helvetica,
+        }
+    }
+
+    /// Embeds the installed font covering most of `missing`, preferring common CJK fonts
+    fn embed_fallback(&mut self, missing: &BTreeSet<char>, db: &fontdb::Database) -> Option<EmbeddedFont> {
+        let coverage = |id: fontdb::ID| {
+            db.with_face_data(id, |data, index| {
+                let face = ttf_parser::Face::parse(data, index).ok()?;
+                let outlines = face.tables().glyf.is_some() || face.tables().cff.is_some();
+                outlines.then(|| missing.iter().filter(|c| face.glyph_index(**c).is_some()).count())
+            })
+            .flatten()
+            .unwrap_or(0)
+        };
+
+        let preferred = FALLBACK_FAMILIES.iter().find_map(|family| {
+            let id = db.query(&fontdb::Query {
+                families: &[fontdb::Family::Name(family)],
+                ..fontdb::Query::default()
+            })?;
+            (coverage(id) == missing.len()).then_some(id)
+        });
+        let face_id = preferred.or_else(|| {
+            db.faces()
+                .map(|face| (coverage(face.id), face.id))
+                .filter(|(count, _)| *count > 0)
+                .max_by_key(|(count, _)| *count)
+                .map(|(_, id)| id)
+        });
+        let Some(face_id) = face_id else {
+            self.warnings.push(format!(
+                "No installed font has {} of the characters in this drawing",
+                missing.len()
+            ));
+            return None;
+        };
+
+        let (reference, kind) = self.embed_face(face_id, missing, db, "Fallback")?;
+        Some(EmbeddedFont {
+            name: FALLBACK_FONT_NAME.to_string(),
+            reference,
+            kind,
+        })
+    }
+
+    /// Embeds a subset of a TrueType or CFF-flavored OpenType face
+    fn embed_face(
+        &mut self,
+        face_id: fontdb::ID,
+        chars: &BTreeSet<char>,
+        db: &fontdb::Database,
+        family: &str,
+    ) -> Option<(Ref, FontKind)> {
+        let (data, index) = db.with_face_data(face_id, |data, index| (data.to_vec(), index))?;
+        let face = ttf_parser::Face::parse(&data, index).ok()?;
+        let cff = face.tables().cff.is_some();
+        if !cff && face.tables().glyf.is_none() {
+            return None;
+        }
+
+        let units_per_em = face.units_per_em() as f64;
+        let advance = |glyph: ttf_parser::GlyphId| {
+            face.glyph_hor_advance(glyph).unwrap_or(0) as f64 / units_per_em
+        };
+        let glyphs: HashMap<char, (u16, f64)> = chars
+            .iter()
+            .filter_map(|c| {
+                let glyph = face.glyph_index(*c)?;
+                Some((*c, (glyph.0, advance(glyph))))
+            })
+            .collect();
+        let missing_advance = advance(ttf_parser::GlyphId(0));
+
+        let mut glyph_ids: Vec<u16> = glyphs.values().map(|(glyph, _)| *glyph).collect();
+        glyph_ids.push(0);
+        glyph_ids.sort_unstable();
+        glyph_ids.dedup();
+        let subset = subsetter::subset(&data, index, subsetter::Profile::pdf(&glyph_ids)).ok()?;
+
+        let postscript_name = face
+            .names()
+            .into_iter()
+            .filter(|n| n.name_id == ttf_parser::name_id::POST_SCRIPT_NAME)
+            .find_map(|n| n.to_string())
+            .unwrap_or_else(|| family.replace(' ', ""));
+        let base_font = format!("{}+{}", subset_tag(&glyph_ids), postscript_name);
+        let base_font = Name(base_font.as_bytes());
+
+        let type0_id = self.alloc();
+        let cid_id = self.alloc();
+        let descriptor_id = self.alloc();
+        let file_id = self.alloc();
+        let cmap_id = self.alloc();
+
+        self.pdf
+            .type0_font(type0_id)
+            .base_font(base_font)
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_id)
+            .to_unicode(cmap_id);
+
+        let to_font_units = |value: f64| (value * 1000.0 / units_per_em) as f32;
+        let mut cid_font = self.pdf.cid_font(cid_id);
+        cid_font
+            .subtype(if cff { CidFontType::Type0 } else { CidFontType::Type2 })
+            .base_font(base_font)
+            .system_info(IDENTITY_SYSTEM_INFO)
+            .font_descriptor(descriptor_id)
+            .default_width(to_font_units(missing_advance * units_per_em));
+        if !cff {
+            cid_font.cid_to_gid_map_predefined(Name(b"Identity"));
+        }
+        let mut widths = cid_font.widths();
+        for glyph in &glyph_ids {
+            let width = advance(ttf_parser::GlyphId(*glyph)) * 1000.0;
+            widths.consecutive(*glyph, [width as f32]);
+        }
+        widths.finish();
+        cid_font.finish();
+
+        let bbox = face.global_bounding_box();
+        let ascender = face.ascender() as f64;
+        let mut descriptor = self.pdf.font_descriptor(descriptor_id);
+        descriptor
+            .name(base_font)
+            .flags(FontFlags::NON_SYMBOLIC)
+            .bbox(Rect::new(
+                to_font_units(bbox.x_min as f64),
+                to_font_units(bbox.y_min as f64),
+                to_font_units(bbox.x_max as f64),
+                to_font_units(bbox.y_max as f64),
+            ))
+            .italic_angle(0.0)
+            .ascent(to_font_units(ascender))
+            .descent(to_font_units(face.descender() as f64))
+            .cap_height(to_font_units(face.capital_height().map_or(ascender, |h| h as f64)))
+            .stem_v(80.0);
+        if cff {
+            descriptor.font_file3(file_id);
+        } else {
+            descriptor.font_file2(file_id);
+        }
+        descriptor.finish();
+
+        let compressed = deflate(&subset);
+        let mut stream = self.pdf.stream(file_id, &compressed);
+        stream.filter(Filter::FlateDecode);
+        // The subset keeps its OpenType wrapper, which PDF accepts for CFF outlines
+        if cff {
+            stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+        } else {
+            stream.pair(Name(b"Length1"), subset.len() as i32);
+        }
+        stream.finish();
+
+        let mut cmap = UnicodeCmap::new(Name(b"Custom"), IDENTITY_SYSTEM_INFO);
+        for (c, (glyph, _)) in &glyphs {
+            cmap.pair(*glyph, *c);
+        }
+        let cmap = cmap.finish();
+        self.pdf.cmap(cmap_id, &cmap);
+
+        Some((
+            type0_id,
+            FontKind::Cid {
+                glyphs,
+                missing_advance,
+            },
+        ))
+    }
+}
+
+/// Font families to try for an Excalidraw font, most specific first
+fn font_families(font: &FontInfo) -> Vec<fontdb::Family<'static>> {
+    let mut families = vec![fontdb::Family::Name(font.family)];
+    for name in font.fallback.split(',').map(str::trim) {
+        families.push(match name {
+            "sans-serif" => fontdb::Family::SansSerif,
+            "serif" => fontdb::Family::Serif,
+            "cursive" => fontdb::Family::Cursive,
+            "fantasy" => fontdb::Family::Fantasy,
+            "monospace" => fontdb::Family::Monospace,
+            // Emoji fonts have no regular letters
+            name if name.contains("Emoji") => continue,
+            name => fontdb::Family::Name(name),
+        });
+    }
+    families.push(fontdb::Family::SansSerif);
+    families
+}
+
+fn collect_chars(primitives: &[Primitive], chars: &mut BTreeMap<i64, BTreeSet<char>>) {
+    for primitive in primitives {
+        match primitive {
+            Primitive::Text { runs, font, .. } => {
+                let set = chars.entry(font.id).or_default();
+                set.extend(runs.iter().flat_map(|run| run.text.chars()));
+            }
+            Primitive::Group { children, .. } => collect_chars(children, chars),
+            _ => {}
+        }
+    }
+}
+
+fn write_path(content: &mut Content, path: &PathData) {
+    for segment in &path.segments {
+        match *segment {
+            Segment::MoveTo((x, y)) => {
+                content.move_to(x as f32, y as f32);
+            }
+            Segment::LineTo((x, y)) => {
+                content.line_to(x as f32, y as f32);
+            }
+            Segment::CubicTo((x1, y1), (x2, y2), (x, y)) => {
+                content.cubic_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32);
+            }
+            Segment::Close => {
+                content.close_path();
+            }
+        }
+    }
+}
+
+/// Color components for PDF operators, unknown colors fall back to black
+fn rgba(color: &str) -> (f32, f32, f32, f32) {
+    let (r, g, b, a) = parse_color(color).unwrap_or((0.0, 0.0, 0.0, 1.0));
+    (r as f32, g as f32, b as f32, a as f32)
+}
+
+/// Six uppercase letters identifying a font subset, as the PDF spec requires
+fn subset_tag(glyphs: &[u16]) -> String {
+    let mut hasher = DefaultHasher::new();
+    glyphs.hash(&mut hasher);
+    let mut value = Hasher::finish(&hasher);
+    (0..6)
+        .map(|_| {
+            let c = (b'A' + (value % 26) as u8) as char;
+            value /= 26;
+            c
+        })
+        .collect()
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec cannot fail
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
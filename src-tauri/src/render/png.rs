@@ -0,0 +1,153 @@
+use crate::models;
+use resvg::tiny_skia;
+use resvg::usvg;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Largest bitmap side we are willing to allocate
+const MAX_DIMENSION: f64 = 16_384.0;
+
+/// Families tried, in order, for generic CSS families like `sans-serif` and `cursive`
+const FALLBACK_FAMILIES: [&str; 7] = [
+    "Helvetica",
+    "Arial",
+    "Liberation Sans",
+    "DejaVu Sans",
+    "Noto Sans",
+    "Segoe UI",
+    "Roboto",
+];
+
+/// Font files found in the font folders, with their modification times
+type FontFiles = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// A loaded font database and the font files that could not be loaded into it
+#[derive(Clone)]
+pub struct FontDatabase {
+    pub db: Arc<usvg::fontdb::Database>,
+    pub failed: Vec<models::SkippedFile>,
+}
+
+impl FontDatabase {
+    /// The failed font files as messages for an export report
+    pub fn warnings(&self) -> Vec<String> {
+        self.failed
+            .iter()
+            .map(|font| format!("Failed to load font {}: {}", font.path, font.error))
+            .collect()
+    }
+}
+
+static FONT_DB: Mutex<Option<(FontFiles, FontDatabase)>> = Mutex::new(None);
+
+fn font_files(font_dirs: &[PathBuf]) -> FontFiles {
+    let mut files: FontFiles = font_dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .filter(|entry| {
+            entry.path().extension().and_then(|e| e.to_str()).is_some_and(|ext| {
+                super::OUTLINE_FONT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+            })
+        })
+        .map(|entry| (entry.path(), entry.metadata().and_then(|m| m.modified()).ok()))
+        .collect();
+    files.sort();
+    files
+}
+
+/// System fonts plus the TrueType and OpenType fonts in `font_dirs`, loaded again only when
+/// those fonts change. WOFF and WOFF2 files are only used by SVG `@font-face` rules.
+pub fn font_database(font_dirs: &[PathBuf]) -> FontDatabase {
+    let files = font_files(font_dirs);
+    let mut cache = FONT_DB.lock().unwrap();
+    if let Some((loaded, db)) = cache.as_ref()
+        && *loaded == files
+    {
+        return db.clone();
+    }
+
+    let mut failed = Vec::new();
+
+    let mut db = usvg::fontdb::Database::new();
+    db.load_system_fonts();
+    for (path, _) in &files {
+        if let Err(e) = db.load_font_file(path) {
+            failed.push(models::SkippedFile {
+                path: path.to_string_lossy().to_string(),
+                error: e.to_string(),
+            });
+        }
+    }
+
+    // Without this, text in a family that isn't installed is dropped entirely
+    let families: Vec<String> = db
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(name, _)| name.clone()))
+        .collect();
+    let fallback = FALLBACK_FAMILIES
+        .iter()
+        .find(|f| families.iter().any(|name| name == *f))
+        .map(|f| f.to_string())
+        .or_else(|| families.first().cloned());
+
+    if let Some(family) = fallback {
+        db.set_sans_serif_family(family.clone());
+        db.set_serif_family(family.clone());
+        db.set_cursive_family(family.clone());
+        db.set_fantasy_family(family);
+    }
+
+    let db = FontDatabase { db: Arc::new(db), failed };
+    *cache = Some((files, db.clone()));
+    db
+}
+
+/// Rasterizes an SVG document on the CPU. With `fit`, a scale that would exceed the largest
+/// bitmap is lowered to fit it instead of failing.
+pub fn svg_to_png(svg: &str, scale: f64, fit: bool, font_dirs: &[PathBuf]) -> Result<Vec<u8>, String> {
+    if !(scale > 0.0 && scale.is_finite()) {
+        return Err("Scale must be a positive number".to_string());
+    }
+
+    let options = usvg::Options {
+        fontdb: font_database(font_dirs).db,
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options)
+        .map_err(|e| format!("Failed to parse SVG: {}", e))?;
+
+    let size = tree.size();
+    let largest = size.width().max(size.height()) as f64;
+    let scale = if fit && largest * scale > MAX_DIMENSION {
+        MAX_DIMENSION / largest
+    } else {
+        scale
+    };
+    let mut width = (size.width() as f64 * scale).ceil();
+    let mut height = (size.height() as f64 * scale).ceil();
+    if fit {
+        // Rounding must not push a fitted side past the limit
+        width = width.min(MAX_DIMENSION);
+        height = height.min(MAX_DIMENSION);
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!(
+            "Image would be {}x{} pixels, the maximum is {}",
+            width, height, MAX_DIMENSION
+        ));
+    }
+
+    let mut pixmap = tiny_skia::Pixmap::new(width.max(1.0) as u32, height.max(1.0) as u32)
+        .ok_or("Failed to allocate image")?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale as f32, scale as f32),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .encode_png()
+        .map_err(|e| format!("Failed to encode PNG: {}", e))
+}
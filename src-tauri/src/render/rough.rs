@@ -0,0 +1,143 @@
+//! Hand-drawn look, following the approach of rough.js: every stroke is drawn
+//! twice as slightly bowed, randomly offset curves.
+
+use super::{PathData, Point, Segment};
+
+const MAX_RANDOMNESS_OFFSET: f64 = 2.0;
+const BOWING: f64 = 1.0;
+
+/// Deterministic generator matching rough.js, so an element's `seed` gives a stable shape
+pub struct Rng {
+    seed: i64,
+}
+
+impl Rng {
+    pub fn new(seed: i64) -> Self {
+        Self {
+            seed: seed.rem_euclid(2_147_483_647).max(1),
+        }
+    }
+
+    pub fn next(&mut self) -> f64 {
+        self.seed = (48_271 * self.seed) % 2_147_483_647;
+        self.seed as f64 / 2_147_483_647.0
+    }
+}
+
+/// Draws every segment of the path twice with hand-drawn jitter
+pub fn roughen(path: &PathData, roughness: f64, rng: &mut Rng) -> PathData {
+    let mut out = roughen_pass(path, roughness, rng, false);
+    out.extend(roughen_pass(path, roughness, rng, true));
+    out
+}
+
+/// Single jittered pass, used for hachure lines where a double stroke looks too heavy
+pub fn roughen_single(path: &PathData, roughness: f64, rng: &mut Rng) -> PathData {
+    roughen_pass(path, roughness, rng, true)
+}
+
+fn roughen_pass(path: &PathData, roughness: f64, rng: &mut Rng, overlay: bool) -> PathData {
+    let mut out = PathData::default();
+    let mut start: Point = (0.0, 0.0);
+    let mut current: Point = (0.0, 0.0);
+
+    for segment in &path.segments {
+        match *segment {
+            Segment::MoveTo(p) => {
+                start = p;
+                current = p;
+            }
+            Segment::LineTo(p) => {
+                rough_line(&mut out, current, p, roughness, rng, overlay);
+                current = p;
+            }
+            Segment::CubicTo(c1, c2, p) => {
+                rough_curve(&mut out, current, c1, c2, p, roughness, rng, overlay);
+                current = p;
+            }
+            Segment::Close => {
+                rough_line(&mut out, current, start, roughness, rng, overlay);
+                current = start;
+            }
+        }
+    }
+
+    out
+}
+
+fn offset(range: f64, roughness: f64, gain: f64, rng: &mut Rng) -> f64 {
+    roughness * gain * (rng.next() * 2.0 * range - range)
+}
+
+fn rough_line(
+    out: &mut PathData,
+    (x1, y1): Point,
+    (x2, y2): Point,
+    roughness: f64,
+    rng: &mut Rng,
+    overlay: bool,
+) {
+    let length_sq = (x1 - x2).powi(2) + (y1 - y2).powi(2);
+    let length = length_sq.sqrt();
+
+    // Long lines get proportionally less jitter
+    let gain = if length < 200.0 {
+        1.0
+    } else if length > 500.0 {
+        0.4
+    } else {
+        -0.001_666_8 * length + 1.233_334
+    };
+
+    let mut max_offset = MAX_RANDOMNESS_OFFSET;
+    if max_offset * max_offset * 100.0 > length_sq {
+        max_offset = length / 10.0;
+    }
+    let range = if overlay { max_offset / 2.0 } else { max_offset };
+
+    let diverge = 0.2 + rng.next() * 0.2;
+    let mid_x = offset(BOWING * MAX_RANDOMNESS_OFFSET * (y2 - y1) / 200.0, roughness, gain, rng);
+    let mid_y = offset(BOWING * MAX_RANDOMNESS_OFFSET * (x1 - x2) / 200.0, roughness, gain, rng);
+
+    let mut jitter = || offset(range, roughness, gain, rng);
+    out.move_to((x1 + jitter(), y1 + jitter()));
+    out.cubic_to(
+        (
+            mid_x + x1 + (x2 - x1) * diverge + jitter(),
+            mid_y + y1 + (y2 - y1) * diverge + jitter(),
+        ),
+        (
+            mid_x + x1 + 2.0 * (x2 - x1) * diverge + jitter(),
+            mid_y + y1 + 2.0 * (y2 - y1) * diverge + jitter(),
+        ),
+        (x2 + jitter(), y2 + jitter()),
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rough_curve(
+    out: &mut PathData,
+    from: Point,
+    c1: Point,
+    c2: Point,
+    to: Point,
+    roughness: f64,
+    rng: &mut Rng,
+    overlay: bool,
+) {
+    let length = (to.0 - from.0).hypot(to.1 - from.1)
+        + (c1.0 - from.0).hypot(c1.1 - from.1)
+        + (c2.0 - to.0).hypot(c2.1 - to.1);
+    let max_offset = MAX_RANDOMNESS_OFFSET.min(length / 10.0);
+    let range = if overlay { max_offset / 2.0 } else { max_offset };
+
+    let mut jitter = |(x, y): Point| {
+        (
+            x + offset(range, roughness, 1.0, rng),
+            y + offset(range, roughness, 1.0, rng),
+        )
+    };
+    out.move_to(jitter(from));
+    let (c1, c2, to) = (jitter(c1), jitter(c2), jitter(to));
+    out.cubic_to(c1, c2, to);
+}
@@ -0,0 +1,244 @@
+use super::{DisplayList, PathData, Primitive, Segment, Stroke, TextAnchor, Transform};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use std::fmt::Write;
+
+/// Font file embedded into the SVG through `@font-face`
+pub struct FontFace {
+    pub family: String,
+    pub data_url: String,
+}
+
+/// Serializes a display list into a standalone SVG document
+pub fn to_svg(list: &DisplayList, fonts: &[FontFace]) -> String {
+    let mut out = String::new();
+    let (width, height) = (num(list.width), num(list.height));
+
+    let _ = write!(
+        out,
+        r#"<svg version="1.1" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="{w}" height="{h}">"#,
+        w = width,
+        h = height
+    );
+    out.push_str("\n<!-- svg-source:excalidraw -->\n");
+
+    if !fonts.is_empty() {
+        out.push_str("<defs><style class=\"style-fonts\">\n");
+        for font in fonts {
+            let _ = writeln!(
+                out,
+                "@font-face {{ font-family: \"{}\"; src: url({}); }}",
+                escape(&font.family),
+                font.data_url
+            );
+        }
+        out.push_str("</style></defs>\n");
+    }
+
+    if let Some(background) = &list.background {
+        let _ = writeln!(
+            out,
+            r#"<rect x="0" y="0" width="{}" height="{}" fill="{}"/>"#,
+            width,
+            height,
+            escape(background)
+        );
+    }
+
+    let mut clip_counter = 0;
+    for primitive in &list.primitives {
+        write_primitive(primitive, &mut out, &mut clip_counter);
+    }
+
+    out.push_str("</svg>\n");
+    out
+}
+
+fn write_primitive(primitive: &Primitive, out: &mut String, clip_counter: &mut usize) {
+    match primitive {
+        Primitive::Path { path, fill, stroke } => {
+            if path.segments.is_empty() {
+                return;
+            }
+            let _ = write!(out, r#"<path d="{}""#, path_data(path));
+            match fill {
+                Some(fill) => {
+                    let _ = write!(out, r#" fill="{}""#, escape(fill));
+                }
+                None => out.push_str(r#" fill="none""#),
+            }
+            if let Some(stroke) = stroke {
+                write_stroke(stroke, out);
+            }
+            out.push_str("/>\n");
+        }
+        Primitive::Text {
+            runs,
+            font,
+            font_size,
+            anchor,
+            color,
+        } => {
+            let anchor = match anchor {
+                TextAnchor::Start => "start",
+                TextAnchor::Middle => "middle",
+                TextAnchor::End => "end",
+            };
+            for run in runs {
+                let _ = writeln!(
+                    out,
+                    r#"<text x="{}" y="{}" font-family="{}, {}" font-size="{}px" fill="{}" text-anchor="{}" style="white-space: pre;" direction="ltr" dominant-baseline="alphabetic">{}</text>"#,
+                    num(run.x),
+                    num(run.y),
+                    escape(font.family),
+                    escape(font.fallback),
+                    num(*font_size),
+                    escape(color),
+                    anchor,
+                    escape(&run.text)
+                );
+            }
+        }
+        Primitive::Image {
+            element_id: _,
+            x,
+            y,
+            width,
+            height,
+            mime_type,
+            data,
+        } => {
+            let _ = writeln!(
+                out,
+                r#"<image x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="none" href="data:{};base64,{}"/>"#,
+                num(*x),
+                num(*y),
+                num(*width),
+                num(*height),
+                escape(mime_type),
+                BASE64.encode(data)
+            );
+        }
+        Primitive::Group {
+            transform,
+            clip,
+            opacity,
+            children,
+        } => {
+            let mut clip_id = None;
+            if let Some(clip) = clip {
+                *clip_counter += 1;
+                let id = format!("clip-{}", clip_counter);
+                // A clip path is resolved in the user space of the element that references
+                // it, so it has to live inside the transformed group
+                clip_id = Some((id, clip));
+            }
+
+            out.push_str("<g");
+            if let Some(transform) = transform.filter(|t| !t.is_identity()) {
+                let _ = write!(out, r#" transform="{}""#, matrix(&transform));
+            }
+            if *opacity < 1.0 {
+                let _ = write!(out, r#" opacity="{}""#, num(*opacity));
+            }
+            out.push_str(">\n");
+
+            if let Some((id, clip)) = &clip_id {
+                let _ = writeln!(
+                    out,
+                    r#"<clipPath id="{}"><path d="{}"/></clipPath>"#,
+                    id,
+                    path_data(clip)
+                );
+                let _ = writeln!(out, r#"<g clip-path="url(#{})">"#, id);
+            }
+
+            for child in children {
+                write_primitive(child, out, clip_counter);
+            }
+
+            if clip_id.is_some() {
+                out.push_str("</g>\n");
+            }
+            out.push_str("</g>\n");
+        }
+    }
+}
+
+fn write_stroke(stroke: &Stroke, out: &mut String) {
+    let _ = write!(
+        out,
+        r#" stroke="{}" stroke-width="{}""#,
+        escape(&stroke.color),
+        num(stroke.width)
+    );
+    if stroke.round_cap {
+        out.push_str(r#" stroke-linecap="round" stroke-linejoin="round""#);
+    }
+    if let Some(dash) = &stroke.dash {
+        let values: Vec<String> = dash.iter().map(|d| num(*d)).collect();
+        let _ = write!(out, r#" stroke-dasharray="{}""#, values.join(" "));
+    }
+}
+
+pub fn path_data(path: &PathData) -> String {
+    let mut d = String::new();
+    for segment in &path.segments {
+        if !d.is_empty() {
+            d.push(' ');
+        }
+        match segment {
+            Segment::MoveTo((x, y)) => {
+                let _ = write!(d, "M{} {}", num(*x), num(*y));
+            }
+            Segment::LineTo((x, y)) => {
+                let _ = write!(d, "L{} {}", num(*x), num(*y));
+            }
+            Segment::CubicTo((x1, y1), (x2, y2), (x, y)) => {
+                let _ = write!(
+                    d,
+                    "C{} {}, {} {}, {} {}",
+                    num(*x1),
+                    num(*y1),
+                    num(*x2),
+                    num(*y2),
+                    num(*x),
+                    num(*y)
+                );
+            }
+            Segment::Close => d.push('Z'),
+        }
+    }
+    d
+}
+
+fn matrix(transform: &Transform) -> String {
+    // Rotation terms need more precision than coordinates
+    let values: Vec<String> = transform
+        .0
+        .iter()
+        .map(|v| {
+            let s = format!("{:.6}", v);
+            let s = s.trim_end_matches('0').trim_end_matches('.');
+            if s == "-0" { "0".to_string() } else { s.to_string() }
+        })
+        .collect();
+    format!("matrix({})", values.join(" "))
+}
+
+/// Formats a coordinate with at most two decimals
+fn num(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded == 0.0 {
+        return "0".to_string();
+    }
+    let s = format!("{:.2}", rounded);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
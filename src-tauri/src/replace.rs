@@ -0,0 +1,230 @@
+use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use regex::NoExpand;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// App data folder holding one undo record per applied batch
+const UNDO_DIR: &str = "undo";
+
+fn undo_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(UNDO_DIR))
+        .map_err(|_| "App data directory is not available".to_string())
+}
+
+/// Drawing changed by a replacement, with its content before and after
+struct ChangedFile {
+    path: PathBuf,
+    before: Vec<u8>,
+    scene: Value,
+}
+
+/// Applies the replacement to every text element of `scene` and returns the changes.
+/// Labels are rewrapped in their container and every touched element gets a new version.
+fn replace_in_scene(
+    scene: &mut Value,
+    path: &Path,
+    replace: &dyn Fn(&str) -> String,
+) -> Vec<models::ReplaceChange> {
+    let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) else {
+        return Vec::new();
+    };
+    let mut changes = Vec::new();
+    for i in 0..elements.len() {
+        let element = &elements[i];
+        if render::str_field(element, "type") != Some("text")
+            || render::bool_field(element, "isDeleted").unwrap_or(false)
+        {
+            continue;
+        }
+        let Some(before) = render::str_field(element, "originalText").or(render::str_field(element, "text")) else {
+            continue;
+        };
+        let after = replace(before);
+        if after == before {
+            continue;
+        }
+        let change = models::ReplaceChange {
+            path: path.to_string_lossy().to_string(),
+            element_id: render::str_field(element, "id").unwrap_or_default().to_string(),
+            container_id: render::str_field(element, "containerId").map(str::to_string),
+            before: before.to_string(),
+            after: after.clone(),
+        };
+
+        let container = change.container_id.as_deref().and_then(|id| {
+            elements.iter().position(|e| {
+                render::str_field(e, "id") == Some(id) && !render::bool_field(e, "isDeleted").unwrap_or(false)
+            })
+        });
+        let mut container_element = container.map(|c| elements[c].clone());
+        let text = &mut elements[i];
+        text["originalText"] = json!(after);
+        elements::refit_text(text, container_element.as_mut());
+        elements::bump_version(text);
+        if let (Some(c), Some(container_element)) = (container, container_element) {
+            elements[c] = container_element;
+        }
+        changes.push(change);
+    }
+    changes
+}
+
+/// Puts back the files of an interrupted batch. Returns `error` with the files that could
+/// not be put back, if any.
+fn restore(files: &[ChangedFile], error: String) -> String {
+    let failed: Vec<String> = files
+        .iter()
+        .filter_map(|file| {
+            let result = formats::write_atomic(&file.path, &file.before);
+            result.err().map(|e| format!("{} ({})", file.path.display(), e))
+        })
+        .collect();
+    if failed.is_empty() {
+        error
+    } else {
+        format!("{}; failed to restore {}", error, failed.join(", "))
+    }
+}
+
+/// Finds and replaces text across every drawing in a folder.
+///
+/// Without `apply` this is a preview listing each affected text element. Applied, every changed
+/// drawing is saved atomically and the batch gets a single undo record, whose id is returned
+/// for `undo_replace`.
+#[tauri::command]
+pub async fn replace_in_workspace(
+    app: AppHandle,
+    directory: String,
+    query: String,
+    replacement: String,
+    options: Option<models::SearchOptions>,
+    apply: Option<bool>,
+) -> Result<models::ReplaceReport, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let options = options.unwrap_or_default();
+    let matcher = search::build_matcher(&query, &options)?;
+    if matcher.is_match("") {
+        return Err("Search pattern matches empty text".to_string());
+    }
+    // Capture groups like `$1` only mean something in regex mode
+    let replace = |text: &str| -> String {
+        if options.regex {
+            matcher.replace_all(text, replacement.as_str()).into_owned()
+        } else {
+            matcher.replace_all(text, NoExpand(&replacement)).into_owned()
+        }
+    };
+
+    let mut changes = Vec::new();
+    let mut changed_files = Vec::new();
+    let mut skipped = Vec::new();
+    for path in search::workspace_drawings(&root)? {
+        let mut scene = match search::read_scene(&path) {
+            Ok(scene) => scene,
+            Err(error) => {
+                skipped.push(models::SkippedFile { path: path.to_string_lossy().to_string(), error });
+                continue;
+            }
+        };
+        let file_changes = replace_in_scene(&mut scene, &path, &replace);
+        if file_changes.is_empty() {
+            continue;
+        }
+        changes.extend(file_changes);
+        let before = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        changed_files.push(ChangedFile { path, before, scene });
+    }
+
+    let files = changed_files.len();
+    if !apply.unwrap_or(false) || changed_files.is_empty() {
+        return Ok(models::ReplaceReport { changes, files, applied: false, undo_id: None, skipped });
+    }
+
+    // Either every drawing is replaced or, after a failed write, none is
+    let fonts = export::font_dirs(&app);
+    let mut record_files = Vec::new();
+    for (i, file) in changed_files.iter().enumerate() {
+        let written = serde_json::to_string_pretty(&file.scene)
+            .map_err(|e| format!("Failed to serialize content: {}", e))
+            .and_then(|content| formats::write_scene_content(&file.path, &content, &fonts))
+            .and_then(|()| fs::read(&file.path).map_err(|e| e.to_string()));
+        match written {
+            Ok(after) => record_files.push(json!({
+                "path": file.path.to_string_lossy(),
+                "before": BASE64.encode(&file.before),
+                "after": assets::hash_bytes(&after),
+            })),
+            Err(e) => {
+                let error = format!("Failed to write {}: {}", file.path.display(), e);
+                return Err(restore(&changed_files[..i], error));
+            }
+        }
+    }
+
+    let undo_id = format!("{}-{}", chrono::Utc::now().format("%Y%m%d%H%M%S"), elements::new_id_prefix());
+    let record = json!({
+        "id": undo_id,
+        "created": chrono::Utc::now().to_rfc3339(),
+        "query": query,
+        "replacement": replacement,
+        "files": record_files,
+    });
+    let saved = undo_dir(&app).and_then(|dir| {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create undo folder: {}", e))?;
+        let record_path = security::safe_path_join(&dir, &format!("{}.json", undo_id))?;
+        formats::write_atomic(&record_path, record.to_string().as_bytes())
+            .map_err(|e| format!("Failed to write undo record: {}", e))
+    });
+    // A batch that could not be undone is not applied
+    if let Err(e) = saved {
+        return Err(restore(&changed_files, e));
+    }
+
+    Ok(models::ReplaceReport { changes, files, applied: true, undo_id: Some(undo_id), skipped })
+}
+
+/// Reverts a replacement batch. Drawings edited since the replacement are left alone and
+/// reported as conflicts.
+#[tauri::command]
+pub async fn undo_replace(app: AppHandle, undo_id: String) -> Result<models::ReplaceUndoReport, String> {
+    let record_path = security::safe_path_join(&undo_dir(&app)?, &format!("{}.json", undo_id))?;
+    let content = fs::read_to_string(&record_path).map_err(|_| format!("Undo record not found: {}", undo_id))?;
+    let mut record: Value = serde_json::from_str(&content).map_err(|e| format!("Invalid undo record: {}", e))?;
+
+    let mut restored = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut remaining = Vec::new();
+    for file in record.get("files").and_then(|f| f.as_array()).into_iter().flatten() {
+        let (Some(path), Some(before), Some(after)) = (
+            render::str_field(file, "path"),
+            render::str_field(file, "before"),
+            render::str_field(file, "after"),
+        ) else {
+            continue;
+        };
+        let unchanged = fs::read(path).is_ok_and(|bytes| assets::hash_bytes(&bytes) == after);
+        let before = BASE64.decode(before).map_err(|e| format!("Invalid undo record: {}", e))?;
+        if unchanged && formats::write_atomic(Path::new(path), &before).is_ok() {
+            restored.push(path.to_string());
+        } else {
+            conflicts.push(path.to_string());
+            remaining.push(file.clone());
+        }
+    }
+
+    // The record holds the only copy of what was replaced, so it stays for the files not restored
+    if remaining.is_empty() {
+        fs::remove_file(&record_path).map_err(|e| format!("Failed to remove undo record: {}", e))?;
+    } else {
+        record["files"] = Value::Array(remaining);
+        formats::write_atomic(&record_path, record.to_string().as_bytes())
+            .map_err(|e| format!("Failed to update undo record: {}", e))?;
+    }
+    Ok(models::ReplaceUndoReport { restored, conflicts })
+}
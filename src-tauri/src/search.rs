@@ -0,0 +1,166 @@
+use super::*;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Results returned when the caller does not set a limit
+const DEFAULT_MAX_RESULTS: usize = 500;
+/// Characters of context kept on each side of a match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Compiles the query into the pattern used for every mode; plain queries are matched literally
+pub fn build_matcher(query: &str, options: &models::SearchOptions) -> Result<Regex, String> {
+    if query.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+    let pattern = if options.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Searchable text of an element as (field, text) pairs
+pub fn element_fields(element: &Value) -> Vec<(&'static str, &str)> {
+    let mut fields = Vec::new();
+    match render::str_field(element, "type") {
+        Some("text") => {
+            if let Some(text) = render::str_field(element, "originalText").or(render::str_field(element, "text")) {
+                fields.push(("text", text));
+            }
+        }
+        Some("frame") | Some("magicframe") => {
+            if let Some(name) = render::str_field(element, "name") {
+                fields.push(("frame", name));
+            }
+        }
+        _ => {}
+    }
+    if let Some(link) = render::str_field(element, "link") {
+        fields.push(("link", link));
+    }
+    fields
+}
+
+/// Drawings under `root`, leaving out templates, libraries and assets
+pub fn workspace_drawings(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    file_ops::collect_excalidraw_files_recursive(root, &mut files)?;
+    let mut paths: Vec<PathBuf> = files
+        .into_iter()
+        .map(|f| PathBuf::from(f.path))
+        .filter(|p| {
+            !p.strip_prefix(root)
+                .unwrap_or(p)
+                .components()
+                .any(|c| bundle::is_workspace_folder(&c.as_os_str().to_string_lossy()))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parses a drawing without inlining its images, which searching never needs
+pub fn read_scene(path: &Path) -> Result<Value, String> {
+    security::validate_excalidraw_file(path)?;
+    let content = formats::read_scene_content(path)?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid JSON: {}", e))
+}
+
+/// Whitespace-collapsed text around `start..end`, with the match offsets in characters
+pub fn snippet(text: &str, start: usize, end: usize) -> (String, usize, usize) {
+    let before: Vec<char> = text[..start].chars().collect();
+    let after: Vec<char> = text[end..].chars().collect();
+    let clean = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    let mut prefix = clean(&before[before.len().saturating_sub(SNIPPET_CONTEXT)..].iter().collect::<String>());
+    if before.len() > SNIPPET_CONTEXT {
+        prefix.insert(0, '…');
+    }
+    if before.last().is_some_and(|c| c.is_whitespace()) {
+        prefix.push(' ');
+    }
+    let mut suffix = clean(&after[..after.len().min(SNIPPET_CONTEXT)].iter().collect::<String>());
+    if after.first().is_some_and(|c| c.is_whitespace()) {
+        suffix.insert(0, ' ');
+    }
+    if after.len() > SNIPPET_CONTEXT {
+        suffix.push('…');
+    }
+
+    let matched = clean(&text[start..end]);
+    let match_start = prefix.chars().count();
+    let match_end = match_start + matched.chars().count();
+    (format!("{}{}{}", prefix, matched, suffix), match_start, match_end)
+}
+
+fn push_matches(
+    matcher: &Regex,
+    text: &str,
+    target: (&Path, Option<&str>, &str),
+    matches: &mut Vec<models::SearchMatch>,
+    limit: usize,
+) {
+    let (path, element_id, field) = target;
+    for found in matcher.find_iter(text).filter(|m| !m.is_empty()) {
+        if matches.len() >= limit {
+            return;
+        }
+        let (snippet, match_start, match_end) = snippet(text, found.start(), found.end());
+        matches.push(models::SearchMatch {
+            path: path.to_string_lossy().to_string(),
+            element_id: element_id.map(str::to_string),
+            field: field.to_string(),
+            snippet,
+            match_start,
+            match_end,
+        });
+    }
+}
+
+/// Searches text, frame names, element links and file names of every drawing in a folder.
+///
+/// Matches carry the element id (none for file names) so the frontend can open the drawing
+/// and select the element. Drawings that cannot be read are returned with the matches.
+#[tauri::command]
+pub async fn search_workspace(
+    directory: String,
+    query: String,
+    options: Option<models::SearchOptions>,
+    max_results: Option<usize>,
+) -> Result<models::SearchResults, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    let matcher = build_matcher(&query, &options.unwrap_or_default())?;
+    let limit = max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut matches = Vec::new();
+    let mut skipped = Vec::new();
+    for path in workspace_drawings(&root)? {
+        if matches.len() >= limit {
+            break;
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        push_matches(&matcher, &file_name, (&path, None, "file_name"), &mut matches, limit);
+
+        let scene = match read_scene(&path) {
+            Ok(scene) => scene,
+            Err(error) => {
+                skipped.push(models::SkippedFile {
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                });
+                continue;
+            }
+        };
+        let elements = scene.get("elements").and_then(|e| e.as_array()).into_iter().flatten();
+        for element in elements.filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)) {
+            let id = render::str_field(element, "id");
+            for (field, text) in element_fields(element) {
+                push_matches(&matcher, text, (&path, id, field), &mut matches, limit);
+            }
+        }
+    }
+
+    Ok(models::SearchResults { matches, skipped })
+}
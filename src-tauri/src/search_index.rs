@@ -0,0 +1,594 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// App data folder holding one index file per workspace
+const INDEX_DIR: &str = "search-index";
+/// Bumped whenever the stored format or the tokenizer changes, which forces a rebuild
+const INDEX_VERSION: u32 = 2;
+const STATUS_EVENT: &str = "search-index-status";
+/// BM25 term frequency saturation and length normalization
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// Score share of a term that only starts with a query word
+const PREFIX_WEIGHT: f64 = 0.5;
+/// Drawings indexed between two progress events
+const PROGRESS_EVERY: usize = 50;
+/// Watcher events arrive in bursts, so saving waits for them to settle
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_RESULTS: usize = 50;
+
+/// Scripts written without spaces between words, which are indexed as character bigrams
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}'   // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}' // CJK Extensions B and later
+    )
+}
+
+/// Lower-case words of `text`; punctuation separates words, so "payment-gateway" is two.
+/// Runs of CJK characters become overlapping bigrams, so "支付网关" is found by "网关".
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let chars: Vec<char> = word.chars().collect();
+        for run in chars.chunk_by(|a, b| is_cjk(*a) == is_cjk(*b)) {
+            if !is_cjk(run[0]) {
+                tokens.push(run.iter().collect::<String>().to_lowercase());
+            } else if run.len() == 1 {
+                tokens.push(run[0].to_string());
+            } else {
+                tokens.extend(run.windows(2).map(|pair| pair.iter().collect::<String>()));
+            }
+        }
+    }
+    tokens
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedEntry {
+    element_id: Option<String>,
+    field: String,
+    text: String,
+}
+
+/// Searchable text of one drawing. Term counts are derived from the entries when loading.
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexedDrawing {
+    modified: u64,
+    entries: Vec<IndexedEntry>,
+    #[serde(skip)]
+    terms: HashMap<String, u32>,
+    #[serde(skip)]
+    length: usize,
+}
+
+impl IndexedDrawing {
+    fn read(path: &Path, modified: u64) -> Result<Self, String> {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let mut entries = vec![IndexedEntry {
+            element_id: None,
+            field: "file_name".to_string(),
+            text: formats::drawing_stem(&file_name).to_string(),
+        }];
+        let scene = search::read_scene(path)?;
+        let elements = scene.get("elements").and_then(|e| e.as_array()).into_iter().flatten();
+        for element in elements.filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)) {
+            for (field, text) in search::element_fields(element) {
+                entries.push(IndexedEntry {
+                    element_id: render::str_field(element, "id").map(str::to_string),
+                    field: field.to_string(),
+                    text: text.to_string(),
+                });
+            }
+        }
+        let mut drawing = Self { modified, entries, terms: HashMap::new(), length: 0 };
+        drawing.count_terms();
+        Ok(drawing)
+    }
+
+    fn count_terms(&mut self) {
+        self.terms.clear();
+        self.length = 0;
+        for entry in &self.entries {
+            for token in tokenize(&entry.text) {
+                *self.terms.entry(token).or_default() += 1;
+                self.length += 1;
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StoredIndex {
+    version: u32,
+    root: String,
+    drawings: HashMap<String, IndexedDrawing>,
+}
+
+/// Inverted index of one workspace, keyed by drawing path
+struct SearchIndex {
+    root: PathBuf,
+    drawings: HashMap<String, IndexedDrawing>,
+    /// Term to (drawing path, count); ordered so prefixes are a range scan
+    postings: BTreeMap<String, HashMap<String, u32>>,
+    total_length: usize,
+}
+
+impl SearchIndex {
+    fn new(root: PathBuf) -> Self {
+        Self { root, drawings: HashMap::new(), postings: BTreeMap::new(), total_length: 0 }
+    }
+
+    fn insert(&mut self, path: String, drawing: IndexedDrawing) {
+        self.remove(&path);
+        for (term, count) in &drawing.terms {
+            self.postings.entry(term.clone()).or_default().insert(path.clone(), *count);
+        }
+        self.total_length += drawing.length;
+        self.drawings.insert(path, drawing);
+    }
+
+    fn remove(&mut self, path: &str) {
+        let Some(drawing) = self.drawings.remove(path) else { return };
+        for term in drawing.terms.keys() {
+            if let Some(postings) = self.postings.get_mut(term) {
+                postings.remove(path);
+                if postings.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+        self.total_length -= drawing.length;
+    }
+
+    /// Removes `path` and every drawing below it, for deleted or moved folders
+    fn remove_under(&mut self, path: &Path) -> bool {
+        let gone: Vec<String> = self
+            .drawings
+            .keys()
+            .filter(|p| Path::new(p).starts_with(path))
+            .cloned()
+            .collect();
+        let removed = !gone.is_empty();
+        for p in gone {
+            self.remove(&p);
+        }
+        removed
+    }
+
+    /// BM25 over drawings. Every query word must match a word of the drawing, either exactly
+    /// or, at a lower weight, as its prefix.
+    fn search(&self, words: &[String], limit: usize) -> Vec<(&str, f64)> {
+        if words.is_empty() || self.drawings.is_empty() {
+            return Vec::new();
+        }
+        let count = self.drawings.len() as f64;
+        let average_length = (self.total_length as f64 / count).max(1.0);
+
+        let mut scores: HashMap<&str, (f64, usize)> = HashMap::new();
+        for word in words {
+            let mut best: HashMap<&str, f64> = HashMap::new();
+            let terms = self.postings.range(word.clone()..).take_while(|(term, _)| term.starts_with(word.as_str()));
+            for (term, postings) in terms {
+                let weight = if term == word { 1.0 } else { PREFIX_WEIGHT };
+                let frequency = postings.len() as f64;
+                let idf = ((count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln();
+                for (path, tf) in postings {
+                    let tf = *tf as f64;
+                    let length = self.drawings.get(path).map_or(0, |d| d.length) as f64;
+                    let score = weight * idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * length / average_length));
+                    let entry = best.entry(path.as_str()).or_default();
+                    *entry = entry.max(score);
+                }
+            }
+            for (path, score) in best {
+                let entry = scores.entry(path).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+
+        let mut ranked: Vec<(&str, f64)> = scores
+            .into_iter()
+            .filter(|(_, (_, matched))| *matched == words.len())
+            .map(|(path, (score, _))| (path, score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
+
+/// The index of the watched workspace, shared by the builder, the watcher and the commands
+#[derive(Default)]
+pub struct SearchIndexState {
+    index: Mutex<Option<SearchIndex>>,
+    status: Mutex<models::SearchIndexStatus>,
+    /// Changes on every `start`, telling a running build for an older workspace to stop
+    generation: AtomicU64,
+    save_pending: AtomicBool,
+}
+
+pub fn modified_millis(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|d| d.as_millis() as u64)
+}
+
+fn in_workspace_folder(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root)
+        .map(|relative| {
+            relative
+                .components()
+                .any(|c| bundle::is_workspace_folder(&c.as_os_str().to_string_lossy()))
+        })
+        .unwrap_or(true)
+}
+
+fn index_file(app: &AppHandle, root: &Path) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?.join(INDEX_DIR);
+    let key = assets::hash_bytes(root.to_string_lossy().as_bytes());
+    Some(dir.join(format!("{}.json", &key[..16])))
+}
+
+/// The stored index of `root`, or an empty one when it is missing, stale or unreadable
+fn load(app: &AppHandle, root: &Path) -> SearchIndex {
+    let mut index = SearchIndex::new(root.to_path_buf());
+    let stored = index_file(app, root)
+        .and_then(|file| fs::read_to_string(file).ok())
+        .and_then(|content| serde_json::from_str::<StoredIndex>(&content).ok())
+        .filter(|stored| stored.version == INDEX_VERSION && Path::new(&stored.root) == root);
+    for (path, mut drawing) in stored.map(|s| s.drawings).unwrap_or_default() {
+        drawing.count_terms();
+        index.insert(path, drawing);
+    }
+    index
+}
+
+fn save(app: &AppHandle, index: &SearchIndex) -> Result<(), String> {
+    let file = index_file(app, &index.root).ok_or("App data directory is not available")?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create index folder: {}", e))?;
+    }
+    let content = json!({
+        "version": INDEX_VERSION,
+        "root": index.root.to_string_lossy(),
+        "drawings": &index.drawings,
+    });
+    formats::write_atomic(&file, content.to_string().as_bytes())
+        .map_err(|e| format!("Failed to write search index: {}", e))
+}
+
+/// Saves once the current burst of changes is over
+fn schedule_save(app: &AppHandle) {
+    let state = app.state::<SearchIndexState>();
+    if state.save_pending.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(SAVE_DELAY);
+        let state = app.state::<SearchIndexState>();
+        state.save_pending.store(false, Ordering::SeqCst);
+        let result = state.index.lock().unwrap().as_ref().map(|index| save(&app, index));
+        if let Some(Err(e)) = result {
+            set_status(&app, |s| {
+                s.state = "error".to_string();
+                s.error = Some(e);
+            });
+        }
+    });
+}
+
+fn set_status(app: &AppHandle, update: impl FnOnce(&mut models::SearchIndexStatus)) {
+    let state = app.state::<SearchIndexState>();
+    let status = {
+        let mut status = state.status.lock().unwrap();
+        update(&mut status);
+        status.clone()
+    };
+    let _ = app.emit(STATUS_EVENT, status);
+}
+
+/// Stores a re-read drawing. One that could not be read is left out of the index and
+/// listed in the status until it changes again.
+fn store(app: &AppHandle, key: String, drawing: Result<IndexedDrawing, String>) {
+    let state = app.state::<SearchIndexState>();
+    let error = drawing.as_ref().err().cloned();
+    if let Some(index) = state.index.lock().unwrap().as_mut() {
+        match drawing {
+            Ok(drawing) => index.insert(key.clone(), drawing),
+            Err(_) => index.remove(&key),
+        }
+    }
+
+    let listed = state.status.lock().unwrap().skipped.iter().any(|s| s.path == key);
+    if error.is_some() || listed {
+        set_status(app, |s| {
+            s.skipped.retain(|skipped| skipped.path != key);
+            if let Some(error) = error {
+                s.skipped.push(models::SkippedFile { path: key, error });
+            }
+        });
+    }
+}
+
+/// Loads the stored index of `root` and brings it up to date in the background,
+/// re-reading only drawings whose modification time changed
+pub fn start(app: &AppHandle, root: PathBuf) {
+    let state = app.state::<SearchIndexState>();
+    let generation = state.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    let app = app.clone();
+
+    std::thread::spawn(move || {
+        let state = app.state::<SearchIndexState>();
+        let current = || state.generation.load(Ordering::SeqCst) == generation;
+
+        set_status(&app, |s| {
+            *s = models::SearchIndexStatus {
+                root: Some(root.to_string_lossy().to_string()),
+                state: "loading".to_string(),
+                ..Default::default()
+            }
+        });
+        let index = load(&app, &root);
+        if !current() {
+            return;
+        }
+        *state.index.lock().unwrap() = Some(index);
+
+        let drawings = match search::workspace_drawings(&root) {
+            Ok(drawings) => drawings,
+            Err(e) => {
+                set_status(&app, |s| {
+                    s.state = "error".to_string();
+                    s.error = Some(e);
+                });
+                return;
+            }
+        };
+        set_status(&app, |s| {
+            s.state = "building".to_string();
+            s.total = drawings.len();
+        });
+
+        for (i, path) in drawings.iter().enumerate() {
+            if !current() {
+                return;
+            }
+            let key = path.to_string_lossy().to_string();
+            let modified = modified_millis(path).unwrap_or_default();
+            let fresh = state
+                .index
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|index| index.drawings.get(&key))
+                .is_some_and(|d| d.modified == modified);
+            if !fresh {
+                store(&app, key, IndexedDrawing::read(path, modified));
+            }
+            if (i + 1) % PROGRESS_EVERY == 0 {
+                set_status(&app, |s| s.indexed = i + 1);
+            }
+        }
+
+        let result = {
+            let mut guard = state.index.lock().unwrap();
+            let Some(index) = guard.as_mut().filter(|_| current()) else { return };
+            let existing: HashSet<String> = drawings.iter().map(|p| p.to_string_lossy().to_string()).collect();
+            let gone: Vec<String> = index
+                .drawings
+                .keys()
+                .filter(|p| !existing.contains(*p))
+                .cloned()
+                .collect();
+            for path in gone {
+                index.remove(&path);
+            }
+            save(&app, index)
+        };
+        set_status(&app, |s| {
+            s.indexed = drawings.len();
+            match result {
+                Ok(()) => s.state = "ready".to_string(),
+                Err(e) => {
+                    s.state = "error".to_string();
+                    s.error = Some(e);
+                }
+            }
+        });
+    });
+}
+
+/// Applies a watcher event for `path`: a created, changed, moved or deleted drawing or folder
+pub fn update(app: &AppHandle, path: &Path) {
+    let state = app.state::<SearchIndexState>();
+    let root = match state.index.lock().unwrap().as_ref() {
+        Some(index) if !in_workspace_folder(&index.root, path) => index.root.clone(),
+        _ => return,
+    };
+
+    let changed: Vec<PathBuf> = if path.is_dir() {
+        search::workspace_drawings(path)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| !in_workspace_folder(&root, p))
+            .collect()
+    } else if path.is_file() && formats::is_drawing_file(path) {
+        vec![path.to_path_buf()]
+    } else {
+        Vec::new()
+    };
+
+    let gone = |skipped: &models::SkippedFile| Path::new(&skipped.path).starts_with(path);
+    if !path.exists() && state.status.lock().unwrap().skipped.iter().any(gone) {
+        set_status(app, |s| s.skipped.retain(|skipped| !gone(skipped)));
+    }
+    let mut dirty = !path.exists()
+        && state
+            .index
+            .lock()
+            .unwrap()
+            .as_mut()
+            .is_some_and(|index| index.remove_under(path));
+    for drawing_path in changed {
+        let key = drawing_path.to_string_lossy().to_string();
+        let modified = modified_millis(&drawing_path).unwrap_or_default();
+        let fresh = state
+            .index
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|index| index.drawings.get(&key))
+            .is_some_and(|d| d.modified == modified);
+        if fresh {
+            continue;
+        }
+        store(app, key, IndexedDrawing::read(&drawing_path, modified));
+        dirty = true;
+    }
+    if dirty {
+        schedule_save(app);
+    }
+}
+
+/// Ranked drawings of the watched workspace that contain every word of `query`, each word
+/// also matching longer words it starts. Each hit points at the element that matches best.
+#[tauri::command]
+pub async fn search_index(
+    state: State<'_, SearchIndexState>,
+    query: String,
+    max_results: Option<usize>,
+) -> Result<Vec<models::IndexSearchHit>, String> {
+    let words = tokenize(&query);
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Highlights words starting with a query word, as the ranking counts them. CJK bigrams
+    // sit inside longer runs, so they have no word boundary.
+    let alternatives = words
+        .iter()
+        .map(|w| match w.starts_with(is_cjk) {
+            true => regex::escape(w),
+            false => format!(r"\b{}", regex::escape(w)),
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    let highlight = search::build_matcher(
+        &format!("(?:{})", alternatives),
+        &models::SearchOptions { regex: true, ..Default::default() },
+    )?;
+
+    let guard = state.index.lock().unwrap();
+    let Some(index) = guard.as_ref() else {
+        return Err("Search index is not available until a folder is opened".to_string());
+    };
+
+    let hits = index
+        .search(&words, max_results.unwrap_or(DEFAULT_MAX_RESULTS))
+        .into_iter()
+        .filter_map(|(path, score)| {
+            let drawing = index.drawings.get(path)?;
+            // The entry with the most different query words, preferring earlier ones
+            let entry = drawing
+                .entries
+                .iter()
+                .rev()
+                .max_by_key(|entry| {
+                    let tokens = tokenize(&entry.text);
+                    words.iter().filter(|w| tokens.iter().any(|t| t.starts_with(w.as_str()))).count()
+                })?;
+            let (start, end) = highlight
+                .find(&entry.text)
+                .map(|m| (m.start(), m.end()))
+                .unwrap_or((0, 0));
+            let (snippet, match_start, match_end) = search::snippet(&entry.text, start, end);
+            Some(models::IndexSearchHit {
+                path: path.to_string(),
+                score,
+                element_id: entry.element_id.clone(),
+                field: entry.field.clone(),
+                snippet,
+                match_start,
+                match_end,
+            })
+        })
+        .collect();
+    Ok(hits)
+}
+
+#[tauri::command]
+pub async fn search_index_status(state: State<'_, SearchIndexState>) -> Result<models::SearchIndexStatus, String> {
+    Ok(state.status.lock().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawing(texts: &[&str]) -> IndexedDrawing {
+        let entries = texts
+            .iter()
+            .map(|text| IndexedEntry { element_id: None, field: "text".to_string(), text: text.to_string() })
+            .collect();
+        let mut drawing = IndexedDrawing { modified: 0, entries, terms: HashMap::new(), length: 0 };
+        drawing.count_terms();
+        drawing
+    }
+
+    fn index(drawings: &[(&str, &[&str])]) -> SearchIndex {
+        let mut index = SearchIndex::new(PathBuf::from("/workspace"));
+        for (path, texts) in drawings {
+            index.insert(path.to_string(), drawing(texts));
+        }
+        index
+    }
+
+    #[test]
+    fn tokenize_splits_latin_words_on_punctuation() {
+        assert_eq!(tokenize("Payment-Gateway v2"), ["payment", "gateway", "v2"]);
+    }
+
+    #[test]
+    fn tokenize_indexes_cjk_as_bigrams() {
+        assert_eq!(tokenize("支付网关服务"), ["支付", "付网", "网关", "关服", "服务"]);
+        assert_eq!(tokenize("网"), ["网"]);
+        assert_eq!(tokenize("API网关v2"), ["api", "网关", "v2"]);
+    }
+
+    #[test]
+    fn search_finds_cjk_words_inside_longer_runs() {
+        let index = index(&[("/workspace/a.excalidraw", &["支付网关服务"]), ("/workspace/b.excalidraw", &["用户中心"])]);
+        let hits = index.search(&tokenize("网关"), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "/workspace/a.excalidraw");
+        assert!(index.search(&tokenize("网关中心"), 10).is_empty());
+    }
+
+    #[test]
+    fn search_ranks_exact_matches_above_prefixes() {
+        let index = index(&[("/workspace/a.excalidraw", &["gateways"]), ("/workspace/b.excalidraw", &["gateway"])]);
+        let hits = index.search(&tokenize("gateway"), 10);
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), ["/workspace/b.excalidraw", "/workspace/a.excalidraw"]);
+    }
+
+    #[test]
+    fn remove_forgets_terms() {
+        let mut index = index(&[("/workspace/a.excalidraw", &["gateway"])]);
+        index.remove("/workspace/a.excalidraw");
+        assert!(index.postings.is_empty());
+        assert_eq!(index.total_length, 0);
+    }
+}
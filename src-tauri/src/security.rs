@@ -27,11 +27,17 @@ pub fn validate_path(path: &Path, allowed_base: Option<&Path>) -> Result<PathBuf
     Ok(canonical_path)
 }
 
-/// Validates that a file has the expected .excalidraw extension
+/// Validates that a file is a drawing: .excalidraw, or an image with an embedded scene
 pub fn validate_excalidraw_file(path: &Path) -> Result<(), String> {
+    if crate::formats::is_drawing_file(path) {
+        return Ok(());
+    }
     match path.extension() {
-        Some(ext) if ext == "excalidraw" => Ok(()),
-        Some(ext) => Err(format!("Invalid file extension: expected .excalidraw, got .{}", ext.to_string_lossy())),
+        Some(ext) => Err(format!(
+            "Invalid file extension: expected one of {}, got .{}",
+            crate::formats::supported_suffixes(),
+            ext.to_string_lossy()
+        )),
         None => Err("File has no extension".to_string()),
     }
 }
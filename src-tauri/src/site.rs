@@ -0,0 +1,401 @@
+use super::*;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+/// Width in pixels that thumbnails are scaled down to
+const THUMBNAIL_WIDTH: f64 = 320.0;
+
+const SEARCH_INDEX_NAME: &str = "search-index.json";
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 0; color: #1e1e1e; background: #f8f9fa; }
+header { background: #fff; border-bottom: 1px solid #e0e0e0; padding: 12px 24px; display: flex; gap: 16px; align-items: center; flex-wrap: wrap; }
+header h1 { font-size: 18px; margin: 0; }
+nav { font-size: 14px; color: #666; }
+a { color: #5b57d1; text-decoration: none; }
+a:hover { text-decoration: underline; }
+main { padding: 24px; }
+h2 { font-size: 15px; color: #666; margin: 24px 0 12px; }
+.grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(240px, 1fr)); gap: 16px; }
+.card { background: #fff; border: 1px solid #e0e0e0; border-radius: 8px; overflow: hidden; display: flex; flex-direction: column; }
+.card img { width: 100%; height: 160px; object-fit: contain; background: #fff; border-bottom: 1px solid #eee; }
+.card span { padding: 8px 12px; font-size: 14px; word-break: break-word; }
+.folders { list-style: none; padding: 0; display: flex; flex-wrap: wrap; gap: 8px; }
+.folders a { display: block; padding: 6px 12px; background: #fff; border: 1px solid #e0e0e0; border-radius: 6px; }
+.drawing { background: #fff; border: 1px solid #e0e0e0; border-radius: 8px; padding: 16px; text-align: center; }
+.drawing img { max-width: 100%; height: auto; }
+#search { padding: 6px 10px; border: 1px solid #ccc; border-radius: 6px; min-width: 240px; }
+#results { list-style: none; padding: 0; }
+#results li { padding: 6px 0; }
+#results small { color: #888; margin-left: 8px; }
+"#;
+
+/// Client side search over the embedded index; works when opened from disk too
+const SEARCH_SCRIPT: &str = r#"
+const index = JSON.parse(document.getElementById("search-index").textContent);
+const input = document.getElementById("search");
+const results = document.getElementById("results");
+const content = document.getElementById("content");
+input.addEventListener("input", () => {
+  const terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+  results.replaceChildren();
+  content.hidden = terms.length > 0;
+  if (!terms.length) return;
+  for (const entry of index) {
+    const haystack = (entry.title + " " + entry.folder + " " + entry.text).toLowerCase();
+    if (!terms.every((t) => haystack.includes(t))) continue;
+    const item = document.createElement("li");
+    const link = document.createElement("a");
+    link.href = entry.page;
+    link.textContent = entry.title;
+    const folder = document.createElement("small");
+    folder.textContent = entry.folder;
+    item.append(link, folder);
+    results.append(item);
+  }
+  if (!results.children.length) results.textContent = "No matches";
+});
+"#;
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `href` for a path relative to the site root, seen from a page `depth` folders deep
+fn href(depth: usize, path: &str) -> String {
+    let encoded: Vec<String> = path.split('/').map(url_encode).collect();
+    format!("{}{}", "../".repeat(depth), encoded.join("/"))
+}
+
+/// Percent-encodes everything but unreserved characters, for one segment of a URL
+pub fn url_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn page(title: &str, site_title: &str, depth: usize, breadcrumb: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<header><h1><a href="{home}">{site}</a></h1><nav>{breadcrumb}</nav></header>
+<main>
+{body}
+</main>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        home = href(depth, "index.html"),
+        site = escape_html(site_title),
+    )
+}
+
+/// Links to each folder from the site root down to `folder`
+fn breadcrumb(folder: &str, depth: usize, current: Option<&str>) -> String {
+    let mut links = Vec::new();
+    let mut path = String::new();
+    for part in folder.split('/').filter(|p| !p.is_empty()) {
+        path = if path.is_empty() { part.to_string() } else { format!("{}/{}", path, part) };
+        links.push(format!(
+            r#"<a href="{}">{}</a>"#,
+            href(depth, &format!("{}/index.html", path)),
+            escape_html(part)
+        ));
+    }
+    links.extend(current.map(escape_html));
+    links.join(" / ")
+}
+
+/// Every piece of text in a scene, for the search index
+fn scene_text(scene: &Value) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for element in scene.get("elements").and_then(|e| e.as_array()).into_iter().flatten() {
+        if render::bool_field(element, "isDeleted").unwrap_or(false) {
+            continue;
+        }
+        let text = render::str_field(element, "originalText").or(render::str_field(element, "text"));
+        parts.extend(text);
+        if render::str_field(element, "type") == Some("frame") {
+            parts.extend(render::str_field(element, "name"));
+        }
+    }
+    parts.join(" ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn svg_width(svg: &str) -> Option<f64> {
+    let start = svg.find("width=\"")? + 7;
+    let end = start + svg[start..].find('"')?;
+    svg[start..end].parse().ok()
+}
+
+struct SiteDrawing {
+    /// Folder relative to the site root, empty for the root
+    folder: String,
+    title: String,
+    /// Base name shared by the drawing's page, SVG and thumbnail
+    base: String,
+}
+
+/// `name`, numbered when another drawing in the same folder already uses it. Case is ignored,
+/// as two names differing only in case are the same file on some file systems.
+fn unique_base(taken: &mut HashSet<String>, folder: &str, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut counter = 1;
+    while !taken.insert(format!("{}/{}", folder, candidate).to_lowercase()) {
+        candidate = format!("{}-{}", name, counter);
+        counter += 1;
+    }
+    candidate
+}
+
+fn write(path: &Path, content: impl AsRef<[u8]>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Renders every drawing under `directory` into a static HTML gallery in `output_dir`.
+///
+/// The gallery mirrors the folder tree with one index page per folder, a page per drawing
+/// with its SVG, PNG thumbnails and a `search-index.json`. Drawings link to their source file,
+/// either copied next to the page or under `source_base_url` when given.
+#[tauri::command]
+pub async fn export_site(
+    app: AppHandle,
+    directory: String,
+    output_dir: String,
+    title: Option<String>,
+    source_base_url: Option<String>,
+    options: Option<models::ExportOptions>,
+) -> Result<models::SiteExportReport, String> {
+    let root = security::validate_path(Path::new(&directory), None)?;
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", directory));
+    }
+    let output = render::output_dir_for(&output_dir)?;
+
+    let options = options.unwrap_or_default();
+    let fonts = export::font_dirs(&app);
+    let site_title = title.unwrap_or_else(|| {
+        root.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Drawings".to_string())
+    });
+
+    let mut files = Vec::new();
+    file_ops::collect_excalidraw_files_recursive(&root, &mut files)?;
+    let mut sources: Vec<(PathBuf, PathBuf)> = files
+        .into_iter()
+        .map(|f| PathBuf::from(f.path))
+        .filter(|p| !p.starts_with(&output))
+        .filter_map(|p| {
+            let relative = p.strip_prefix(&root).ok()?.to_path_buf();
+            let hidden = relative
+                .components()
+                .any(|c| bundle::is_workspace_folder(&c.as_os_str().to_string_lossy()));
+            (!hidden).then_some((p, relative))
+        })
+        .collect();
+    sources.sort_by(|a, b| a.1.cmp(&b.1));
+
+    let mut drawings: Vec<SiteDrawing> = Vec::new();
+    let mut failed = Vec::new();
+    let mut search_index = Vec::new();
+    let mut bases = HashSet::new();
+
+    for (path, relative) in &sources {
+        let file_name = relative.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let folder = relative
+            .parent()
+            .map(|p| p.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default();
+        let depth = folder.split('/').filter(|p| !p.is_empty()).count();
+        let title = formats::drawing_stem(&file_name).to_string();
+        // Two formats of the same drawing may share a stem, so the file name is the base,
+        // numbered when `a.b.excalidraw` and `a_b.excalidraw` would both become `a_b_excalidraw`
+        let base = unique_base(&mut bases, &folder, &file_name.replace('.', "_"));
+        let in_folder = |name: &str| if folder.is_empty() { name.to_string() } else { format!("{}/{}", folder, name) };
+        let site_dir = output.join(&folder);
+
+        let rendered = file_ops::load_scene(path).and_then(|scene| {
+            let svg = export::scene_to_svg(&scene, &options, &fonts)?;
+            let thumbnail_options = models::ExportOptions {
+                embed_fonts: false,
+                embed_scene: false,
+                ..options.clone()
+            };
+            let plain_svg = export::scene_to_svg(&scene, &thumbnail_options, &fonts)?;
+            let scale = svg_width(&plain_svg).map(|w| (THUMBNAIL_WIDTH / w.max(1.0)).min(1.0)).unwrap_or(1.0);
+            let thumbnail = render::png::svg_to_png(&plain_svg, scale, false, &fonts)?;
+            Ok((scene, svg, thumbnail))
+        });
+        let (scene, svg, thumbnail) = match rendered {
+            Ok(rendered) => rendered,
+            Err(error) => {
+                failed.push(models::SkippedFile {
+                    path: path.to_string_lossy().to_string(),
+                    error,
+                });
+                continue;
+            }
+        };
+
+        write(&site_dir.join(format!("{}.svg", base)), &svg)?;
+        write(&site_dir.join(format!("{}.thumb.png", base)), &thumbnail)?;
+
+        let source_href = match &source_base_url {
+            Some(url) => format!(
+                "{}/{}",
+                url.trim_end_matches('/'),
+                relative.components().map(|c| url_encode(&c.as_os_str().to_string_lossy())).collect::<Vec<_>>().join("/")
+            ),
+            None => {
+                fs::copy(path, site_dir.join(&file_name)).map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+                url_encode(&file_name)
+            }
+        };
+        let body = format!(
+            r#"<p><a href="{source}" download>Download {file}</a> · <a href="{svg}">SVG</a></p>
+<div class="drawing"><img src="{svg}" alt="{title}"></div>"#,
+            source = escape_html(&source_href),
+            file = escape_html(&file_name),
+            svg = url_encode(&format!("{}.svg", base)),
+            title = escape_html(&title),
+        );
+        let html = page(&title, &site_title, depth, &breadcrumb(&folder, depth, Some(&title)), &body);
+        write(&site_dir.join(format!("{}.html", base)), html)?;
+
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+            .ok();
+        search_index.push(json!({
+            "title": title,
+            "folder": folder,
+            "page": in_folder(&format!("{}.html", base)),
+            "thumbnail": in_folder(&format!("{}.thumb.png", base)),
+            "source": if source_base_url.is_some() { source_href.clone() } else { in_folder(&source_href) },
+            "modified": modified,
+            "text": scene_text(&scene),
+        }));
+        drawings.push(SiteDrawing { folder, title, base });
+    }
+
+    // Every folder on the way to a drawing gets an index page
+    let mut folders: BTreeMap<String, Vec<&SiteDrawing>> = BTreeMap::new();
+    folders.entry(String::new()).or_default();
+    for drawing in &drawings {
+        let mut path = String::new();
+        for part in drawing.folder.split('/').filter(|p| !p.is_empty()) {
+            folders.entry(path.clone()).or_default();
+            path = if path.is_empty() { part.to_string() } else { format!("{}/{}", path, part) };
+        }
+        folders.entry(drawing.folder.clone()).or_default().push(drawing);
+    }
+
+    let index_json = serde_json::to_string(&search_index).map_err(|e| format!("Failed to serialize search index: {}", e))?;
+    write(&output.join(SEARCH_INDEX_NAME), &index_json)?;
+
+    for (folder, folder_drawings) in &folders {
+        let depth = folder.split('/').filter(|p| !p.is_empty()).count();
+        let prefix = if folder.is_empty() { String::new() } else { format!("{}/", folder) };
+        let subfolders: Vec<&String> = folders
+            .keys()
+            .filter(|f| f.strip_prefix(&prefix).is_some_and(|rest| !rest.is_empty() && !rest.contains('/')))
+            .collect();
+
+        let mut body = String::new();
+        if depth == 0 {
+            body.push_str(r#"<ul id="results"></ul><div id="content">"#);
+        }
+        if !subfolders.is_empty() {
+            body.push_str("<h2>Folders</h2><ul class=\"folders\">");
+            for sub in subfolders {
+                let name = &sub[prefix.len()..];
+                body.push_str(&format!(
+                    r#"<li><a href="{}">{}</a></li>"#,
+                    url_encode(name) + "/index.html",
+                    escape_html(name)
+                ));
+            }
+            body.push_str("</ul>");
+        }
+        if !folder_drawings.is_empty() {
+            body.push_str("<h2>Drawings</h2><div class=\"grid\">");
+            for drawing in folder_drawings {
+                body.push_str(&format!(
+                    r#"<a class="card" href="{page}"><img src="{thumb}" alt="" loading="lazy"><span>{title}</span></a>"#,
+                    page = url_encode(&format!("{}.html", drawing.base)),
+                    thumb = url_encode(&format!("{}.thumb.png", drawing.base)),
+                    title = escape_html(&drawing.title),
+                ));
+            }
+            body.push_str("</div>");
+        }
+        if depth == 0 {
+            body.push_str("</div>");
+            // `</` cannot appear inside a script element
+            body.push_str(&format!(
+                "<script type=\"application/json\" id=\"search-index\">{}</script><script>{}</script>",
+                index_json.replace("</", "<\\/"),
+                SEARCH_SCRIPT
+            ));
+        }
+
+        let name = folder.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or(&site_title);
+        let mut crumbs = breadcrumb(folder, depth, None);
+        if depth == 0 {
+            crumbs = r#"<input id="search" type="search" placeholder="Search drawings">"#.to_string();
+        }
+        write(&output.join(&prefix).join("index.html"), page(name, &site_title, depth, &crumbs, &body))?;
+    }
+
+    Ok(models::SiteExportReport {
+        output_dir: output.to_string_lossy().to_string(),
+        index_path: output.join("index.html").to_string_lossy().to_string(),
+        drawings: drawings.len(),
+        folders: folders.len(),
+        failed,
+        warnings: render::png::font_database(&fonts).warnings(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_base_numbers_colliding_names_per_folder() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_base(&mut taken, "", "a_b_excalidraw"), "a_b_excalidraw");
+        assert_eq!(unique_base(&mut taken, "", "a_b_excalidraw"), "a_b_excalidraw-1");
+        assert_eq!(unique_base(&mut taken, "", "A_B_excalidraw"), "A_B_excalidraw-2");
+        assert_eq!(unique_base(&mut taken, "sub", "a_b_excalidraw"), "a_b_excalidraw");
+    }
+
+    #[test]
+    fn url_encode_escapes_reserved_and_non_ascii_characters() {
+        assert_eq!(url_encode("a (1).svg"), "a%20%281%29.svg");
+        assert_eq!(url_encode("网关"), "%E7%BD%91%E5%85%B3");
+    }
+}
@@ -0,0 +1,366 @@
+use super::*;
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Horizontal space between merged drawings
+const MERGE_GAP: f64 = 100.0;
+
+fn live_elements(scene: &Value) -> Vec<Value> {
+    scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| {
+            e.iter()
+                .filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The `files` entries that `elements` use
+fn used_files(scene: &Value, elements: &[Value]) -> Map<String, Value> {
+    let ids: HashSet<&str> = elements.iter().filter_map(|e| render::str_field(e, "fileId")).collect();
+    scene
+        .get("files")
+        .and_then(|f| f.as_object())
+        .map(|files| {
+            files
+                .iter()
+                .filter(|(id, _)| ids.contains(id.as_str()))
+                .map(|(id, file)| (id.clone(), file.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn scene_with(source: &Value, elements: Vec<Value>, files: Map<String, Value>) -> Value {
+    let mut scene = elements::scene(elements);
+    if let Some(app_state) = source.get("appState") {
+        scene["appState"] = app_state.clone();
+    }
+    scene["files"] = Value::Object(files);
+    scene
+}
+
+/// The frame `frame_id` and the elements inside it as a scene of their own
+fn frame_scene(scene: &Value, live: &[Value], frame_id: &str) -> Value {
+    let mut keep: HashSet<&str> = live
+        .iter()
+        .filter(|e| render::str_field(e, "id") == Some(frame_id) || render::str_field(e, "frameId") == Some(frame_id))
+        .filter_map(|e| render::str_field(e, "id"))
+        .collect();
+    // Labels follow their container even when they do not carry the frame id themselves
+    let labels: Vec<&str> = live
+        .iter()
+        .filter(|e| render::str_field(e, "containerId").is_some_and(|c| keep.contains(c)))
+        .filter_map(|e| render::str_field(e, "id"))
+        .collect();
+    keep.extend(labels);
+
+    let frame_elements = elements::copy_subset(live, &keep);
+    let files = used_files(scene, &frame_elements);
+    scene_with(scene, frame_elements, files)
+}
+
+/// Writes each frame of a drawing, with the elements inside it, to its own file
+#[tauri::command]
+pub async fn split_by_frames(file_path: String, output_dir: Option<String>) -> Result<Vec<String>, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    // Loading puts externalized images back, so split and merged files stand on their own
+    let scene = file_ops::load_scene(&validated_path)?;
+    let live = live_elements(&scene);
+
+    let frames = export::ordered_frames(&scene, &[], "position")?;
+    if frames.is_empty() {
+        return Err("Drawing has no frames".to_string());
+    }
+
+    let dir = match output_dir {
+        Some(dir) => security::validate_path(Path::new(&dir), None)?,
+        None => validated_path
+            .parent()
+            .ok_or("File has no parent directory")?
+            .to_path_buf(),
+    };
+    let file_name = validated_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let stem = formats::drawing_stem(&file_name);
+
+    let mut created = Vec::new();
+    for (frame_id, name) in frames {
+        let frame_scene = frame_scene(&scene, &live, &frame_id);
+
+        let path = file_ops::unique_file_path(&dir, &format!("{}-{}.excalidraw", stem, drawio::file_name_safe(&name)))?;
+        let content = serde_json::to_string_pretty(&frame_scene)
+            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        created.push(path.to_string_lossy().to_string());
+    }
+
+    Ok(created)
+}
+
+/// Ids used by the scenes merged so far
+#[derive(Default)]
+struct TakenIds {
+    elements: HashSet<String>,
+    groups: HashSet<String>,
+    files: HashSet<String>,
+}
+
+/// Replacement ids for one merged scene: every id already taken by an earlier scene gets a new one
+struct IdMap {
+    elements: HashMap<String, String>,
+    groups: HashMap<String, String>,
+    files: HashMap<String, String>,
+}
+
+impl IdMap {
+    fn new(live: &[Value], files: &Map<String, Value>, taken: &mut TakenIds) -> Self {
+        let mut ids = IdMap {
+            elements: HashMap::new(),
+            groups: HashMap::new(),
+            files: HashMap::new(),
+        };
+        for element in live {
+            if let Some(id) = render::str_field(element, "id") {
+                ids.elements.insert(id.to_string(), fresh_id(id, &mut taken.elements));
+            }
+            for group in element.get("groupIds").and_then(|g| g.as_array()).into_iter().flatten() {
+                if let Some(id) = group.as_str()
+                    && !ids.groups.contains_key(id)
+                {
+                    ids.groups.insert(id.to_string(), fresh_id(id, &mut taken.groups));
+                }
+            }
+        }
+        for id in files.keys() {
+            ids.files.insert(id.clone(), fresh_id(id, &mut taken.files));
+        }
+        ids
+    }
+
+    /// Number of ids that collided and were replaced
+    fn renamed(&self) -> usize {
+        [&self.elements, &self.groups, &self.files]
+            .iter()
+            .map(|map| map.iter().filter(|(old, new)| old != new).count())
+            .sum()
+    }
+}
+
+fn fresh_id(id: &str, taken: &mut HashSet<String>) -> String {
+    let mut candidate = id.to_string();
+    while taken.contains(&candidate) {
+        candidate = format!("{}-{}", id, elements::new_id_prefix());
+    }
+    taken.insert(candidate.clone());
+    candidate
+}
+
+fn remap(map: &HashMap<String, String>, id: &str) -> String {
+    map.get(id).cloned().unwrap_or_else(|| id.to_string())
+}
+
+/// Rewrites every id and reference in `element` through `ids`
+fn rename_element(element: &mut Value, ids: &IdMap) {
+    for key in ["id", "containerId", "frameId"] {
+        if let Some(id) = render::str_field(element, key) {
+            element[key] = json!(remap(&ids.elements, id));
+        }
+    }
+    for key in ["startBinding", "endBinding"] {
+        if let Some(target) = element.get(key).and_then(|b| render::str_field(b, "elementId")) {
+            element[key]["elementId"] = json!(remap(&ids.elements, target));
+        }
+    }
+    if let Some(bound) = element.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+        for entry in bound {
+            if let Some(id) = render::str_field(entry, "id") {
+                entry["id"] = json!(remap(&ids.elements, id));
+            }
+        }
+    }
+    if let Some(groups) = element.get_mut("groupIds").and_then(|g| g.as_array_mut()) {
+        for group in groups {
+            if let Some(id) = group.as_str() {
+                *group = json!(remap(&ids.groups, id));
+            }
+        }
+    }
+    if let Some(file_id) = render::str_field(element, "fileId") {
+        element["fileId"] = json!(remap(&ids.files, file_id));
+    }
+}
+
+/// Combines drawings side by side into a new file. Ids that collide with an earlier drawing
+/// are regenerated together with every reference to them, so bindings, groups, frames and
+/// images keep working.
+#[tauri::command]
+pub async fn merge_files(
+    file_paths: Vec<String>,
+    output_path: String,
+    gap: Option<f64>,
+) -> Result<models::MergeReport, String> {
+    if file_paths.len() < 2 {
+        return Err("Choose at least two drawings to merge".to_string());
+    }
+    let paths = file_paths
+        .iter()
+        .map(|p| security::validate_path(Path::new(p), None))
+        .collect::<Result<Vec<_>, _>>()?;
+    let gap = gap.unwrap_or(MERGE_GAP);
+
+    let mut taken = TakenIds::default();
+    let mut merged_elements = Vec::new();
+    let mut merged_files = Map::new();
+    let mut renamed = 0;
+    let mut cursor: Option<(f64, f64)> = None;
+    let mut first_scene = None;
+
+    for path in &paths {
+        let scene = file_ops::load_scene(path)?;
+        let mut live = live_elements(&scene);
+        let files = scene.get("files").and_then(|f| f.as_object()).cloned().unwrap_or_default();
+
+        let ids = IdMap::new(&live, &files, &mut taken);
+        renamed += ids.renamed();
+
+        // Each drawing starts to the right of the previous one, tops aligned
+        let refs: Vec<&Value> = live.iter().collect();
+        if let Some((x1, y1, x2, _)) = render::scene_bounds(&refs) {
+            let (dx, dy) = match cursor {
+                Some((right, top)) => (right + gap - x1, top - y1),
+                None => (0.0, 0.0),
+            };
+            for element in &mut live {
+                elements::translate(element, dx, dy);
+            }
+            let top = cursor.map(|(_, top)| top).unwrap_or(y1);
+            cursor = Some((x2 + dx, top));
+        }
+
+        for mut element in live {
+            rename_element(&mut element, &ids);
+            // Fractional indices of different drawings interleave; Excalidraw regenerates them
+            if let Some(object) = element.as_object_mut() {
+                object.remove("index");
+            }
+            merged_elements.push(element);
+        }
+        for (id, mut file) in files {
+            let new_id = remap(&ids.files, &id);
+            file["id"] = json!(new_id);
+            merged_files.insert(new_id, file);
+        }
+        first_scene.get_or_insert(scene);
+    }
+
+    let element_count = merged_elements.len();
+    let scene = scene_with(&first_scene.unwrap_or_default(), merged_elements, merged_files);
+    let requested = render::output_path_for(&paths[0], Some(output_path), "excalidraw")?;
+    if formats::drawing_format(&requested) != Some(formats::DrawingFormat::Json) {
+        return Err("Merged drawings are saved as .excalidraw files".to_string());
+    }
+    // Never overwrite an existing drawing, least of all one of the inputs
+    let output = file_ops::unique_file_path(
+        requested.parent().ok_or("Invalid output path")?,
+        &requested.file_name().unwrap_or_default().to_string_lossy(),
+    )?;
+    let content = serde_json::to_string_pretty(&scene).map_err(|e| format!("Failed to serialize content: {}", e))?;
+    formats::write_atomic(&output, content.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+
+    Ok(models::MergeReport {
+        output_path: output.to_string_lossy().to_string(),
+        drawings: paths.len(),
+        elements: element_count,
+        renamed_ids: renamed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element<'a>(elements: &'a [Value], id: &str) -> &'a Value {
+        elements.iter().find(|e| e["id"] == id).unwrap()
+    }
+
+    /// A box with a bound arrow, grouped, next to an image
+    fn drawing() -> (Vec<Value>, Map<String, Value>) {
+        let elements = vec![
+            json!({ "id": "box", "type": "rectangle", "groupIds": ["group"],
+                    "boundElements": [{ "id": "arrow", "type": "arrow" }] }),
+            json!({ "id": "arrow", "type": "arrow", "groupIds": ["group"],
+                    "startBinding": { "elementId": "box", "focus": 0, "gap": 5 }, "endBinding": null }),
+            json!({ "id": "photo", "type": "image", "fileId": "file" }),
+        ];
+        let mut files = Map::new();
+        files.insert("file".to_string(), json!({ "id": "file", "mimeType": "image/png" }));
+        (elements, files)
+    }
+
+    #[test]
+    fn colliding_ids_are_renamed_with_their_references() {
+        let mut taken = TakenIds::default();
+        let (first, files) = drawing();
+        let ids = IdMap::new(&first, &files, &mut taken);
+        assert_eq!(ids.renamed(), 0);
+
+        let (mut second, files) = drawing();
+        let ids = IdMap::new(&second, &files, &mut taken);
+        // box, arrow, photo, the group and the file
+        assert_eq!(ids.renamed(), 5);
+        for element in &mut second {
+            rename_element(element, &ids);
+        }
+
+        let (box_id, arrow_id) = (&ids.elements["box"], &ids.elements["arrow"]);
+        assert!(box_id != "box" && arrow_id != "arrow");
+        let shape = element(&second, box_id);
+        let arrow = element(&second, arrow_id);
+        assert_eq!(arrow["startBinding"]["elementId"], json!(box_id));
+        assert_eq!(shape["boundElements"][0]["id"], json!(arrow_id));
+
+        let group = &ids.groups["group"];
+        assert_ne!(group, "group");
+        assert_eq!(shape["groupIds"], json!([group]));
+        assert_eq!(arrow["groupIds"], json!([group]));
+
+        let photo = element(&second, &ids.elements["photo"]);
+        assert_ne!(photo["fileId"], "file");
+        assert_eq!(photo["fileId"], json!(ids.files["file"]));
+    }
+
+    #[test]
+    fn fresh_ids_never_repeat() {
+        let mut taken = HashSet::new();
+        let ids: HashSet<String> = (0..20).map(|_| fresh_id("a", &mut taken)).collect();
+        assert_eq!(ids.len(), 20);
+        assert!(ids.contains("a"));
+    }
+
+    #[test]
+    fn labels_follow_their_container_into_the_frame() {
+        let live = vec![
+            json!({ "id": "frame", "type": "frame" }),
+            json!({ "id": "box", "type": "rectangle", "frameId": "frame",
+                    "boundElements": [{ "id": "label", "type": "text" }] }),
+            json!({ "id": "label", "type": "text", "containerId": "box", "frameId": null }),
+            json!({ "id": "outside", "type": "ellipse", "frameId": null }),
+        ];
+        let scene = json!({ "elements": live.clone(), "appState": {}, "files": {} });
+
+        let split = frame_scene(&scene, &live, "frame");
+        let ids: Vec<&str> = split["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|e| e["id"].as_str())
+            .collect();
+        assert_eq!(ids, ["frame", "box", "label"]);
+    }
+}
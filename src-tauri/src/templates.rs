@@ -0,0 +1,194 @@
+use super::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+/// Workspace folder holding per-workspace templates
+pub const TEMPLATES_DIR: &str = ".templates";
+
+/// Id of the built-in empty scene
+pub const BLANK_TEMPLATE_ID: &str = "blank";
+
+const WORKSPACE_SOURCE: &str = "workspace";
+const APP_SOURCE: &str = "app";
+
+pub fn blank_scene() -> Value {
+    serde_json::json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "SAG-Excalidraw",
+        "elements": [],
+        "appState": {
+            "gridSize": null,
+            "viewBackgroundColor": "#ffffff"
+        },
+        "files": {}
+    })
+}
+
+fn app_templates_dir(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join("templates"))
+}
+
+/// Finds the nearest `.templates` folder in `dir` or one of its ancestors, stopping at the
+/// open workspace so folders in the home directory or above are never picked up
+fn workspace_templates_dir(app: &AppHandle, dir: &Path) -> Option<PathBuf> {
+    let workspace = file_ops::workspace_dir(&app.state::<models::AppState>(), dir);
+    file_ops::find_workspace_folder(dir, &workspace, TEMPLATES_DIR)
+}
+
+fn collect_templates(dir: &Path, source: &str, templates: &mut Vec<models::TemplateInfo>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut found: Vec<models::TemplateInfo> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "excalidraw"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            Some(models::TemplateInfo {
+                id: format!("{}/{}", source, name),
+                name,
+                source: source.to_string(),
+                path: Some(path.to_string_lossy().to_string()),
+            })
+        })
+        .collect();
+
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    templates.extend(found);
+}
+
+#[tauri::command]
+pub async fn list_templates(
+    app: AppHandle,
+    directory: Option<String>,
+) -> Result<Vec<models::TemplateInfo>, String> {
+    let mut templates = vec![models::TemplateInfo {
+        id: BLANK_TEMPLATE_ID.to_string(),
+        name: "Blank".to_string(),
+        source: "builtin".to_string(),
+        path: None,
+    }];
+
+    if let Some(directory) = directory {
+        let validated_dir = security::validate_path(Path::new(&directory), None)?;
+        if let Some(dir) = workspace_templates_dir(&app, &validated_dir) {
+            collect_templates(&dir, WORKSPACE_SOURCE, &mut templates);
+        }
+    }
+
+    if let Some(dir) = app_templates_dir(&app) {
+        collect_templates(&dir, APP_SOURCE, &mut templates);
+    }
+
+    Ok(templates)
+}
+
+/// Loads the scene for a template id such as `workspace/meeting` or `app/retro`
+pub fn resolve_template(app: &AppHandle, directory: &Path, template_id: &str) -> Result<Value, String> {
+    if template_id == BLANK_TEMPLATE_ID {
+        return Ok(blank_scene());
+    }
+
+    let (source, name) = template_id
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid template id: {}", template_id))?;
+
+    let dir = match source {
+        WORKSPACE_SOURCE => workspace_templates_dir(app, directory),
+        APP_SOURCE => app_templates_dir(app),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Template not found: {}", template_id))?;
+
+    let path = security::safe_path_join(&dir, &format!("{}.excalidraw", name))?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read template {}: {}", template_id, e))?;
+    security::validate_excalidraw_content(&content)?;
+
+    let content = assets::inline_assets(&content, &path)?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid template JSON: {}", e))
+}
+
+/// Built-in variables available to every template
+pub fn default_variables(title: &str) -> HashMap<String, String> {
+    let now = chrono::Local::now();
+    let author = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+
+    HashMap::from([
+        ("title".to_string(), title.to_string()),
+        ("date".to_string(), now.format("%Y-%m-%d").to_string()),
+        ("time".to_string(), now.format("%H:%M").to_string()),
+        ("datetime".to_string(), now.format("%Y-%m-%d %H:%M").to_string()),
+        ("year".to_string(), now.format("%Y").to_string()),
+        ("author".to_string(), author),
+    ])
+}
+
+/// Element fields that may hold placeholders
+const VARIABLE_FIELDS: [&str; 3] = ["text", "originalText", "name"];
+
+/// Replaces each `{{name}}` placeholder in a single left-to-right pass, so the result does not
+/// depend on the order of `variables` and substituted values are never expanded again
+fn fill_placeholders(text: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}").and_then(|end| variables.get(&after[..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                result.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                result.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replaces `{{name}}` placeholders in element text and frame names. Changed text is rewrapped
+/// and labels are refitted in their container.
+pub fn apply_variables(scene: &mut Value, variables: &HashMap<String, String>) {
+    let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) else {
+        return;
+    };
+    for i in 0..elements.len() {
+        let mut changed = false;
+        for field in VARIABLE_FIELDS {
+            let Some(value) = render::str_field(&elements[i], field).filter(|v| v.contains("{{")) else {
+                continue;
+            };
+            let filled = fill_placeholders(value, variables);
+            if filled != value {
+                elements[i][field] = Value::String(filled);
+                changed = true;
+            }
+        }
+        if !changed || render::str_field(&elements[i], "type") != Some("text") {
+            continue;
+        }
+
+        let container = render::str_field(&elements[i], "containerId")
+            .and_then(|id| elements.iter().position(|e| render::str_field(e, "id") == Some(id)));
+        let mut container_element = container.map(|c| elements[c].clone());
+        elements::refit_text(&mut elements[i], container_element.as_mut());
+        if let (Some(c), Some(container_element)) = (container, container_element) {
+            elements[c] = container_element;
+        }
+    }
+}
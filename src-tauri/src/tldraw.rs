@@ -0,0 +1,1166 @@
+use super::*;
+use elements::Style;
+use render::{Bounds, Point};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// tldraw's palette: name, stroke color and the light tint used for fills
+const COLORS: [(&str, &str, &str); 13] = [
+    ("black", "#1d1d1d", "#e8e8e8"),
+    ("grey", "#9fa8b2", "#eceef0"),
+    ("light-violet", "#e085f4", "#f5eafa"),
+    ("violet", "#ae3ec9", "#ecdcf2"),
+    ("blue", "#4465e9", "#dce1f8"),
+    ("light-blue", "#4ba1f1", "#ddedfa"),
+    ("yellow", "#f1ac4b", "#f9f0e6"),
+    ("orange", "#e16919", "#f8e2d4"),
+    ("green", "#099268", "#d3e9e3"),
+    ("light-green", "#4cb05e", "#dbf0e0"),
+    ("light-red", "#f87777", "#f4dadb"),
+    ("red", "#e03131", "#f4dadb"),
+    ("white", "#ffffff", "#f5f5f5"),
+];
+
+/// Stroke widths and font sizes of tldraw's `size` style
+const SIZES: [(&str, f64, f64); 4] = [("s", 1.0, 18.0), ("m", 2.0, 24.0), ("l", 4.0, 36.0), ("xl", 6.0, 44.0)];
+
+const FONTS: [(&str, i64); 4] = [("draw", 5), ("sans", 2), ("serif", 2), ("mono", 3)];
+
+/// Record versions written on export; tldraw migrates them to its current schema on load
+const SCHEMA_SEQUENCES: [(&str, u32); 15] = [
+    ("com.tldraw.store", 4),
+    ("com.tldraw.asset", 1),
+    ("com.tldraw.asset.image", 3),
+    ("com.tldraw.document", 2),
+    ("com.tldraw.page", 1),
+    ("com.tldraw.shape", 4),
+    ("com.tldraw.shape.geo", 9),
+    ("com.tldraw.shape.text", 2),
+    ("com.tldraw.shape.arrow", 5),
+    ("com.tldraw.shape.line", 5),
+    ("com.tldraw.shape.draw", 2),
+    ("com.tldraw.shape.image", 4),
+    ("com.tldraw.shape.frame", 0),
+    ("com.tldraw.shape.group", 0),
+    ("com.tldraw.binding.arrow", 0),
+];
+
+/// A page of a `.tldr` document converted to a scene
+#[derive(Debug)]
+pub struct TldrawPage {
+    pub name: String,
+    pub scene: Value,
+    /// Shapes with no Excalidraw equivalent
+    pub skipped: Vec<models::SkippedElement>,
+}
+
+fn rotate((x, y): Point, angle: f64) -> Point {
+    let (sin, cos) = angle.sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+fn num(value: &Value, key: &str) -> f64 {
+    value.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0)
+}
+
+fn prop<'a>(shape: &'a Value, key: &str) -> Option<&'a str> {
+    shape.get("props").and_then(|p| render::str_field(p, key))
+}
+
+fn prop_num(shape: &Value, key: &str) -> Option<f64> {
+    shape.get("props").and_then(|p| render::num_field(p, key))
+}
+
+/// Label of a shape, from `text` or the `richText` document of newer files
+fn shape_text(shape: &Value) -> String {
+    if let Some(text) = prop(shape, "text") {
+        return text.to_string();
+    }
+    fn flatten(node: &Value, out: &mut String) {
+        match render::str_field(node, "type") {
+            Some("text") => out.push_str(render::str_field(node, "text").unwrap_or_default()),
+            Some("hardBreak") => out.push('\n'),
+            _ => {}
+        }
+        if let Some(children) = node.get("content").and_then(|c| c.as_array()) {
+            for (i, child) in children.iter().enumerate() {
+                if i > 0 && render::str_field(node, "type") == Some("doc") {
+                    out.push('\n');
+                }
+                flatten(child, out);
+            }
+        }
+    }
+    let mut text = String::new();
+    if let Some(rich) = shape.get("props").and_then(|p| p.get("richText")) {
+        flatten(rich, &mut text);
+    }
+    text
+}
+
+fn color_hex(name: Option<&str>) -> (&'static str, &'static str) {
+    let name = name.unwrap_or("black");
+    COLORS
+        .iter()
+        .find(|(n, ..)| *n == name)
+        .map(|(_, stroke, fill)| (*stroke, *fill))
+        .unwrap_or((COLORS[0].1, COLORS[0].2))
+}
+
+fn style_of(shape: &Value) -> Style {
+    let scale = prop_num(shape, "scale").unwrap_or(1.0);
+    let (stroke, tint) = color_hex(prop(shape, "color"));
+    let size = prop(shape, "size").unwrap_or("m");
+    let (_, stroke_width, font_size) = SIZES.iter().find(|(s, ..)| *s == size).copied().unwrap_or(SIZES[1]);
+    let dash = prop(shape, "dash").unwrap_or("draw");
+    let fill = prop(shape, "fill").unwrap_or("none");
+
+    Style {
+        stroke_color: stroke.to_string(),
+        background_color: if fill == "none" { "transparent".to_string() } else { tint.to_string() },
+        fill_style: if fill == "pattern" { "hachure" } else { "solid" }.to_string(),
+        stroke_width: stroke_width * scale,
+        stroke_style: match dash {
+            "dashed" | "dotted" => dash,
+            _ => "solid",
+        }
+        .to_string(),
+        roughness: if dash == "draw" { 1.0 } else { 0.0 },
+        opacity: shape.get("opacity").and_then(|o| o.as_f64()).unwrap_or(1.0) * 100.0,
+        rounded: false,
+        angle: 0.0,
+        font_size: font_size * scale,
+        font_family: FONTS
+            .iter()
+            .find(|(f, _)| Some(*f) == prop(shape, "font"))
+            .map(|(_, id)| *id)
+            .unwrap_or(5),
+        text_align: match prop(shape, "align").or(prop(shape, "textAlign")).unwrap_or("middle") {
+            a if a.starts_with("start") => "left",
+            a if a.starts_with("end") => "right",
+            _ => "center",
+        }
+        .to_string(),
+        vertical_align: match prop(shape, "verticalAlign").unwrap_or("middle") {
+            "start" => "top",
+            "end" => "bottom",
+            _ => "middle",
+        }
+        .to_string(),
+    }
+}
+
+fn label_style(shape: &Value, style: &Style) -> Style {
+    let mut label_style = style.clone();
+    // Notes color their background, so their text defaults to black
+    let fallback = if render::str_field(shape, "type") == Some("note") { None } else { prop(shape, "color") };
+    label_style.stroke_color = color_hex(prop(shape, "labelColor").or(fallback)).0.to_string();
+    label_style
+}
+
+fn tl_arrowhead(value: Option<&str>) -> Option<&'static str> {
+    match value.unwrap_or("none") {
+        "none" => None,
+        "triangle" | "inverted" => Some("triangle"),
+        "dot" => Some("circle"),
+        "diamond" => Some("diamond"),
+        "bar" | "pipe" => Some("bar"),
+        "square" => Some("triangle_outline"),
+        _ => Some("arrow"),
+    }
+}
+
+fn strip_id(id: &str) -> &str {
+    id.split_once(':').map(|(_, rest)| rest).unwrap_or(id)
+}
+
+/// World position and rotation of every shape; shapes store them relative to their parent
+fn world_transforms(shapes: &HashMap<&str, &Value>) -> HashMap<String, (Point, f64)> {
+    fn resolve(id: &str, shapes: &HashMap<&str, &Value>, cache: &mut HashMap<String, (Point, f64)>, depth: usize) -> (Point, f64) {
+        if let Some(transform) = cache.get(id) {
+            return *transform;
+        }
+        let Some(shape) = shapes.get(id) else {
+            return ((0.0, 0.0), 0.0);
+        };
+        let local = ((num(shape, "x"), num(shape, "y")), num(shape, "rotation"));
+        let parent = render::str_field(shape, "parentId").unwrap_or_default();
+        let transform = if parent.starts_with("shape:") && depth < 64 {
+            let ((px, py), parent_rotation) = resolve(parent, shapes, cache, depth + 1);
+            let (dx, dy) = rotate(local.0, parent_rotation);
+            ((px + dx, py + dy), parent_rotation + local.1)
+        } else {
+            local
+        };
+        cache.insert(id.to_string(), transform);
+        transform
+    }
+
+    let mut cache = HashMap::new();
+    for id in shapes.keys() {
+        resolve(id, shapes, &mut cache, 0);
+    }
+    cache
+}
+
+/// Axis-aligned box and angle of a `w`×`h` shape placed at `origin`, rotated around that corner
+fn excalidraw_box(origin: Point, rotation: f64, width: f64, height: f64) -> Bounds {
+    let (dx, dy) = rotate((width / 2.0, height / 2.0), rotation);
+    let (cx, cy) = (origin.0 + dx, origin.1 + dy);
+    (cx - width / 2.0, cy - height / 2.0, cx + width / 2.0, cy + height / 2.0)
+}
+
+struct ArrowEnds {
+    start: Option<(String, Point)>,
+    end: Option<(String, Point)>,
+}
+
+/// Bound shapes of arrows, from binding records or the props of older files
+fn arrow_bindings(records: &[Value], shapes: &HashMap<&str, &Value>) -> HashMap<String, ArrowEnds> {
+    let mut ends: HashMap<String, ArrowEnds> = HashMap::new();
+    let mut set = |arrow: &str, terminal: &str, target: &str, anchor: Point| {
+        let entry = ends.entry(arrow.to_string()).or_insert(ArrowEnds { start: None, end: None });
+        let value = Some((target.to_string(), anchor));
+        if terminal == "start" {
+            entry.start = value;
+        } else {
+            entry.end = value;
+        }
+    };
+    let anchor_of = |value: Option<&Value>| {
+        value
+            .map(|a| (num(a, "x"), num(a, "y")))
+            .unwrap_or((0.5, 0.5))
+    };
+
+    for record in records {
+        if render::str_field(record, "typeName") == Some("binding")
+            && render::str_field(record, "type") == Some("arrow")
+            && let (Some(from), Some(to)) = (render::str_field(record, "fromId"), render::str_field(record, "toId"))
+        {
+            let props = record.get("props").cloned().unwrap_or_default();
+            let terminal = render::str_field(&props, "terminal").unwrap_or("end");
+            set(from, terminal, to, anchor_of(props.get("normalizedAnchor")));
+        }
+    }
+    for (id, shape) in shapes {
+        if render::str_field(shape, "type") != Some("arrow") {
+            continue;
+        }
+        for terminal in ["start", "end"] {
+            let Some(value) = shape.get("props").and_then(|p| p.get(terminal)) else { continue };
+            if render::str_field(value, "type") == Some("binding")
+                && let Some(target) = render::str_field(value, "boundShapeId")
+            {
+                set(id, terminal, target, anchor_of(value.get("normalizedAnchor")));
+            }
+        }
+    }
+    ends
+}
+
+/// Converts the shapes of one page
+fn convert_page(
+    page_id: &str,
+    records: &[Value],
+    shapes: &HashMap<&str, &Value>,
+    transforms: &HashMap<String, (Point, f64)>,
+    assets: &HashMap<&str, &Value>,
+) -> (Vec<Value>, Map<String, Value>, Vec<models::SkippedElement>) {
+    let mut out: Vec<Value> = Vec::new();
+    let mut files = Map::new();
+    let mut skipped = Vec::new();
+    let bindings = arrow_bindings(records, shapes);
+
+    // Shapes on this page in drawing order: parents first, then by fractional index
+    let on_page = |shape: &Value| {
+        let mut parent = render::str_field(shape, "parentId").unwrap_or_default();
+        for _ in 0..64 {
+            if !parent.starts_with("shape:") {
+                break;
+            }
+            parent = shapes.get(parent).and_then(|p| render::str_field(p, "parentId")).unwrap_or_default();
+        }
+        parent == page_id
+    };
+    let sort_key = |shape: &Value| {
+        let mut keys = Vec::new();
+        let mut current = Some(shape);
+        while let Some(s) = current {
+            keys.push(render::str_field(s, "index").unwrap_or_default().to_string());
+            current = render::str_field(s, "parentId").and_then(|p| shapes.get(p)).copied();
+        }
+        keys.reverse();
+        keys
+    };
+    let mut ordered: Vec<&Value> = shapes.values().copied().filter(|s| on_page(s)).collect();
+    ordered.sort_by_key(|s| sort_key(s));
+
+    let ancestor_ids = |shape: &Value, kind: &str| {
+        let mut ids = Vec::new();
+        let mut parent = render::str_field(shape, "parentId");
+        while let Some(id) = parent.filter(|p| p.starts_with("shape:")) {
+            let Some(parent_shape) = shapes.get(id) else { break };
+            if render::str_field(parent_shape, "type") == Some(kind) {
+                ids.push(strip_id(id).to_string());
+            }
+            parent = render::str_field(parent_shape, "parentId");
+        }
+        ids
+    };
+
+    // Geometry of bindable shapes, for clipping bound arrow ends to outlines
+    let mut outlines: HashMap<String, (&'static str, Bounds)> = HashMap::new();
+
+    for shape in &ordered {
+        let id = render::str_field(shape, "id").unwrap_or_default();
+        let element_id = strip_id(id).to_string();
+        let kind = render::str_field(shape, "type").unwrap_or_default();
+        let Some(&(origin, rotation)) = transforms.get(id) else { continue };
+        let mut style = style_of(shape);
+        let scale = prop_num(shape, "scale").unwrap_or(1.0);
+        let (width, height) = (
+            prop_num(shape, "w").unwrap_or(0.0),
+            prop_num(shape, "h").unwrap_or(0.0) + prop_num(shape, "growY").unwrap_or(0.0),
+        );
+        let text = shape_text(shape);
+
+        let mut created: Vec<Value> = match kind {
+            "geo" | "note" => {
+                let (element_type, width, height) = if kind == "note" {
+                    style.background_color = color_hex(prop(shape, "color")).1.to_string();
+                    style.stroke_color = "transparent".to_string();
+                    ("rectangle", 200.0 * scale, (200.0 + prop_num(shape, "growY").unwrap_or(0.0)) * scale)
+                } else {
+                    let element_type = match prop(shape, "geo").unwrap_or("rectangle") {
+                        "ellipse" | "oval" => "ellipse",
+                        "diamond" | "rhombus" => "diamond",
+                        _ => "rectangle",
+                    };
+                    (element_type, width, height)
+                };
+                style.angle = rotation;
+                let bounds = excalidraw_box(origin, rotation, width, height);
+                let mut element = elements::shape(element_type, &element_id, bounds, &style);
+                element["link"] = json!(prop(shape, "url").filter(|u| !u.is_empty()));
+                outlines.insert(id.to_string(), (element_type, bounds));
+                let mut created = vec![];
+                let label = (!text.is_empty()).then(|| {
+                    elements::label(&format!("{}-label", element_id), &mut element, &text, &label_style(shape, &style))
+                });
+                created.push(element);
+                created.extend(label);
+                created
+            }
+            "text" => {
+                style.angle = rotation;
+                let (text_width, text_height) = elements::text_size(&text, style.font_size);
+                let bounds = excalidraw_box(origin, rotation, width.max(text_width), text_height);
+                let mut element = elements::text(&element_id, (bounds.0, bounds.1), &text, &style);
+                element["width"] = json!(bounds.2 - bounds.0);
+                outlines.insert(id.to_string(), ("rectangle", bounds));
+                vec![element]
+            }
+            "frame" => {
+                style = Style {
+                    roughness: 0.0,
+                    ..Style::default()
+                };
+                let bounds = excalidraw_box(origin, 0.0, width, height);
+                let mut element = elements::shape("frame", &element_id, bounds, &style);
+                element["name"] = json!(prop(shape, "name").filter(|n| !n.is_empty()));
+                outlines.insert(id.to_string(), ("rectangle", bounds));
+                vec![element]
+            }
+            "image" => {
+                style.angle = rotation;
+                let bounds = excalidraw_box(origin, rotation, width, height);
+                let asset = prop(shape, "assetId").and_then(|a| assets.get(a));
+                let source = asset.and_then(|a| a.get("props")).and_then(|p| render::str_field(p, "src"));
+                let Some(data_url) = source.filter(|s| s.starts_with("data:")) else {
+                    skipped.push(models::SkippedElement {
+                        id: element_id,
+                        reason: "Image is not embedded in the file".to_string(),
+                    });
+                    continue;
+                };
+                let file_id = strip_id(prop(shape, "assetId").unwrap_or(id)).to_string();
+                let mime_type = asset
+                    .and_then(|a| a.get("props"))
+                    .and_then(|p| render::str_field(p, "mimeType"))
+                    .unwrap_or("image/png");
+                files.insert(
+                    file_id.clone(),
+                    json!({
+                        "mimeType": mime_type,
+                        "id": file_id,
+                        "dataURL": data_url,
+                        "created": chrono::Utc::now().timestamp_millis(),
+                    }),
+                );
+                let mut element = elements::shape("image", &element_id, bounds, &style);
+                element["fileId"] = json!(file_id);
+                element["status"] = json!("saved");
+                element["scale"] = json!([
+                    if prop_value_bool(shape, "flipX") { -1 } else { 1 },
+                    if prop_value_bool(shape, "flipY") { -1 } else { 1 }
+                ]);
+                element["strokeColor"] = json!("transparent");
+                outlines.insert(id.to_string(), ("rectangle", bounds));
+                vec![element]
+            }
+            "draw" | "highlight" => {
+                let mut points = Vec::new();
+                let segments = shape.get("props").and_then(|p| p.get("segments")).and_then(|s| s.as_array());
+                for segment in segments.into_iter().flatten() {
+                    for point in segment.get("points").and_then(|p| p.as_array()).into_iter().flatten() {
+                        let (dx, dy) = rotate((num(point, "x") * scale, num(point, "y") * scale), rotation);
+                        points.push((origin.0 + dx, origin.1 + dy));
+                    }
+                }
+                if points.is_empty() {
+                    skipped.push(models::SkippedElement {
+                        id: element_id,
+                        reason: "Drawing without points".to_string(),
+                    });
+                    continue;
+                }
+                if kind == "highlight" {
+                    style.opacity = 40.0;
+                    style.stroke_width *= 4.0;
+                }
+                let mut element = elements::linear("freedraw", &element_id, &points, &style, None, None);
+                for key in ["startBinding", "endBinding", "startArrowhead", "endArrowhead", "elbowed", "lastCommittedPoint"] {
+                    if let Some(object) = element.as_object_mut() {
+                        object.remove(key);
+                    }
+                }
+                element["pressures"] = json!([]);
+                element["simulatePressure"] = json!(true);
+                vec![element]
+            }
+            "line" => {
+                let raw = shape.get("props").and_then(|p| p.get("points").or(p.get("handles")));
+                let mut handles: Vec<(String, Point)> = match raw {
+                    Some(Value::Object(map)) => map
+                        .values()
+                        .map(|h| (render::str_field(h, "index").unwrap_or_default().to_string(), (num(h, "x"), num(h, "y"))))
+                        .collect(),
+                    Some(Value::Array(list)) => list
+                        .iter()
+                        .enumerate()
+                        .map(|(i, h)| (format!("{:08}", i), (num(h, "x"), num(h, "y"))))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                handles.sort_by(|a, b| a.0.cmp(&b.0));
+                let points: Vec<Point> = handles
+                    .iter()
+                    .map(|(_, p)| {
+                        let (dx, dy) = rotate((p.0 * scale, p.1 * scale), rotation);
+                        (origin.0 + dx, origin.1 + dy)
+                    })
+                    .collect();
+                if points.len() < 2 {
+                    skipped.push(models::SkippedElement {
+                        id: element_id,
+                        reason: "Line without points".to_string(),
+                    });
+                    continue;
+                }
+                style.rounded = prop(shape, "spline") == Some("cubic");
+                vec![elements::linear("line", &element_id, &points, &style, None, None)]
+            }
+            "arrow" => {
+                let terminal = |name: &str| {
+                    let value = shape.get("props").and_then(|p| p.get(name));
+                    let local = value.map(|v| (num(v, "x"), num(v, "y"))).unwrap_or_default();
+                    let (dx, dy) = rotate(local, rotation);
+                    (origin.0 + dx, origin.1 + dy)
+                };
+                let (start, end) = (terminal("start"), terminal("end"));
+                let bend = prop_num(shape, "bend").unwrap_or(0.0);
+                let mut points = vec![start, end];
+                if bend.abs() > 0.5 {
+                    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+                    let length = dx.hypot(dy).max(1e-6);
+                    let middle = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+                    points.insert(1, (middle.0 - dy / length * bend, middle.1 + dx / length * bend));
+                    style.rounded = true;
+                }
+                let mut element = elements::linear(
+                    "arrow",
+                    &element_id,
+                    &points,
+                    &style,
+                    tl_arrowhead(prop(shape, "arrowheadStart")),
+                    tl_arrowhead(prop(shape, "arrowheadEnd")),
+                );
+                let label = (!text.is_empty()).then(|| {
+                    elements::label(&format!("{}-label", element_id), &mut element, &text, &label_style(shape, &style))
+                });
+                let mut created = vec![element];
+                created.extend(label);
+                created
+            }
+            // Groups only add groupIds to their children
+            "group" => continue,
+            _ => {
+                skipped.push(models::SkippedElement {
+                    id: element_id,
+                    reason: format!("No Excalidraw equivalent for {}", kind),
+                });
+                continue;
+            }
+        };
+
+        let frame = ancestor_ids(shape, "frame").into_iter().next();
+        let mut groups = ancestor_ids(shape, "group");
+        groups.reverse();
+        for element in &mut created {
+            element["frameId"] = json!(frame);
+            element["locked"] = json!(shape.get("isLocked").and_then(|l| l.as_bool()).unwrap_or(false));
+            for group in groups.iter().rev() {
+                elements::add_group(element, group);
+            }
+        }
+        out.extend(created);
+    }
+
+    // Bound arrow ends are moved onto the outline of their shape, like the editor does
+    for (arrow_id, ends) in &bindings {
+        let element_id = strip_id(arrow_id);
+        let Some(index) = out.iter().position(|e| render::str_field(e, "id") == Some(element_id)) else {
+            continue;
+        };
+        let mut points = elements::absolute_points(&out[index]);
+        if points.len() < 2 {
+            continue;
+        }
+        let last = points.len() - 1;
+        for (end, is_start) in [(&ends.start, true), (&ends.end, false)] {
+            let Some((target, _)) = end else { continue };
+            let Some(&(kind, bounds)) = outlines.get(target.as_str()) else { continue };
+            let toward = if is_start { points[1] } else { points[last - 1] };
+            let point = elements::outline_point(kind, bounds, toward, elements::BINDING_GAP);
+            points[if is_start { 0 } else { last }] = point;
+        }
+        let arrow = &out[index];
+        let rebuilt = elements::linear(
+            "arrow",
+            element_id,
+            &points,
+            &Style::default(),
+            render::str_field(arrow, "startArrowhead"),
+            render::str_field(arrow, "endArrowhead"),
+        );
+        for key in ["x", "y", "width", "height", "points"] {
+            out[index][key] = rebuilt[key].clone();
+        }
+        let target = |end: &Option<(String, Point)>| end.as_ref().map(|(t, _)| strip_id(t).to_string());
+        let (start, end) = (target(&ends.start), target(&ends.end));
+        elements::bind_arrow(&mut out, element_id, start.as_deref(), end.as_deref());
+    }
+
+    (out, files, skipped)
+}
+
+fn prop_value_bool(shape: &Value, key: &str) -> bool {
+    shape
+        .get("props")
+        .and_then(|p| p.get(key))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Splits a `.tldr` document into one scene per page
+pub fn parse_tldraw(content: &str) -> Result<Vec<TldrawPage>, String> {
+    let document: Value = serde_json::from_str(content).map_err(|e| format!("Invalid tldraw file: {}", e))?;
+    let Some(records) = document.get("records").and_then(|r| r.as_array()) else {
+        return Err("Unsupported tldraw file: no records found (files from tldraw 1.x are not supported)".to_string());
+    };
+
+    let of_type = |type_name: &'static str| {
+        records
+            .iter()
+            .filter(move |r| render::str_field(r, "typeName") == Some(type_name))
+    };
+    let shapes: HashMap<&str, &Value> = of_type("shape")
+        .filter_map(|s| render::str_field(s, "id").map(|id| (id, s)))
+        .collect();
+    let assets: HashMap<&str, &Value> = of_type("asset")
+        .filter_map(|a| render::str_field(a, "id").map(|id| (id, a)))
+        .collect();
+    let transforms = world_transforms(&shapes);
+
+    let mut pages: Vec<&Value> = of_type("page").collect();
+    pages.sort_by_key(|p| render::str_field(p, "index").unwrap_or_default().to_string());
+
+    let mut result = Vec::new();
+    for page in pages {
+        let page_id = render::str_field(page, "id").unwrap_or_default();
+        let (page_elements, files, skipped) = convert_page(page_id, records, &shapes, &transforms, &assets);
+        let mut scene = elements::scene(page_elements);
+        scene["files"] = Value::Object(files);
+        result.push(TldrawPage {
+            name: render::str_field(page, "name").unwrap_or("Page").to_string(),
+            scene,
+            skipped,
+        });
+    }
+    if result.is_empty() {
+        return Err("tldraw file has no pages".to_string());
+    }
+    Ok(result)
+}
+
+/// Imports the pages of a `.tldr` file as drawings
+#[tauri::command]
+pub async fn import_tldraw(
+    file_path: String,
+    output_dir: Option<String>,
+) -> Result<models::DiagramImportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let content = fs::read_to_string(&validated_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let pages = parse_tldraw(&content)?;
+
+    let dir = match output_dir {
+        Some(dir) => security::validate_path(Path::new(&dir), None)?,
+        None => validated_path
+            .parent()
+            .ok_or("File has no parent directory")?
+            .to_path_buf(),
+    };
+    let stem = validated_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "tldraw".to_string());
+
+    let single = pages.len() == 1;
+    let mut report = models::DiagramImportReport {
+        created: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for page in pages {
+        let name = if single {
+            format!("{}.excalidraw", stem)
+        } else {
+            format!("{}-{}.excalidraw", stem, drawio::file_name_safe(&page.name))
+        };
+        let path = file_ops::unique_file_path(&dir, &name)?;
+        let content = serde_json::to_string_pretty(&page.scene)
+            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        report.created.push(path.to_string_lossy().to_string());
+        report.skipped.extend(page.skipped);
+    }
+
+    Ok(report)
+}
+
+/// Fractional index keys in drawing order: a1…az, then b10…bzz and so on
+fn index_key(n: usize) -> String {
+    const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut digits = Vec::new();
+    let mut rest = n;
+    loop {
+        digits.push(DIGITS[rest % 62] as char);
+        rest /= 62;
+        if rest == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    let head = (b'a' + (digits.len() - 1) as u8) as char;
+    format!("{}{}", head, digits.into_iter().collect::<String>())
+}
+
+fn hex_rgb(color: &str) -> Option<(f64, f64, f64)> {
+    let hex = color.strip_prefix('#')?;
+    let hex = if hex.len() == 3 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.get(..6)?.to_string()
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(f64::from);
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Closest palette color, comparing against stroke or fill shades
+fn nearest_color(color: &str, fill: bool) -> &'static str {
+    let Some((r, g, b)) = hex_rgb(color) else {
+        return "black";
+    };
+    COLORS
+        .iter()
+        .min_by(|a, b2| {
+            let distance = |(_, stroke, tint): &(&str, &str, &str)| {
+                let (cr, cg, cb) = hex_rgb(if fill { tint } else { stroke }).unwrap_or_default();
+                (r - cr).powi(2) + (g - cg).powi(2) + (b - cb).powi(2)
+            };
+            distance(a).total_cmp(&distance(b2))
+        })
+        .map(|(name, ..)| *name)
+        .unwrap_or("black")
+}
+
+fn tl_size(stroke_width: f64) -> &'static str {
+    SIZES
+        .iter()
+        .find(|(_, width, _)| stroke_width <= *width)
+        .map(|(size, ..)| *size)
+        .unwrap_or("xl")
+}
+
+fn tl_font_size(font_size: f64) -> &'static str {
+    SIZES
+        .iter()
+        .min_by(|a, b| (a.2 - font_size).abs().total_cmp(&(b.2 - font_size).abs()))
+        .map(|(size, ..)| *size)
+        .unwrap_or("m")
+}
+
+fn tl_font(family: i64) -> &'static str {
+    match family {
+        2 | 6 | 9 => "sans",
+        3 | 8 => "mono",
+        _ => "draw",
+    }
+}
+
+fn tl_dash(element: &Value) -> &'static str {
+    match render::str_field(element, "strokeStyle") {
+        Some("dashed") => "dashed",
+        Some("dotted") => "dotted",
+        _ if render::num_field(element, "roughness").unwrap_or(0.0) > 0.0 => "draw",
+        _ => "solid",
+    }
+}
+
+fn tl_fill(element: &Value) -> &'static str {
+    match (render::str_field(element, "backgroundColor"), render::str_field(element, "fillStyle")) {
+        (None | Some("transparent"), _) => "none",
+        (_, Some("hachure" | "cross-hatch" | "zigzag")) => "pattern",
+        _ => "solid",
+    }
+}
+
+fn excalidraw_arrowhead(value: Option<&str>) -> &'static str {
+    match value {
+        None => "none",
+        Some("triangle" | "triangle_outline") => "triangle",
+        Some("dot" | "circle" | "circle_outline") => "dot",
+        Some("diamond" | "diamond_outline") => "diamond",
+        Some("bar") => "bar",
+        _ => "arrow",
+    }
+}
+
+fn tl_align(value: Option<&str>) -> &'static str {
+    match value {
+        Some("left") => "start",
+        Some("right") => "end",
+        _ => "middle",
+    }
+}
+
+/// Converts a scene into a single-page `.tldr` document; returns it with the elements
+/// that have no tldraw equivalent
+pub fn scene_to_tldraw(scene: &Value, page_name: &str) -> (Value, Vec<models::SkippedElement>) {
+    let live: Vec<&Value> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| e.iter().filter(|e| !render::bool_field(e, "isDeleted").unwrap_or(false)).collect())
+        .unwrap_or_default();
+    let bound_text: HashMap<&str, &Value> = live
+        .iter()
+        .filter(|e| render::str_field(e, "type") == Some("text"))
+        .filter_map(|e| render::str_field(e, "containerId").map(|c| (c, *e)))
+        .collect();
+    let frames: HashMap<&str, Bounds> = live
+        .iter()
+        .filter(|e| render::str_field(e, "type") == Some("frame"))
+        .filter_map(|e| render::str_field(e, "id").map(|id| (id, render::element_bounds(e))))
+        .collect();
+    let files = scene.get("files").and_then(|f| f.as_object());
+
+    let page_id = "page:page";
+    let mut records = vec![
+        json!({ "gridSize": 10, "name": "", "meta": {}, "id": "document:document", "typeName": "document" }),
+        json!({ "meta": {}, "id": page_id, "name": page_name, "index": "a1", "typeName": "page" }),
+    ];
+    let mut skipped = Vec::new();
+    let mut assets_added: Vec<String> = Vec::new();
+
+    for (n, element) in live.iter().enumerate() {
+        let Some(id) = render::str_field(element, "id") else { continue };
+        let kind = render::str_field(element, "type").unwrap_or_default();
+        if kind == "text" && bound_text.values().any(|t| render::str_field(t, "id") == Some(id)) {
+            continue;
+        }
+
+        let x = render::num_field(element, "x").unwrap_or(0.0);
+        let y = render::num_field(element, "y").unwrap_or(0.0);
+        let width = render::num_field(element, "width").unwrap_or(0.0);
+        let height = render::num_field(element, "height").unwrap_or(0.0);
+        let angle = render::num_field(element, "angle").unwrap_or(0.0);
+        let stroke = render::str_field(element, "strokeColor").unwrap_or("#1e1e1e");
+        let background = render::str_field(element, "backgroundColor").unwrap_or("transparent");
+        let color = if background != "transparent" && stroke == "transparent" {
+            nearest_color(background, true)
+        } else {
+            nearest_color(stroke, false)
+        };
+        let size = tl_size(render::num_field(element, "strokeWidth").unwrap_or(2.0));
+        let label = bound_text.get(id).copied();
+        let label_text = label
+            .and_then(|t| render::str_field(t, "originalText").or(render::str_field(t, "text")))
+            .unwrap_or_default();
+        let label_font = label.map(|t| tl_font(render::num_field(t, "fontFamily").unwrap_or(5.0) as i64)).unwrap_or("draw");
+        let label_color = label
+            .and_then(|t| render::str_field(t, "strokeColor"))
+            .map(|c| nearest_color(c, false))
+            .unwrap_or(color);
+
+        // Children of frames are stored relative to the frame
+        let frame = render::str_field(element, "frameId").filter(|f| frames.contains_key(f) && kind != "frame");
+        let (parent_id, offset) = match frame {
+            Some(frame) => (format!("shape:{}", frame), (frames[frame].0, frames[frame].1)),
+            None => (page_id.to_string(), (0.0, 0.0)),
+        };
+        // tldraw rotates around the top left corner, Excalidraw around the center
+        let (cx, cy) = (x + width / 2.0, y + height / 2.0);
+        let (rx, ry) = rotate((width / 2.0, height / 2.0), angle);
+        let origin = (cx - rx - offset.0, cy - ry - offset.1);
+
+        let props = match kind {
+            "rectangle" | "ellipse" | "diamond" => json!({
+                "w": width,
+                "h": height,
+                "geo": kind,
+                "color": color,
+                "labelColor": label_color,
+                "fill": tl_fill(element),
+                "dash": tl_dash(element),
+                "size": size,
+                "font": label_font,
+                "text": label_text,
+                "align": tl_align(label.and_then(|t| render::str_field(t, "textAlign"))),
+                "verticalAlign": match label.and_then(|t| render::str_field(t, "verticalAlign")) {
+                    Some("top") => "start",
+                    Some("bottom") => "end",
+                    _ => "middle",
+                },
+                "growY": 0,
+                "url": render::str_field(element, "link").unwrap_or_default(),
+                "scale": 1,
+            }),
+            "text" => json!({
+                "color": color,
+                "size": tl_font_size(render::num_field(element, "fontSize").unwrap_or(20.0)),
+                "font": tl_font(render::num_field(element, "fontFamily").unwrap_or(5.0) as i64),
+                "textAlign": tl_align(render::str_field(element, "textAlign")),
+                "w": width,
+                "text": render::str_field(element, "originalText").or(render::str_field(element, "text")).unwrap_or_default(),
+                "scale": 1,
+                "autoSize": true,
+            }),
+            "frame" | "magicframe" => json!({
+                "w": width,
+                "h": height,
+                "name": render::str_field(element, "name").unwrap_or_default(),
+            }),
+            "arrow" => {
+                let points = elements::absolute_points(element);
+                let (Some(first), Some(last)) = (points.first().copied(), points.last().copied()) else {
+                    skipped.push(models::SkippedElement {
+                        id: id.to_string(),
+                        reason: "Arrow without points".to_string(),
+                    });
+                    continue;
+                };
+                // A three point arrow keeps its curve as a bend
+                let bend = if points.len() == 3 {
+                    let (dx, dy) = (last.0 - first.0, last.1 - first.1);
+                    let length = dx.hypot(dy).max(1e-6);
+                    let middle = ((first.0 + last.0) / 2.0, (first.1 + last.1) / 2.0);
+                    (points[1].0 - middle.0) * -dy / length + (points[1].1 - middle.1) * dx / length
+                } else {
+                    0.0
+                };
+                let local = |(px, py): Point| json!({ "x": px - x, "y": py - y });
+                for (terminal, field) in [("start", "startBinding"), ("end", "endBinding")] {
+                    if let Some(target) = element.get(field).and_then(|b| render::str_field(b, "elementId"))
+                        && live.iter().any(|e| render::str_field(e, "id") == Some(target))
+                    {
+                        records.push(json!({
+                            "meta": {},
+                            "id": format!("binding:{}-{}", id, terminal),
+                            "type": "arrow",
+                            "fromId": format!("shape:{}", id),
+                            "toId": format!("shape:{}", target),
+                            "props": {
+                                "terminal": terminal,
+                                "normalizedAnchor": { "x": 0.5, "y": 0.5 },
+                                "isExact": false,
+                                "isPrecise": false,
+                            },
+                            "typeName": "binding",
+                        }));
+                    }
+                }
+                json!({
+                    "dash": tl_dash(element),
+                    "size": size,
+                    "fill": "none",
+                    "color": color,
+                    "labelColor": label_color,
+                    "bend": bend,
+                    "start": local(first),
+                    "end": local(last),
+                    "arrowheadStart": excalidraw_arrowhead(render::str_field(element, "startArrowhead")),
+                    "arrowheadEnd": excalidraw_arrowhead(render::str_field(element, "endArrowhead")),
+                    "text": label_text,
+                    "labelPosition": 0.5,
+                    "font": label_font,
+                    "scale": 1,
+                })
+            }
+            "line" => {
+                let points: Map<String, Value> = render::element_points(element)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (px, py))| {
+                        let key = index_key(i + 1);
+                        (key.clone(), json!({ "id": key, "index": key, "x": px, "y": py }))
+                    })
+                    .collect();
+                json!({
+                    "color": color,
+                    "dash": tl_dash(element),
+                    "size": size,
+                    "spline": if element.get("roundness").is_some_and(|r| !r.is_null()) { "cubic" } else { "line" },
+                    "points": points,
+                    "scale": 1,
+                })
+            }
+            "freedraw" => {
+                let points: Vec<Value> = render::element_points(element)
+                    .into_iter()
+                    .map(|(px, py)| json!({ "x": px, "y": py, "z": 0.5 }))
+                    .collect();
+                json!({
+                    "segments": [{ "type": "free", "points": points }],
+                    "color": color,
+                    "fill": "none",
+                    "dash": "draw",
+                    "size": size,
+                    "isComplete": true,
+                    "isClosed": false,
+                    "isPen": false,
+                    "scale": 1,
+                })
+            }
+            "image" => {
+                let file_id = render::str_field(element, "fileId").unwrap_or_default();
+                let Some(file) = files.and_then(|f| f.get(file_id)) else {
+                    skipped.push(models::SkippedElement {
+                        id: id.to_string(),
+                        reason: format!("Image file {} is missing", file_id),
+                    });
+                    continue;
+                };
+                let asset_id = format!("asset:{}", file_id);
+                if !assets_added.contains(&asset_id) {
+                    assets_added.push(asset_id.clone());
+                    records.push(json!({
+                        "meta": {},
+                        "id": asset_id,
+                        "type": "image",
+                        "typeName": "asset",
+                        "props": {
+                            "name": file_id,
+                            "src": render::str_field(file, "dataURL").unwrap_or_default(),
+                            "w": width,
+                            "h": height,
+                            "mimeType": render::str_field(file, "mimeType").unwrap_or("image/png"),
+                            "isAnimated": false,
+                        },
+                    }));
+                }
+                let scale = element.get("scale").and_then(|s| s.as_array());
+                let flipped = |i: usize| scale.and_then(|s| s.get(i)).and_then(|v| v.as_f64()).is_some_and(|v| v < 0.0);
+                json!({
+                    "w": width,
+                    "h": height,
+                    "playing": true,
+                    "url": "",
+                    "assetId": asset_id,
+                    "crop": null,
+                    "flipX": flipped(0),
+                    "flipY": flipped(1),
+                })
+            }
+            _ => {
+                skipped.push(models::SkippedElement {
+                    id: id.to_string(),
+                    reason: format!("No tldraw equivalent for {}", kind),
+                });
+                continue;
+            }
+        };
+
+        let shape_type = match kind {
+            "rectangle" | "ellipse" | "diamond" => "geo",
+            "magicframe" => "frame",
+            "freedraw" => "draw",
+            other => other,
+        };
+        // Linear shapes keep their points relative to x/y and are not rotated
+        let (position, rotation) = match kind {
+            "arrow" | "line" | "freedraw" => ((x - offset.0, y - offset.1), 0.0),
+            _ => (origin, angle),
+        };
+        records.push(json!({
+            "x": position.0,
+            "y": position.1,
+            "rotation": rotation,
+            "isLocked": render::bool_field(element, "locked").unwrap_or(false),
+            "opacity": render::num_field(element, "opacity").unwrap_or(100.0) / 100.0,
+            "meta": {},
+            "id": format!("shape:{}", id),
+            "type": shape_type,
+            "props": props,
+            "parentId": parent_id,
+            "index": index_key(n + 1),
+            "typeName": "shape",
+        }));
+    }
+
+    let sequences: Map<String, Value> = SCHEMA_SEQUENCES
+        .iter()
+        .map(|(name, version)| (name.to_string(), json!(version)))
+        .collect();
+    let document = json!({
+        "tldrawFileFormatVersion": 1,
+        "schema": { "schemaVersion": 2, "sequences": sequences },
+        "records": records,
+    });
+    (document, skipped)
+}
+
+/// Writes a drawing as a `.tldr` file
+#[tauri::command]
+pub async fn export_tldraw(
+    file_path: String,
+    output_path: Option<String>,
+) -> Result<models::DiagramExportReport, String> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let scene = file_ops::load_scene(&validated_path)?;
+
+    let page_name = validated_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(formats::drawing_stem)
+        .unwrap_or("Page 1");
+    let (document, skipped) = scene_to_tldraw(&scene, page_name);
+
+    let output = render::output_path_for(&validated_path, output_path, "tldr")?;
+    let content = serde_json::to_string_pretty(&document)
+        .map_err(|e| format!("Failed to serialize tldraw file: {}", e))?;
+    fs::write(&output, content).map_err(|e| format!("Failed to write tldraw file: {}", e))?;
+
+    Ok(models::DiagramExportReport {
+        output_path: output.to_string_lossy().to_string(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene() -> Value {
+        json!({
+            "type": "excalidraw",
+            "version": 2,
+            "elements": [
+                { "id": "box", "type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50,
+                  "boundElements": [{ "id": "label", "type": "text" }] },
+                { "id": "label", "type": "text", "x": 10, "y": 10, "width": 80, "height": 25,
+                  "text": "Box", "originalText": "Box", "fontSize": 20, "containerId": "box" },
+                { "id": "ring", "type": "ellipse", "x": 200, "y": 0, "width": 50, "height": 50 },
+                { "id": "web", "type": "embeddable", "x": 0, "y": 100, "width": 50, "height": 50 },
+            ],
+            "appState": {},
+            "files": {},
+        })
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert!(parse_tldraw("{").unwrap_err().starts_with("Invalid tldraw file"));
+        assert!(parse_tldraw(r#"{"document": {}}"#).unwrap_err().starts_with("Unsupported tldraw file"));
+        assert_eq!(parse_tldraw(r#"{"records": []}"#).unwrap_err(), "tldraw file has no pages");
+    }
+
+    #[test]
+    fn reports_shapes_that_cannot_be_imported() {
+        let shape = |id: &str, kind: &str, props: Value| {
+            json!({
+                "typeName": "shape", "id": format!("shape:{}", id), "type": kind, "parentId": "page:1",
+                "index": "a1", "x": 0, "y": 0, "rotation": 0, "props": props,
+            })
+        };
+        let document = json!({
+            "records": [
+                { "typeName": "page", "id": "page:1", "name": "Page", "index": "a1" },
+                { "typeName": "asset", "id": "asset:1", "type": "image",
+                  "props": { "src": "https://example.com/photo.png", "mimeType": "image/png" } },
+                shape("box", "geo", json!({ "w": 10, "h": 10, "geo": "rectangle" })),
+                shape("web", "embed", json!({ "w": 10, "h": 10 })),
+                shape("photo", "image", json!({ "w": 10, "h": 10, "assetId": "asset:1" })),
+                shape("scribble", "draw", json!({ "segments": [] })),
+                shape("stroke", "line", json!({ "points": {} })),
+            ],
+        });
+
+        let pages = parse_tldraw(&document.to_string()).unwrap();
+        let mut skipped: Vec<(&str, &str)> = pages[0]
+            .skipped
+            .iter()
+            .map(|s| (s.id.as_str(), s.reason.as_str()))
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                ("photo", "Image is not embedded in the file"),
+                ("scribble", "Drawing without points"),
+                ("stroke", "Line without points"),
+                ("web", "No Excalidraw equivalent for embed"),
+            ]
+        );
+        assert_eq!(pages[0].scene["elements"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn exported_documents_import_again() {
+        let (document, skipped) = scene_to_tldraw(&scene(), "Page");
+        let skipped: Vec<&str> = skipped.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(skipped, ["web"]);
+
+        let pages = parse_tldraw(&document.to_string()).unwrap();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].name, "Page");
+        assert!(pages[0].skipped.is_empty());
+        let elements = pages[0].scene["elements"].as_array().unwrap();
+        let kinds: Vec<&str> = elements.iter().filter_map(|e| e["type"].as_str()).collect();
+        assert_eq!(kinds, ["rectangle", "text", "ellipse"]);
+        let text = elements.iter().find(|e| e["type"] == "text").unwrap();
+        assert_eq!(text["text"], "Box");
+        assert!(text["containerId"].is_string());
+    }
+}
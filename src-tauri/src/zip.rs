@@ -0,0 +1,253 @@
+//! Minimal zip archive support: deflate or stored entries, no zip64, no encryption
+
+use flate2::Compression;
+use flate2::Crc;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// General purpose flag marking entry names as UTF-8
+const UTF8_NAMES: u16 = 1 << 11;
+
+/// Largest uncompressed entry accepted when reading, to stop zip bombs
+const MAX_ENTRY_SIZE: u64 = 512 * 1024 * 1024;
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+/// Current local time in MS-DOS format
+fn dos_time() -> (u16, u16) {
+    use chrono::{Datelike, Timelike};
+    let now = chrono::Local::now();
+    let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+    let date = ((((now.year() - 1980).max(0) as u32) << 9) | (now.month() << 5) | now.day()) as u16;
+    (time, date)
+}
+
+/// Builds a zip archive from `(name, data)` pairs; names use `/` as separator
+pub fn write_zip(entries: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    // Without zip64 the entry count is a 16-bit field
+    let count = u16::try_from(entries.len())
+        .map_err(|_| format!("Archive has {} entries, the maximum is {}", entries.len(), u16::MAX))?;
+    let (time, date) = dos_time();
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, data) in entries {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        let deflated = encoder.finish().map_err(|e| format!("Failed to compress {}: {}", name, e))?;
+        // Already compressed data such as PNG images is stored as is
+        let (method, body) = if deflated.len() < data.len() { (DEFLATED, deflated) } else { (STORED, data.clone()) };
+
+        if out.len() > u32::MAX as usize || data.len() > u32::MAX as usize {
+            return Err("Archive is too large".to_string());
+        }
+        let name_length = u16::try_from(name.len())
+            .map_err(|_| format!("Entry name is {} bytes long, the maximum is {}", name.len(), u16::MAX))?;
+        let offset = out.len() as u32;
+        let crc = crc32(data);
+
+        let mut header = Vec::new();
+        for value in [0x14u16, UTF8_NAMES, method, time, date] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [crc, body.len() as u32, data.len() as u32] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&name_length.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+
+        out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&body);
+
+        central.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        central.extend_from_slice(&0x14u16.to_le_bytes());
+        central.extend_from_slice(&header);
+        // Comment length, disk number, internal and external attributes
+        for value in [0u16, 0, 0] {
+            central.extend_from_slice(&value.to_le_bytes());
+        }
+        central.extend_from_slice(&0u32.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let central_offset = out.len() as u32;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    for value in [0u16, 0, count, count] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_offset.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    Ok(out)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, String> {
+    bytes
+        .get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Truncated zip archive".to_string())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, String> {
+    bytes
+        .get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "Truncated zip archive".to_string())
+}
+
+/// Reads every file entry of a zip archive; directory entries are skipped
+pub fn read_zip(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    // The end record sits at the end, possibly followed by a comment of up to 64 KiB
+    let search_start = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (search_start..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(bytes, i).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or("Not a zip archive")?;
+
+    let count = u16_at(bytes, end + 10)? as usize;
+    let mut at = u32_at(bytes, end + 16)? as usize;
+    let mut entries = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if u32_at(bytes, at)? != CENTRAL_HEADER {
+            return Err("Corrupt zip central directory".to_string());
+        }
+        let flags = u16_at(bytes, at + 8)?;
+        let method = u16_at(bytes, at + 10)?;
+        let crc = u32_at(bytes, at + 16)?;
+        let compressed_size = u32_at(bytes, at + 20)? as usize;
+        let size = u32_at(bytes, at + 24)? as u64;
+        let name_length = u16_at(bytes, at + 28)? as usize;
+        let extra_length = u16_at(bytes, at + 30)? as usize;
+        let comment_length = u16_at(bytes, at + 32)? as usize;
+        let local = u32_at(bytes, at + 42)? as usize;
+        let name_bytes = bytes.get(at + 46..at + 46 + name_length).ok_or("Truncated zip archive")?;
+        let name = String::from_utf8_lossy(name_bytes).to_string();
+        at += 46 + name_length + extra_length + comment_length;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(format!("Encrypted zip entries are not supported: {}", name));
+        }
+        if size > MAX_ENTRY_SIZE {
+            return Err(format!("Zip entry is too large: {}", name));
+        }
+        if u32_at(bytes, local)? != LOCAL_HEADER {
+            return Err(format!("Corrupt zip entry: {}", name));
+        }
+        let data_start = local + 30 + u16_at(bytes, local + 26)? as usize + u16_at(bytes, local + 28)? as usize;
+        let body = bytes
+            .get(data_start..data_start + compressed_size)
+            .ok_or_else(|| format!("Truncated zip entry: {}", name))?;
+
+        let data = match method {
+            STORED => body.to_vec(),
+            DEFLATED => {
+                let mut data = Vec::with_capacity(size as usize);
+                DeflateDecoder::new(body)
+                    .take(MAX_ENTRY_SIZE + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("Failed to decompress {}: {}", name, e))?;
+                data
+            }
+            _ => return Err(format!("Unsupported compression method {} for {}", method, name)),
+        };
+        if data.len() as u64 != size || crc32(&data) != crc {
+            return Err(format!("Checksum mismatch in zip entry: {}", name));
+        }
+        entries.push((name, data));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Digest;
+
+    fn entries() -> Vec<(String, Vec<u8>)> {
+        // Hashes do not compress, so this entry is stored
+        let noise: Vec<u8> = (0..16u8).flat_map(|i| sha2::Sha256::digest([i])).collect();
+        vec![
+            ("drawings/plan.excalidraw".to_string(), "{\"elements\":[]}".repeat(50).into_bytes()),
+            ("assets/ab12.png".to_string(), noise),
+            ("drawings/übersicht.excalidraw".to_string(), Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn archives_round_trip() {
+        let archive = write_zip(&entries()).unwrap();
+        assert_eq!(read_zip(&archive).unwrap(), entries());
+    }
+
+    /// Written by Python's `zipfile`: a directory entry and a deflated `dir/a.txt`
+    const FOREIGN_ARCHIVE: [u8; 211] = [
+        80, 75, 3, 4, 20, 0, 0, 0, 0, 0, 0, 0, 33, 88, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 100, 105, 114, 47, 80, 75,
+        3, 4, 20, 0, 0, 0, 8, 0, 0, 0, 33, 88, 47, 250, 248, 72, 11, 0,
+        0, 0, 120, 0, 0, 0, 9, 0, 0, 0, 100, 105, 114, 47, 97, 46, 116, 120,
+        116, 203, 72, 205, 201, 201, 87, 200, 160, 59, 9, 0, 80, 75, 1, 2, 20, 3,
+        20, 0, 0, 0, 0, 0, 0, 0, 33, 88, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 1,
+        0, 0, 0, 0, 100, 105, 114, 47, 80, 75, 1, 2, 20, 3, 20, 0, 0, 0,
+        8, 0, 0, 0, 33, 88, 47, 250, 248, 72, 11, 0, 0, 0, 120, 0, 0, 0,
+        9, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 1, 34, 0, 0, 0,
+        100, 105, 114, 47, 97, 46, 116, 120, 116, 80, 75, 5, 6, 0, 0, 0, 0, 2,
+        0, 2, 0, 105, 0, 0, 0, 84, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn reads_archives_of_other_tools() {
+        let entries = read_zip(&FOREIGN_ARCHIVE).unwrap();
+        assert_eq!(entries, [("dir/a.txt".to_string(), "hello ".repeat(20).into_bytes())]);
+    }
+
+    #[test]
+    fn refuses_more_entries_than_the_format_counts() {
+        let too_many = vec![(String::new(), Vec::new()); u16::MAX as usize + 1];
+        assert_eq!(
+            write_zip(&too_many).unwrap_err(),
+            format!("Archive has {} entries, the maximum is {}", too_many.len(), u16::MAX)
+        );
+    }
+
+    #[test]
+    fn refuses_names_longer_than_the_format_stores() {
+        let name = "a".repeat(u16::MAX as usize + 1);
+        assert_eq!(
+            write_zip(&[(name.clone(), Vec::new())]).unwrap_err(),
+            format!("Entry name is {} bytes long, the maximum is {}", name.len(), u16::MAX)
+        );
+    }
+
+    #[test]
+    fn detects_damaged_archives() {
+        assert_eq!(read_zip(b"not a zip").unwrap_err(), "Not a zip archive");
+
+        let mut archive = write_zip(&entries()[1..2]).unwrap();
+        // The stored data starts right after the local header and the name
+        let data_start = 30 + "assets/ab12.png".len();
+        archive[data_start] ^= 0xff;
+        assert_eq!(read_zip(&archive).unwrap_err(), "Checksum mismatch in zip entry: assets/ab12.png");
+    }
+}
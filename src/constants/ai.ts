@@ -174,4 +174,23 @@ export const DEFAULT_TOOLS: Tool[] = [
       required: ["directory"],
     },
   },
+  {
+    name: "mermaid_to_excalidraw",
+    description:
+      "将 Mermaid 代码转换为带绑定箭头的 Excalidraw 元素，支持 flowchart/graph、sequenceDiagram 和 classDiagram。语法错误会返回行号和列号，请据此修正后重试。传入 file_path 时会把图表追加到该文件已有内容的右侧并保存。",
+    parameters: {
+      type: "object",
+      properties: {
+        code: {
+          type: "string",
+          description: "Mermaid 图表代码，不要包含 ```mermaid 代码块标记",
+        },
+        file_path: {
+          type: "string",
+          description: "可选，要写入的 .excalidraw 文件路径",
+        },
+      },
+      required: ["code"],
+    },
+  },
 ];
@@ -11,6 +11,8 @@ export const TIMING = {
 // File system constants
 export const FILE_SYSTEM = {
   EXTENSION: "excalidraw",
+  // Images with an embedded scene and Obsidian notes are opened and saved like drawings
+  CONTAINER_EXTENSIONS: ["png", "svg", "md"],
   DEFAULT_FILE_PREFIX: "Untitled",
   MAX_RENAME_ATTEMPTS: 100,
 } as const;
@@ -1,5 +1,9 @@
 import { parseMermaidToExcalidraw } from "@excalidraw/mermaid-to-excalidraw";
-import { convertToExcalidrawElements } from "@excalidraw/excalidraw";
+import {
+  convertToExcalidrawElements,
+  restoreElements,
+} from "@excalidraw/excalidraw";
+import { invoke } from "@tauri-apps/api/core";
 import { getGlobalExcalidrawAPI } from "@/hooks/useMenuHandler";
 import { message } from "antd";
 
@@ -12,6 +16,32 @@ export interface MermaidConversionOptions {
   fontSize?: string;
 }
 
+/** 由 Rust 后端解析和布局的图表类型 */
+const NATIVE_DIAGRAM_TYPES = [
+  "flowchart",
+  "graph",
+  "sequenceDiagram",
+  "classDiagram",
+];
+
+/**
+ * 获取 Mermaid 代码的图表类型（跳过 front matter 和注释）
+ */
+function getDiagramType(mermaidCode: string): string {
+  const lines = mermaidCode.split("\n").map((line) => line.trim());
+  let inFrontMatter = lines[0] === "---";
+  for (const line of lines.slice(inFrontMatter ? 1 : 0)) {
+    if (inFrontMatter) {
+      inFrontMatter = line !== "---";
+      continue;
+    }
+    if (line && !line.startsWith("%%")) {
+      return line.split(/\s+/)[0];
+    }
+  }
+  return "";
+}
+
 /**
  * 将 Mermaid 代码转换为 Excalidraw 元素
  */
@@ -21,6 +51,21 @@ export async function convertMermaidToExcalidraw(
 ): Promise<MermaidConversionResult> {
   const { fontSize = "16px" } = options;
 
+  // 流程图、时序图和类图使用后端解析，语法错误会带有行号和列号
+  const diagramType = getDiagramType(mermaidCode).replace(/-v2$/, "");
+  if (NATIVE_DIAGRAM_TYPES.includes(diagramType)) {
+    const result = await invoke<{ elements: any[] }>("mermaid_to_excalidraw", {
+      code: mermaidCode,
+    });
+    return {
+      // 按实际字体重新测量文本尺寸
+      elements: restoreElements(result.elements, null, {
+        refreshDimensions: true,
+      }),
+      files: {},
+    };
+  }
+
   // 解析 Mermaid 代码为骨架格式
   const { elements, files } = await parseMermaidToExcalidraw(mermaidCode, {
     themeVariables: { fontSize },
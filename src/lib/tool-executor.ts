@@ -87,6 +87,17 @@ export class TauriToolExecutor implements ToolExecutor {
           break;
         }
 
+        // Mermaid 转换为 Excalidraw 元素
+        case "mermaid_to_excalidraw": {
+          const code = this.validateStringArg(args, "code", name);
+          const filePath =
+            args.file_path === undefined
+              ? undefined
+              : this.validateStringArg(args, "file_path", name);
+          result = await invoke("mermaid_to_excalidraw", { code, filePath });
+          break;
+        }
+
         // 未知工具
         default:
           return {
@@ -104,7 +115,12 @@ export class TauriToolExecutor implements ToolExecutor {
       // 捕获错误并返回
       return {
         success: false,
-        error: error instanceof Error ? error.message : "Unknown error",
+        error:
+          error instanceof Error
+            ? error.message
+            : typeof error === "string"
+              ? error
+              : "Unknown error",
       };
     }
   }
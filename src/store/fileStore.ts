@@ -1,7 +1,8 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
-import { ExcalidrawFile, FileTreeNode } from "../types";
+import { ExcalidrawFile, FileTreeNode, RelocateReport } from "../types";
 import { message, Modal } from "antd";
+import { FILE_SYSTEM } from "../constants";
 
 export interface FileStore {
   // 当前选中的目录
@@ -396,16 +397,80 @@ export const useFileStore = create<FileStore>((set, get) => ({
   renameFile: async (oldPath, newName) => {
     try {
       message.loading("正在重命名文件...", 0);
-      const finalName = newName.endsWith(".excalidraw")
-        ? newName
-        : `${newName}.excalidraw`;
-
-      const newPath = await invoke<string>("rename_file", {
-        oldPath,
-        newName: finalName,
-      });
+      // 文件树中 "name.excalidraw.png" 显示为 "name.png"，重命名时保留容器格式
+      const containerExtension = FILE_SYSTEM.CONTAINER_EXTENSIONS.map(
+        (ext) => `.${ext}`,
+      ).find((ext) => oldPath.endsWith(`.${FILE_SYSTEM.EXTENSION}${ext}`));
+      const baseName =
+        containerExtension && newName.endsWith(containerExtension)
+          ? newName.slice(0, -containerExtension.length)
+          : newName;
+      const finalName = `${
+        baseName.endsWith(".excalidraw") ? baseName : `${baseName}.excalidraw`
+      }${containerExtension ?? ""}`;
 
       const state = get();
+      let newPath: string;
+
+      if (state.currentDirectory) {
+        // 先预览，其他绘图中指向该文件的链接会一并更新
+        const args = {
+          directory: state.currentDirectory,
+          filePath: oldPath,
+          newName: finalName,
+        };
+        const preview = await invoke<RelocateReport>("rename_with_links", {
+          ...args,
+          dryRun: true,
+        });
+        const linkedFiles = preview.files.filter(
+          (file) => file.path !== preview.new_path,
+        );
+
+        if (linkedFiles.length > 0 || preview.skipped.length > 0) {
+          message.destroy();
+          const names = (files: { path: string }[]) =>
+            files.map((file) => file.path.split(/[\\/]/).pop()).join("、");
+          const notes: string[] = [];
+          if (linkedFiles.length > 0) {
+            notes.push(
+              `${linkedFiles.length} 个绘图链接到此文件，重命名后将同时更新其中的链接：${names(linkedFiles)}。`,
+            );
+          }
+          if (preview.skipped.length > 0) {
+            // 无法读取的绘图中即使有指向此文件的链接也不会被更新
+            notes.push(
+              `${preview.skipped.length} 个绘图无法读取，其中的链接不会更新：${names(preview.skipped)}。`,
+            );
+          }
+          const confirmed = await new Promise<boolean>((resolve) => {
+            Modal.confirm({
+              title: "更新链接",
+              content: notes.join(""),
+              okText: "重命名",
+              cancelText: "取消",
+              onOk: () => resolve(true),
+              onCancel: () => resolve(false),
+            });
+          });
+
+          if (!confirmed) {
+            return;
+          }
+          message.loading("正在重命名文件...", 0);
+        }
+
+        const report = await invoke<RelocateReport>("rename_with_links", {
+          ...args,
+          dryRun: false,
+        });
+        newPath = report.new_path;
+      } else {
+        newPath = await invoke<string>("rename_file", {
+          oldPath,
+          newName: finalName,
+        });
+      }
 
       if (state.activeFile?.path === oldPath) {
         set({
@@ -33,3 +33,51 @@ export interface Preferences {
   /** 主题模式：light-浅色, dark-深色, system-跟随系统 */
   theme: "light" | "dark" | "system";
 }
+
+/**
+ * 绘图中一处被改写的链接
+ */
+export interface LinkChange {
+  /** 元素 ID */
+  element_id: string;
+  /** 改写前的链接 */
+  before: string;
+  /** 改写后的链接 */
+  after: string;
+}
+
+/**
+ * 重命名或移动绘图后，需要更新链接的绘图
+ */
+export interface RelinkedFile {
+  /** 绘图移动后的路径 */
+  path: string;
+  /** 改写的链接列表 */
+  changes: LinkChange[];
+}
+
+/**
+ * 无法读取而被跳过的文件
+ */
+export interface SkippedFile {
+  /** 文件路径 */
+  path: string;
+  /** 错误信息 */
+  error: string;
+}
+
+/**
+ * 重命名或移动绘图的结果（或预览）
+ */
+export interface RelocateReport {
+  /** 原路径 */
+  old_path: string;
+  /** 新路径 */
+  new_path: string;
+  /** 需要更新链接的绘图 */
+  files: RelinkedFile[];
+  /** 无法读取的绘图，其中的链接不会更新 */
+  skipped: SkippedFile[];
+  /** 是否已执行，预览时为 false */
+  applied: boolean;
+}